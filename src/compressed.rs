@@ -0,0 +1,245 @@
+//! Expand RV64C compressed instructions to their 32 bit equivalents.
+//!
+//! Every compressed instruction is a short form of a 32 bit instruction,
+//! so [`expand`] builds that instruction word and
+//! [`decode`](crate::decode::decode) decodes it like any other. Offsets
+//! are kept relative to the compressed instruction, so branch targets
+//! don't change.
+//!
+//! The immediates are scattered over the parcel, and each is described
+//! below by the bit of the immediate in each bit of the parcel, from the
+//! highest, as in the specification:
+//! https://github.com/riscv/riscv-isa-manual/blob/main/src/c-st-ext.adoc
+use crate::decode::bits;
+
+/// The 32 bit instruction word of the compressed instruction `parcel`.
+pub(crate) fn expand(parcel: u16) -> Result<u32, &'static str> {
+    let parcel = u32::from(parcel);
+    let funct3 = bits(parcel, 15, 13);
+    // the full register fields
+    let rd = bits(parcel, 11, 7);
+    let rs2 = bits(parcel, 6, 2);
+    // the 3 bit register fields of x8 to x15, where bits 4:2 are the rd' or
+    // rs2' of loads and stores, and bits 9:7 their rs1', or the rd' of
+    // arithmetic
+    let rd_short = bits(parcel, 4, 2) + 8;
+    let rs1_short = bits(parcel, 9, 7) + 8;
+    let sp = 2;
+    let ra = 1;
+
+    // the 6 bit immediate of most instructions with a full register
+    let imm6 = sext(
+        field(parcel, 12, &[5]) | field(parcel, 6, &[4, 3, 2, 1, 0]),
+        6,
+    );
+    let shamt = field(parcel, 12, &[5]) | field(parcel, 6, &[4, 3, 2, 1, 0]);
+    // the offsets of the loads and stores of doubles and words
+    let double_offset = field(parcel, 12, &[5, 4, 3]) | field(parcel, 6, &[7, 6]);
+    let word_offset = field(parcel, 12, &[5, 4, 3]) | field(parcel, 6, &[2, 6]);
+
+    Ok(match (bits(parcel, 1, 0), funct3) {
+        (0b00, _) if parcel == 0 => return Err("illegal instruction"),
+        // c.addi4spn
+        (0b00, 0b000) => {
+            let imm = field(parcel, 12, &[5, 4, 9, 8, 7, 6, 2, 3]);
+            if imm == 0 {
+                return Err("unknown instruction");
+            }
+            i_type(OP_IMM, 0, rd_short, sp, imm as i32)
+        }
+        // c.fld
+        (0b00, 0b001) => i_type(LOAD_FP, 3, rd_short, rs1_short, double_offset as i32),
+        // c.lw
+        (0b00, 0b010) => i_type(LOAD, 2, rd_short, rs1_short, word_offset as i32),
+        // c.ld
+        (0b00, 0b011) => i_type(LOAD, 3, rd_short, rs1_short, double_offset as i32),
+        // c.fsd
+        (0b00, 0b101) => s_type(STORE_FP, 3, rs1_short, rd_short, double_offset as i32),
+        // c.sw
+        (0b00, 0b110) => s_type(STORE, 2, rs1_short, rd_short, word_offset as i32),
+        // c.sd
+        (0b00, 0b111) => s_type(STORE, 3, rs1_short, rd_short, double_offset as i32),
+        // c.addi, and c.nop when rd is x0
+        (0b01, 0b000) => i_type(OP_IMM, 0, rd, rd, imm6),
+        // c.addiw
+        (0b01, 0b001) if rd != 0 => i_type(OP_IMM_32, 0, rd, rd, imm6),
+        // c.li
+        (0b01, 0b010) => i_type(OP_IMM, 0, rd, 0, imm6),
+        // c.addi16sp
+        (0b01, 0b011) if rd == sp => {
+            let imm = sext(
+                field(parcel, 12, &[9]) | field(parcel, 6, &[4, 6, 8, 7, 5]),
+                10,
+            );
+            if imm == 0 {
+                return Err("unknown instruction");
+            }
+            i_type(OP_IMM, 0, sp, sp, imm)
+        }
+        // c.lui
+        (0b01, 0b011) => {
+            if imm6 == 0 {
+                return Err("unknown instruction");
+            }
+            (imm6 as u32) << 12 | rd << 7 | LUI
+        }
+        (0b01, 0b100) => expand_arithmetic(parcel, rs1_short, rd_short, shamt, imm6)?,
+        // c.j
+        (0b01, 0b101) => {
+            let offset = sext(field(parcel, 12, &[11, 4, 9, 8, 10, 6, 7, 3, 2, 1, 5]), 12);
+            j_type(0, offset)
+        }
+        // c.beqz and c.bnez
+        (0b01, 0b110 | 0b111) => {
+            let offset = sext(
+                field(parcel, 12, &[8, 4, 3]) | field(parcel, 6, &[7, 6, 2, 1, 5]),
+                9,
+            );
+            b_type(funct3 - 0b110, rs1_short, 0, offset)
+        }
+        // c.slli
+        (0b10, 0b000) => i_type(OP_IMM, 1, rd, rd, shamt as i32),
+        // c.fldsp
+        (0b10, 0b001) => i_type(LOAD_FP, 3, rd, sp, double_sp_offset(parcel)),
+        // c.lwsp
+        (0b10, 0b010) if rd != 0 => {
+            let offset = field(parcel, 12, &[5]) | field(parcel, 6, &[4, 3, 2, 7, 6]);
+            i_type(LOAD, 2, rd, sp, offset as i32)
+        }
+        // c.ldsp
+        (0b10, 0b011) if rd != 0 => i_type(LOAD, 3, rd, sp, double_sp_offset(parcel)),
+        (0b10, 0b100) => match (bits(parcel, 12, 12), rd, rs2) {
+            (0, 0, 0) => return Err("unknown instruction"),
+            // c.jr
+            (0, _, 0) => i_type(JALR, 0, 0, rd, 0),
+            // c.mv
+            (0, _, _) => r_type(OP, 0, 0, rd, 0, rs2),
+            // c.ebreak
+            (1, 0, 0) => return Err("unsupported instruction"),
+            // c.jalr
+            (1, _, 0) => i_type(JALR, 0, ra, rd, 0),
+            // c.add
+            (_, _, _) => r_type(OP, 0, 0, rd, rd, rs2),
+        },
+        // c.fsdsp
+        (0b10, 0b101) => s_type(STORE_FP, 3, sp, rs2, double_sp_store_offset(parcel)),
+        // c.swsp
+        (0b10, 0b110) => {
+            let offset = field(parcel, 12, &[5, 4, 3, 2, 7, 6]);
+            s_type(STORE, 2, sp, rs2, offset as i32)
+        }
+        // c.sdsp
+        (0b10, 0b111) => s_type(STORE, 3, sp, rs2, double_sp_store_offset(parcel)),
+        _ => return Err("unknown instruction"),
+    })
+}
+
+/// The shifts, `c.andi` and the register to register instructions, which
+/// share a `funct3` and only use x8 to x15.
+fn expand_arithmetic(
+    parcel: u32,
+    rd: u32,
+    rs2: u32,
+    shamt: u32,
+    imm: i32,
+) -> Result<u32, &'static str> {
+    let funct = (
+        bits(parcel, 11, 10),
+        bits(parcel, 12, 12),
+        bits(parcel, 6, 5),
+    );
+    Ok(match funct {
+        // c.srli
+        (0b00, _, _) => i_type(OP_IMM, 5, rd, rd, shamt as i32),
+        // c.srai
+        (0b01, _, _) => i_type(OP_IMM, 5, rd, rd, (0b010000 << 6 | shamt) as i32),
+        // c.andi
+        (0b10, _, _) => i_type(OP_IMM, 7, rd, rd, imm),
+        // c.sub
+        (0b11, 0, 0b00) => r_type(OP, 0, 0b0100000, rd, rd, rs2),
+        // c.xor
+        (0b11, 0, 0b01) => r_type(OP, 4, 0, rd, rd, rs2),
+        // c.or
+        (0b11, 0, 0b10) => r_type(OP, 6, 0, rd, rd, rs2),
+        // c.and
+        (0b11, 0, 0b11) => r_type(OP, 7, 0, rd, rd, rs2),
+        // c.subw
+        (0b11, 1, 0b00) => r_type(OP_32, 0, 0b0100000, rd, rd, rs2),
+        // c.addw
+        (0b11, 1, 0b01) => r_type(OP_32, 0, 0, rd, rd, rs2),
+        _ => return Err("unknown instruction"),
+    })
+}
+
+/// The offset of `c.fldsp` and `c.ldsp`.
+fn double_sp_offset(parcel: u32) -> i32 {
+    (field(parcel, 12, &[5]) | field(parcel, 6, &[4, 3, 8, 7, 6])) as i32
+}
+
+/// The offset of `c.fsdsp` and `c.sdsp`.
+fn double_sp_store_offset(parcel: u32) -> i32 {
+    field(parcel, 12, &[5, 4, 3, 8, 7, 6]) as i32
+}
+
+const LOAD: u32 = 0b0000011;
+const LOAD_FP: u32 = 0b0000111;
+const STORE: u32 = 0b0100011;
+const STORE_FP: u32 = 0b0100111;
+const OP_IMM: u32 = 0b0010011;
+const OP_IMM_32: u32 = 0b0011011;
+const OP: u32 = 0b0110011;
+const OP_32: u32 = 0b0111011;
+const LUI: u32 = 0b0110111;
+const BRANCH: u32 = 0b1100011;
+const JALR: u32 = 0b1100111;
+const JAL: u32 = 0b1101111;
+
+fn r_type(opcode: u32, funct3: u32, funct7: u32, rd: u32, rs1: u32, rs2: u32) -> u32 {
+    funct7 << 25 | rs2 << 20 | rs1 << 15 | funct3 << 12 | rd << 7 | opcode
+}
+
+fn i_type(opcode: u32, funct3: u32, rd: u32, rs1: u32, imm: i32) -> u32 {
+    (imm as u32 & 0xfff) << 20 | rs1 << 15 | funct3 << 12 | rd << 7 | opcode
+}
+
+fn s_type(opcode: u32, funct3: u32, rs1: u32, rs2: u32, imm: i32) -> u32 {
+    let imm = imm as u32;
+    bits(imm, 11, 5) << 25 | rs2 << 20 | rs1 << 15 | funct3 << 12 | bits(imm, 4, 0) << 7 | opcode
+}
+
+fn b_type(funct3: u32, rs1: u32, rs2: u32, offset: i32) -> u32 {
+    let offset = offset as u32;
+    bits(offset, 12, 12) << 31
+        | bits(offset, 10, 5) << 25
+        | rs2 << 20
+        | rs1 << 15
+        | funct3 << 12
+        | bits(offset, 4, 1) << 8
+        | bits(offset, 11, 11) << 7
+        | BRANCH
+}
+
+fn j_type(rd: u32, offset: i32) -> u32 {
+    let offset = offset as u32;
+    bits(offset, 20, 20) << 31
+        | bits(offset, 10, 1) << 21
+        | bits(offset, 11, 11) << 20
+        | bits(offset, 19, 12) << 12
+        | rd << 7
+        | JAL
+}
+
+/// The immediate bits in the parcel bits from `high` down, where `layout`
+/// has the bit of the immediate for each of them.
+fn field(parcel: u32, high: u32, layout: &[u32]) -> u32 {
+    layout
+        .iter()
+        .enumerate()
+        .map(|(index, bit)| bits(parcel, high - index as u32, high - index as u32) << bit)
+        .fold(0, |imm, bit| imm | bit)
+}
+
+/// Sign extend the low `width` bits of `imm`.
+fn sext(imm: u32, width: u32) -> i32 {
+    ((imm << (32 - width)) as i32) >> (32 - width)
+}
@@ -0,0 +1,995 @@
+//! Decode RV64GC machine code into [`RiscVInstruction`]s.
+//!
+//! Compressed instructions are expanded to the 32 bit instructions they
+//! stand for by [`expand`], so they decode to the same
+//! [`RiscVInstruction`], while keeping their own address and size.
+//!
+//! Each instruction word becomes the instruction the parser would make of
+//! its disassembly, so binaries are translated the same way as assembly.
+//! Branch and jump targets become synthetic labels named after their
+//! address, such as `.L1040`, and [`decode`] puts a
+//! [`RiscVInstruction::Label`] before every instruction that is a target.
+//!
+//! An `auipc` only makes sense with the instructions which use its result
+//! as a base address, like `%pcrel_hi` and `%pcrel_lo` in assembly. Its
+//! label is the address the first of them accesses, and each of them keeps
+//! its offset from that label:
+//!
+//! ```text
+//! 1000: auipc a5, 0x1          auipc a5, .L2010
+//! 1004: ld    a0, 16(a5)   =>  ld    a0, 0(a5)
+//! 1008: ld    a1, 24(a5)       ld    a1, 8(a5)
+//! ```
+use std::collections::HashSet;
+use std::ops::Range;
+
+use crate::compressed::expand;
+use crate::error::AsmError;
+use crate::instruction::{
+    RiscVAmoOp, RiscVFRegister, RiscVFenceSet, RiscVInstruction, RiscVOrdering, RiscVPrecision,
+    RiscVRegister, RiscVRounding, RiscVVal, RiscVWidth,
+};
+
+/// An instruction decoded from the bytes at `address`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Decoded {
+    pub address: u64,
+    /// The length of the instruction in bytes, 2 for compressed instructions
+    /// and otherwise 4
+    pub size: u64,
+    /// The instruction word, or the 16 bit parcel of a compressed
+    /// instruction
+    pub raw: u32,
+    pub instr: RiscVInstruction,
+    /// The address a branch, jump or `auipc` refers to, which is named by
+    /// [`label_name`] in `instr`
+    pub target: Option<u64>,
+}
+
+/// The synthetic label of `address`.
+pub fn label_name(address: u64) -> String {
+    format!(".L{:x}", address)
+}
+
+/// Decode the code in `bytes`, which is loaded at `base`, with a label
+/// before each instruction that a branch, jump or `auipc` refers to.
+///
+/// Targets outside of `bytes` are left for the caller to define.
+pub fn decode(bytes: &[u8], base: u64) -> Result<Vec<RiscVInstruction>, Vec<AsmError>> {
+    let decoded = decode_instructions(bytes, base)?;
+    check_targets(&decoded, base..base + bytes.len() as u64)?;
+    let targets = decoded
+        .iter()
+        .filter_map(|decoded| decoded.target)
+        .collect();
+    Ok(label_instructions(decoded, &targets, label_name))
+}
+
+/// Check that each target of `decoded` within `code`, the addresses it was
+/// decoded from, is the start of an instruction.
+pub fn check_targets(decoded: &[Decoded], code: Range<u64>) -> Result<(), Vec<AsmError>> {
+    let starts: HashSet<u64> = decoded.iter().map(|decoded| decoded.address).collect();
+    let errors: Vec<AsmError> = decoded
+        .iter()
+        .enumerate()
+        .filter(|(_, decoded)| {
+            decoded
+                .target
+                .is_some_and(|target| code.contains(&target) && !starts.contains(&target))
+        })
+        .map(|(index, decoded)| {
+            error(
+                index,
+                decoded.address,
+                decoded.raw,
+                "target is inside of an instruction",
+            )
+        })
+        .collect();
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(errors)
+    }
+}
+
+/// Put a label before each instruction whose address is in `labels`, with
+/// the label of `name`, which also renames the targets of the instructions.
+pub fn label_instructions(
+    decoded: Vec<Decoded>,
+    labels: &HashSet<u64>,
+    name: impl Fn(u64) -> String,
+) -> Vec<RiscVInstruction> {
+    let mut riscv_instrs = vec![];
+    for mut decoded in decoded {
+        if labels.contains(&decoded.address) {
+            riscv_instrs.push(RiscVInstruction::Label {
+                name: name(decoded.address),
+            });
+        }
+        if let (Some(target), Some(RiscVVal::LabelOffset { label, .. })) =
+            (decoded.target, target_val(&mut decoded.instr))
+        {
+            *label = name(target);
+        }
+        riscv_instrs.push(decoded.instr);
+    }
+    riscv_instrs
+}
+
+/// The operand of a branch, jump or `auipc` which has its target.
+pub(crate) fn target_val(riscv_instr: &mut RiscVInstruction) -> Option<&mut RiscVVal> {
+    match riscv_instr {
+        RiscVInstruction::Beq { target, .. }
+        | RiscVInstruction::Bne { target, .. }
+        | RiscVInstruction::Blt { target, .. }
+        | RiscVInstruction::Bge { target, .. }
+        | RiscVInstruction::Bltu { target, .. }
+        | RiscVInstruction::Bgeu { target, .. }
+        | RiscVInstruction::Jal { target, .. }
+        | RiscVInstruction::Auipc { src: target, .. } => Some(target),
+        _ => None,
+    }
+}
+
+/// Decode the code in `bytes`, which is loaded at `base`, without adding
+/// labels.
+pub fn decode_instructions(bytes: &[u8], base: u64) -> Result<Vec<Decoded>, Vec<AsmError>> {
+    let mut decoded = vec![];
+    let mut errors = vec![];
+    let mut offset = 0;
+    while offset < bytes.len() {
+        let address = base + offset as u64;
+        let index = decoded.len() + errors.len();
+        let rest = &bytes[offset..];
+        let Some(parcel) = rest.get(..2) else {
+            errors.push(error(
+                index,
+                address,
+                rest[0].into(),
+                "truncated instruction",
+            ));
+            break;
+        };
+        let parcel = u16::from_le_bytes(parcel.try_into().unwrap());
+
+        // the low two bits of the first parcel are 11 for 32 bit
+        // instructions, and anything else for compressed ones
+        let (raw, size, word) = if parcel & 0b11 != 0b11 {
+            (parcel.into(), 2, expand(parcel))
+        } else if let Some(word) = rest.get(..4) {
+            let raw = u32::from_le_bytes(word.try_into().unwrap());
+            (raw, 4, Ok(raw))
+        } else {
+            errors.push(error(
+                index,
+                address,
+                parcel.into(),
+                "truncated instruction",
+            ));
+            break;
+        };
+        match word.and_then(|word| decode_word(word, address)) {
+            Ok((instr, target)) => decoded.push(Decoded {
+                address,
+                size,
+                raw,
+                instr,
+                target,
+            }),
+            Err(reason) => errors.push(error(index, address, raw, reason)),
+        }
+        offset += size as usize;
+    }
+
+    if !errors.is_empty() {
+        return Err(errors);
+    }
+    pair_auipc(&mut decoded);
+    Ok(decoded)
+}
+
+/// An error for the instruction at `index`, shown like a line of
+/// `objdump -d`.
+pub(crate) fn error(index: usize, address: u64, raw: u32, reason: &str) -> AsmError {
+    let text = if raw & 0b11 == 0b11 {
+        format!("{:x}: {:08x}", address, raw)
+    } else {
+        format!("{:x}: {:04x}", address, raw)
+    };
+    AsmError::new(index + 1, 0, &text, reason)
+}
+
+/// Decode one instruction word, and the address it refers to.
+fn decode_word(word: u32, address: u64) -> Result<(RiscVInstruction, Option<u64>), &'static str> {
+    let rd = register(bits(word, 11, 7));
+    let rs1 = register(bits(word, 19, 15));
+    let rs2 = register(bits(word, 24, 20));
+    let funct3 = bits(word, 14, 12);
+    let funct7 = bits(word, 31, 25);
+    let target = |offset: i32| address.wrapping_add_signed(offset.into());
+
+    let riscv_instr = match bits(word, 6, 0) {
+        0b0000011 => RiscVInstruction::L {
+            width: match funct3 {
+                0 => RiscVWidth::Byte,
+                1 => RiscVWidth::Half,
+                2 => RiscVWidth::Word,
+                3 => RiscVWidth::Double,
+                4 => RiscVWidth::UnsignedByte,
+                5 => RiscVWidth::UnsignedHalf,
+                6 => RiscVWidth::UnsignedWord,
+                _ => return Err("unknown instruction"),
+            },
+            dest: rd,
+            src: offset(rs1, i_imm(word)),
+        },
+        0b0100011 => RiscVInstruction::S {
+            width: match funct3 {
+                0 => RiscVWidth::Byte,
+                1 => RiscVWidth::Half,
+                2 => RiscVWidth::Word,
+                3 => RiscVWidth::Double,
+                _ => return Err("unknown instruction"),
+            },
+            src: rs2,
+            dest: offset(rs1, s_imm(word)),
+        },
+        0b0000111 => RiscVInstruction::FL {
+            precision: access_precision(funct3)?,
+            dest: fregister(bits(word, 11, 7)),
+            src: offset(rs1, i_imm(word)),
+        },
+        0b0100111 => RiscVInstruction::FS {
+            precision: access_precision(funct3)?,
+            src: fregister(bits(word, 24, 20)),
+            dest: offset(rs1, s_imm(word)),
+        },
+        // writes to x0 are hints, which do nothing
+        0b0010011 | 0b0011011 | 0b0110011 | 0b0111011 | 0b0110111 | 0b0010111
+            if rd == RiscVRegister::X0 =>
+        {
+            RiscVInstruction::Nop
+        }
+        0b0010011 => decode_op_imm(word, rd, rs1)?,
+        0b0011011 => decode_op_imm_word(word, rd, rs1)?,
+        0b0110011 => decode_op(funct7, funct3, RiscVWidth::Double, rd, rs1, rs2)?,
+        0b0111011 => decode_op(funct7, funct3, RiscVWidth::Word, rd, rs1, rs2)?,
+        0b0110111 => RiscVInstruction::Lui {
+            dest: rd,
+            src: RiscVVal::Immediate(u_imm(word)),
+        },
+        // paired with the instructions using it by `pair_auipc`
+        0b0010111 => RiscVInstruction::Auipc {
+            dest: rd,
+            src: RiscVVal::Immediate(u_imm(word)),
+        },
+        0b1101111 => {
+            let target = target(j_imm(word));
+            return Ok((
+                RiscVInstruction::Jal {
+                    dest: rd,
+                    target: label(target),
+                },
+                Some(target),
+            ));
+        }
+        0b1100111 if funct3 == 0 => RiscVInstruction::Jalr {
+            dest: rd,
+            target: rs1,
+            offset: i_imm(word),
+        },
+        0b1100011 => {
+            let target = target(b_imm(word));
+            let (arg1, arg2, label) = (rs1, rs2, label(target));
+            let riscv_instr = match funct3 {
+                0 => RiscVInstruction::Beq {
+                    arg1,
+                    arg2,
+                    target: label,
+                },
+                1 => RiscVInstruction::Bne {
+                    arg1,
+                    arg2,
+                    target: label,
+                },
+                4 => RiscVInstruction::Blt {
+                    arg1,
+                    arg2,
+                    target: label,
+                },
+                5 => RiscVInstruction::Bge {
+                    arg1,
+                    arg2,
+                    target: label,
+                },
+                6 => RiscVInstruction::Bltu {
+                    arg1,
+                    arg2,
+                    target: label,
+                },
+                7 => RiscVInstruction::Bgeu {
+                    arg1,
+                    arg2,
+                    target: label,
+                },
+                _ => return Err("unknown instruction"),
+            };
+            return Ok((riscv_instr, Some(target)));
+        }
+        0b0001111 => decode_fence(word)?,
+        0b0101111 => decode_atomic(word, rd, rs1, rs2)?,
+        0b1010011 => decode_op_fp(word)?,
+        0b1000011 | 0b1000111 | 0b1001011 | 0b1001111 => decode_fused(word)?,
+        0b1110011 if word == 0x00000073 => RiscVInstruction::ECall,
+        // `ebreak` and the CSR instructions
+        0b1110011 => return Err("unsupported instruction"),
+        _ => return Err("unknown instruction"),
+    };
+    Ok((riscv_instr, None))
+}
+
+/// The integer instructions with an immediate, `OP-IMM`.
+fn decode_op_imm(
+    word: u32,
+    dest: RiscVRegister,
+    src: RiscVRegister,
+) -> Result<RiscVInstruction, &'static str> {
+    let imm = i_imm(word);
+    // 64 bit shifts have a 6 bit amount, and the top 6 bits pick the shift
+    let shamt = bits(word, 25, 20) as i32;
+    let width = RiscVWidth::Double;
+    Ok(match (bits(word, 14, 12), bits(word, 31, 26)) {
+        (0, _) => RiscVInstruction::Addi { dest, src, imm },
+        (1, 0) => RiscVInstruction::Slli {
+            width,
+            dest,
+            src,
+            imm: shamt,
+        },
+        (2, _) => RiscVInstruction::Slti { dest, src, imm },
+        (3, _) => RiscVInstruction::Sltiu { dest, src, imm },
+        (4, _) => RiscVInstruction::Xori { dest, src, imm },
+        (5, 0) => RiscVInstruction::Srli {
+            width,
+            dest,
+            src,
+            imm: shamt,
+        },
+        (5, 0b010000) => RiscVInstruction::Srai {
+            width,
+            dest,
+            src,
+            imm: shamt,
+        },
+        (6, _) => RiscVInstruction::Ori { dest, src, imm },
+        (7, _) => RiscVInstruction::Andi { dest, src, imm },
+        _ => return Err("unknown instruction"),
+    })
+}
+
+/// The 32 bit integer instructions with an immediate, `OP-IMM-32`.
+fn decode_op_imm_word(
+    word: u32,
+    dest: RiscVRegister,
+    src: RiscVRegister,
+) -> Result<RiscVInstruction, &'static str> {
+    let shamt = bits(word, 24, 20) as i32;
+    let width = RiscVWidth::Word;
+    Ok(match (bits(word, 14, 12), bits(word, 31, 25)) {
+        (0, _) => RiscVInstruction::Addiw {
+            dest,
+            src,
+            imm: i_imm(word),
+        },
+        (1, 0) => RiscVInstruction::Slli {
+            width,
+            dest,
+            src,
+            imm: shamt,
+        },
+        (5, 0) => RiscVInstruction::Srli {
+            width,
+            dest,
+            src,
+            imm: shamt,
+        },
+        (5, 0b0100000) => RiscVInstruction::Srai {
+            width,
+            dest,
+            src,
+            imm: shamt,
+        },
+        _ => return Err("unknown instruction"),
+    })
+}
+
+/// The register to register integer instructions, `OP`, or `OP-32` for the
+/// `*w` instructions when `width` is [`RiscVWidth::Word`].
+fn decode_op(
+    funct7: u32,
+    funct3: u32,
+    width: RiscVWidth,
+    dest: RiscVRegister,
+    arg1: RiscVRegister,
+    arg2: RiscVRegister,
+) -> Result<RiscVInstruction, &'static str> {
+    let is_double = width == RiscVWidth::Double;
+    Ok(match (funct7, funct3) {
+        // `c.mv`, which can copy `sp` unlike an ARM `add` of registers
+        (0, 0) if is_double && arg1 == RiscVRegister::X0 => {
+            RiscVInstruction::Mv { dest, src: arg2 }
+        }
+        (0, 0) => RiscVInstruction::Add {
+            width,
+            dest,
+            arg1,
+            arg2,
+        },
+        (0b0100000, 0) => RiscVInstruction::Sub {
+            width,
+            dest,
+            arg1,
+            arg2,
+        },
+        (0, 1) => RiscVInstruction::Sll {
+            width,
+            dest,
+            arg1,
+            arg2,
+        },
+        (0, 5) => RiscVInstruction::Srl {
+            width,
+            dest,
+            arg1,
+            arg2,
+        },
+        (0b0100000, 5) => RiscVInstruction::Sra {
+            width,
+            dest,
+            arg1,
+            arg2,
+        },
+        (0, 2) if is_double => RiscVInstruction::Slt { dest, arg1, arg2 },
+        (0, 3) if is_double => RiscVInstruction::Sltu { dest, arg1, arg2 },
+        (0, 4) if is_double => RiscVInstruction::Xor { dest, arg1, arg2 },
+        (0, 6) if is_double => RiscVInstruction::Or { dest, arg1, arg2 },
+        (0, 7) if is_double => RiscVInstruction::And { dest, arg1, arg2 },
+        (1, 0) => RiscVInstruction::Mul {
+            width,
+            dest,
+            arg1,
+            arg2,
+        },
+        (1, 1) if is_double => RiscVInstruction::Mulh { dest, arg1, arg2 },
+        (1, 2) if is_double => RiscVInstruction::Mulhsu { dest, arg1, arg2 },
+        (1, 3) if is_double => RiscVInstruction::Mulhu { dest, arg1, arg2 },
+        (1, 4) => RiscVInstruction::Div {
+            width,
+            dest,
+            arg1,
+            arg2,
+        },
+        (1, 5) => RiscVInstruction::Divu {
+            width,
+            dest,
+            arg1,
+            arg2,
+        },
+        (1, 6) => RiscVInstruction::Rem {
+            width,
+            dest,
+            arg1,
+            arg2,
+        },
+        (1, 7) => RiscVInstruction::Remu {
+            width,
+            dest,
+            arg1,
+            arg2,
+        },
+        _ => return Err("unknown instruction"),
+    })
+}
+
+/// `fence`, `fence.tso`, `pause` and `fence.i`, `MISC-MEM`.
+fn decode_fence(word: u32) -> Result<RiscVInstruction, &'static str> {
+    let (fm, pred, succ) = (bits(word, 31, 28), bits(word, 27, 24), bits(word, 23, 20));
+    Ok(match (bits(word, 14, 12), fm, pred, succ) {
+        (0, 0b1000, 0b0011, 0b0011) => RiscVInstruction::FenceTso,
+        // `fence w, 0`
+        (0, 0, 0b0001, 0) => RiscVInstruction::Pause,
+        (0, 0, _, _) => RiscVInstruction::Fence {
+            pred: fence_set(pred),
+            succ: fence_set(succ),
+        },
+        (1, _, _, _) => RiscVInstruction::FenceI,
+        _ => return Err("unknown instruction"),
+    })
+}
+
+/// The `iorw` bits of a fence set, from the most significant.
+fn fence_set(set: u32) -> RiscVFenceSet {
+    RiscVFenceSet {
+        input: set & 0b1000 != 0,
+        output: set & 0b0100 != 0,
+        read: set & 0b0010 != 0,
+        write: set & 0b0001 != 0,
+    }
+}
+
+/// `lr`, `sc` and the AMOs, `AMO`.
+fn decode_atomic(
+    word: u32,
+    dest: RiscVRegister,
+    addr: RiscVRegister,
+    src: RiscVRegister,
+) -> Result<RiscVInstruction, &'static str> {
+    let width = match bits(word, 14, 12) {
+        2 => RiscVWidth::Word,
+        3 => RiscVWidth::Double,
+        _ => return Err("unknown instruction"),
+    };
+    let ordering = match (bits(word, 26, 26), bits(word, 25, 25)) {
+        (0, 0) => RiscVOrdering::Relaxed,
+        (1, 0) => RiscVOrdering::Acquire,
+        (0, 1) => RiscVOrdering::Release,
+        _ => RiscVOrdering::AcquireRelease,
+    };
+    let op = match bits(word, 31, 27) {
+        0b00010 if src == RiscVRegister::X0 => {
+            return Ok(RiscVInstruction::Lr {
+                width,
+                ordering,
+                dest,
+                addr,
+            })
+        }
+        0b00011 => {
+            return Ok(RiscVInstruction::Sc {
+                width,
+                ordering,
+                dest,
+                src,
+                addr,
+            })
+        }
+        0b00001 => RiscVAmoOp::Swap,
+        0b00000 => RiscVAmoOp::Add,
+        0b00100 => RiscVAmoOp::Xor,
+        0b01100 => RiscVAmoOp::And,
+        0b01000 => RiscVAmoOp::Or,
+        0b10000 => RiscVAmoOp::Min,
+        0b10100 => RiscVAmoOp::Max,
+        0b11000 => RiscVAmoOp::Minu,
+        0b11100 => RiscVAmoOp::Maxu,
+        _ => return Err("unknown instruction"),
+    };
+    Ok(RiscVInstruction::Amo {
+        op,
+        width,
+        ordering,
+        dest,
+        src,
+        addr,
+    })
+}
+
+/// The floating point instructions other than loads, stores and fused
+/// multiply adds, `OP-FP`.
+fn decode_op_fp(word: u32) -> Result<RiscVInstruction, &'static str> {
+    let precision = precision(bits(word, 26, 25))?;
+    let funct3 = bits(word, 14, 12);
+    let rs2 = bits(word, 24, 20);
+    let dest = fregister(bits(word, 11, 7));
+    let arg1 = fregister(bits(word, 19, 15));
+    let arg2 = fregister(rs2);
+    let int_dest = register(bits(word, 11, 7));
+    let int_src = register(bits(word, 19, 15));
+    let rounding = || rounding(funct3);
+
+    Ok(match (bits(word, 31, 27), funct3, rs2) {
+        (0b00000, _, _) => RiscVInstruction::FAdd {
+            precision,
+            rounding: rounding()?,
+            dest,
+            arg1,
+            arg2,
+        },
+        (0b00001, _, _) => RiscVInstruction::FSub {
+            precision,
+            rounding: rounding()?,
+            dest,
+            arg1,
+            arg2,
+        },
+        (0b00010, _, _) => RiscVInstruction::FMul {
+            precision,
+            rounding: rounding()?,
+            dest,
+            arg1,
+            arg2,
+        },
+        (0b00011, _, _) => RiscVInstruction::FDiv {
+            precision,
+            rounding: rounding()?,
+            dest,
+            arg1,
+            arg2,
+        },
+        (0b01011, _, 0) => RiscVInstruction::FSqrt {
+            precision,
+            rounding: rounding()?,
+            dest,
+            src: arg1,
+        },
+        (0b00100, 0, _) => RiscVInstruction::FSgnj {
+            precision,
+            dest,
+            arg1,
+            arg2,
+        },
+        (0b00100, 1, _) => RiscVInstruction::FSgnjn {
+            precision,
+            dest,
+            arg1,
+            arg2,
+        },
+        (0b00100, 2, _) => RiscVInstruction::FSgnjx {
+            precision,
+            dest,
+            arg1,
+            arg2,
+        },
+        (0b00101, 0, _) => RiscVInstruction::FMin {
+            precision,
+            dest,
+            arg1,
+            arg2,
+        },
+        (0b00101, 1, _) => RiscVInstruction::FMax {
+            precision,
+            dest,
+            arg1,
+            arg2,
+        },
+        // the source has the other precision, in rs2
+        (0b01000, _, 1) if precision == RiscVPrecision::Single => RiscVInstruction::FCvt {
+            precision,
+            rounding: rounding()?,
+            dest,
+            src: arg1,
+        },
+        (0b01000, _, 0) if precision == RiscVPrecision::Double => RiscVInstruction::FCvt {
+            precision,
+            rounding: rounding()?,
+            dest,
+            src: arg1,
+        },
+        (0b10100, 2, _) => RiscVInstruction::FEq {
+            precision,
+            dest: int_dest,
+            arg1,
+            arg2,
+        },
+        (0b10100, 1, _) => RiscVInstruction::FLt {
+            precision,
+            dest: int_dest,
+            arg1,
+            arg2,
+        },
+        (0b10100, 0, _) => RiscVInstruction::FLe {
+            precision,
+            dest: int_dest,
+            arg1,
+            arg2,
+        },
+        (0b11000, _, 0..=3) => {
+            let (width, signed) = int_format(rs2);
+            RiscVInstruction::FCvtToInt {
+                precision,
+                width,
+                signed,
+                rounding: rounding()?,
+                dest: int_dest,
+                src: arg1,
+            }
+        }
+        (0b11010, _, 0..=3) => {
+            let (width, signed) = int_format(rs2);
+            RiscVInstruction::FCvtFromInt {
+                precision,
+                width,
+                signed,
+                rounding: rounding()?,
+                dest,
+                src: int_src,
+            }
+        }
+        (0b11100, 0, 0) => RiscVInstruction::FMvToInt {
+            precision,
+            dest: int_dest,
+            src: arg1,
+        },
+        (0b11100, 1, 0) => RiscVInstruction::FClass {
+            precision,
+            dest: int_dest,
+            src: arg1,
+        },
+        (0b11110, 0, 0) => RiscVInstruction::FMvFromInt {
+            precision,
+            dest,
+            src: int_src,
+        },
+        _ => return Err("unknown instruction"),
+    })
+}
+
+/// The fused multiply adds, which have their own major opcodes and a third
+/// source register in the top bits.
+fn decode_fused(word: u32) -> Result<RiscVInstruction, &'static str> {
+    let precision = precision(bits(word, 26, 25))?;
+    let rounding = rounding(bits(word, 14, 12))?;
+    let dest = fregister(bits(word, 11, 7));
+    let arg1 = fregister(bits(word, 19, 15));
+    let arg2 = fregister(bits(word, 24, 20));
+    let arg3 = fregister(bits(word, 31, 27));
+    Ok(match bits(word, 6, 0) {
+        0b1000011 => RiscVInstruction::FMadd {
+            precision,
+            rounding,
+            dest,
+            arg1,
+            arg2,
+            arg3,
+        },
+        0b1000111 => RiscVInstruction::FMsub {
+            precision,
+            rounding,
+            dest,
+            arg1,
+            arg2,
+            arg3,
+        },
+        0b1001011 => RiscVInstruction::FNmsub {
+            precision,
+            rounding,
+            dest,
+            arg1,
+            arg2,
+            arg3,
+        },
+        _ => RiscVInstruction::FNmadd {
+            precision,
+            rounding,
+            dest,
+            arg1,
+            arg2,
+            arg3,
+        },
+    })
+}
+
+/// Give each `auipc` the label of the address accessed through its
+/// register, and each instruction using that register as a base its offset
+/// from the label. An `auipc` nothing uses takes the label of its own
+/// result.
+///
+/// Uses are looked for up to the next branch target, control transfer or
+/// write of the register.
+fn pair_auipc(decoded: &mut [Decoded]) {
+    let targets: HashSet<u64> = decoded
+        .iter()
+        .filter_map(|decoded| decoded.target)
+        .collect();
+
+    for index in 0..decoded.len() {
+        let (before, after) = decoded.split_at_mut(index + 1);
+        let auipc = &mut before[index];
+        let RiscVInstruction::Auipc {
+            dest,
+            src: RiscVVal::Immediate(imm),
+        } = auipc.instr
+        else {
+            continue;
+        };
+        let upper = auipc
+            .address
+            .wrapping_add_signed(((imm as u32) << 12) as i32 as i64);
+
+        let mut target = None;
+        for user in after {
+            if targets.contains(&user.address) {
+                break;
+            }
+            if let Some(offset) = base_offset(&mut user.instr, dest) {
+                let address = upper.wrapping_add_signed((*offset).into());
+                let target = *target.get_or_insert(address);
+                *offset = address.wrapping_sub(target) as i32;
+            }
+            if ends_pairing(&user.instr, dest) {
+                break;
+            }
+        }
+
+        let target = target.unwrap_or(upper);
+        auipc.instr = RiscVInstruction::Auipc {
+            dest,
+            src: label(target),
+        };
+        auipc.target = Some(target);
+    }
+}
+
+/// The offset added to `base` when `riscv_instr` uses it as an address.
+fn base_offset(riscv_instr: &mut RiscVInstruction, base: RiscVRegister) -> Option<&mut i32> {
+    match riscv_instr {
+        RiscVInstruction::Addi { src, imm, .. } if *src == base => Some(imm),
+        RiscVInstruction::Jalr { target, offset, .. } if *target == base => Some(offset),
+        RiscVInstruction::L { src: val, .. }
+        | RiscVInstruction::S { dest: val, .. }
+        | RiscVInstruction::FL { src: val, .. }
+        | RiscVInstruction::FS { dest: val, .. } => match val {
+            RiscVVal::Offset { register, offset } if *register == base => Some(offset),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+/// Whether `riscv_instr` leaves straight line code, or overwrites `reg`.
+fn ends_pairing(riscv_instr: &RiscVInstruction, reg: RiscVRegister) -> bool {
+    match riscv_instr {
+        RiscVInstruction::Beq { .. }
+        | RiscVInstruction::Bne { .. }
+        | RiscVInstruction::Blt { .. }
+        | RiscVInstruction::Bge { .. }
+        | RiscVInstruction::Bltu { .. }
+        | RiscVInstruction::Bgeu { .. }
+        | RiscVInstruction::Jal { .. }
+        | RiscVInstruction::Jalr { .. }
+        | RiscVInstruction::ECall => true,
+        RiscVInstruction::Addi { dest, .. }
+        | RiscVInstruction::Addiw { dest, .. }
+        | RiscVInstruction::Add { dest, .. }
+        | RiscVInstruction::Sub { dest, .. }
+        | RiscVInstruction::Mul { dest, .. }
+        | RiscVInstruction::Mulh { dest, .. }
+        | RiscVInstruction::Mulhu { dest, .. }
+        | RiscVInstruction::Mulhsu { dest, .. }
+        | RiscVInstruction::Div { dest, .. }
+        | RiscVInstruction::Divu { dest, .. }
+        | RiscVInstruction::Rem { dest, .. }
+        | RiscVInstruction::Remu { dest, .. }
+        | RiscVInstruction::And { dest, .. }
+        | RiscVInstruction::Or { dest, .. }
+        | RiscVInstruction::Xor { dest, .. }
+        | RiscVInstruction::Andi { dest, .. }
+        | RiscVInstruction::Ori { dest, .. }
+        | RiscVInstruction::Xori { dest, .. }
+        | RiscVInstruction::Sll { dest, .. }
+        | RiscVInstruction::Srl { dest, .. }
+        | RiscVInstruction::Sra { dest, .. }
+        | RiscVInstruction::Slli { dest, .. }
+        | RiscVInstruction::Srli { dest, .. }
+        | RiscVInstruction::Srai { dest, .. }
+        | RiscVInstruction::Slt { dest, .. }
+        | RiscVInstruction::Sltu { dest, .. }
+        | RiscVInstruction::Slti { dest, .. }
+        | RiscVInstruction::Sltiu { dest, .. }
+        | RiscVInstruction::L { dest, .. }
+        | RiscVInstruction::Lr { dest, .. }
+        | RiscVInstruction::Sc { dest, .. }
+        | RiscVInstruction::Amo { dest, .. }
+        | RiscVInstruction::FEq { dest, .. }
+        | RiscVInstruction::FLt { dest, .. }
+        | RiscVInstruction::FLe { dest, .. }
+        | RiscVInstruction::FClass { dest, .. }
+        | RiscVInstruction::FMvToInt { dest, .. }
+        | RiscVInstruction::FCvtToInt { dest, .. }
+        | RiscVInstruction::Lui { dest, .. }
+        | RiscVInstruction::Auipc { dest, .. } => *dest == reg,
+        _ => false,
+    }
+}
+
+/// Bits `high` to `low` of `word`, inclusive.
+pub(crate) fn bits(word: u32, high: u32, low: u32) -> u32 {
+    (word >> low) & (u32::MAX >> (31 - (high - low)))
+}
+
+/// The immediate of I-type instructions, `addi` and loads.
+fn i_imm(word: u32) -> i32 {
+    word as i32 >> 20
+}
+
+/// The immediate of S-type instructions, stores.
+fn s_imm(word: u32) -> i32 {
+    (word as i32 >> 25 << 5) | bits(word, 11, 7) as i32
+}
+
+/// The offset of B-type instructions, branches.
+fn b_imm(word: u32) -> i32 {
+    (word as i32 >> 31 << 12)
+        | (bits(word, 7, 7) << 11) as i32
+        | (bits(word, 30, 25) << 5) as i32
+        | (bits(word, 11, 8) << 1) as i32
+}
+
+/// The upper 20 bits of U-type instructions, `lui` and `auipc`.
+fn u_imm(word: u32) -> i32 {
+    bits(word, 31, 12) as i32
+}
+
+/// The offset of J-type instructions, `jal`.
+fn j_imm(word: u32) -> i32 {
+    (word as i32 >> 31 << 20)
+        | (bits(word, 19, 12) << 12) as i32
+        | (bits(word, 20, 20) << 11) as i32
+        | (bits(word, 30, 21) << 1) as i32
+}
+
+fn offset(register: RiscVRegister, offset: i32) -> RiscVVal {
+    RiscVVal::Offset { register, offset }
+}
+
+fn label(address: u64) -> RiscVVal {
+    RiscVVal::LabelOffset {
+        label: label_name(address),
+        offset: 0,
+    }
+}
+
+fn register(number: u32) -> RiscVRegister {
+    format!("x{}", number)
+        .parse()
+        .expect("register numbers are 5 bits")
+}
+
+fn fregister(number: u32) -> RiscVFRegister {
+    format!("f{}", number)
+        .parse()
+        .expect("register numbers are 5 bits")
+}
+
+/// The `fmt` field of floating point instructions.
+fn precision(fmt: u32) -> Result<RiscVPrecision, &'static str> {
+    match fmt {
+        0 => Ok(RiscVPrecision::Single),
+        1 => Ok(RiscVPrecision::Double),
+        // half and quad precision
+        _ => Err("unsupported instruction"),
+    }
+}
+
+/// The width of `flw` and `fld`, in the `funct3` field.
+fn access_precision(funct3: u32) -> Result<RiscVPrecision, &'static str> {
+    match funct3 {
+        2 => Ok(RiscVPrecision::Single),
+        3 => Ok(RiscVPrecision::Double),
+        _ => Err("unsupported instruction"),
+    }
+}
+
+/// The `rm` field of floating point instructions.
+fn rounding(rm: u32) -> Result<RiscVRounding, &'static str> {
+    match rm {
+        0 => Ok(RiscVRounding::Rne),
+        1 => Ok(RiscVRounding::Rtz),
+        2 => Ok(RiscVRounding::Rdn),
+        3 => Ok(RiscVRounding::Rup),
+        4 => Ok(RiscVRounding::Rmm),
+        7 => Ok(RiscVRounding::Dyn),
+        _ => Err("invalid rounding mode"),
+    }
+}
+
+/// The integer format of `fcvt`, in its `rs2` field: `w`, `wu`, `l` or
+/// `lu`.
+fn int_format(rs2: u32) -> (RiscVWidth, bool) {
+    let width = match rs2 {
+        0 | 1 => RiscVWidth::Word,
+        _ => RiscVWidth::Double,
+    };
+    (width, rs2 & 1 == 0)
+}
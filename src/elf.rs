@@ -0,0 +1,896 @@
+//! Read RV64 ELF files, both relocatable objects and static executables.
+//!
+//! [`read_elf`] extracts the sections of code and data, the symbol table,
+//! the `R_RISCV_*` relocations of those sections and the entry point.
+//! [`Elf::instructions`] then decodes the code with
+//! [`decode`](crate::decode) and turns everything else into the labels and
+//! directives an assembler would have read, so the program is translated
+//! like assembly:
+//!
+//! - symbols become labels, and other addresses that are referred to get
+//!   the synthetic labels of [`label_name`]
+//! - relocations of code become relocation operands, such as
+//!   `%pcrel_hi(message)`, and the `auipc` and `jalr` of a `call` become a
+//!   `jal`
+//! - data is copied with `.byte`, with a `.quad` or `.word` of the label
+//!   of each absolute relocation, and `.bss` becomes `.zero`
+//!
+//! Executables have no relocations left, and only the addresses found from
+//! an `auipc` are turned back into labels. An address made by `lui` or
+//! stored in data would still refer to where it was in the RISC-V program,
+//! so they are errors.
+//!
+//! The format is described in
+//! https://github.com/riscv-non-isa/riscv-elf-psabi-doc/blob/master/riscv-elf.adoc
+use std::collections::{BTreeSet, HashMap, HashSet};
+
+use crate::decode::{
+    check_targets, decode_instructions, error, label_instructions, label_name, target_val, Decoded,
+};
+use crate::error::AsmError;
+use crate::instruction::{RiscVInstruction, RiscVReloc, RiscVVal};
+use crate::parse::is_symbol_char;
+
+/// The parts of an ELF file that are translated.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Elf {
+    /// Whether this is an object file, `ET_REL`, whose sections are given
+    /// consecutive addresses from 0
+    pub relocatable: bool,
+    /// `e_entry`, the address execution starts at
+    pub entry: u64,
+    pub sections: Vec<Section>,
+    /// The symbol table, in order, so [`Relocation::symbol`] indexes it
+    pub symbols: Vec<Symbol>,
+}
+
+/// What a [`Section`] holds, from its flags.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SectionKind {
+    /// Executable code, like `.text`
+    Text,
+    /// Constants, like `.rodata`
+    ReadOnly,
+    /// Writable data, like `.data`
+    Data,
+    /// Zeroed data, like `.bss`, which has no contents in the file
+    Bss,
+}
+
+/// A section which is loaded into memory.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Section {
+    pub name: String,
+    pub kind: SectionKind,
+    pub address: u64,
+    pub size: u64,
+    pub align: u64,
+    /// The contents, empty for [`SectionKind::Bss`]
+    pub data: Vec<u8>,
+    pub relocations: Vec<Relocation>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Symbol {
+    pub name: String,
+    /// The address, which is in the address of its section for objects
+    pub address: u64,
+    pub size: u64,
+    /// Whether the binding is global or weak
+    pub global: bool,
+    /// Whether this is `STT_SECTION`, the symbol of its section itself
+    pub is_section: bool,
+    /// The index in [`Elf::sections`] of the section the symbol is in, or
+    /// `None` when it is undefined or in a section that isn't read
+    pub section: Option<usize>,
+}
+
+/// A relocation with an addend, `Elf64_Rela`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Relocation {
+    /// The address of the relocated instruction or data
+    pub address: u64,
+    pub kind: RelocationKind,
+    /// The index in [`Elf::symbols`]
+    pub symbol: usize,
+    pub addend: i64,
+}
+
+/// The `R_RISCV_*` relocation types.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RelocationKind {
+    /// `R_RISCV_32`
+    Abs32,
+    /// `R_RISCV_64`
+    Abs64,
+    /// `R_RISCV_BRANCH`, a conditional branch
+    Branch,
+    /// `R_RISCV_JAL`
+    Jal,
+    /// `R_RISCV_CALL`, an `auipc` and `jalr` pair
+    Call,
+    /// `R_RISCV_CALL_PLT`, a [`RelocationKind::Call`] through the PLT
+    CallPlt,
+    /// `R_RISCV_GOT_HI20`, `%got_pcrel_hi`
+    GotHi20,
+    /// `R_RISCV_PCREL_HI20`, `%pcrel_hi`
+    PcrelHi20,
+    /// `R_RISCV_PCREL_LO12_I`, `%pcrel_lo` of a load or `addi`
+    PcrelLo12I,
+    /// `R_RISCV_PCREL_LO12_S`, `%pcrel_lo` of a store
+    PcrelLo12S,
+    /// `R_RISCV_HI20`, `%hi`
+    Hi20,
+    /// `R_RISCV_LO12_I`, `%lo` of a load or `addi`
+    Lo12I,
+    /// `R_RISCV_LO12_S`, `%lo` of a store
+    Lo12S,
+    /// `R_RISCV_RVC_BRANCH`, `c.beqz` and `c.bnez`
+    RvcBranch,
+    /// `R_RISCV_RVC_JUMP`, `c.j`
+    RvcJump,
+    /// `R_RISCV_ALIGN`, padding the linker may remove
+    Align,
+    /// `R_RISCV_RELAX`, an instruction the linker may relax
+    Relax,
+    /// Any other type
+    Other(u32),
+}
+
+impl From<u32> for RelocationKind {
+    fn from(kind: u32) -> Self {
+        match kind {
+            1 => Self::Abs32,
+            2 => Self::Abs64,
+            16 => Self::Branch,
+            17 => Self::Jal,
+            18 => Self::Call,
+            19 => Self::CallPlt,
+            20 => Self::GotHi20,
+            23 => Self::PcrelHi20,
+            24 => Self::PcrelLo12I,
+            25 => Self::PcrelLo12S,
+            26 => Self::Hi20,
+            27 => Self::Lo12I,
+            28 => Self::Lo12S,
+            43 => Self::Align,
+            44 => Self::RvcBranch,
+            45 => Self::RvcJump,
+            51 => Self::Relax,
+            _ => Self::Other(kind),
+        }
+    }
+}
+
+const ET_REL: u16 = 1;
+const ET_EXEC: u16 = 2;
+const EM_RISCV: u16 = 243;
+const SHT_PROGBITS: u32 = 1;
+const SHT_SYMTAB: u32 = 2;
+const SHT_RELA: u32 = 4;
+const SHT_NOBITS: u32 = 8;
+const SHF_WRITE: u64 = 0x1;
+const SHF_ALLOC: u64 = 0x2;
+const SHF_EXECINSTR: u64 = 0x4;
+const SHF_TLS: u64 = 0x400;
+const STB_GLOBAL: u8 = 1;
+const STB_WEAK: u8 = 2;
+const STT_SECTION: u8 = 3;
+/// The largest `.bss` that is read, far more than any real program needs.
+const MAX_BSS_SIZE: u64 = 1 << 32;
+
+/// `Elf64_Shdr`, with the fields that are used.
+struct SectionHeader {
+    name: u32,
+    kind: u32,
+    flags: u64,
+    address: u64,
+    offset: u64,
+    size: u64,
+    link: u32,
+    info: u32,
+    align: u64,
+}
+
+/// Read the sections, symbols and relocations of an RV64 ELF file.
+///
+/// Only the allocated sections of code and data are read, other than
+/// thread local storage. Errors have no line or column, and their text is
+/// the part of the file that is invalid.
+pub fn read_elf(bytes: &[u8]) -> Result<Elf, AsmError> {
+    if !bytes.starts_with(b"\x7fELF") {
+        return Err(invalid("header", "not an ELF file"));
+    }
+    if bytes.get(4) != Some(&2) {
+        return Err(invalid("EI_CLASS", "not a 64 bit ELF file"));
+    }
+    if bytes.get(5) != Some(&1) {
+        return Err(invalid("EI_DATA", "not a little endian ELF file"));
+    }
+    let relocatable = match read_u16(bytes, 0x10)? {
+        ET_REL => true,
+        ET_EXEC => false,
+        kind => {
+            return Err(invalid(
+                &format!("e_type {}", kind),
+                "not an object file or static executable",
+            ))
+        }
+    };
+    let machine = read_u16(bytes, 0x12)?;
+    if machine != EM_RISCV {
+        return Err(invalid(
+            &format!("e_machine {}", machine),
+            "not a RISC-V ELF file",
+        ));
+    }
+    let entry = read_u64(bytes, 0x18)?;
+    let header_offset = read_u64(bytes, 0x28)?;
+    let header_size = u64::from(read_u16(bytes, 0x3a)?);
+    let header_count = u64::from(read_u16(bytes, 0x3c)?);
+    let names_index = usize::from(read_u16(bytes, 0x3e)?);
+
+    let headers = (0..header_count)
+        .map(|index| {
+            let offset = header_offset + index * header_size;
+            Ok(SectionHeader {
+                name: read_u32(bytes, offset)?,
+                kind: read_u32(bytes, offset + 0x4)?,
+                flags: read_u64(bytes, offset + 0x8)?,
+                address: read_u64(bytes, offset + 0x10)?,
+                offset: read_u64(bytes, offset + 0x18)?,
+                size: read_u64(bytes, offset + 0x20)?,
+                link: read_u32(bytes, offset + 0x28)?,
+                info: read_u32(bytes, offset + 0x2c)?,
+                align: read_u64(bytes, offset + 0x30)?,
+            })
+        })
+        .collect::<Result<Vec<_>, AsmError>>()?;
+    let names = headers
+        .get(names_index)
+        .ok_or_else(|| invalid("e_shstrndx", "no section names"))?;
+
+    // the index in `sections` of each section header that is read
+    let mut indices = HashMap::new();
+    let mut sections = vec![];
+    let mut next_address: u64 = 0;
+    for (index, header) in headers.iter().enumerate() {
+        let loaded = header.flags & SHF_ALLOC != 0 && header.flags & SHF_TLS == 0;
+        if !loaded || !matches!(header.kind, SHT_PROGBITS | SHT_NOBITS) {
+            continue;
+        }
+        let name = read_string(bytes, names, header.name)?;
+        let kind = if header.kind == SHT_NOBITS {
+            SectionKind::Bss
+        } else if header.flags & SHF_EXECINSTR != 0 {
+            SectionKind::Text
+        } else if header.flags & SHF_WRITE != 0 {
+            SectionKind::Data
+        } else {
+            SectionKind::ReadOnly
+        };
+        if kind == SectionKind::Bss && header.size > MAX_BSS_SIZE {
+            return Err(invalid(&name, "section is too large"));
+        }
+        let align = header.align.max(1);
+        let address = if relocatable {
+            next_address.checked_next_multiple_of(align)
+        } else {
+            Some(header.address)
+        }
+        .filter(|address| address.checked_add(header.size).is_some())
+        .ok_or_else(|| invalid(&name, "section doesn't fit in the address space"))?;
+        next_address = address + header.size;
+        let data = match kind {
+            SectionKind::Bss => vec![],
+            _ => read_bytes(bytes, header.offset, header.size, &name)?.to_vec(),
+        };
+        indices.insert(index, sections.len());
+        sections.push(Section {
+            name,
+            kind,
+            address,
+            size: header.size,
+            align,
+            data,
+            relocations: vec![],
+        });
+    }
+
+    let mut symbols = vec![];
+    if let Some(table) = headers.iter().find(|header| header.kind == SHT_SYMTAB) {
+        let strings = headers
+            .get(table.link as usize)
+            .ok_or_else(|| invalid(".symtab", "no symbol names"))?;
+        let entries = read_bytes(bytes, table.offset, table.size, ".symtab")?;
+        for entry in entries.chunks_exact(24) {
+            let info = entry[4];
+            let section = indices.get(&usize::from(read_u16(entry, 6)?)).copied();
+            let value = read_u64(entry, 8)?;
+            symbols.push(Symbol {
+                name: read_string(bytes, strings, read_u32(entry, 0)?)?,
+                address: match section {
+                    Some(section) if relocatable => sections[section].address + value,
+                    _ => value,
+                },
+                size: read_u64(entry, 16)?,
+                global: matches!(info >> 4, STB_GLOBAL | STB_WEAK),
+                is_section: info & 0xf == STT_SECTION,
+                section,
+            });
+        }
+    }
+
+    for header in headers.iter().filter(|header| header.kind == SHT_RELA) {
+        let Some(&section) = indices.get(&(header.info as usize)) else {
+            continue;
+        };
+        let base = if relocatable {
+            sections[section].address
+        } else {
+            0
+        };
+        let entries = read_bytes(bytes, header.offset, header.size, ".rela")?;
+        for entry in entries.chunks_exact(24) {
+            let info = read_u64(entry, 8)?;
+            sections[section].relocations.push(Relocation {
+                address: base + read_u64(entry, 0)?,
+                kind: RelocationKind::from(info as u32),
+                symbol: (info >> 32) as usize,
+                addend: read_u64(entry, 16)? as i64,
+            });
+        }
+    }
+
+    Ok(Elf {
+        relocatable,
+        entry,
+        sections,
+        symbols,
+    })
+}
+
+impl Elf {
+    /// The program as the instructions, labels and directives of its
+    /// assembly, with each section in turn.
+    ///
+    /// Errors are those of [`decode_instructions`], and relocations which
+    /// can't be expressed in assembly. Their `line` is the position of the
+    /// instruction in its section.
+    pub fn instructions(&self) -> Result<Vec<RiscVInstruction>, Vec<AsmError>> {
+        let names = self.names();
+        let name = |address| {
+            names
+                .get(&address)
+                .cloned()
+                .unwrap_or_else(|| label_name(address))
+        };
+        let mut labels: HashSet<u64> = names.keys().copied().collect();
+        let mut errors = vec![];
+
+        // relocate everything first, since code refers to data and data to
+        // code
+        let mut code = HashMap::new();
+        for (index, section) in self.sections.iter().enumerate() {
+            if section.kind != SectionKind::Text {
+                for relocation in &section.relocations {
+                    let result = match relocation.kind {
+                        RelocationKind::Abs32 | RelocationKind::Abs64 => {
+                            self.resolve(relocation, name, &mut labels).map(|_| ())
+                        }
+                        RelocationKind::Relax | RelocationKind::Align => Ok(()),
+                        _ => Err("unsupported relocation"),
+                    };
+                    if let Err(reason) = result {
+                        errors.push(relocation_error(0, relocation, reason));
+                    }
+                }
+                continue;
+            }
+            let mut decoded = match decode_instructions(&section.data, section.address) {
+                Ok(decoded) => decoded,
+                Err(decode_errors) => {
+                    errors.extend(decode_errors);
+                    continue;
+                }
+            };
+            errors.extend(self.relocate(section, &mut decoded, name, &mut labels));
+            let end = section.address + section.size;
+            if let Err(target_errors) = check_targets(&decoded, section.address..end) {
+                errors.extend(target_errors);
+            }
+            labels.extend(decoded.iter().filter_map(|decoded| decoded.target));
+            code.insert(index, decoded);
+        }
+        if !self.relocatable {
+            errors.extend(self.absolute_addresses(&code));
+        }
+        // a label in the middle of an instruction would be lost
+        for (&index, decoded) in &code {
+            let section = &self.sections[index];
+            let starts: HashSet<u64> = decoded.iter().map(|decoded| decoded.address).collect();
+            let mut inside: Vec<u64> = labels
+                .iter()
+                .copied()
+                .filter(|address| {
+                    (section.address..section.address + section.size).contains(address)
+                        && !starts.contains(address)
+                })
+                .collect();
+            inside.sort();
+            errors.extend(inside.into_iter().map(|address| {
+                AsmError::new(0, 0, &name(address), "label is inside of an instruction")
+            }));
+        }
+        if !errors.is_empty() {
+            return Err(errors);
+        }
+
+        let mut riscv_instrs = vec![];
+        for (index, section) in self.sections.iter().enumerate() {
+            let (flags, kind) = match section.kind {
+                SectionKind::Text => ("ax", "progbits"),
+                SectionKind::ReadOnly => ("a", "progbits"),
+                SectionKind::Data => ("aw", "progbits"),
+                SectionKind::Bss => ("aw", "nobits"),
+            };
+            riscv_instrs.push(directive(
+                "section",
+                format!("{},\"{}\",@{}", section.name, flags, kind),
+            ));
+            // every AArch64 instruction is 4 bytes, even where RISC-V ones
+            // are compressed
+            let align = match section.kind {
+                SectionKind::Text => section.align.max(4),
+                _ => section.align,
+            };
+            if align > 1 {
+                riscv_instrs.push(directive("balign", align.to_string()));
+            }
+            let end = section.address + section.size;
+            let mut globals: Vec<&str> = vec![];
+            for symbol in &self.symbols {
+                let global = symbol.global && symbol.section == Some(index);
+                if global && names.get(&symbol.address) == Some(&symbol.name) {
+                    globals.push(&symbol.name);
+                }
+            }
+            if !self.relocatable && (section.address..end).contains(&self.entry) {
+                globals.extend(names.get(&self.entry).map(String::as_str));
+            }
+            let mut seen = HashSet::new();
+            globals.retain(|global| seen.insert(*global));
+            for global in globals {
+                riscv_instrs.push(directive("globl", global.to_string()));
+            }
+            match code.remove(&index) {
+                Some(decoded) => riscv_instrs.extend(label_instructions(decoded, &labels, name)),
+                None => riscv_instrs.extend(self.data(section, &labels, name)),
+            }
+        }
+        Ok(riscv_instrs)
+    }
+
+    /// The label of each address with a symbol, preferring global symbols,
+    /// and `_start` for an entry point without one.
+    ///
+    /// Every name is used once, so a local symbol with the same name as
+    /// another is left to [`label_name`], as is one whose name isn't a
+    /// label in assembly, such as the `.L0 ` that GNU `as` names the
+    /// anchors of `la` and `call`.
+    fn names(&self) -> HashMap<u64, String> {
+        let mut symbols: Vec<&Symbol> = self
+            .symbols
+            .iter()
+            .filter(|symbol| {
+                // `$x` and `$d` are mapping symbols, which mark code and data
+                symbol.section.is_some()
+                    && !symbol.is_section
+                    && !symbol.name.is_empty()
+                    && !symbol.name.starts_with('$')
+                    && symbol.name.chars().all(is_symbol_char)
+                    && !symbol.name.starts_with(|c: char| c.is_ascii_digit())
+            })
+            .collect();
+        symbols.sort_by_key(|symbol| !symbol.global);
+
+        let mut names = HashMap::new();
+        let mut used = HashSet::new();
+        for symbol in symbols {
+            if !names.contains_key(&symbol.address) && used.insert(symbol.name.as_str()) {
+                names.insert(symbol.address, symbol.name.clone());
+            }
+        }
+        if !self.relocatable && !names.contains_key(&self.entry) && !used.contains("_start") {
+            names.insert(self.entry, "_start".to_string());
+        }
+        names
+    }
+
+    /// The `lui`s and data of an executable which look like an absolute
+    /// address in the program, since they can't be relocated.
+    ///
+    /// A `lui` makes the upper bits of an address, which may be up to 2 KiB
+    /// away from it, and a pointer in data is 8 byte aligned. A constant
+    /// which happens to be in the same range is also reported.
+    fn absolute_addresses(&self, code: &HashMap<usize, Vec<Decoded>>) -> Vec<AsmError> {
+        let reason = "absolute address in an executable";
+        let near = |start: u64, end: u64| {
+            self.sections
+                .iter()
+                .any(|section| start < section.address + section.size && section.address < end)
+        };
+        let mut errors = vec![];
+        let mut indices: Vec<usize> = code.keys().copied().collect();
+        indices.sort();
+        for index in indices {
+            for (line, decoded) in code[&index].iter().enumerate() {
+                if let RiscVInstruction::Lui {
+                    src: RiscVVal::Immediate(imm),
+                    ..
+                } = decoded.instr
+                {
+                    let upper = (imm << 12) as i64 as u64;
+                    if near(upper.saturating_sub(0x800), upper.saturating_add(0x800)) {
+                        errors.push(error(line, decoded.address, decoded.raw, reason));
+                    }
+                }
+            }
+        }
+        for section in &self.sections {
+            if matches!(section.kind, SectionKind::Text | SectionKind::Bss) {
+                continue;
+            }
+            let first = section.address.next_multiple_of(8);
+            for address in (first..section.address + section.size).step_by(8) {
+                let offset = (address - section.address) as usize;
+                let Some(bytes) = section.data.get(offset..offset + 8) else {
+                    break;
+                };
+                let value = u64::from_le_bytes(bytes.try_into().unwrap());
+                if near(value, value.saturating_add(1)) {
+                    let text = format!("{:x}: {:#x}", address, value);
+                    errors.push(AsmError::new(0, 0, &text, reason));
+                }
+            }
+        }
+        errors
+    }
+
+    /// The label and offset `relocation` refers to, adding the address of
+    /// the label to `labels` when it is in the program.
+    fn resolve(
+        &self,
+        relocation: &Relocation,
+        name: impl Fn(u64) -> String,
+        labels: &mut HashSet<u64>,
+    ) -> Result<(String, i32), &'static str> {
+        let symbol = self
+            .symbols
+            .get(relocation.symbol)
+            .ok_or("invalid symbol index")?;
+        let (label, offset) = if symbol.section.is_none() {
+            if symbol.name.is_empty() {
+                return Err("relocation of an unnamed symbol");
+            }
+            (symbol.name.clone(), relocation.addend)
+        } else if symbol.is_section
+            || symbol
+                .section
+                .is_some_and(|index| self.sections[index].kind == SectionKind::Text)
+        {
+            // local labels are often left out of the symbol table, and
+            // referred to from the start of their section. Offsets into code
+            // are RISC-V bytes, which don't match the translation, so code
+            // gets a label of its own
+            let address = symbol.address.wrapping_add_signed(relocation.addend);
+            labels.insert(address);
+            (name(address), 0)
+        } else {
+            labels.insert(symbol.address);
+            (name(symbol.address), relocation.addend)
+        };
+        let offset = i32::try_from(offset).map_err(|_| "relocation addend is out of range")?;
+        Ok((label, offset))
+    }
+
+    /// Apply the relocations of `section` to its instructions, replacing the
+    /// targets found by decoding.
+    fn relocate(
+        &self,
+        section: &Section,
+        decoded: &mut Vec<Decoded>,
+        name: impl Fn(u64) -> String,
+        labels: &mut HashSet<u64>,
+    ) -> Vec<AsmError> {
+        let indices: HashMap<u64, usize> = decoded
+            .iter()
+            .enumerate()
+            .map(|(index, decoded)| (decoded.address, index))
+            .collect();
+        let mut errors = vec![];
+        let mut removed = HashSet::new();
+
+        for relocation in &section.relocations {
+            if matches!(
+                relocation.kind,
+                RelocationKind::Relax | RelocationKind::Align
+            ) {
+                continue;
+            }
+            let Some(&index) = indices.get(&relocation.address) else {
+                errors.push(relocation_error(
+                    0,
+                    relocation,
+                    "relocation is inside of an instruction",
+                ));
+                continue;
+            };
+            let result = self
+                .resolve(relocation, &name, labels)
+                .and_then(|(label, offset)| {
+                    if matches!(
+                        relocation.kind,
+                        RelocationKind::PcrelHi20 | RelocationKind::GotHi20
+                    ) {
+                        // the anchor of the `%pcrel_lo`s
+                        labels.insert(relocation.address);
+                    }
+                    relocate_instruction(decoded, index, relocation.kind, label, offset)
+                });
+            match result {
+                Ok(true) => {
+                    removed.insert(index + 1);
+                }
+                Ok(false) => {}
+                Err(reason) => errors.push(relocation_error(index + 1, relocation, reason)),
+            }
+        }
+
+        let mut index = 0;
+        decoded.retain(|_| {
+            index += 1;
+            !removed.contains(&(index - 1))
+        });
+        errors
+    }
+
+    /// The directives of the contents of a data section, with the labels
+    /// in it.
+    fn data(
+        &self,
+        section: &Section,
+        labels: &HashSet<u64>,
+        name: impl Fn(u64) -> String,
+    ) -> Vec<RiscVInstruction> {
+        let relocations: HashMap<u64, &Relocation> = section
+            .relocations
+            .iter()
+            .filter(|relocation| {
+                matches!(
+                    relocation.kind,
+                    RelocationKind::Abs32 | RelocationKind::Abs64
+                )
+            })
+            .map(|relocation| (relocation.address, relocation))
+            .collect();
+        // where a label or relocation ends a run of data
+        let stops: BTreeSet<u64> = labels.iter().chain(relocations.keys()).copied().collect();
+        let mut riscv_instrs = vec![];
+        let mut pending = vec![];
+        let end = section.address + section.size;
+        let mut address = section.address;
+        while address < end {
+            if labels.contains(&address) {
+                flush_data(&mut riscv_instrs, &mut pending);
+                riscv_instrs.push(RiscVInstruction::Label {
+                    name: name(address),
+                });
+            }
+            if let Some(relocation) = relocations.get(&address) {
+                flush_data(&mut riscv_instrs, &mut pending);
+                // the labels were added when relocating
+                let (label, offset) = self
+                    .resolve(relocation, &name, &mut HashSet::new())
+                    .expect("data relocations were checked");
+                let (size, width) = match relocation.kind {
+                    RelocationKind::Abs32 => (4, "word"),
+                    _ => (8, "quad"),
+                };
+                riscv_instrs.push(directive(width, label_operand(&label, offset)));
+                address += size;
+                continue;
+            }
+            if section.kind == SectionKind::Bss {
+                // zeros up to the next label or relocation
+                let next = stops.range(address + 1..end).next().copied().unwrap_or(end);
+                riscv_instrs.push(directive("zero", (next - address).to_string()));
+                address = next;
+                continue;
+            }
+            let offset = (address - section.address) as usize;
+            pending.push(section.data.get(offset).copied().unwrap_or(0));
+            if pending.len() == 16 {
+                flush_data(&mut riscv_instrs, &mut pending);
+            }
+            address += 1;
+        }
+        flush_data(&mut riscv_instrs, &mut pending);
+        riscv_instrs
+    }
+}
+
+/// Apply a relocation to the instruction at `index`, which is `Ok(true)`
+/// when it also replaced the next instruction.
+fn relocate_instruction(
+    decoded: &mut [Decoded],
+    index: usize,
+    kind: RelocationKind,
+    label: String,
+    offset: i32,
+) -> Result<bool, &'static str> {
+    let mismatch = "relocation doesn't match the instruction";
+    let reloc = |reloc| RiscVVal::Reloc {
+        reloc,
+        label: label.clone(),
+        offset,
+    };
+    let reloc_offset = |val: &mut RiscVVal, reloc| match val {
+        RiscVVal::Offset { register, .. } => {
+            *val = RiscVVal::RelocOffset {
+                register: *register,
+                reloc,
+                label: label.clone(),
+                offset,
+            };
+            Ok(())
+        }
+        _ => Err(mismatch),
+    };
+    let lo12 = match kind {
+        RelocationKind::PcrelLo12I | RelocationKind::PcrelLo12S => RiscVReloc::PcrelLo12,
+        _ => RiscVReloc::Lo12,
+    };
+
+    decoded[index].target = None;
+    let riscv_instr = &mut decoded[index].instr;
+    match (kind, &mut *riscv_instr) {
+        (
+            RelocationKind::Branch
+            | RelocationKind::Jal
+            | RelocationKind::RvcBranch
+            | RelocationKind::RvcJump,
+            _,
+        ) => {
+            let target = target_val(riscv_instr).ok_or(mismatch)?;
+            *target = RiscVVal::LabelOffset {
+                label: label.clone(),
+                offset,
+            };
+        }
+        (RelocationKind::Call | RelocationKind::CallPlt, RiscVInstruction::Auipc { dest, .. }) => {
+            let link = *dest;
+            let Some(RiscVInstruction::Jalr { dest, target, .. }) =
+                decoded.get(index + 1).map(|decoded| &decoded.instr)
+            else {
+                return Err(mismatch);
+            };
+            if *target != link {
+                return Err(mismatch);
+            }
+            decoded[index].instr = RiscVInstruction::Jal {
+                dest: *dest,
+                target: RiscVVal::LabelOffset { label, offset },
+            };
+            return Ok(true);
+        }
+        (RelocationKind::PcrelHi20, RiscVInstruction::Auipc { src, .. }) => {
+            *src = reloc(RiscVReloc::PcrelHi20)
+        }
+        (RelocationKind::GotHi20, RiscVInstruction::Auipc { src, .. }) => {
+            *src = reloc(RiscVReloc::GotPcrelHi20)
+        }
+        (RelocationKind::Hi20, RiscVInstruction::Lui { src, .. }) => *src = reloc(RiscVReloc::Hi20),
+        (
+            RelocationKind::PcrelLo12I | RelocationKind::Lo12I,
+            RiscVInstruction::Addi { dest, src, .. },
+        ) => {
+            *riscv_instr = RiscVInstruction::Addl {
+                dest: *dest,
+                src: *src,
+                label: reloc(lo12),
+            }
+        }
+        (
+            RelocationKind::PcrelLo12I | RelocationKind::Lo12I,
+            RiscVInstruction::L { src: val, .. } | RiscVInstruction::FL { src: val, .. },
+        )
+        | (
+            RelocationKind::PcrelLo12S | RelocationKind::Lo12S,
+            RiscVInstruction::S { dest: val, .. } | RiscVInstruction::FS { dest: val, .. },
+        ) => reloc_offset(val, lo12)?,
+        (RelocationKind::Abs32 | RelocationKind::Abs64 | RelocationKind::Other(_), _) => {
+            return Err("unsupported relocation")
+        }
+        _ => return Err(mismatch),
+    }
+    Ok(false)
+}
+
+/// Emit the bytes in `pending` as `.byte`.
+fn flush_data(riscv_instrs: &mut Vec<RiscVInstruction>, pending: &mut Vec<u8>) {
+    if pending.is_empty() {
+        return;
+    }
+    let bytes: Vec<String> = pending
+        .iter()
+        .map(|byte| format!("0x{:02x}", byte))
+        .collect();
+    riscv_instrs.push(directive("byte", bytes.join(", ")));
+    pending.clear();
+}
+
+fn directive(name: &str, operands: String) -> RiscVInstruction {
+    RiscVInstruction::Directive {
+        name: name.to_string(),
+        operands,
+    }
+}
+
+/// `label+offset`, as an operand of a directive.
+fn label_operand(label: &str, offset: i32) -> String {
+    match offset {
+        0 => label.to_string(),
+        _ => format!("{}{:+}", label, offset),
+    }
+}
+
+fn relocation_error(line: usize, relocation: &Relocation, reason: &str) -> AsmError {
+    let text = format!("{:x}: {:?}", relocation.address, relocation.kind);
+    AsmError::new(line, 0, &text, reason)
+}
+
+fn invalid(text: &str, reason: &str) -> AsmError {
+    AsmError::new(0, 0, text, reason)
+}
+
+fn read_bytes<'a>(
+    bytes: &'a [u8],
+    offset: u64,
+    size: u64,
+    what: &str,
+) -> Result<&'a [u8], AsmError> {
+    usize::try_from(offset)
+        .ok()
+        .zip(usize::try_from(size).ok())
+        .and_then(|(offset, size)| bytes.get(offset..offset.checked_add(size)?))
+        .ok_or_else(|| invalid(what, "truncated ELF file"))
+}
+
+fn read_u16(bytes: &[u8], offset: u64) -> Result<u16, AsmError> {
+    let bytes = read_bytes(bytes, offset, 2, &format!("offset {:#x}", offset))?;
+    Ok(u16::from_le_bytes(bytes.try_into().unwrap()))
+}
+
+fn read_u32(bytes: &[u8], offset: u64) -> Result<u32, AsmError> {
+    let bytes = read_bytes(bytes, offset, 4, &format!("offset {:#x}", offset))?;
+    Ok(u32::from_le_bytes(bytes.try_into().unwrap()))
+}
+
+fn read_u64(bytes: &[u8], offset: u64) -> Result<u64, AsmError> {
+    let bytes = read_bytes(bytes, offset, 8, &format!("offset {:#x}", offset))?;
+    Ok(u64::from_le_bytes(bytes.try_into().unwrap()))
+}
+
+/// The string at `offset` in the string table `table`.
+fn read_string(bytes: &[u8], table: &SectionHeader, offset: u32) -> Result<String, AsmError> {
+    let strings = read_bytes(bytes, table.offset, table.size, "string table")?;
+    let start = strings
+        .get(offset as usize..)
+        .ok_or_else(|| invalid(&format!("string {}", offset), "truncated ELF file"))?;
+    let end = start
+        .iter()
+        .position(|&byte| byte == 0)
+        .unwrap_or(start.len());
+    Ok(String::from_utf8_lossy(&start[..end]).into_owned())
+}
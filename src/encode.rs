@@ -0,0 +1,1293 @@
+//! Encode [`ArmInstruction`]s into AArch64 machine code.
+//!
+//! [`encode`] builds the 32 bit word of one instruction, given its address
+//! and the address of every label it may refer to. [`assemble`] lays out a
+//! whole translation in two passes, first sizing the sections to place the
+//! labels, then encoding with their addresses, so branches and `adrp` can
+//! refer forwards.
+//!
+//! Only the instructions and operands the text output could assemble are
+//! encoded, anything else is an error rather than a different instruction.
+//! The encodings are from the Arm Architecture Reference Manual:
+//! https://developer.arm.com/documentation/ddi0602/latest/Index-by-Encoding
+
+use std::collections::HashMap;
+
+use crate::error::AsmError;
+use crate::executable::{Image, Layout, BASE_ADDRESS};
+use crate::instruction::{
+    ArmAtomicOp, ArmBarrier, ArmCondition, ArmInstruction, ArmRegister, ArmRegisterName, ArmReloc,
+    ArmRounding, ArmSystemRegister, ArmVal, ArmWidth,
+};
+use crate::legalize::{is_arith_imm, is_logical_imm};
+
+/// The word of `arm_instr` at `address`, where `labels` has the address of
+/// every label. `.` is the address of the instruction itself.
+pub fn encode(
+    arm_instr: &ArmInstruction,
+    address: u64,
+    labels: &HashMap<String, u64>,
+) -> Result<u32, &'static str> {
+    let label = |val: &ArmVal| label_address(val, address, labels);
+    Ok(match arm_instr {
+        ArmInstruction::Adc { dest, arg1, arg2 } => {
+            same_width(&[dest, arg1, arg2])? << 31
+                | 0x1a000000
+                | reg(arg2)? << 16
+                | reg(arg1)? << 5
+                | reg(dest)?
+        }
+        ArmInstruction::Add { dest, arg1, arg2 } => {
+            add_sub(false, false, dest, arg1, arg2, labels)?
+        }
+        ArmInstruction::Sub { dest, arg1, arg2 } => add_sub(true, false, dest, arg1, arg2, labels)?,
+        ArmInstruction::Cmp { op1, op2 } => add_sub(true, true, &zr(op1), op1, op2, labels)?,
+        ArmInstruction::Cmn { op1, op2 } => add_sub(false, true, &zr(op1), op1, op2, labels)?,
+        ArmInstruction::And { dest, arg1, arg2 } => logical(0b00, dest, arg1, arg2)?,
+        ArmInstruction::Orr { dest, arg1, arg2 } => logical(0b01, dest, arg1, arg2)?,
+        ArmInstruction::Eor { dest, arg1, arg2 } => logical(0b10, dest, arg1, arg2)?,
+        ArmInstruction::Tst { op1, op2 } => logical(0b11, &zr(op1), op1, op2)?,
+        // orn rd, zr, rm
+        ArmInstruction::Mvn { dest, src } => {
+            same_width(&[dest, src])? << 31 | 0x2a2003e0 | reg(src)? << 16 | reg(dest)?
+        }
+        // sub rd, zr, rm
+        ArmInstruction::Neg { dest, src } => {
+            same_width(&[dest, src])? << 31 | 0x4b0003e0 | reg(src)? << 16 | reg(dest)?
+        }
+        ArmInstruction::Mov { dest, src, .. } => mov(dest, src)?,
+        ArmInstruction::Movk { dest, imm, shift } => {
+            if shift % 16 != 0 || *shift >= 32 << sf(dest) {
+                return Err("shift out of range");
+            }
+            sf(dest) << 31 | 0x72800000 | (shift / 16) << 21 | u32::from(*imm) << 5 | reg(dest)?
+        }
+        ArmInstruction::Lsl { dest, src, imm } => {
+            let size = 32 << same_width(&[dest, src])?;
+            let shift = shift_amount(*imm, size)?;
+            bitfield(0b10, dest, src, (size - shift) % size, size - 1 - shift)?
+        }
+        ArmInstruction::Lsr { dest, src, imm } => {
+            let size = 32 << same_width(&[dest, src])?;
+            bitfield(0b10, dest, src, shift_amount(*imm, size)?, size - 1)?
+        }
+        ArmInstruction::Asr { dest, src, imm } => {
+            let size = 32 << same_width(&[dest, src])?;
+            bitfield(0b00, dest, src, shift_amount(*imm, size)?, size - 1)?
+        }
+        // sbfm xd, xn, 0, 31
+        ArmInstruction::Sxtw { dest, src } => {
+            if sf(dest) != 1 || sf(src) != 0 {
+                return Err("sxtw is from a w register to an x register");
+            }
+            0x93407c00 | reg(src)? << 5 | reg(dest)?
+        }
+        ArmInstruction::Lslv { dest, arg1, arg2 } => data_2(0b001000, dest, arg1, arg2)?,
+        ArmInstruction::Lsrv { dest, arg1, arg2 } => data_2(0b001001, dest, arg1, arg2)?,
+        ArmInstruction::Asrv { dest, arg1, arg2 } => data_2(0b001010, dest, arg1, arg2)?,
+        ArmInstruction::Udiv { dest, arg1, arg2 } => data_2(0b000010, dest, arg1, arg2)?,
+        ArmInstruction::Sdiv { dest, arg1, arg2 } => data_2(0b000011, dest, arg1, arg2)?,
+        // madd rd, rn, rm, zr
+        ArmInstruction::Mul { dest, arg1, arg2 } => {
+            data_3(0x1b000000, dest, arg1, arg2, &zr(dest))?
+        }
+        ArmInstruction::Msub {
+            dest,
+            arg1,
+            arg2,
+            arg3,
+        } => data_3(0x1b008000, dest, arg1, arg2, arg3)?,
+        ArmInstruction::Smulh { dest, arg1, arg2 } => high_multiply(0x9b400000, dest, arg1, arg2)?,
+        ArmInstruction::Umulh { dest, arg1, arg2 } => high_multiply(0x9bc00000, dest, arg1, arg2)?,
+        ArmInstruction::Csel {
+            dest,
+            arg1,
+            arg2,
+            cond,
+        } => select(0x1a800000, dest, arg1, arg2, *cond)?,
+        ArmInstruction::Csinc {
+            dest,
+            arg1,
+            arg2,
+            cond,
+        } => select(0x1a800400, dest, arg1, arg2, *cond)?,
+        ArmInstruction::Csinv {
+            dest,
+            arg1,
+            arg2,
+            cond,
+        } => select(0x5a800000, dest, arg1, arg2, *cond)?,
+        // csinc rd, zr, zr, !cond
+        ArmInstruction::Cset { dest, cond } => {
+            sf(dest) << 31 | 0x1a9f07e0 | (*cond as u32 ^ 1) << 12 | reg(dest)?
+        }
+        ArmInstruction::B { target } => 0x14000000 | branch_offset(label(target)?, address, 26)?,
+        ArmInstruction::Bl { target } => 0x94000000 | branch_offset(label(target)?, address, 26)?,
+        ArmInstruction::BCond { cond, target } => {
+            0x54000000 | branch_offset(label(target)?, address, 19)? << 5 | *cond as u32
+        }
+        ArmInstruction::Cbnz { src, target } => {
+            sf(src) << 31
+                | 0x35000000
+                | branch_offset(label(target)?, address, 19)? << 5
+                | reg(src)?
+        }
+        ArmInstruction::Br { target } => 0xd61f0000 | reg(&ArmRegister::try_from(*target)?)? << 5,
+        ArmInstruction::Blr { target } => 0xd63f0000 | reg(&ArmRegister::try_from(*target)?)? << 5,
+        ArmInstruction::Ret => 0xd65f03c0,
+        ArmInstruction::Adr { dest, label: val } => {
+            let offset = label(val)?.wrapping_sub(address) as i64;
+            if !fits_signed(offset, 21) {
+                return Err("label out of range");
+            }
+            address_immediate(offset as u32) | 0x10000000 | reg(dest)?
+        }
+        ArmInstruction::Adrp { dest, label: val } => {
+            let val = match val {
+                // a static executable has no GOT, so the address of the
+                // label is used directly, see `Ldr` below
+                ArmVal::Reloc(ArmReloc::GotPage, name, offset) => {
+                    &ArmVal::Reloc(ArmReloc::Page, name.clone(), *offset)
+                }
+                val => val,
+            };
+            let pages = (label(val)? >> 12).wrapping_sub(address >> 12) as i64;
+            if !fits_signed(pages, 21) {
+                return Err("label out of range");
+            }
+            address_immediate(pages as u32) | 0x90000000 | reg(dest)?
+        }
+        ArmInstruction::Svc { id } => {
+            let id = u16::try_from(*id).map_err(|_| "immediate out of range")?;
+            0xd4000001 | u32::from(id) << 5
+        }
+        // the GOT entry would hold the address of the label, so its `ldr`
+        // becomes the `add` of the page offset, like a linker relaxes it
+        ArmInstruction::Ldr {
+            dest,
+            src: ArmVal::RelocOffset(base, reloc),
+            ..
+        } if matches!(**reloc, ArmVal::Reloc(ArmReloc::GotLo12, ..)) => {
+            let ArmVal::Reloc(_, name, offset) = &**reloc else {
+                unreachable!()
+            };
+            let lo12 = ArmVal::Reloc(ArmReloc::Lo12, name.clone(), *offset);
+            add_sub(false, false, dest, base, &lo12, labels)?
+        }
+        ArmInstruction::Ldr { width, dest, src } => {
+            load_store(true, *width, dest, src, address, labels)?
+        }
+        ArmInstruction::Str { width, src, dest } => {
+            load_store(false, *width, src, dest, address, labels)?
+        }
+        ArmInstruction::Ldp { dest1, dest2, src } => pair(true, dest1, dest2, src)?,
+        ArmInstruction::Stp { src1, src2, dest } => pair(false, src1, src2, dest)?,
+        ArmInstruction::Ldxr {
+            acquire,
+            dest,
+            addr,
+        } => size(dest) << 30 | 0x085f7c00 | u32::from(*acquire) << 15 | base(*addr)? | reg(dest)?,
+        ArmInstruction::Stxr {
+            release,
+            status,
+            src,
+            addr,
+        } => {
+            if sf(status) != 0 {
+                return Err("the status of stxr is a w register");
+            }
+            let status_number = reg(status)?;
+            if status_number == reg(src)? || Some(status_number) == addr.number() {
+                return Err("the status of stxr can't be another operand");
+            }
+            size(src) << 30
+                | 0x08007c00
+                | status_number << 16
+                | u32::from(*release) << 15
+                | base(*addr)?
+                | reg(src)?
+        }
+        ArmInstruction::Swp {
+            acquire,
+            release,
+            src,
+            dest,
+            addr,
+        } => atomic(0x38208000, *acquire, *release, src, dest, *addr)?,
+        ArmInstruction::LdOp {
+            op,
+            acquire,
+            release,
+            src,
+            dest,
+            addr,
+        } => {
+            let opc = match op {
+                ArmAtomicOp::Add => 0,
+                ArmAtomicOp::Clr => 1,
+                ArmAtomicOp::Eor => 2,
+                ArmAtomicOp::Set => 3,
+                ArmAtomicOp::Smax => 4,
+                ArmAtomicOp::Smin => 5,
+                ArmAtomicOp::Umax => 6,
+                ArmAtomicOp::Umin => 7,
+            };
+            atomic(0x38200000 | opc << 12, *acquire, *release, src, dest, *addr)?
+        }
+        ArmInstruction::Ins { dest, src } => ins(dest, src)?,
+        ArmInstruction::Fmov { dest, src } => fmov(dest, src)?,
+        ArmInstruction::Fadd { dest, arg1, arg2 } => fp_2(0x1e202800, dest, arg1, arg2)?,
+        ArmInstruction::Fsub { dest, arg1, arg2 } => fp_2(0x1e203800, dest, arg1, arg2)?,
+        ArmInstruction::Fmul { dest, arg1, arg2 } => fp_2(0x1e200800, dest, arg1, arg2)?,
+        ArmInstruction::Fdiv { dest, arg1, arg2 } => fp_2(0x1e201800, dest, arg1, arg2)?,
+        ArmInstruction::Fmaxnm { dest, arg1, arg2 } => fp_2(0x1e206800, dest, arg1, arg2)?,
+        ArmInstruction::Fminnm { dest, arg1, arg2 } => fp_2(0x1e207800, dest, arg1, arg2)?,
+        ArmInstruction::Fmadd {
+            dest,
+            arg1,
+            arg2,
+            arg3,
+        } => fp_3(0x1f000000, dest, arg1, arg2, arg3)?,
+        ArmInstruction::Fmsub {
+            dest,
+            arg1,
+            arg2,
+            arg3,
+        } => fp_3(0x1f008000, dest, arg1, arg2, arg3)?,
+        ArmInstruction::Fnmadd {
+            dest,
+            arg1,
+            arg2,
+            arg3,
+        } => fp_3(0x1f200000, dest, arg1, arg2, arg3)?,
+        ArmInstruction::Fnmsub {
+            dest,
+            arg1,
+            arg2,
+            arg3,
+        } => fp_3(0x1f208000, dest, arg1, arg2, arg3)?,
+        ArmInstruction::Fsqrt { dest, src } => fp_1(0x1e21c000, dest, src)?,
+        ArmInstruction::Fabs { dest, src } => fp_1(0x1e20c000, dest, src)?,
+        ArmInstruction::Fneg { dest, src } => fp_1(0x1e214000, dest, src)?,
+        ArmInstruction::Fcmp { op1, op2 } => {
+            fp_same_width(&[op1, op2])? << 22 | 0x1e202000 | vreg(op2)? << 16 | vreg(op1)? << 5
+        }
+        ArmInstruction::Fcvt { dest, src } => {
+            if sf(dest) == sf(src) {
+                return Err("fcvt converts between precisions");
+            }
+            sf(src) << 22 | 0x1e224000 | sf(dest) << 15 | vreg(src)? << 5 | vreg(dest)?
+        }
+        ArmInstruction::FcvtToInt {
+            rounding,
+            signed,
+            dest,
+            src,
+        } => {
+            // rmode and the top bits of opcode
+            let (rmode, opcode) = match rounding {
+                ArmRounding::Nearest => (0b00, 0b000),
+                ArmRounding::PlusInf => (0b01, 0b000),
+                ArmRounding::MinusInf => (0b10, 0b000),
+                ArmRounding::Zero => (0b11, 0b000),
+                ArmRounding::Away => (0b00, 0b100),
+            };
+            let opcode = opcode | u32::from(!signed);
+            sf(dest) << 31
+                | 0x1e200000
+                | sf(src) << 22
+                | rmode << 19
+                | opcode << 16
+                | vreg(src)? << 5
+                | reg(dest)?
+        }
+        ArmInstruction::Scvtf { dest, src } => {
+            sf(src) << 31 | 0x1e220000 | sf(dest) << 22 | reg(src)? << 5 | vreg(dest)?
+        }
+        ArmInstruction::Ucvtf { dest, src } => {
+            sf(src) << 31 | 0x1e230000 | sf(dest) << 22 | reg(src)? << 5 | vreg(dest)?
+        }
+        ArmInstruction::Dmb { option } => 0xd50330bf | barrier(*option) << 8,
+        ArmInstruction::Dsb { option } => 0xd503309f | barrier(*option) << 8,
+        ArmInstruction::Isb => 0xd5033fdf,
+        ArmInstruction::DcCvau { addr } => 0xd50b7b20 | xreg(addr)?,
+        ArmInstruction::IcIvau { addr } => 0xd50b7520 | xreg(addr)?,
+        ArmInstruction::Mrs { dest, src } => {
+            let sysreg = match src {
+                ArmSystemRegister::CtrEl0 => 0x3b0020,
+            };
+            0xd5000000 | sysreg | xreg(dest)?
+        }
+        ArmInstruction::Yield => 0xd503203f,
+        ArmInstruction::Nop => NOP,
+        ArmInstruction::Label { .. } | ArmInstruction::Directive { .. } => {
+            return Err("not an instruction")
+        }
+        ArmInstruction::Verbatim { .. } => return Err("verbatim text can't be encoded"),
+    })
+}
+
+const NOP: u32 = 0xd503201f;
+
+/// 1 for `x` and `d` registers, 0 for `w` and `s` registers, the `sf` bit
+/// of integer instructions and the `ftype` of floating point ones.
+fn sf(register: &ArmRegister) -> u32 {
+    u32::from(matches!(register.width(), ArmWidth::Double))
+}
+
+/// The `size` of loads and stores of a whole register, 3 for 64 bits and 2
+/// for 32 bits.
+fn size(register: &ArmRegister) -> u32 {
+    2 + sf(register)
+}
+
+/// The [`sf`] of registers which must have the same width.
+fn same_width(registers: &[&ArmRegister]) -> Result<u32, &'static str> {
+    let sf = sf(registers[0]);
+    if registers.iter().any(|register| self::sf(register) != sf) {
+        return Err("registers must have the same width");
+    }
+    Ok(sf)
+}
+
+/// The [`same_width`] of floating point registers, their `ftype`.
+fn fp_same_width(registers: &[&ArmRegister]) -> Result<u32, &'static str> {
+    same_width(registers).map_err(|_| "registers must have the same precision")
+}
+
+/// The zero register as wide as `register`.
+fn zr(register: &ArmRegister) -> ArmRegister {
+    ArmRegister::new(ArmRegisterName::Zero, register.width())
+}
+
+/// The number of a general register in an operand where 31 is the zero
+/// register.
+fn reg(register: &ArmRegister) -> Result<u32, &'static str> {
+    match register.name() {
+        ArmRegisterName::Sp => Err("sp can't be this operand"),
+        name if name.is_vector() => Err("expected a general register"),
+        name => name.number().ok_or("pc can't be an operand"),
+    }
+}
+
+/// The [`reg`] of an operand which is always 64 bits, like the address of
+/// cache maintenance.
+fn xreg(register: &ArmRegister) -> Result<u32, &'static str> {
+    match register.width() {
+        ArmWidth::Double => reg(register),
+        _ => Err("expected a 64 bit register"),
+    }
+}
+
+/// The number of a general register in an operand where 31 is `sp`.
+fn reg_or_sp(register: &ArmRegister) -> Result<u32, &'static str> {
+    match register.name() {
+        ArmRegisterName::Zero => Err("the zero register can't be this operand"),
+        name if name.is_vector() => Err("expected a general register"),
+        name => name.number().ok_or("pc can't be an operand"),
+    }
+}
+
+/// The number of a floating point register.
+fn vreg(register: &ArmRegister) -> Result<u32, &'static str> {
+    match register.name() {
+        name if name.is_vector() => Ok(name.number().unwrap()),
+        _ => Err("expected a floating point register"),
+    }
+}
+
+/// The `Rn` field of the address register of an exclusive or atomic access.
+fn base(addr: ArmRegisterName) -> Result<u32, &'static str> {
+    Ok(reg_or_sp(&ArmRegister::try_from(addr)?)? << 5)
+}
+
+fn is_sp(register: &ArmRegister) -> bool {
+    register.name() == ArmRegisterName::Sp
+}
+
+/// Whether `value` fits in a `bits` wide two's complement field.
+fn fits_signed(value: i64, bits: u32) -> bool {
+    let half = 1 << (bits - 1);
+    (-half..half).contains(&value)
+}
+
+/// The address of the label of `val`.
+fn label_address(
+    val: &ArmVal,
+    address: u64,
+    labels: &HashMap<String, u64>,
+) -> Result<u64, &'static str> {
+    let (name, offset) = match val {
+        ArmVal::LabelOffset(name, offset) | ArmVal::Reloc(ArmReloc::Page, name, offset) => {
+            (name, offset)
+        }
+        ArmVal::Reloc(ArmReloc::GotPage | ArmReloc::GotLo12, ..) => {
+            return Err("GOT relocations can't be encoded here")
+        }
+        _ => return Err("expected a label"),
+    };
+    let label = match name.as_str() {
+        "." => address,
+        name => *labels.get(name).ok_or("undefined label")?,
+    };
+    Ok(label.wrapping_add_signed(i64::from(*offset)))
+}
+
+/// The `:lo12:` offset of the label of `val` in its page.
+fn lo12(val: &ArmVal, labels: &HashMap<String, u64>) -> Result<u32, &'static str> {
+    match val {
+        ArmVal::Reloc(ArmReloc::Lo12, name, offset) => {
+            let label = ArmVal::LabelOffset(name.clone(), *offset);
+            Ok(label_address(&label, 0, labels)? as u32 & 0xfff)
+        }
+        ArmVal::Reloc(ArmReloc::GotLo12, ..) => Err("GOT relocations can't be encoded here"),
+        _ => Err("expected a :lo12: relocation"),
+    }
+}
+
+/// The instruction offset from `address` to `target` as a `bits` wide field.
+fn branch_offset(target: u64, address: u64, bits: u32) -> Result<u32, &'static str> {
+    let offset = target.wrapping_sub(address) as i64;
+    if offset % 4 != 0 {
+        return Err("misaligned branch target");
+    }
+    if !fits_signed(offset / 4, bits) {
+        return Err("branch target out of range");
+    }
+    Ok((offset / 4) as u32 & (u32::MAX >> (32 - bits)))
+}
+
+/// The `immlo` and `immhi` fields of `adr` and `adrp`.
+fn address_immediate(imm: u32) -> u32 {
+    (imm & 0b11) << 29 | (imm >> 2 & 0x7ffff) << 5
+}
+
+fn shift_amount(imm: i32, size: u32) -> Result<u32, &'static str> {
+    u32::try_from(imm)
+        .ok()
+        .filter(|&shift| shift < size)
+        .ok_or("shift out of range")
+}
+
+/// `add`, `sub`, and with `set_flags` the `adds` and `subs` of `cmn` and
+/// `cmp`. An `sp` operand needs the extended register form.
+fn add_sub(
+    sub: bool,
+    set_flags: bool,
+    dest: &ArmRegister,
+    arg1: &ArmRegister,
+    arg2: &ArmVal,
+    labels: &HashMap<String, u64>,
+) -> Result<u32, &'static str> {
+    let op = u32::from(sub) << 30 | u32::from(set_flags) << 29;
+    // flags are set into the zero register rather than sp
+    let rd = |dest| match set_flags {
+        true => reg(dest),
+        false => reg_or_sp(dest),
+    };
+    let imm12 = match arg2 {
+        ArmVal::Imm(imm) => {
+            let imm = u64::try_from(*imm).map_err(|_| "immediate out of range")?;
+            if !is_arith_imm(imm) {
+                return Err("immediate out of range");
+            }
+            match imm < 1 << 12 {
+                true => imm as u32,
+                // `lsl 12`
+                false => 1 << 12 | (imm >> 12) as u32,
+            }
+        }
+        ArmVal::Reloc(..) if !set_flags => lo12(arg2, labels)?,
+        ArmVal::Reg(arg2) => {
+            let sf = same_width(&[dest, arg1, arg2])?;
+            if is_sp(arg1) || (is_sp(dest) && !set_flags) {
+                // `uxtx` or `uxtw`, which is no extension at the width
+                let option = 0b010 | sf;
+                return Ok(sf << 31
+                    | op
+                    | 0x0b200000
+                    | reg(arg2)? << 16
+                    | option << 13
+                    | reg_or_sp(arg1)? << 5
+                    | rd(dest)?);
+            }
+            return Ok(sf << 31
+                | op
+                | 0x0b000000
+                | reg(arg2)? << 16
+                | reg(arg1)? << 5
+                | reg(dest)?);
+        }
+        _ => return Err("expected a register or an immediate"),
+    };
+    let sf = same_width(&[dest, arg1])?;
+    Ok(sf << 31 | op | 0x11000000 | imm12 << 10 | reg_or_sp(arg1)? << 5 | rd(dest)?)
+}
+
+/// `and`, `orr`, `eor` and `ands` by `opc`.
+fn logical(
+    opc: u32,
+    dest: &ArmRegister,
+    arg1: &ArmRegister,
+    arg2: &ArmVal,
+) -> Result<u32, &'static str> {
+    match arg2 {
+        ArmVal::Imm(imm) => {
+            let sf = same_width(&[dest, arg1])?;
+            // `ands` writes the flags into the zero register rather than sp
+            let rd = match opc {
+                0b11 => reg(dest)?,
+                _ => reg_or_sp(dest)?,
+            };
+            Ok(sf << 31 | opc << 29 | 0x12000000 | bitmask(*imm, dest)? | reg(arg1)? << 5 | rd)
+        }
+        ArmVal::Reg(arg2) => {
+            let sf = same_width(&[dest, arg1, arg2])?;
+            Ok(sf << 31 | opc << 29 | 0x0a000000 | reg(arg2)? << 16 | reg(arg1)? << 5 | reg(dest)?)
+        }
+        _ => Err("expected a register or an immediate"),
+    }
+}
+
+/// The `N`, `immr` and `imms` fields of the logical immediate `imm` for
+/// registers as wide as `register`, see
+/// [`is_logical_imm`](crate::legalize::is_logical_imm).
+fn bitmask(imm: i64, register: &ArmRegister) -> Result<u32, &'static str> {
+    let width = register.width();
+    let value = match width {
+        ArmWidth::Double => imm as u64,
+        // a `w` register takes 32 bits, signed or not
+        _ if (i64::from(i32::MIN)..=i64::from(u32::MAX)).contains(&imm) => {
+            let low = imm as u64 & 0xffff_ffff;
+            low | low << 32
+        }
+        _ => return Err("immediate out of range"),
+    };
+    if !is_logical_imm(value, width) {
+        return Err("immediate can't be encoded as a bit pattern");
+    }
+    let mut size = 64;
+    while size > 2 {
+        let half = size / 2;
+        let mask = (1u64 << half) - 1;
+        if value & mask != (value >> half) & mask {
+            break;
+        }
+        size = half;
+    }
+    let mask = u64::MAX >> (64 - size);
+    let element = value & mask;
+    let ones = element.count_ones();
+    // rotate the run of ones down to bit 0, which undoes the rotation right
+    // by immr that decoding does
+    let immr = (0..size)
+        .find(|&rotation| {
+            let rotated = (element << rotation | element >> ((size - rotation) % size)) & mask;
+            rotated == (1 << ones) - 1
+        })
+        .unwrap();
+    // the high bits of imms give the element size
+    let imms = (!(size - 1) << 1 | (ones - 1)) & 0x3f;
+    let n = u32::from(size == 64);
+    Ok(n << 22 | immr << 16 | imms << 10)
+}
+
+/// `mov`, which is an alias of `movz`, `movn` or `orr` for an immediate,
+/// of `orr` or `add` for a register, and of `umov` for a vector element.
+fn mov(dest: &ArmRegister, src: &ArmVal) -> Result<u32, &'static str> {
+    let sf = sf(dest);
+    match src {
+        ArmVal::Reg(src) => {
+            same_width(&[dest, src])?;
+            match is_sp(dest) || is_sp(src) {
+                // add rd, rn, 0
+                true => Ok(sf << 31 | 0x11000000 | reg_or_sp(src)? << 5 | reg_or_sp(dest)?),
+                // orr rd, zr, rm
+                false => Ok(sf << 31 | 0x2a0003e0 | reg(src)? << 16 | reg(dest)?),
+            }
+        }
+        ArmVal::Imm(imm) => {
+            let (value, chunks) = match sf {
+                1 => (*imm as u64, 4),
+                _ if (i64::from(i32::MIN)..=i64::from(u32::MAX)).contains(imm) => {
+                    (*imm as u64 & 0xffff_ffff, 2)
+                }
+                _ => return Err("immediate out of range"),
+            };
+            let mask = u64::MAX >> (64 - 16 * chunks);
+            if !is_sp(dest) {
+                // movz sets one chunk of zeros, and movn one chunk of ones
+                for (opc, bits) in [(0b10, value), (0b00, !value & mask)] {
+                    if let Some(hw) = (0..chunks).find(|hw| bits & !(0xffff << (16 * hw)) == 0) {
+                        let imm16 = (bits >> (16 * hw)) as u32 & 0xffff;
+                        return Ok(sf << 31
+                            | opc << 29
+                            | 0x12800000
+                            | hw << 21
+                            | imm16 << 5
+                            | reg(dest)?);
+                    }
+                }
+            }
+            logical(0b01, dest, &zr(dest), src).map_err(|_| "immediate can't be moved")
+        }
+        ArmVal::Element(..) => umov(dest, src),
+        _ => Err("expected a register, an immediate or a vector element"),
+    }
+}
+
+/// `sbfm` and `ubfm` by `opc`, which are `asr`, `lsl` and `lsr`.
+fn bitfield(
+    opc: u32,
+    dest: &ArmRegister,
+    src: &ArmRegister,
+    immr: u32,
+    imms: u32,
+) -> Result<u32, &'static str> {
+    let sf = sf(dest);
+    Ok(sf << 31
+        | opc << 29
+        | 0x13000000
+        | sf << 22
+        | immr << 16
+        | imms << 10
+        | reg(src)? << 5
+        | reg(dest)?)
+}
+
+/// The data processing instructions with two sources, by `opcode`.
+fn data_2(
+    opcode: u32,
+    dest: &ArmRegister,
+    arg1: &ArmRegister,
+    arg2: &ArmRegister,
+) -> Result<u32, &'static str> {
+    let sf = same_width(&[dest, arg1, arg2])?;
+    Ok(sf << 31 | 0x1ac00000 | reg(arg2)? << 16 | opcode << 10 | reg(arg1)? << 5 | reg(dest)?)
+}
+
+/// `madd` and `msub`, `Rd := Ra +/- Rn * Rm`.
+fn data_3(
+    op: u32,
+    dest: &ArmRegister,
+    arg1: &ArmRegister,
+    arg2: &ArmRegister,
+    arg3: &ArmRegister,
+) -> Result<u32, &'static str> {
+    let sf = same_width(&[dest, arg1, arg2, arg3])?;
+    Ok(sf << 31 | op | reg(arg2)? << 16 | reg(arg3)? << 10 | reg(arg1)? << 5 | reg(dest)?)
+}
+
+/// `smulh` and `umulh`, which only have 64 bit forms.
+fn high_multiply(
+    op: u32,
+    dest: &ArmRegister,
+    arg1: &ArmRegister,
+    arg2: &ArmRegister,
+) -> Result<u32, &'static str> {
+    if same_width(&[dest, arg1, arg2])? != 1 {
+        return Err("expected x registers");
+    }
+    Ok(op | reg(arg2)? << 16 | 0x1f << 10 | reg(arg1)? << 5 | reg(dest)?)
+}
+
+/// The conditional selects.
+fn select(
+    op: u32,
+    dest: &ArmRegister,
+    arg1: &ArmRegister,
+    arg2: &ArmRegister,
+    cond: ArmCondition,
+) -> Result<u32, &'static str> {
+    let sf = same_width(&[dest, arg1, arg2])?;
+    Ok(sf << 31 | op | reg(arg2)? << 16 | (cond as u32) << 12 | reg(arg1)? << 5 | reg(dest)?)
+}
+
+/// `ldr`, `str` and their narrower and sign extending forms.
+fn load_store(
+    load: bool,
+    width: ArmWidth,
+    register: &ArmRegister,
+    addr: &ArmVal,
+    address: u64,
+    labels: &HashMap<String, u64>,
+) -> Result<u32, &'static str> {
+    let vector = register.name().is_vector();
+    let sf = sf(register);
+    // the log2 of the access size, and opc, which is 00 for stores, 01 for
+    // zero extending loads, and 10 or 11 to sign extend to 64 or 32 bits
+    let (size, opc) = match (vector, width, load) {
+        (true, _, _) => (2 + sf, u32::from(load)),
+        (_, ArmWidth::Byte | ArmWidth::SignedByte, false) if sf == 0 => (0, 0b00),
+        (_, ArmWidth::Half | ArmWidth::SignedHalf, false) if sf == 0 => (1, 0b00),
+        (_, ArmWidth::Byte, true) if sf == 0 => (0, 0b01),
+        (_, ArmWidth::Half, true) if sf == 0 => (1, 0b01),
+        (_, ArmWidth::SignedByte, true) => (0, 0b11 - sf),
+        (_, ArmWidth::SignedHalf, true) => (1, 0b11 - sf),
+        (_, ArmWidth::SignedWord, true) if sf == 1 => (2, 0b10),
+        (_, ArmWidth::Word | ArmWidth::Double, _) | (_, ArmWidth::SignedWord, false) => {
+            (2 + sf, u32::from(load))
+        }
+        _ => return Err("the register doesn't match the access width"),
+    };
+    let rt = match vector {
+        true => vreg(register)?,
+        false => reg(register)?,
+    };
+    let op = size << 30 | u32::from(vector) << 26 | opc << 22;
+    let unsigned_offset = |base: &ArmRegister, offset: u32| -> Result<u32, &'static str> {
+        Ok(op | 0x39000000 | offset << 10 | reg_or_sp(base)? << 5 | rt)
+    };
+    // ldur, stur, and the pre and post index forms by `mode`
+    let signed_offset = |base: &ArmRegister, offset: i32, mode: u32| -> Result<u32, &'static str> {
+        if !fits_signed(i64::from(offset), 9) {
+            return Err("offset out of range");
+        }
+        Ok(op
+            | 0x38000000
+            | (offset as u32 & 0x1ff) << 12
+            | mode << 10
+            | reg_or_sp(base)? << 5
+            | rt)
+    };
+    match addr {
+        ArmVal::RegOffset(base, offset) => {
+            let scaled = offset >> size;
+            if *offset >= 0 && scaled << size == *offset && scaled < 1 << 12 {
+                unsigned_offset(base, scaled as u32)
+            } else {
+                // what an assembler picks for an offset that can't be scaled
+                signed_offset(base, *offset, 0b00)
+            }
+        }
+        ArmVal::UnscaledOffset(base, offset) => signed_offset(base, *offset, 0b00),
+        ArmVal::PreIndex(base, offset) => signed_offset(base, *offset, 0b11),
+        ArmVal::PostIndex(base, offset) => signed_offset(base, *offset, 0b01),
+        ArmVal::RegIndex(base, index) => {
+            if self::sf(index) != 1 {
+                return Err("expected an x register index");
+            }
+            // `lsl 0` of an x register
+            Ok(op | 0x38206800 | reg(index)? << 16 | reg_or_sp(base)? << 5 | rt)
+        }
+        ArmVal::RelocOffset(base, reloc) => {
+            let offset = lo12(reloc, labels)?;
+            if offset % (1 << size) != 0 {
+                return Err("misaligned :lo12: offset");
+            }
+            unsigned_offset(base, offset >> size)
+        }
+        ArmVal::LabelOffset(..) if load => {
+            // the literal forms only load whole registers and ldrsw
+            let opc = match (vector, width) {
+                (true, _) => sf,
+                (false, ArmWidth::Word | ArmWidth::Double) => sf,
+                (false, ArmWidth::SignedWord) => 0b10,
+                _ => return Err("narrow loads can't load a label"),
+            };
+            let offset = branch_offset(label_address(addr, address, labels)?, address, 19)?;
+            Ok(opc << 30 | u32::from(vector) << 26 | 0x18000000 | offset << 5 | rt)
+        }
+        _ => Err("expected a memory operand"),
+    }
+}
+
+/// `ldp` and `stp`.
+fn pair(
+    load: bool,
+    register1: &ArmRegister,
+    register2: &ArmRegister,
+    addr: &ArmVal,
+) -> Result<u32, &'static str> {
+    let vector = register1.name().is_vector();
+    let sf = same_width(&[register1, register2])?;
+    let (opc, rt1, rt2) = match vector {
+        true => (sf, vreg(register1)?, vreg(register2)?),
+        false => (sf << 1, reg(register1)?, reg(register2)?),
+    };
+    let (base, offset, mode) = match addr {
+        ArmVal::PostIndex(base, offset) => (base, offset, 0b01),
+        ArmVal::RegOffset(base, offset) => (base, offset, 0b10),
+        ArmVal::PreIndex(base, offset) => (base, offset, 0b11),
+        _ => return Err("expected a memory operand"),
+    };
+    let scale = 2 + sf;
+    let scaled = offset >> scale;
+    if scaled << scale != *offset || !fits_signed(i64::from(scaled), 7) {
+        return Err("offset out of range");
+    }
+    Ok(opc << 30
+        | 0x28000000
+        | u32::from(vector) << 26
+        | mode << 23
+        | u32::from(load) << 22
+        | (scaled as u32 & 0x7f) << 15
+        | rt2 << 10
+        | reg_or_sp(base)? << 5
+        | rt1)
+}
+
+/// `swp` and the `ld<op>` instructions.
+fn atomic(
+    op: u32,
+    acquire: bool,
+    release: bool,
+    src: &ArmRegister,
+    dest: &ArmRegister,
+    addr: ArmRegisterName,
+) -> Result<u32, &'static str> {
+    Ok(size(src) << 30
+        | op
+        | u32::from(acquire) << 23
+        | u32::from(release) << 22
+        | reg(src)? << 16
+        | base(addr)?
+        | reg(dest)?)
+}
+
+/// The `imm5` field of an element, which has its size and index, and the
+/// number of its register.
+fn element(val: &ArmVal) -> Result<(u32, ArmWidth, u32, u32), &'static str> {
+    let ArmVal::Element(name, width, index) = val else {
+        return Err("expected a vector element");
+    };
+    if !name.is_vector() {
+        return Err("expected a floating point register");
+    }
+    let imm5 = match width {
+        ArmWidth::Double if *index < 2 => index << 4 | 0b1000,
+        ArmWidth::Word if *index < 4 => index << 3 | 0b100,
+        _ => return Err("element index out of range"),
+    };
+    Ok((imm5, *width, *index, name.number().unwrap()))
+}
+
+fn ins(dest: &ArmVal, src: &ArmVal) -> Result<u32, &'static str> {
+    let (imm5, width, _, rd) = element(dest)?;
+    match src {
+        ArmVal::Reg(src) => {
+            if sf(src) != u32::from(matches!(width, ArmWidth::Double)) {
+                return Err("the register doesn't match the element width");
+            }
+            Ok(0x4e001c00 | imm5 << 16 | reg(src)? << 5 | rd)
+        }
+        _ => {
+            let (_, src_width, index, rn) = element(src)?;
+            if !matches!(
+                (width, src_width),
+                (ArmWidth::Word, ArmWidth::Word) | (ArmWidth::Double, ArmWidth::Double)
+            ) {
+                return Err("elements must have the same width");
+            }
+            let imm4 = match width {
+                ArmWidth::Double => index << 3,
+                _ => index << 2,
+            };
+            Ok(0x6e000400 | imm5 << 16 | imm4 << 11 | rn << 5 | rd)
+        }
+    }
+}
+
+/// `umov`, which moves a vector element to a general register of its width.
+fn umov(dest: &ArmRegister, src: &ArmVal) -> Result<u32, &'static str> {
+    let (imm5, width, _, rn) = element(src)?;
+    let q = u32::from(matches!(width, ArmWidth::Double));
+    if sf(dest) != q {
+        return Err("the register doesn't match the element width");
+    }
+    Ok(q << 30 | 0x0e003c00 | imm5 << 16 | rn << 5 | reg(dest)?)
+}
+
+/// `fmov` between floating point registers, or to or from a general
+/// register of the same width.
+fn fmov(dest: &ArmRegister, src: &ArmRegister) -> Result<u32, &'static str> {
+    let ftype = fp_same_width(&[dest, src])?;
+    match (dest.name().is_vector(), src.name().is_vector()) {
+        (true, true) => Ok(0x1e204000 | ftype << 22 | vreg(src)? << 5 | vreg(dest)?),
+        (true, false) => Ok(ftype << 31 | ftype << 22 | 0x1e270000 | reg(src)? << 5 | vreg(dest)?),
+        (false, true) => Ok(ftype << 31 | ftype << 22 | 0x1e260000 | vreg(src)? << 5 | reg(dest)?),
+        (false, false) => Err("expected a floating point register"),
+    }
+}
+
+/// The floating point instructions with one source.
+fn fp_1(op: u32, dest: &ArmRegister, src: &ArmRegister) -> Result<u32, &'static str> {
+    let ftype = fp_same_width(&[dest, src])?;
+    Ok(op | ftype << 22 | vreg(src)? << 5 | vreg(dest)?)
+}
+
+/// The floating point instructions with two sources.
+fn fp_2(
+    op: u32,
+    dest: &ArmRegister,
+    arg1: &ArmRegister,
+    arg2: &ArmRegister,
+) -> Result<u32, &'static str> {
+    let ftype = fp_same_width(&[dest, arg1, arg2])?;
+    Ok(op | ftype << 22 | vreg(arg2)? << 16 | vreg(arg1)? << 5 | vreg(dest)?)
+}
+
+/// The fused multiply adds, `Rd := +/-Ra +/- Rn * Rm`.
+fn fp_3(
+    op: u32,
+    dest: &ArmRegister,
+    arg1: &ArmRegister,
+    arg2: &ArmRegister,
+    arg3: &ArmRegister,
+) -> Result<u32, &'static str> {
+    let ftype = fp_same_width(&[dest, arg1, arg2, arg3])?;
+    Ok(op | ftype << 22 | vreg(arg2)? << 16 | vreg(arg3)? << 10 | vreg(arg1)? << 5 | vreg(dest)?)
+}
+
+/// The `CRm` of a barrier option.
+fn barrier(option: ArmBarrier) -> u32 {
+    match option {
+        ArmBarrier::Ish => 0b1011,
+        ArmBarrier::Ishld => 0b1001,
+        ArmBarrier::Ishst => 0b1010,
+    }
+}
+
+/// The sections of an executable. Others, like `.note.GNU-stack`, don't
+/// take part in running the program and are dropped.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum Section {
+    Text,
+    Data,
+    Bss,
+    Other,
+}
+
+/// What an [`ArmInstruction`] adds to its section.
+enum Piece {
+    Label(String),
+    Section(Section),
+    /// Padding to a multiple of this alignment
+    Align(u64),
+    Bytes(Vec<u8>),
+    /// The operands of `.quad` and friends, each this many bytes
+    Values(u64, Vec<String>),
+    Zeros(u64),
+    Instruction,
+    /// Directives which don't add anything, like `.globl`
+    Nothing,
+}
+
+/// Lay out and encode a whole translation into an [`Image`] for a static
+/// executable, whose entry point is `_start`.
+///
+/// `.text` has the instructions, `.data` and `.rodata` become the data,
+/// and `.bss` is only sized. Every error is reported, and the `line` of
+/// each is the 1-based position of the instruction in `arm_instrs`.
+pub fn assemble(arm_instrs: Vec<ArmInstruction>) -> Result<Image, Vec<AsmError>> {
+    let mut errors = vec![];
+    let error = |index: usize, instr: &ArmInstruction, reason: &str| {
+        AsmError::new(index + 1, 0, &String::from(instr.clone()), reason)
+    };
+
+    let pieces: Vec<Piece> = arm_instrs
+        .iter()
+        .enumerate()
+        .map(|(index, instr)| {
+            piece(instr).unwrap_or_else(|reason| {
+                errors.push(error(index, instr, reason));
+                Piece::Nothing
+            })
+        })
+        .collect();
+
+    // the first pass places every piece, and the labels at their offsets
+    let text_base = Layout::new(0, 0).text;
+    let mut sizes = HashMap::from([(Section::Text, 0), (Section::Data, 0), (Section::Bss, 0)]);
+    let mut section = Section::Text;
+    let mut offsets = vec![];
+    let mut label_offsets = HashMap::new();
+    for (index, (instr, piece)) in arm_instrs.iter().zip(&pieces).enumerate() {
+        let offset = sizes.entry(section).or_insert(0);
+        offsets.push((section, *offset));
+        match piece {
+            Piece::Label(name) => {
+                if label_offsets.insert(name, (section, *offset)).is_some() {
+                    errors.push(error(index, instr, "label is defined more than once"));
+                }
+            }
+            Piece::Section(next) => section = *next,
+            // `.data` and `.bss` are page aligned, so only `.text` has to
+            // be aligned by its address
+            Piece::Align(align) if section == Section::Text => {
+                *offset = (text_base + *offset).next_multiple_of(*align) - text_base
+            }
+            Piece::Align(align) => *offset = offset.next_multiple_of(*align),
+            Piece::Bytes(bytes) => *offset += bytes.len() as u64,
+            Piece::Values(size, values) => *offset += size * values.len() as u64,
+            Piece::Zeros(size) => *offset += size,
+            Piece::Instruction => *offset += 4,
+            Piece::Nothing => {}
+        }
+        let has_data = matches!(
+            piece,
+            Piece::Bytes(_) | Piece::Values(..) | Piece::Instruction
+        );
+        if section == Section::Bss && has_data {
+            errors.push(error(index, instr, ".bss can only be zeros"));
+        }
+    }
+
+    let layout = Layout::new(sizes[&Section::Text], sizes[&Section::Data]);
+    let section_address = |section| match section {
+        Section::Text => Some(layout.text),
+        Section::Data => Some(layout.data),
+        Section::Bss => Some(layout.bss),
+        Section::Other => None,
+    };
+    let mut labels: HashMap<String, u64> = label_offsets
+        .into_iter()
+        .filter_map(|(name, (section, offset))| {
+            Some((name.clone(), section_address(section)? + offset))
+        })
+        .collect();
+    // the bounds of the code which GNU ld provides, which `fence.i` flushes
+    labels
+        .entry("__executable_start".to_string())
+        .or_insert(BASE_ADDRESS);
+    labels
+        .entry("etext".to_string())
+        .or_insert(layout.text + sizes[&Section::Text]);
+
+    // the second pass encodes everything at its address
+    let mut image = Image {
+        bss_size: sizes[&Section::Bss],
+        ..Image::default()
+    };
+    for (index, ((instr, piece), (section, offset))) in
+        arm_instrs.iter().zip(pieces).zip(offsets).enumerate()
+    {
+        let bytes = match section {
+            Section::Text => &mut image.text,
+            Section::Data => &mut image.data,
+            Section::Bss | Section::Other => continue,
+        };
+        // padding up to this piece, with nops where they fit
+        while (bytes.len() as u64) < offset {
+            match (bytes.len() % 4, offset - bytes.len() as u64) {
+                (0, 4..) if section == Section::Text => bytes.extend(NOP.to_le_bytes()),
+                _ => bytes.push(0),
+            }
+        }
+        let address = section_address(section).unwrap() + offset;
+        match piece {
+            Piece::Bytes(data) => bytes.extend(data),
+            Piece::Values(size, values) => {
+                for value in &values {
+                    match data_value(value, size, &labels) {
+                        Ok(value) => bytes.extend(&value.to_le_bytes()[..size as usize]),
+                        Err(reason) => errors.push(error(index, instr, reason)),
+                    }
+                }
+                // keep the offsets of the rest after an error
+                bytes.resize((offset + size * values.len() as u64) as usize, 0);
+            }
+            Piece::Zeros(size) => bytes.resize(bytes.len() + size as usize, 0),
+            Piece::Instruction => {
+                let word = match address % 4 {
+                    0 => encode(instr, address, &labels),
+                    _ => Err("instruction is not 4 byte aligned"),
+                };
+                let word = word.unwrap_or_else(|reason| {
+                    errors.push(error(index, instr, reason));
+                    0
+                });
+                bytes.extend(word.to_le_bytes());
+            }
+            Piece::Label(_) | Piece::Section(_) | Piece::Align(_) | Piece::Nothing => {}
+        }
+    }
+
+    match labels.get("_start") {
+        Some(&start) if (layout.text..layout.text + image.text.len() as u64).contains(&start) => {
+            image.entry = start - layout.text
+        }
+        Some(_) => errors.push(AsmError::new(0, 0, "_start", "entry point isn't in .text")),
+        None => errors.push(AsmError::new(0, 0, "_start", "undefined entry point")),
+    }
+    if !errors.is_empty() {
+        return Err(errors);
+    }
+    Ok(image)
+}
+
+/// What `instr` adds to its section.
+fn piece(instr: &ArmInstruction) -> Result<Piece, &'static str> {
+    let (name, operands) = match instr {
+        ArmInstruction::Label { name } => return Ok(Piece::Label(name.clone())),
+        ArmInstruction::Directive { name, operands } => (name.as_str(), operands.trim()),
+        ArmInstruction::Verbatim { .. } => return Err("verbatim text can't be encoded"),
+        _ => return Ok(Piece::Instruction),
+    };
+    let first = operands.split(',').next().unwrap_or_default().trim();
+    Ok(match name {
+        "text" => Piece::Section(Section::Text),
+        "data" | "rodata" => Piece::Section(Section::Data),
+        "bss" => Piece::Section(Section::Bss),
+        "section" => Piece::Section(section(operands)),
+        "balign" => Piece::Align(alignment(parse_integer(first)?)?),
+        "align" | "p2align" => {
+            let power = parse_integer(first)?;
+            Piece::Align(alignment(1i64.checked_shl(power as u32).unwrap_or(0))?)
+        }
+        "byte" => values(1, operands),
+        "2byte" | "half" | "hword" | "short" => values(2, operands),
+        "4byte" | "word" | "long" | "int" => values(4, operands),
+        "8byte" | "dword" | "xword" | "quad" => values(8, operands),
+        "zero" | "skip" | "space" => {
+            let mut operands = operands.split(',');
+            let size = parse_integer(operands.next().unwrap_or_default())?;
+            if let Some(fill) = operands.next() {
+                if parse_integer(fill)? != 0 {
+                    return Err("only zero fill is supported");
+                }
+            }
+            Piece::Zeros(u64::try_from(size).map_err(|_| "size out of range")?)
+        }
+        "ascii" => Piece::Bytes(parse_strings(operands)?.concat()),
+        "string" | "asciz" => Piece::Bytes(
+            parse_strings(operands)?
+                .into_iter()
+                .flat_map(|string| string.into_iter().chain([0]))
+                .collect(),
+        ),
+        "globl" | "global" | "local" | "weak" | "hidden" | "type" | "size" | "file" | "ident"
+        | "option" | "attribute" | "addrsig" | "loc" => Piece::Nothing,
+        name if name.starts_with("cfi_") => Piece::Nothing,
+        _ => return Err("unsupported directive"),
+    })
+}
+
+/// The section of the operands of `.section`, by its name or its flags.
+fn section(operands: &str) -> Section {
+    let mut operands = operands.split(',').map(str::trim);
+    let name = operands.next().unwrap_or_default();
+    let flags = operands.next().unwrap_or_default();
+    let kind = operands.next().unwrap_or_default();
+    let has_prefix = |prefixes: &[&str]| prefixes.iter().any(|prefix| name.starts_with(prefix));
+    if has_prefix(&[".text"]) || flags.contains('x') {
+        Section::Text
+    } else if has_prefix(&[".bss", ".sbss"]) || kind.ends_with("nobits") {
+        Section::Bss
+    } else if has_prefix(&[".data", ".sdata", ".rodata", ".srodata"]) || flags.contains('a') {
+        Section::Data
+    } else {
+        Section::Other
+    }
+}
+
+fn alignment(align: i64) -> Result<u64, &'static str> {
+    match u64::try_from(align) {
+        Ok(align) if align.is_power_of_two() && align <= 0x1000 => Ok(align),
+        _ => Err("alignment must be a power of 2 up to a page"),
+    }
+}
+
+fn values(size: u64, operands: &str) -> Piece {
+    let values = operands.split(',').map(|value| value.trim().to_string());
+    Piece::Values(size, values.collect())
+}
+
+/// An operand of `.quad` and friends, an integer or `label+offset`, which
+/// must fit in `size` bytes as a signed or unsigned number.
+fn data_value(value: &str, size: u64, labels: &HashMap<String, u64>) -> Result<u64, &'static str> {
+    let value = match parse_integer(value) {
+        Ok(value) => value as u64,
+        Err(_) if value.is_empty() => return Err("expected a value"),
+        Err(_) => {
+            let split = value[1..].find(['+', '-']).map_or(value.len(), |i| i + 1);
+            let (name, offset) = value.split_at(split);
+            let offset = match offset {
+                "" => 0,
+                offset => parse_integer(offset.trim_start_matches('+'))?,
+            };
+            let label = labels.get(name.trim()).ok_or("undefined label")?;
+            label.wrapping_add_signed(offset)
+        }
+    };
+    let bits = 8 * size as u32;
+    let fits = bits == 64 || value >> bits == 0 || fits_signed(value as i64, bits);
+    match fits {
+        true => Ok(value),
+        false => Err("value out of range"),
+    }
+}
+
+/// A decimal, `0x` hexadecimal or `0b` binary integer, possibly negative.
+fn parse_integer(text: &str) -> Result<i64, &'static str> {
+    let text = text.trim();
+    let (negative, digits) = match text.strip_prefix('-') {
+        Some(digits) => (true, digits),
+        None => (false, text),
+    };
+    let (radix, digits) = match digits.get(..2) {
+        Some("0x" | "0X") => (16, &digits[2..]),
+        Some("0b" | "0B") => (2, &digits[2..]),
+        _ => (10, digits),
+    };
+    let value = u64::from_str_radix(digits, radix).map_err(|_| "expected an integer")? as i64;
+    Ok(if negative {
+        value.wrapping_neg()
+    } else {
+        value
+    })
+}
+
+/// The bytes of the comma separated string literals of `.ascii` and
+/// friends, with C escapes.
+fn parse_strings(operands: &str) -> Result<Vec<Vec<u8>>, &'static str> {
+    let mut strings = vec![];
+    let mut chars = operands.chars().peekable();
+    loop {
+        while chars.next_if(|c| c.is_whitespace() || *c == ',').is_some() {}
+        match chars.next() {
+            None => return Ok(strings),
+            Some('"') => {}
+            Some(_) => return Err("expected a string"),
+        }
+        let mut string = vec![];
+        loop {
+            let c = match chars.next().ok_or("unterminated string")? {
+                '"' => break,
+                '\\' => match chars.next().ok_or("unterminated string")? {
+                    'n' => '\n',
+                    't' => '\t',
+                    'r' => '\r',
+                    'b' => '\x08',
+                    'f' => '\x0c',
+                    'v' => '\x0b',
+                    'a' => '\x07',
+                    'x' => {
+                        let mut value = 0;
+                        while let Some(digit) = chars.next_if(char::is_ascii_hexdigit) {
+                            value = value << 4 | digit.to_digit(16).unwrap();
+                        }
+                        string.push(value as u8);
+                        continue;
+                    }
+                    digit @ '0'..='7' => {
+                        let mut value = digit.to_digit(8).unwrap();
+                        for _ in 0..2 {
+                            match chars.next_if(|c| ('0'..='7').contains(c)) {
+                                Some(digit) => value = value << 3 | digit.to_digit(8).unwrap(),
+                                None => break,
+                            }
+                        }
+                        string.push(value as u8);
+                        continue;
+                    }
+                    c => c,
+                },
+                c => c,
+            };
+            let mut buffer = [0; 4];
+            string.extend(c.encode_utf8(&mut buffer).as_bytes());
+        }
+        strings.push(string);
+    }
+}
@@ -0,0 +1,42 @@
+use std::fmt::Display;
+
+/// An unsupported or malformed construct found while parsing or
+/// translating assembly.
+///
+/// Parsing and translation collect every error they find instead of
+/// stopping at the first one, so a whole program can be checked in one run.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AsmError {
+    /// 1-based line in the source, or the 1-based position in the
+    /// instruction list when the instructions did not come from text.
+    pub line: usize,
+    /// 1-based column where `text` starts, or 0 when unknown.
+    pub column: usize,
+    /// The offending source text.
+    pub text: String,
+    /// Why the text was rejected.
+    pub reason: String,
+}
+
+impl AsmError {
+    pub fn new(line: usize, column: usize, text: &str, reason: impl Into<String>) -> Self {
+        Self {
+            line,
+            column,
+            text: text.to_string(),
+            reason: reason.into(),
+        }
+    }
+}
+
+impl Display for AsmError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}:{}: {}: `{}`",
+            self.line, self.column, self.reason, self.text
+        )
+    }
+}
+
+impl std::error::Error for AsmError {}
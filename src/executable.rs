@@ -0,0 +1,240 @@
+//! Write static AArch64 Linux executables, so a translation can run without
+//! an assembler or linker.
+//!
+//! An executable has two segments. The first holds the ELF and program
+//! headers followed by `.text`, and is readable and executable. The second
+//! holds `.data` followed by `.bss`, and is readable and writable. It
+//! starts on the next 64KB page after the first, the largest page size of
+//! AArch64 Linux, so the two never share a page whatever the kernel uses.
+//!
+//! The code has to be encoded for the addresses it is loaded at, so
+//! [`Layout`] gives them before [`write_executable`] is called, which
+//! [`assemble`](crate::encode::assemble) takes care of. Section
+//! headers are written for tools like `objdump`, but loading only uses the
+//! program headers.
+
+/// The address of the first segment, the usual one for static executables.
+pub const BASE_ADDRESS: u64 = 0x400000;
+/// The alignment of segments, and the largest page size.
+const PAGE_SIZE: u64 = 0x10000;
+/// The alignment of `.data` and `.bss`, which is enough for any `.balign`
+/// the translation emits.
+const SECTION_ALIGN: u64 = 0x1000;
+
+const ELF_HEADER_SIZE: u64 = 64;
+const PROGRAM_HEADER_SIZE: u64 = 56;
+const SECTION_HEADER_SIZE: u64 = 64;
+/// Room for the program headers of both segments and the stack.
+const PROGRAM_HEADER_COUNT: u64 = 3;
+
+const ET_EXEC: u16 = 2;
+const EM_AARCH64: u16 = 183;
+const PT_LOAD: u32 = 1;
+const PT_GNU_STACK: u32 = 0x6474e551;
+const PF_X: u32 = 0x1;
+const PF_W: u32 = 0x2;
+const PF_R: u32 = 0x4;
+const SHT_PROGBITS: u32 = 1;
+const SHT_STRTAB: u32 = 3;
+const SHT_NOBITS: u32 = 8;
+const SHF_WRITE: u64 = 0x1;
+const SHF_ALLOC: u64 = 0x2;
+const SHF_EXECINSTR: u64 = 0x4;
+
+/// The contents of an executable.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Image {
+    /// The encoded instructions, for the addresses of [`Layout::new`]
+    pub text: Vec<u8>,
+    /// The initialized data
+    pub data: Vec<u8>,
+    /// The size of `.bss`, the zeroed data after `.data`
+    pub bss_size: u64,
+    /// The offset of the entry point in `text`
+    pub entry: u64,
+}
+
+/// Where each section of an executable is loaded.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Layout {
+    pub text: u64,
+    pub data: u64,
+    pub bss: u64,
+}
+
+impl Layout {
+    /// The addresses of a `.text` and `.data` of these sizes.
+    pub fn new(text_size: u64, data_size: u64) -> Self {
+        let text = BASE_ADDRESS + text_offset();
+        // the data segment is at the same offset in its page as in the file
+        let data =
+            (text + text_size).next_multiple_of(PAGE_SIZE) + data_offset(text_size) % PAGE_SIZE;
+        let bss = (data + data_size).next_multiple_of(SECTION_ALIGN);
+        Self { text, data, bss }
+    }
+}
+
+/// The offset of `.text` in the file, after the headers.
+fn text_offset() -> u64 {
+    (ELF_HEADER_SIZE + PROGRAM_HEADER_COUNT * PROGRAM_HEADER_SIZE).next_multiple_of(16)
+}
+
+/// The offset of `.data` in the file, after `.text`.
+fn data_offset(text_size: u64) -> u64 {
+    (text_offset() + text_size).next_multiple_of(SECTION_ALIGN)
+}
+
+/// The bytes of a static executable of `image`, loaded at the addresses of
+/// its [`Layout`].
+pub fn write_executable(image: &Image) -> Vec<u8> {
+    let text_size = image.text.len() as u64;
+    let data_size = image.data.len() as u64;
+    let layout = Layout::new(text_size, data_size);
+    let data_offset = data_offset(text_size);
+    let names = b"\0.text\0.data\0.bss\0.shstrtab\0";
+    let names_offset = data_offset + data_size;
+    let section_headers_offset = (names_offset + names.len() as u64).next_multiple_of(8);
+    let has_data = data_size > 0 || image.bss_size > 0;
+
+    let mut elf = vec![];
+    // e_ident: 64 bit, little endian, version 1, System V ABI
+    elf.extend(b"\x7fELF\x02\x01\x01\x00");
+    elf.extend([0; 8]);
+    elf.extend(ET_EXEC.to_le_bytes());
+    elf.extend(EM_AARCH64.to_le_bytes());
+    elf.extend(1u32.to_le_bytes());
+    elf.extend((layout.text + image.entry).to_le_bytes());
+    elf.extend(ELF_HEADER_SIZE.to_le_bytes());
+    elf.extend(section_headers_offset.to_le_bytes());
+    elf.extend(0u32.to_le_bytes());
+    elf.extend((ELF_HEADER_SIZE as u16).to_le_bytes());
+    elf.extend((PROGRAM_HEADER_SIZE as u16).to_le_bytes());
+    elf.extend((if has_data { 3u16 } else { 2 }).to_le_bytes());
+    elf.extend((SECTION_HEADER_SIZE as u16).to_le_bytes());
+    // null, .text, .data, .bss and .shstrtab
+    elf.extend(5u16.to_le_bytes());
+    elf.extend(4u16.to_le_bytes());
+
+    let text_end = text_offset() + text_size;
+    program_header(
+        &mut elf,
+        PT_LOAD,
+        PF_R | PF_X,
+        0,
+        BASE_ADDRESS,
+        text_end,
+        text_end,
+    );
+    if has_data {
+        let memory_size = layout.bss + image.bss_size - layout.data;
+        program_header(
+            &mut elf,
+            PT_LOAD,
+            PF_R | PF_W,
+            data_offset,
+            layout.data,
+            data_size,
+            memory_size,
+        );
+    }
+    program_header(&mut elf, PT_GNU_STACK, PF_R | PF_W, 0, 0, 0, 0);
+
+    elf.resize(text_offset() as usize, 0);
+    elf.extend(&image.text);
+    elf.resize(data_offset as usize, 0);
+    elf.extend(&image.data);
+    elf.extend(names);
+    elf.resize(section_headers_offset as usize, 0);
+
+    elf.extend([0; SECTION_HEADER_SIZE as usize]);
+    let text = SectionHeader {
+        name: 1,
+        kind: SHT_PROGBITS,
+        flags: SHF_ALLOC | SHF_EXECINSTR,
+        address: layout.text,
+        offset: text_offset(),
+        size: text_size,
+        align: 4,
+    };
+    let data = SectionHeader {
+        name: 7,
+        kind: SHT_PROGBITS,
+        flags: SHF_ALLOC | SHF_WRITE,
+        address: layout.data,
+        offset: data_offset,
+        size: data_size,
+        align: SECTION_ALIGN,
+    };
+    let bss = SectionHeader {
+        name: 13,
+        kind: SHT_NOBITS,
+        flags: SHF_ALLOC | SHF_WRITE,
+        address: layout.bss,
+        offset: names_offset,
+        size: image.bss_size,
+        align: SECTION_ALIGN,
+    };
+    let names = SectionHeader {
+        name: 18,
+        kind: SHT_STRTAB,
+        flags: 0,
+        address: 0,
+        offset: names_offset,
+        size: names.len() as u64,
+        align: 1,
+    };
+    for header in [text, data, bss, names] {
+        header.write(&mut elf);
+    }
+    elf
+}
+
+/// `Elf64_Phdr`
+fn program_header(
+    elf: &mut Vec<u8>,
+    kind: u32,
+    flags: u32,
+    offset: u64,
+    address: u64,
+    file_size: u64,
+    memory_size: u64,
+) {
+    elf.extend(kind.to_le_bytes());
+    elf.extend(flags.to_le_bytes());
+    elf.extend(offset.to_le_bytes());
+    elf.extend(address.to_le_bytes());
+    // p_paddr
+    elf.extend(address.to_le_bytes());
+    elf.extend(file_size.to_le_bytes());
+    elf.extend(memory_size.to_le_bytes());
+    let align = if kind == PT_LOAD { PAGE_SIZE } else { 16 };
+    elf.extend(align.to_le_bytes());
+}
+
+/// `Elf64_Shdr`, without the fields that are always 0 here.
+struct SectionHeader {
+    name: u32,
+    kind: u32,
+    flags: u64,
+    address: u64,
+    offset: u64,
+    size: u64,
+    align: u64,
+}
+
+impl SectionHeader {
+    fn write(&self, elf: &mut Vec<u8>) {
+        elf.extend(self.name.to_le_bytes());
+        elf.extend(self.kind.to_le_bytes());
+        elf.extend(self.flags.to_le_bytes());
+        elf.extend(self.address.to_le_bytes());
+        elf.extend(self.offset.to_le_bytes());
+        elf.extend(self.size.to_le_bytes());
+        // sh_link and sh_info
+        elf.extend(0u32.to_le_bytes());
+        elf.extend(0u32.to_le_bytes());
+        elf.extend(self.align.to_le_bytes());
+        // sh_entsize
+        elf.extend(0u64.to_le_bytes());
+    }
+}
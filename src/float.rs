@@ -0,0 +1,989 @@
+//! Translate the F and D extensions.
+//!
+//! `f0-f31` are the `s` and `d` views of `v0-v31`, but RISC-V and ARM
+//! disagree on what a single precision value in a 64 bit register is. ARM
+//! zeroes the upper 32 bits when it writes an `s` register and ignores them
+//! when it reads one. RISC-V NaN-boxes singles, setting the upper 32 bits
+//! to ones, and reads a single which isn't NaN-boxed as the canonical NaN.
+//! So every single precision result is boxed after it is written, and
+//! every single precision operand is unboxed in place before it is read:
+//!
+//! ```text
+//! mov  v16.d[1], v16.d[0]     // keep the register in the upper half
+//! mov  w7, v16.s[1]
+//! cmn  w7, 1                  // NaN-boxed?
+//! b.eq .+12
+//! mov  w7, 2143289344         // the canonical NaN
+//! mov  v16.s[0], w7
+//! fadd s16, s16, s17          // ...
+//! mov  w7, -1
+//! mov  v16.s[1], w7           // box the result
+//! mov  v17.d[0], v17.d[1]     // restore the operands which weren't written
+//! ```
+//!
+//! The upper half of each `v` register is never a RISC-V register, so it
+//! holds the operand while the lower half is fixed up. Moves, loads and
+//! stores of the raw bits don't unbox, as on RISC-V.
+//!
+//! RISC-V also returns the canonical NaN for any NaN result, where ARM
+//! keeps the payload of a NaN operand unless `FPCR.DN` is set, so
+//! arithmetic results are canonicalized too. The accrued exception flags
+//! of `fflags` are not modeled, and static rounding modes other than `rne`
+//! are only supported by conversions to integers.
+use crate::error::AsmError;
+use crate::instruction::{
+    ArmCondition, ArmInstruction, ArmRegister, ArmRegisterName, ArmRounding, ArmVal, ArmWidth,
+    RiscVFRegister, RiscVInstruction, RiscVPrecision, RiscVRegister, RiscVRounding, RiscVWidth,
+};
+use crate::legalize::legalize;
+use crate::translate::{arm_register, map_register, map_val, sign_extend_word, SCRATCH};
+
+/// Translate a floating point instruction.
+///
+/// # Panics
+///
+/// If `riscv_instr` is not one of the F or D extension instructions.
+pub(crate) fn translate_float(
+    riscv_instr: RiscVInstruction,
+) -> Result<Vec<ArmInstruction>, AsmError> {
+    Ok(match riscv_instr {
+        RiscVInstruction::FL {
+            precision,
+            dest,
+            src,
+        } => {
+            let mut instrs = vec![ArmInstruction::Ldr {
+                width: map_precision(precision),
+                dest: map_fregister(dest, precision)?,
+                src: map_val(src, &RiscVWidth::Double)?,
+            }];
+            instrs.extend(nan_box(precision, dest));
+            instrs
+        }
+        RiscVInstruction::FS {
+            precision,
+            src,
+            dest,
+        } => vec![ArmInstruction::Str {
+            width: map_precision(precision),
+            src: map_fregister(src, precision)?,
+            dest: map_val(dest, &RiscVWidth::Double)?,
+        }],
+        RiscVInstruction::FAdd {
+            precision,
+            dest,
+            arg1,
+            arg2,
+            ..
+        } => arithmetic(precision, dest, &[arg1, arg2], |dest, args| {
+            ArmInstruction::Fadd {
+                dest,
+                arg1: args[0],
+                arg2: args[1],
+            }
+        })?,
+        RiscVInstruction::FSub {
+            precision,
+            dest,
+            arg1,
+            arg2,
+            ..
+        } => arithmetic(precision, dest, &[arg1, arg2], |dest, args| {
+            ArmInstruction::Fsub {
+                dest,
+                arg1: args[0],
+                arg2: args[1],
+            }
+        })?,
+        RiscVInstruction::FMul {
+            precision,
+            dest,
+            arg1,
+            arg2,
+            ..
+        } => arithmetic(precision, dest, &[arg1, arg2], |dest, args| {
+            ArmInstruction::Fmul {
+                dest,
+                arg1: args[0],
+                arg2: args[1],
+            }
+        })?,
+        RiscVInstruction::FDiv {
+            precision,
+            dest,
+            arg1,
+            arg2,
+            ..
+        } => arithmetic(precision, dest, &[arg1, arg2], |dest, args| {
+            ArmInstruction::Fdiv {
+                dest,
+                arg1: args[0],
+                arg2: args[1],
+            }
+        })?,
+        RiscVInstruction::FSqrt {
+            precision,
+            dest,
+            src,
+            ..
+        } => arithmetic(precision, dest, &[src], |dest, args| {
+            ArmInstruction::Fsqrt { dest, src: args[0] }
+        })?,
+        // ARM `fmsub` negates the product and `fnmsub` the addend, the
+        // other way around from RISC-V
+        RiscVInstruction::FMadd {
+            precision,
+            dest,
+            arg1,
+            arg2,
+            arg3,
+            ..
+        } => arithmetic(precision, dest, &[arg1, arg2, arg3], |dest, args| {
+            ArmInstruction::Fmadd {
+                dest,
+                arg1: args[0],
+                arg2: args[1],
+                arg3: args[2],
+            }
+        })?,
+        RiscVInstruction::FMsub {
+            precision,
+            dest,
+            arg1,
+            arg2,
+            arg3,
+            ..
+        } => arithmetic(precision, dest, &[arg1, arg2, arg3], |dest, args| {
+            ArmInstruction::Fnmsub {
+                dest,
+                arg1: args[0],
+                arg2: args[1],
+                arg3: args[2],
+            }
+        })?,
+        RiscVInstruction::FNmsub {
+            precision,
+            dest,
+            arg1,
+            arg2,
+            arg3,
+            ..
+        } => arithmetic(precision, dest, &[arg1, arg2, arg3], |dest, args| {
+            ArmInstruction::Fmsub {
+                dest,
+                arg1: args[0],
+                arg2: args[1],
+                arg3: args[2],
+            }
+        })?,
+        RiscVInstruction::FNmadd {
+            precision,
+            dest,
+            arg1,
+            arg2,
+            arg3,
+            ..
+        } => arithmetic(precision, dest, &[arg1, arg2, arg3], |dest, args| {
+            ArmInstruction::Fnmadd {
+                dest,
+                arg1: args[0],
+                arg2: args[1],
+                arg3: args[2],
+            }
+        })?,
+        RiscVInstruction::FMin {
+            precision,
+            dest,
+            arg1,
+            arg2,
+        } => translate_min_max(precision, dest, arg1, arg2, |dest, arg1, arg2| {
+            ArmInstruction::Fminnm { dest, arg1, arg2 }
+        })?,
+        RiscVInstruction::FMax {
+            precision,
+            dest,
+            arg1,
+            arg2,
+        } => translate_min_max(precision, dest, arg1, arg2, |dest, arg1, arg2| {
+            ArmInstruction::Fmaxnm { dest, arg1, arg2 }
+        })?,
+        RiscVInstruction::FSgnj {
+            precision,
+            dest,
+            arg1,
+            arg2,
+        } => translate_sign_injection(precision, dest, arg1, arg2, SignInjection::Copy)?,
+        RiscVInstruction::FSgnjn {
+            precision,
+            dest,
+            arg1,
+            arg2,
+        } => translate_sign_injection(precision, dest, arg1, arg2, SignInjection::Negate)?,
+        RiscVInstruction::FSgnjx {
+            precision,
+            dest,
+            arg1,
+            arg2,
+        } => translate_sign_injection(precision, dest, arg1, arg2, SignInjection::Xor)?,
+        // an unordered `fcmp` sets C and V, so none of these hold for NaN
+        RiscVInstruction::FEq {
+            precision,
+            dest,
+            arg1,
+            arg2,
+        } => translate_compare(precision, dest, arg1, arg2, ArmCondition::Eq)?,
+        RiscVInstruction::FLt {
+            precision,
+            dest,
+            arg1,
+            arg2,
+        } => translate_compare(precision, dest, arg1, arg2, ArmCondition::Mi)?,
+        RiscVInstruction::FLe {
+            precision,
+            dest,
+            arg1,
+            arg2,
+        } => translate_compare(precision, dest, arg1, arg2, ArmCondition::Ls)?,
+        RiscVInstruction::FClass {
+            precision,
+            dest,
+            src,
+        } => translate_class(precision, dest, src)?,
+        RiscVInstruction::FMvToInt {
+            precision,
+            dest,
+            src,
+        } => {
+            if dest == RiscVRegister::X0 {
+                return Ok(vec![]);
+            }
+            let width = int_width(precision);
+            let mut instrs = vec![ArmInstruction::Fmov {
+                dest: map_register(dest, &width)?,
+                src: map_fregister(src, precision)?,
+            }];
+            if width == RiscVWidth::Word {
+                instrs.extend(sign_extend_word(dest)?);
+            }
+            instrs
+        }
+        RiscVInstruction::FMvFromInt {
+            precision,
+            dest,
+            src,
+        } => {
+            let mut instrs = vec![ArmInstruction::Fmov {
+                dest: map_fregister(dest, precision)?,
+                src: map_register(src, &int_width(precision))?,
+            }];
+            instrs.extend(nan_box(precision, dest));
+            instrs
+        }
+        RiscVInstruction::FCvtToInt {
+            precision,
+            width,
+            signed,
+            rounding,
+            dest,
+            src,
+        } => translate_convert_to_int(precision, width, signed, rounding, dest, src)?,
+        RiscVInstruction::FCvtFromInt {
+            precision,
+            width,
+            signed,
+            dest,
+            src,
+            ..
+        } => {
+            let (dest_reg, src) = (map_fregister(dest, precision)?, map_register(src, &width)?);
+            let mut instrs = vec![if signed {
+                ArmInstruction::Scvtf {
+                    dest: dest_reg,
+                    src,
+                }
+            } else {
+                ArmInstruction::Ucvtf {
+                    dest: dest_reg,
+                    src,
+                }
+            }];
+            instrs.extend(nan_box(precision, dest));
+            instrs
+        }
+        RiscVInstruction::FCvt {
+            precision,
+            dest,
+            src,
+            ..
+        } => {
+            let src_precision = match precision {
+                RiscVPrecision::Single => RiscVPrecision::Double,
+                RiscVPrecision::Double => RiscVPrecision::Single,
+            };
+            let mut op = vec![ArmInstruction::Fcvt {
+                dest: map_fregister(dest, precision)?,
+                src: map_fregister(src, src_precision)?,
+            }];
+            op.extend(canonicalize(precision, dest)?);
+            op.extend(nan_box(precision, dest));
+            with_operands(src_precision, &[src], Some(dest), false, op)?
+        }
+        other => panic!("{:?} is not a floating point instruction", other),
+    })
+}
+
+/// An arithmetic instruction, whose NaN results are canonicalized.
+fn arithmetic(
+    precision: RiscVPrecision,
+    dest: RiscVFRegister,
+    args: &[RiscVFRegister],
+    op: impl FnOnce(ArmRegister, Vec<ArmRegister>) -> ArmInstruction,
+) -> Result<Vec<ArmInstruction>, AsmError> {
+    let arm_args = args
+        .iter()
+        .map(|arg| map_fregister(*arg, precision))
+        .collect::<Result<_, _>>()?;
+    let mut instrs = vec![op(map_fregister(dest, precision)?, arm_args)];
+    instrs.extend(canonicalize(precision, dest)?);
+    instrs.extend(nan_box(precision, dest));
+    with_operands(precision, args, Some(dest), false, instrs)
+}
+
+/// RISC-V `fmin`/`fmax` ignore a NaN operand of either kind, and give the
+/// canonical NaN when both are NaN. ARM `fminnm`/`fmaxnm` only ignore quiet
+/// NaNs, so NaN operands are first replaced with the canonical NaN, which
+/// also makes the result the canonical NaN when both are.
+fn translate_min_max(
+    precision: RiscVPrecision,
+    dest: RiscVFRegister,
+    arg1: RiscVFRegister,
+    arg2: RiscVFRegister,
+    op: impl FnOnce(ArmRegister, ArmRegister, ArmRegister) -> ArmInstruction,
+) -> Result<Vec<ArmInstruction>, AsmError> {
+    let mut instrs = vec![op(
+        map_fregister(dest, precision)?,
+        map_fregister(arg1, precision)?,
+        map_fregister(arg2, precision)?,
+    )];
+    instrs.extend(nan_box(precision, dest));
+    with_operands(precision, &[arg1, arg2], Some(dest), true, instrs)
+}
+
+/// Where the sign of an `fsgnj*` result comes from.
+#[derive(PartialEq)]
+enum SignInjection {
+    /// the sign of `arg2`
+    Copy,
+    /// the opposite sign of `arg2`
+    Negate,
+    /// the sign of `arg1` xor the sign of `arg2`
+    Xor,
+}
+
+/// `fsgnj*` only move the sign bit, so NaNs keep their payload. With the
+/// same operand twice they are `fmv`, `fneg` and `fabs`, which ARM has.
+/// Otherwise the result is built from `|arg1|` or `arg1` and negated
+/// depending on the sign of `arg2`:
+///
+/// ```text
+/// fmov w7, s17
+/// tst  w7, 2147483648
+/// fabs s16, s18
+/// b.eq .+8
+/// fneg s16, s16
+/// ```
+fn translate_sign_injection(
+    precision: RiscVPrecision,
+    dest: RiscVFRegister,
+    arg1: RiscVFRegister,
+    arg2: RiscVFRegister,
+    injection: SignInjection,
+) -> Result<Vec<ArmInstruction>, AsmError> {
+    let arm_dest = map_fregister(dest, precision)?;
+    let src = map_fregister(arg1, precision)?;
+    let mut instrs = if arg1 == arg2 {
+        vec![match injection {
+            SignInjection::Copy => ArmInstruction::Fmov {
+                dest: arm_dest,
+                src,
+            },
+            SignInjection::Negate => ArmInstruction::Fneg {
+                dest: arm_dest,
+                src,
+            },
+            SignInjection::Xor => ArmInstruction::Fabs {
+                dest: arm_dest,
+                src,
+            },
+        }]
+    } else {
+        let layout = Layout::of(precision);
+        let scratch = scratch(precision)?;
+        let negate = ArmInstruction::Fneg {
+            dest: arm_dest,
+            src: arm_dest,
+        };
+        vec![
+            ArmInstruction::Fmov {
+                dest: scratch,
+                src: map_fregister(arg2, precision)?,
+            },
+            ArmInstruction::Tst {
+                op1: scratch,
+                op2: ArmVal::Imm(layout.sign),
+            },
+            match injection {
+                SignInjection::Xor => ArmInstruction::Fmov {
+                    dest: arm_dest,
+                    src,
+                },
+                _ => ArmInstruction::Fabs {
+                    dest: arm_dest,
+                    src,
+                },
+            },
+            ArmInstruction::BCond {
+                cond: match injection {
+                    SignInjection::Negate => ArmCondition::Ne,
+                    _ => ArmCondition::Eq,
+                },
+                target: skip(std::slice::from_ref(&negate)),
+            },
+            negate,
+        ]
+    };
+    instrs.extend(nan_box(precision, dest));
+    with_operands(precision, &[arg1, arg2], Some(dest), false, instrs)
+}
+
+/// Set `dest` to 1 if `cond` holds after comparing `arg1` with `arg2`.
+fn translate_compare(
+    precision: RiscVPrecision,
+    dest: RiscVRegister,
+    arg1: RiscVFRegister,
+    arg2: RiscVFRegister,
+    cond: ArmCondition,
+) -> Result<Vec<ArmInstruction>, AsmError> {
+    if dest == RiscVRegister::X0 {
+        return Ok(vec![]);
+    }
+    let instrs = vec![
+        ArmInstruction::Fcmp {
+            op1: map_fregister(arg1, precision)?,
+            op2: map_fregister(arg2, precision)?,
+        },
+        ArmInstruction::Cset {
+            dest: map_register(dest, &RiscVWidth::Double)?,
+            cond,
+        },
+    ];
+    with_operands(precision, &[arg1, arg2], None, false, instrs)
+}
+
+/// `fclass` sets one of 10 bits, which is found from the fields of the
+/// float without branches, except to tell NaNs apart:
+///
+/// ```text
+/// n     = zero 0, subnormal 1, normal 2, infinite 3
+/// class = negative ? 3 - n : 4 + n
+/// class = signaling NaN 8, quiet NaN 9
+/// dest  = 1 << class
+/// ```
+fn translate_class(
+    precision: RiscVPrecision,
+    dest: RiscVRegister,
+    src: RiscVFRegister,
+) -> Result<Vec<ArmInstruction>, AsmError> {
+    if dest == RiscVRegister::X0 {
+        return Ok(vec![]);
+    }
+    let layout = Layout::of(precision);
+    let src_reg = map_fregister(src, precision)?;
+    let scratch = scratch(precision)?;
+    let scratch_word = ArmRegister::new(SCRATCH, ArmWidth::Word);
+    let class = map_register(dest, &RiscVWidth::Word)?;
+    let zero = ArmRegister::new(ArmRegisterName::Zero, ArmWidth::Word);
+
+    let ordered = vec![
+        ArmInstruction::Fmov {
+            dest: scratch,
+            src: src_reg,
+        },
+        // n is 1 for a nonzero mantissa, which is a subnormal if the
+        // exponent is 0
+        ArmInstruction::Tst {
+            op1: scratch,
+            op2: ArmVal::Imm(layout.mantissa),
+        },
+        ArmInstruction::Cset {
+            dest: class,
+            cond: ArmCondition::Ne,
+        },
+        ArmInstruction::Lsl {
+            dest: scratch,
+            src: scratch,
+            imm: 1,
+        },
+        ArmInstruction::Lsr {
+            dest: scratch,
+            src: scratch,
+            imm: layout.mantissa_bits + 1,
+        },
+        ArmInstruction::Cmp {
+            op1: scratch_word,
+            op2: ArmVal::Imm(0),
+        },
+        ArmInstruction::Csinc {
+            dest: class,
+            arg1: class,
+            arg2: zero,
+            cond: ArmCondition::Eq,
+        },
+        ArmInstruction::Csinc {
+            dest: class,
+            arg1: class,
+            arg2: class,
+            cond: ArmCondition::Eq,
+        },
+        ArmInstruction::Cmp {
+            op1: scratch_word,
+            op2: ArmVal::Imm(layout.max_exponent),
+        },
+        ArmInstruction::Csinc {
+            dest: class,
+            arg1: class,
+            arg2: class,
+            cond: ArmCondition::Ne,
+        },
+        ArmInstruction::Fmov {
+            dest: scratch,
+            src: src_reg,
+        },
+        ArmInstruction::Tst {
+            op1: scratch,
+            op2: ArmVal::Imm(layout.sign),
+        },
+        ArmInstruction::Mov {
+            width: ArmWidth::Word,
+            dest: scratch_word,
+            src: ArmVal::Imm(3),
+        },
+        ArmInstruction::Sub {
+            dest: scratch_word,
+            arg1: scratch_word,
+            arg2: ArmVal::Reg(class),
+        },
+        ArmInstruction::Add {
+            dest: class,
+            arg1: class,
+            arg2: ArmVal::Imm(4),
+        },
+        ArmInstruction::Csel {
+            dest: class,
+            arg1: scratch_word,
+            arg2: class,
+            cond: ArmCondition::Ne,
+        },
+    ];
+    let nan = vec![
+        ArmInstruction::Fmov {
+            dest: scratch,
+            src: src_reg,
+        },
+        ArmInstruction::Tst {
+            op1: scratch,
+            op2: ArmVal::Imm(layout.quiet),
+        },
+        ArmInstruction::Cset {
+            dest: class,
+            cond: ArmCondition::Ne,
+        },
+        ArmInstruction::Add {
+            dest: class,
+            arg1: class,
+            arg2: ArmVal::Imm(8),
+        },
+        ArmInstruction::B {
+            target: skip(&ordered),
+        },
+    ];
+
+    let mut instrs = vec![
+        ArmInstruction::Fcmp {
+            op1: src_reg,
+            op2: src_reg,
+        },
+        ArmInstruction::BCond {
+            cond: ArmCondition::Vc,
+            target: skip(&nan),
+        },
+    ];
+    instrs.extend(nan);
+    instrs.extend(ordered);
+    instrs.extend([
+        ArmInstruction::Mov {
+            width: ArmWidth::Word,
+            dest: scratch_word,
+            src: ArmVal::Imm(1),
+        },
+        ArmInstruction::Lslv {
+            dest: map_register(dest, &RiscVWidth::Double)?,
+            arg1: ArmRegister::new(SCRATCH, ArmWidth::Double),
+            arg2: map_register(dest, &RiscVWidth::Double)?,
+        },
+    ]);
+    with_operands(precision, &[src], None, false, instrs)
+}
+
+/// Both ISAs saturate out of range conversions, but ARM converts NaN to 0
+/// where RISC-V gives the largest integer, so that is selected when the
+/// operand is unordered with itself. `*.w` results are sign extended like
+/// other 32 bit results.
+fn translate_convert_to_int(
+    precision: RiscVPrecision,
+    width: RiscVWidth,
+    signed: bool,
+    rounding: RiscVRounding,
+    dest: RiscVRegister,
+    src: RiscVFRegister,
+) -> Result<Vec<ArmInstruction>, AsmError> {
+    if dest == RiscVRegister::X0 {
+        return Ok(vec![]);
+    }
+    let src_reg = map_fregister(src, precision)?;
+    let dest_reg = map_register(dest, &width)?;
+    let arm_width = match width {
+        RiscVWidth::Word => ArmWidth::Word,
+        _ => ArmWidth::Double,
+    };
+    let mut instrs = vec![
+        ArmInstruction::Fcmp {
+            op1: src_reg,
+            op2: src_reg,
+        },
+        ArmInstruction::FcvtToInt {
+            rounding: map_rounding(rounding),
+            signed,
+            dest: dest_reg,
+            src: src_reg,
+        },
+    ];
+    if signed {
+        let scratch = ArmRegister::new(SCRATCH, arm_width);
+        let max = match width {
+            RiscVWidth::Word => i32::MAX.into(),
+            _ => i64::MAX,
+        };
+        instrs.extend([
+            ArmInstruction::Mov {
+                width: arm_width,
+                dest: scratch,
+                src: ArmVal::Imm(max),
+            },
+            ArmInstruction::Csel {
+                dest: dest_reg,
+                arg1: dest_reg,
+                arg2: scratch,
+                cond: ArmCondition::Vc,
+            },
+        ]);
+    } else {
+        instrs.push(ArmInstruction::Csinv {
+            dest: dest_reg,
+            arg1: dest_reg,
+            arg2: ArmRegister::new(ArmRegisterName::Zero, arm_width),
+            cond: ArmCondition::Vc,
+        });
+    }
+    if width == RiscVWidth::Word {
+        instrs.extend(sign_extend_word(dest)?);
+    }
+    with_operands(precision, &[src], None, false, instrs)
+}
+
+/// Surround `op` with the fixups of its floating point operands `args`:
+/// unboxing singles, and with `quiet` replacing NaNs with the canonical
+/// NaN. Fixed up operands are put back afterwards unless `op` wrote them
+/// as `dest`.
+fn with_operands(
+    precision: RiscVPrecision,
+    args: &[RiscVFRegister],
+    dest: Option<RiscVFRegister>,
+    quiet: bool,
+    op: Vec<ArmInstruction>,
+) -> Result<Vec<ArmInstruction>, AsmError> {
+    if precision == RiscVPrecision::Double && !quiet {
+        return Ok(op);
+    }
+    let mut operands: Vec<RiscVFRegister> = vec![];
+    for arg in args {
+        if !operands.contains(arg) {
+            operands.push(*arg);
+        }
+    }
+
+    let mut instrs = vec![];
+    for &operand in &operands {
+        let name = map_fregister_name(operand);
+        instrs.push(ArmInstruction::Ins {
+            dest: ArmVal::Element(name, ArmWidth::Double, 1),
+            src: ArmVal::Element(name, ArmWidth::Double, 0),
+        });
+        if precision == RiscVPrecision::Single {
+            instrs.extend(unbox(operand));
+        }
+        if quiet {
+            instrs.extend(quiet_nan(precision, operand)?);
+        }
+    }
+    instrs.extend(op);
+    for &operand in &operands {
+        if Some(operand) != dest {
+            let name = map_fregister_name(operand);
+            instrs.push(ArmInstruction::Ins {
+                dest: ArmVal::Element(name, ArmWidth::Double, 0),
+                src: ArmVal::Element(name, ArmWidth::Double, 1),
+            });
+        }
+    }
+    Ok(instrs)
+}
+
+/// Replace a single which isn't NaN-boxed with the canonical NaN, keeping
+/// the upper 32 bits.
+fn unbox(reg: RiscVFRegister) -> Vec<ArmInstruction> {
+    let name = map_fregister_name(reg);
+    let scratch = ArmRegister::new(SCRATCH, ArmWidth::Word);
+    let replace = vec![
+        ArmInstruction::Mov {
+            width: ArmWidth::Word,
+            dest: scratch,
+            src: ArmVal::Imm(Layout::of(RiscVPrecision::Single).canonical_nan),
+        },
+        ArmInstruction::Ins {
+            dest: ArmVal::Element(name, ArmWidth::Word, 0),
+            src: ArmVal::Reg(scratch),
+        },
+    ];
+    let mut instrs = vec![
+        ArmInstruction::Mov {
+            width: ArmWidth::Word,
+            dest: scratch,
+            src: ArmVal::Element(name, ArmWidth::Word, 1),
+        },
+        ArmInstruction::Cmn {
+            op1: scratch,
+            op2: ArmVal::Imm(1),
+        },
+        ArmInstruction::BCond {
+            cond: ArmCondition::Eq,
+            target: skip(&replace),
+        },
+    ];
+    instrs.extend(replace);
+    instrs
+}
+
+/// Replace a NaN in `reg` with the canonical NaN, keeping the upper half
+/// of the register.
+fn quiet_nan(
+    precision: RiscVPrecision,
+    reg: RiscVFRegister,
+) -> Result<Vec<ArmInstruction>, AsmError> {
+    let name = map_fregister_name(reg);
+    let arm_reg = map_fregister(reg, precision)?;
+    let scratch = scratch(precision)?;
+    let replace = vec![
+        ArmInstruction::Mov {
+            width: scratch.width(),
+            dest: scratch,
+            src: ArmVal::Imm(Layout::of(precision).canonical_nan),
+        },
+        ArmInstruction::Ins {
+            dest: ArmVal::Element(name, scratch.width(), 0),
+            src: ArmVal::Reg(scratch),
+        },
+    ];
+    Ok(replace_if_nan(arm_reg, replace))
+}
+
+/// Replace a NaN result in `reg` with the canonical NaN.
+fn canonicalize(
+    precision: RiscVPrecision,
+    reg: RiscVFRegister,
+) -> Result<Vec<ArmInstruction>, AsmError> {
+    let arm_reg = map_fregister(reg, precision)?;
+    let scratch = scratch(precision)?;
+    let replace = vec![
+        ArmInstruction::Mov {
+            width: scratch.width(),
+            dest: scratch,
+            src: ArmVal::Imm(Layout::of(precision).canonical_nan),
+        },
+        ArmInstruction::Fmov {
+            dest: arm_reg,
+            src: scratch,
+        },
+    ];
+    Ok(replace_if_nan(arm_reg, replace))
+}
+
+/// Run `replace` only if `reg` is NaN, which is unordered with itself.
+fn replace_if_nan(reg: ArmRegister, replace: Vec<ArmInstruction>) -> Vec<ArmInstruction> {
+    let mut instrs = vec![
+        ArmInstruction::Fcmp { op1: reg, op2: reg },
+        ArmInstruction::BCond {
+            cond: ArmCondition::Vc,
+            target: skip(&replace),
+        },
+    ];
+    instrs.extend(replace);
+    instrs
+}
+
+/// Set the upper 32 bits of a single precision result to ones.
+fn nan_box(precision: RiscVPrecision, reg: RiscVFRegister) -> Vec<ArmInstruction> {
+    if precision == RiscVPrecision::Double {
+        return vec![];
+    }
+    let scratch = ArmRegister::new(SCRATCH, ArmWidth::Word);
+    vec![
+        ArmInstruction::Mov {
+            width: ArmWidth::Word,
+            dest: scratch,
+            src: ArmVal::Imm(-1),
+        },
+        ArmInstruction::Ins {
+            dest: ArmVal::Element(map_fregister_name(reg), ArmWidth::Word, 1),
+            src: ArmVal::Reg(scratch),
+        },
+    ]
+}
+
+/// A branch target past the `skipped` instructions right after the branch.
+/// The offset is counted before [`legalize`] runs on the translation, so it
+/// must leave them as they are, which the constants moved between a branch
+/// and its target are all a single `movz` or `movn` for.
+fn skip(skipped: &[ArmInstruction]) -> ArmVal {
+    debug_assert_eq!(
+        legalize(skipped.to_vec()).len(),
+        skipped.len(),
+        "legalize changes the skipped instructions {:?}",
+        skipped
+    );
+    ArmVal::LabelOffset(".".to_string(), 4 * (skipped.len() as i32 + 1))
+}
+
+/// The fields of an IEEE 754 float.
+struct Layout {
+    sign: i64,
+    quiet: i64,
+    mantissa: i64,
+    mantissa_bits: i32,
+    max_exponent: i64,
+    /// The quiet NaN which RISC-V returns for every NaN result
+    canonical_nan: i64,
+}
+
+impl Layout {
+    fn of(precision: RiscVPrecision) -> Layout {
+        match precision {
+            RiscVPrecision::Single => Layout {
+                sign: 1 << 31,
+                quiet: 1 << 22,
+                mantissa: (1 << 23) - 1,
+                mantissa_bits: 23,
+                max_exponent: 0xff,
+                canonical_nan: 0x7fc0_0000,
+            },
+            RiscVPrecision::Double => Layout {
+                sign: i64::MIN,
+                quiet: 1 << 51,
+                mantissa: (1 << 52) - 1,
+                mantissa_bits: 52,
+                max_exponent: 0x7ff,
+                canonical_nan: 0x7ff8_0000_0000_0000,
+            },
+        }
+    }
+}
+
+/// The scratch register as wide as a float of `precision`.
+fn scratch(precision: RiscVPrecision) -> Result<ArmRegister, AsmError> {
+    arm_register(SCRATCH, map_precision(precision))
+}
+
+/// The integer registers which hold the bits of a float.
+fn int_width(precision: RiscVPrecision) -> RiscVWidth {
+    match precision {
+        RiscVPrecision::Single => RiscVWidth::Word,
+        RiscVPrecision::Double => RiscVWidth::Double,
+    }
+}
+
+/// Singles are `s` registers and 32 bit memory accesses, doubles are `d`
+/// registers and 64 bit accesses.
+fn map_precision(precision: RiscVPrecision) -> ArmWidth {
+    match precision {
+        RiscVPrecision::Single => ArmWidth::Word,
+        RiscVPrecision::Double => ArmWidth::Double,
+    }
+}
+
+/// `dyn` is the `rne` of the `frm` CSR.
+fn map_rounding(rounding: RiscVRounding) -> ArmRounding {
+    match rounding {
+        RiscVRounding::Rne | RiscVRounding::Dyn => ArmRounding::Nearest,
+        RiscVRounding::Rtz => ArmRounding::Zero,
+        RiscVRounding::Rdn => ArmRounding::MinusInf,
+        RiscVRounding::Rup => ArmRounding::PlusInf,
+        RiscVRounding::Rmm => ArmRounding::Away,
+    }
+}
+
+fn map_fregister(reg: RiscVFRegister, precision: RiscVPrecision) -> Result<ArmRegister, AsmError> {
+    arm_register(map_fregister_name(reg), map_precision(precision))
+}
+
+/// The RISC-V and ARM calling conventions both pass floats in the first 8
+/// registers, so `fa0-fa7` are `v0-v7`. ARM only saves `v8-v15` across
+/// calls, which hold `fs0-fs7`, and `fs8-fs11` are caller-saved `v24-v27`,
+/// so translated code which calls ARM code must not keep values in them.
+fn map_fregister_name(reg: RiscVFRegister) -> ArmRegisterName {
+    use ArmRegisterName::*;
+
+    match reg {
+        RiscVFRegister::FT0 => V16,
+        RiscVFRegister::FT1 => V17,
+        RiscVFRegister::FT2 => V18,
+        RiscVFRegister::FT3 => V19,
+        RiscVFRegister::FT4 => V20,
+        RiscVFRegister::FT5 => V21,
+        RiscVFRegister::FT6 => V22,
+        RiscVFRegister::FT7 => V23,
+        RiscVFRegister::FS0 => V8,
+        RiscVFRegister::FS1 => V9,
+        RiscVFRegister::FA0 => V0,
+        RiscVFRegister::FA1 => V1,
+        RiscVFRegister::FA2 => V2,
+        RiscVFRegister::FA3 => V3,
+        RiscVFRegister::FA4 => V4,
+        RiscVFRegister::FA5 => V5,
+        RiscVFRegister::FA6 => V6,
+        RiscVFRegister::FA7 => V7,
+        RiscVFRegister::FS2 => V10,
+        RiscVFRegister::FS3 => V11,
+        RiscVFRegister::FS4 => V12,
+        RiscVFRegister::FS5 => V13,
+        RiscVFRegister::FS6 => V14,
+        RiscVFRegister::FS7 => V15,
+        RiscVFRegister::FS8 => V24,
+        RiscVFRegister::FS9 => V25,
+        RiscVFRegister::FS10 => V26,
+        RiscVFRegister::FS11 => V27,
+        RiscVFRegister::FT8 => V28,
+        RiscVFRegister::FT9 => V29,
+        RiscVFRegister::FT10 => V30,
+        RiscVFRegister::FT11 => V31,
+    }
+}
@@ -1,6 +1,5 @@
-use std::convert::Into;
-use std::default;
-use std::fmt::{format, write, Display};
+use std::fmt::Display;
+use std::str::FromStr;
 
 /// This file defines all the supported ARM and RISC-V instructions we support.
 /// We use `strum` to assist in serializing asm files to our [`Instruction`] enum.
@@ -13,13 +12,15 @@ use std::fmt::{format, write, Display};
 /// https://github.com/nbdd0121/r2vm/blob/5118be6b9e757c6fef2f019385873f403c23c548/lib/riscv/src/op.rs#L30
 use strum_macros::EnumString;
 
+pub use crate::parse::parse_asm;
+
 pub enum RiscVSyscalls {
     WRITE,
     EXIT,
 }
 
 impl RiscVSyscalls {
-    const fn value(&self) -> i32 {
+    pub const fn value(&self) -> i32 {
         match self {
             Self::WRITE => 64,
             Self::EXIT => 0,
@@ -27,9 +28,24 @@ impl RiscVSyscalls {
     }
 }
 
-#[derive(Debug, Default)]
+/// Width of an operation, or of the memory accessed by a load or store.
+///
+/// Loads narrower than 64 bits sign extend, except for the `Unsigned`
+/// widths which zero extend. Stores only use the signed widths.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
 pub enum RiscVWidth {
+    /// 8 bits, `lb`/`sb`
+    Byte,
+    /// 8 bits, `lbu`
+    UnsignedByte,
+    /// 16 bits, `lh`/`sh`
+    Half,
+    /// 16 bits, `lhu`
+    UnsignedHalf,
+    /// 32 bits, `lw`/`sw` and the `*w` instructions
     Word,
+    /// 32 bits, `lwu`
+    UnsignedWord,
     #[default]
     Double,
 }
@@ -40,7 +56,7 @@ pub enum RiscVWidth {
 /// To make a function call in RISC-V you use the `jal` (jump and link)
 /// instruction. This would require us ensure that we translate the RISC-V
 /// calling convention into ARM. (`https://riscv.org/wp-content/uploads/2024/12/riscv-calling.pdf)
-#[derive(Debug, EnumString)]
+#[derive(Debug, EnumString, Clone, PartialEq)]
 pub enum RiscVInstruction {
     /// add immediate
     ///
@@ -51,6 +67,15 @@ pub enum RiscVInstruction {
         src: RiscVRegister,
         imm: i32,
     },
+    /// add word immediate
+    ///
+    /// `x[rd] = sext((x[rs1] + sext(immediate))[31:0])`
+    #[strum(serialize = "addiw")]
+    Addiw {
+        dest: RiscVRegister,
+        src: RiscVRegister,
+        imm: i32,
+    },
     /// add label/offset addr (not a real RISC-V instr)
     Addl {
         dest: RiscVRegister,
@@ -78,16 +103,304 @@ pub enum RiscVInstruction {
         arg1: RiscVRegister,
         arg2: RiscVRegister,
     },
-    /// branch if less than or equal
-    #[strum(serialize = "ble")]
-    Ble {
+    /// multiply
+    ///     either mul or mulw
+    ///
+    /// `x[rd] = x[rs1] * x[rs2]`
+    #[strum(serialize = "mul", serialize = "mulw")]
+    Mul {
+        width: RiscVWidth,
+        dest: RiscVRegister,
+        arg1: RiscVRegister,
+        arg2: RiscVRegister,
+    },
+    /// multiply high, signed x signed
+    ///
+    /// `x[rd] = (x[rs1] s*s x[rs2]) >>s XLEN`
+    #[strum(serialize = "mulh")]
+    Mulh {
+        dest: RiscVRegister,
+        arg1: RiscVRegister,
+        arg2: RiscVRegister,
+    },
+    /// multiply high, unsigned x unsigned
+    ///
+    /// `x[rd] = (x[rs1] u*u x[rs2]) >>u XLEN`
+    #[strum(serialize = "mulhu")]
+    Mulhu {
+        dest: RiscVRegister,
+        arg1: RiscVRegister,
+        arg2: RiscVRegister,
+    },
+    /// multiply high, signed x unsigned
+    ///
+    /// `x[rd] = (x[rs1] s*u x[rs2]) >>s XLEN`
+    #[strum(serialize = "mulhsu")]
+    Mulhsu {
+        dest: RiscVRegister,
+        arg1: RiscVRegister,
+        arg2: RiscVRegister,
+    },
+    /// signed divide, rounding towards zero
+    ///     either div or divw
+    ///
+    /// `x[rd] = x[rs1] /s x[rs2]`, or -1 when dividing by zero
+    #[strum(serialize = "div", serialize = "divw")]
+    Div {
+        width: RiscVWidth,
+        dest: RiscVRegister,
+        arg1: RiscVRegister,
+        arg2: RiscVRegister,
+    },
+    /// unsigned divide
+    ///     either divu or divuw
+    ///
+    /// `x[rd] = x[rs1] /u x[rs2]`, or all ones when dividing by zero
+    #[strum(serialize = "divu", serialize = "divuw")]
+    Divu {
+        width: RiscVWidth,
+        dest: RiscVRegister,
+        arg1: RiscVRegister,
+        arg2: RiscVRegister,
+    },
+    /// signed remainder, with the sign of the dividend
+    ///     either rem or remw
+    ///
+    /// `x[rd] = x[rs1] %s x[rs2]`, or x[rs1] when dividing by zero
+    #[strum(serialize = "rem", serialize = "remw")]
+    Rem {
+        width: RiscVWidth,
+        dest: RiscVRegister,
+        arg1: RiscVRegister,
+        arg2: RiscVRegister,
+    },
+    /// unsigned remainder
+    ///     either remu or remuw
+    ///
+    /// `x[rd] = x[rs1] %u x[rs2]`, or x[rs1] when dividing by zero
+    #[strum(serialize = "remu", serialize = "remuw")]
+    Remu {
+        width: RiscVWidth,
+        dest: RiscVRegister,
+        arg1: RiscVRegister,
+        arg2: RiscVRegister,
+    },
+    /// bitwise and
+    ///
+    /// `x[rd] = x[rs1] & x[rs2]`
+    #[strum(serialize = "and")]
+    And {
+        dest: RiscVRegister,
+        arg1: RiscVRegister,
+        arg2: RiscVRegister,
+    },
+    /// bitwise or
+    ///
+    /// `x[rd] = x[rs1] | x[rs2]`
+    #[strum(serialize = "or")]
+    Or {
+        dest: RiscVRegister,
+        arg1: RiscVRegister,
+        arg2: RiscVRegister,
+    },
+    /// bitwise exclusive or
+    ///
+    /// `x[rd] = x[rs1] ^ x[rs2]`
+    #[strum(serialize = "xor")]
+    Xor {
+        dest: RiscVRegister,
+        arg1: RiscVRegister,
+        arg2: RiscVRegister,
+    },
+    /// bitwise and immediate
+    ///
+    /// `x[rd] = x[rs1] & sext(immediate)`
+    #[strum(serialize = "andi")]
+    Andi {
+        dest: RiscVRegister,
+        src: RiscVRegister,
+        imm: i32,
+    },
+    /// bitwise or immediate
+    ///
+    /// `x[rd] = x[rs1] | sext(immediate)`
+    #[strum(serialize = "ori")]
+    Ori {
+        dest: RiscVRegister,
+        src: RiscVRegister,
+        imm: i32,
+    },
+    /// bitwise exclusive or immediate
+    ///
+    /// `x[rd] = x[rs1] ^ sext(immediate)`
+    #[strum(serialize = "xori")]
+    Xori {
+        dest: RiscVRegister,
+        src: RiscVRegister,
+        imm: i32,
+    },
+    /// bitwise not, psuedo instruction for `xori rd, rs, -1`
+    ///
+    /// `x[rd] = ~x[rs1]`
+    #[strum(serialize = "not")]
+    Not {
+        dest: RiscVRegister,
+        src: RiscVRegister,
+    },
+    /// negate, psuedo instruction for `sub rd, x0, rs`
+    ///     either neg or negw
+    ///
+    /// `x[rd] = -x[rs1]`
+    #[strum(serialize = "neg", serialize = "negw")]
+    Neg {
+        width: RiscVWidth,
+        dest: RiscVRegister,
+        src: RiscVRegister,
+    },
+    /// shift left logical
+    ///     either sll or sllw
+    ///
+    /// `x[rd] = x[rs1] << x[rs2]`, shifting by the low 6 (5 for sllw) bits
+    #[strum(serialize = "sll", serialize = "sllw")]
+    Sll {
+        width: RiscVWidth,
+        dest: RiscVRegister,
+        arg1: RiscVRegister,
+        arg2: RiscVRegister,
+    },
+    /// shift right logical
+    ///     either srl or srlw
+    ///
+    /// `x[rd] = x[rs1] >>u x[rs2]`, shifting by the low 6 (5 for srlw) bits
+    #[strum(serialize = "srl", serialize = "srlw")]
+    Srl {
+        width: RiscVWidth,
+        dest: RiscVRegister,
+        arg1: RiscVRegister,
+        arg2: RiscVRegister,
+    },
+    /// shift right arithmetic
+    ///     either sra or sraw
+    ///
+    /// `x[rd] = x[rs1] >>s x[rs2]`, shifting by the low 6 (5 for sraw) bits
+    #[strum(serialize = "sra", serialize = "sraw")]
+    Sra {
+        width: RiscVWidth,
+        dest: RiscVRegister,
+        arg1: RiscVRegister,
+        arg2: RiscVRegister,
+    },
+    /// shift left logical immediate
+    ///     either slli or slliw
+    ///
+    /// `x[rd] = x[rs1] << shamt`
+    #[strum(serialize = "slli", serialize = "slliw")]
+    Slli {
+        width: RiscVWidth,
+        dest: RiscVRegister,
+        src: RiscVRegister,
+        imm: i32,
+    },
+    /// shift right logical immediate
+    ///     either srli or srliw
+    ///
+    /// `x[rd] = x[rs1] >>u shamt`
+    #[strum(serialize = "srli", serialize = "srliw")]
+    Srli {
+        width: RiscVWidth,
+        dest: RiscVRegister,
+        src: RiscVRegister,
+        imm: i32,
+    },
+    /// shift right arithmetic immediate
+    ///     either srai or sraiw
+    ///
+    /// `x[rd] = x[rs1] >>s shamt`
+    #[strum(serialize = "srai", serialize = "sraiw")]
+    Srai {
+        width: RiscVWidth,
+        dest: RiscVRegister,
+        src: RiscVRegister,
+        imm: i32,
+    },
+    /// set if less than
+    ///
+    /// `x[rd] = x[rs1] <s x[rs2]`
+    #[strum(serialize = "slt")]
+    Slt {
+        dest: RiscVRegister,
+        arg1: RiscVRegister,
+        arg2: RiscVRegister,
+    },
+    /// set if less than, unsigned
+    ///
+    /// `x[rd] = x[rs1] <u x[rs2]`
+    #[strum(serialize = "sltu")]
+    Sltu {
+        dest: RiscVRegister,
+        arg1: RiscVRegister,
+        arg2: RiscVRegister,
+    },
+    /// set if less than immediate
+    ///
+    /// `x[rd] = x[rs1] <s sext(immediate)`
+    #[strum(serialize = "slti")]
+    Slti {
+        dest: RiscVRegister,
+        src: RiscVRegister,
+        imm: i32,
+    },
+    /// set if less than immediate, unsigned
+    ///
+    /// `x[rd] = x[rs1] <u sext(immediate)`
+    #[strum(serialize = "sltiu")]
+    Sltiu {
+        dest: RiscVRegister,
+        src: RiscVRegister,
+        imm: i32,
+    },
+    /// set if equal to zero, psuedo instruction for `sltiu rd, rs, 1`
+    ///
+    /// `x[rd] = x[rs1] == 0`
+    #[strum(serialize = "seqz")]
+    Seqz {
+        dest: RiscVRegister,
+        src: RiscVRegister,
+    },
+    /// set if not equal to zero, psuedo instruction for `sltu rd, x0, rs`
+    ///
+    /// `x[rd] = x[rs1] != 0`
+    #[strum(serialize = "snez")]
+    Snez {
+        dest: RiscVRegister,
+        src: RiscVRegister,
+    },
+    /// set if less than zero, psuedo instruction for `slt rd, rs, x0`
+    ///
+    /// `x[rd] = x[rs1] <s 0`
+    #[strum(serialize = "sltz")]
+    Sltz {
+        dest: RiscVRegister,
+        src: RiscVRegister,
+    },
+    /// set if greater than zero, psuedo instruction for `slt rd, x0, rs`
+    ///
+    /// `x[rd] = x[rs1] >s 0`
+    #[strum(serialize = "sgtz")]
+    Sgtz {
+        dest: RiscVRegister,
+        src: RiscVRegister,
+    },
+    /// branch if equal
+    #[strum(serialize = "beq")]
+    Beq {
         arg1: RiscVRegister,
         arg2: RiscVRegister,
         target: RiscVVal,
     },
-    /// branch if greater than or equal
-    #[strum(serialize = "bge")]
-    Bge {
+    /// branch if not equal
+    #[strum(serialize = "bne")]
+    Bne {
         arg1: RiscVRegister,
         arg2: RiscVRegister,
         target: RiscVVal,
@@ -99,22 +412,59 @@ pub enum RiscVInstruction {
         arg2: RiscVRegister,
         target: RiscVVal,
     },
-    /// branch if greater than
+    /// branch if greater than or equal
+    #[strum(serialize = "bge")]
+    Bge {
+        arg1: RiscVRegister,
+        arg2: RiscVRegister,
+        target: RiscVVal,
+    },
+    /// branch if greater than, psuedo instruction for `blt rs2, rs1, offset`
     #[strum(serialize = "bgt")]
     Bgt {
         arg1: RiscVRegister,
         arg2: RiscVRegister,
         target: RiscVVal,
     },
-    /// branch if greater than
-    #[strum(serialize = "bne")]
-    Bne {
+    /// branch if less than or equal, psuedo instruction for `bge rs2, rs1, offset`
+    #[strum(serialize = "ble")]
+    Ble {
+        arg1: RiscVRegister,
+        arg2: RiscVRegister,
+        target: RiscVVal,
+    },
+    /// branch if less than, unsigned
+    #[strum(serialize = "bltu")]
+    Bltu {
+        arg1: RiscVRegister,
+        arg2: RiscVRegister,
+        target: RiscVVal,
+    },
+    /// branch if greater than or equal, unsigned
+    #[strum(serialize = "bgeu")]
+    Bgeu {
+        arg1: RiscVRegister,
+        arg2: RiscVRegister,
+        target: RiscVVal,
+    },
+    /// branch if greater than, unsigned, psuedo instruction for `bltu rs2, rs1, offset`
+    #[strum(serialize = "bgtu")]
+    Bgtu {
+        arg1: RiscVRegister,
+        arg2: RiscVRegister,
+        target: RiscVVal,
+    },
+    /// branch if less than or equal, unsigned, psuedo instruction for `bgeu rs2, rs1, offset`
+    #[strum(serialize = "bleu")]
+    Bleu {
         arg1: RiscVRegister,
         arg2: RiscVRegister,
         target: RiscVVal,
     },
-    /// branch if greater than or equal to
     /// call label
+    ///
+    /// Psuedo instruction for `jal ra, label`, or an `auipc`/`jalr` pair
+    /// when the label is far away
     #[strum(serialize = "call")]
     Call {
         label: RiscVVal,
@@ -128,13 +478,6 @@ pub enum RiscVInstruction {
         src: RiscVRegister,
         dest: RiscVVal,
     },
-    /// Rd := Rs << Imm
-    #[strum(serialize = "Slli")]
-    Slli {
-        dest: RiscVRegister,
-        src: RiscVRegister,
-        imm: i32
-    },
     /// Loads a value from memory into register rd for RV64I.
     ///
     /// `x[rd] = M[x[rs1] + sext(offset)]`
@@ -144,6 +487,313 @@ pub enum RiscVInstruction {
         dest: RiscVRegister,
         src: RiscVVal,
     },
+    /// load reserved, either lr.w or lr.d, with the ordering bits as a
+    /// suffix such as `lr.w.aq`
+    ///
+    /// `x[rd] = M[x[rs1]]`, and reserve `x[rs1]`
+    #[strum(serialize = "lr.w", serialize = "lr.d")]
+    Lr {
+        width: RiscVWidth,
+        ordering: RiscVOrdering,
+        dest: RiscVRegister,
+        addr: RiscVRegister,
+    },
+    /// store conditional, either sc.w or sc.d
+    ///
+    /// `if reserved { M[x[rs1]] = x[rs2]; x[rd] = 0 } else { x[rd] = 1 }`
+    #[strum(serialize = "sc.w", serialize = "sc.d")]
+    Sc {
+        width: RiscVWidth,
+        ordering: RiscVOrdering,
+        dest: RiscVRegister,
+        src: RiscVRegister,
+        addr: RiscVRegister,
+    },
+    /// atomic memory operation, such as amoadd.w or amomaxu.d.aqrl
+    ///
+    /// `x[rd] = M[x[rs1]]; M[x[rs1]] = x[rd] op x[rs2]`, atomically
+    Amo {
+        op: RiscVAmoOp,
+        width: RiscVWidth,
+        ordering: RiscVOrdering,
+        dest: RiscVRegister,
+        src: RiscVRegister,
+        addr: RiscVRegister,
+    },
+    /// floating point load, either flw or fld
+    ///
+    /// `f[rd] = M[x[rs1] + sext(offset)]`
+    #[strum(serialize = "flw", serialize = "fld")]
+    FL {
+        precision: RiscVPrecision,
+        dest: RiscVFRegister,
+        src: RiscVVal,
+    },
+    /// floating point store, either fsw or fsd
+    ///
+    /// `M[x[rs1] + sext(offset)] = f[rs2]`
+    #[strum(serialize = "fsw", serialize = "fsd")]
+    FS {
+        precision: RiscVPrecision,
+        src: RiscVFRegister,
+        dest: RiscVVal,
+    },
+    /// floating point add, either fadd.s or fadd.d
+    ///
+    /// `f[rd] = f[rs1] + f[rs2]`
+    #[strum(serialize = "fadd.s", serialize = "fadd.d")]
+    FAdd {
+        precision: RiscVPrecision,
+        rounding: RiscVRounding,
+        dest: RiscVFRegister,
+        arg1: RiscVFRegister,
+        arg2: RiscVFRegister,
+    },
+    /// floating point subtract, either fsub.s or fsub.d
+    ///
+    /// `f[rd] = f[rs1] - f[rs2]`
+    #[strum(serialize = "fsub.s", serialize = "fsub.d")]
+    FSub {
+        precision: RiscVPrecision,
+        rounding: RiscVRounding,
+        dest: RiscVFRegister,
+        arg1: RiscVFRegister,
+        arg2: RiscVFRegister,
+    },
+    /// floating point multiply, either fmul.s or fmul.d
+    ///
+    /// `f[rd] = f[rs1] * f[rs2]`
+    #[strum(serialize = "fmul.s", serialize = "fmul.d")]
+    FMul {
+        precision: RiscVPrecision,
+        rounding: RiscVRounding,
+        dest: RiscVFRegister,
+        arg1: RiscVFRegister,
+        arg2: RiscVFRegister,
+    },
+    /// floating point divide, either fdiv.s or fdiv.d
+    ///
+    /// `f[rd] = f[rs1] / f[rs2]`
+    #[strum(serialize = "fdiv.s", serialize = "fdiv.d")]
+    FDiv {
+        precision: RiscVPrecision,
+        rounding: RiscVRounding,
+        dest: RiscVFRegister,
+        arg1: RiscVFRegister,
+        arg2: RiscVFRegister,
+    },
+    /// floating point square root, either fsqrt.s or fsqrt.d
+    ///
+    /// `f[rd] = sqrt(f[rs1])`
+    #[strum(serialize = "fsqrt.s", serialize = "fsqrt.d")]
+    FSqrt {
+        precision: RiscVPrecision,
+        rounding: RiscVRounding,
+        dest: RiscVFRegister,
+        src: RiscVFRegister,
+    },
+    /// floating point minimum, either fmin.s or fmin.d. A NaN operand is
+    /// ignored, and the result is the canonical NaN only when both are NaN.
+    ///
+    /// `f[rd] = min(f[rs1], f[rs2])`
+    #[strum(serialize = "fmin.s", serialize = "fmin.d")]
+    FMin {
+        precision: RiscVPrecision,
+        dest: RiscVFRegister,
+        arg1: RiscVFRegister,
+        arg2: RiscVFRegister,
+    },
+    /// floating point maximum, either fmax.s or fmax.d, with the NaN
+    /// handling of [`RiscVInstruction::FMin`]
+    ///
+    /// `f[rd] = max(f[rs1], f[rs2])`
+    #[strum(serialize = "fmax.s", serialize = "fmax.d")]
+    FMax {
+        precision: RiscVPrecision,
+        dest: RiscVFRegister,
+        arg1: RiscVFRegister,
+        arg2: RiscVFRegister,
+    },
+    /// fused multiply add, either fmadd.s or fmadd.d
+    ///
+    /// `f[rd] = f[rs1] * f[rs2] + f[rs3]`
+    #[strum(serialize = "fmadd.s", serialize = "fmadd.d")]
+    FMadd {
+        precision: RiscVPrecision,
+        rounding: RiscVRounding,
+        dest: RiscVFRegister,
+        arg1: RiscVFRegister,
+        arg2: RiscVFRegister,
+        arg3: RiscVFRegister,
+    },
+    /// fused multiply subtract, either fmsub.s or fmsub.d
+    ///
+    /// `f[rd] = f[rs1] * f[rs2] - f[rs3]`
+    #[strum(serialize = "fmsub.s", serialize = "fmsub.d")]
+    FMsub {
+        precision: RiscVPrecision,
+        rounding: RiscVRounding,
+        dest: RiscVFRegister,
+        arg1: RiscVFRegister,
+        arg2: RiscVFRegister,
+        arg3: RiscVFRegister,
+    },
+    /// fused negative multiply add, either fnmadd.s or fnmadd.d
+    ///
+    /// `f[rd] = -f[rs1] * f[rs2] - f[rs3]`
+    #[strum(serialize = "fnmadd.s", serialize = "fnmadd.d")]
+    FNmadd {
+        precision: RiscVPrecision,
+        rounding: RiscVRounding,
+        dest: RiscVFRegister,
+        arg1: RiscVFRegister,
+        arg2: RiscVFRegister,
+        arg3: RiscVFRegister,
+    },
+    /// fused negative multiply subtract, either fnmsub.s or fnmsub.d
+    ///
+    /// `f[rd] = -f[rs1] * f[rs2] + f[rs3]`
+    #[strum(serialize = "fnmsub.s", serialize = "fnmsub.d")]
+    FNmsub {
+        precision: RiscVPrecision,
+        rounding: RiscVRounding,
+        dest: RiscVFRegister,
+        arg1: RiscVFRegister,
+        arg2: RiscVFRegister,
+        arg3: RiscVFRegister,
+    },
+    /// sign injection, either fsgnj.s or fsgnj.d. `fmv.s rd, rs` is
+    /// `fsgnj.s rd, rs, rs`.
+    ///
+    /// `f[rd] = {f[rs2][sign], f[rs1][rest]}`
+    #[strum(serialize = "fsgnj.s", serialize = "fsgnj.d")]
+    FSgnj {
+        precision: RiscVPrecision,
+        dest: RiscVFRegister,
+        arg1: RiscVFRegister,
+        arg2: RiscVFRegister,
+    },
+    /// negated sign injection, either fsgnjn.s or fsgnjn.d. `fneg.s rd, rs`
+    /// is `fsgnjn.s rd, rs, rs`.
+    ///
+    /// `f[rd] = {~f[rs2][sign], f[rs1][rest]}`
+    #[strum(serialize = "fsgnjn.s", serialize = "fsgnjn.d")]
+    FSgnjn {
+        precision: RiscVPrecision,
+        dest: RiscVFRegister,
+        arg1: RiscVFRegister,
+        arg2: RiscVFRegister,
+    },
+    /// xor sign injection, either fsgnjx.s or fsgnjx.d. `fabs.s rd, rs` is
+    /// `fsgnjx.s rd, rs, rs`.
+    ///
+    /// `f[rd] = {f[rs1][sign] ^ f[rs2][sign], f[rs1][rest]}`
+    #[strum(serialize = "fsgnjx.s", serialize = "fsgnjx.d")]
+    FSgnjx {
+        precision: RiscVPrecision,
+        dest: RiscVFRegister,
+        arg1: RiscVFRegister,
+        arg2: RiscVFRegister,
+    },
+    /// floating point equal, either feq.s or feq.d
+    ///
+    /// `x[rd] = f[rs1] == f[rs2]`
+    #[strum(serialize = "feq.s", serialize = "feq.d")]
+    FEq {
+        precision: RiscVPrecision,
+        dest: RiscVRegister,
+        arg1: RiscVFRegister,
+        arg2: RiscVFRegister,
+    },
+    /// floating point less than, either flt.s or flt.d. `fgt.s` swaps the
+    /// operands.
+    ///
+    /// `x[rd] = f[rs1] < f[rs2]`
+    #[strum(serialize = "flt.s", serialize = "flt.d")]
+    FLt {
+        precision: RiscVPrecision,
+        dest: RiscVRegister,
+        arg1: RiscVFRegister,
+        arg2: RiscVFRegister,
+    },
+    /// floating point less than or equal, either fle.s or fle.d. `fge.s`
+    /// swaps the operands.
+    ///
+    /// `x[rd] = f[rs1] <= f[rs2]`
+    #[strum(serialize = "fle.s", serialize = "fle.d")]
+    FLe {
+        precision: RiscVPrecision,
+        dest: RiscVRegister,
+        arg1: RiscVFRegister,
+        arg2: RiscVFRegister,
+    },
+    /// classify, either fclass.s or fclass.d, setting the bit of the class:
+    /// -inf, negative normal, negative subnormal, -0, +0, positive
+    /// subnormal, positive normal, +inf, signaling NaN and quiet NaN
+    ///
+    /// `x[rd] = 1 << class(f[rs1])`
+    #[strum(serialize = "fclass.s", serialize = "fclass.d")]
+    FClass {
+        precision: RiscVPrecision,
+        dest: RiscVRegister,
+        src: RiscVFRegister,
+    },
+    /// move the bits of a float to an integer register, either fmv.x.w,
+    /// which sign extends, or fmv.x.d
+    ///
+    /// `x[rd] = sext(f[rs1])`
+    #[strum(serialize = "fmv.x.w", serialize = "fmv.x.d")]
+    FMvToInt {
+        precision: RiscVPrecision,
+        dest: RiscVRegister,
+        src: RiscVFRegister,
+    },
+    /// move the bits of an integer register to a float, either fmv.w.x or
+    /// fmv.d.x
+    ///
+    /// `f[rd] = x[rs1]`
+    #[strum(serialize = "fmv.w.x", serialize = "fmv.d.x")]
+    FMvFromInt {
+        precision: RiscVPrecision,
+        dest: RiscVFRegister,
+        src: RiscVRegister,
+    },
+    /// convert a float to a `width` integer, such as fcvt.w.s or fcvt.lu.d.
+    /// Out of range values saturate, and NaN is the largest integer.
+    ///
+    /// `x[rd] = sext(int(f[rs1]))`
+    #[strum(serialize = "fcvt.w.s", serialize = "fcvt.l.d")]
+    FCvtToInt {
+        precision: RiscVPrecision,
+        width: RiscVWidth,
+        signed: bool,
+        rounding: RiscVRounding,
+        dest: RiscVRegister,
+        src: RiscVFRegister,
+    },
+    /// convert a `width` integer to a float, such as fcvt.s.w or fcvt.d.lu
+    ///
+    /// `f[rd] = float(x[rs1])`
+    #[strum(serialize = "fcvt.s.w", serialize = "fcvt.d.l")]
+    FCvtFromInt {
+        precision: RiscVPrecision,
+        width: RiscVWidth,
+        signed: bool,
+        rounding: RiscVRounding,
+        dest: RiscVFRegister,
+        src: RiscVRegister,
+    },
+    /// convert between precisions, either fcvt.s.d or fcvt.d.s, where
+    /// `precision` is that of `dest`
+    ///
+    /// `f[rd] = f[rs1]`
+    #[strum(serialize = "fcvt.s.d", serialize = "fcvt.d.s")]
+    FCvt {
+        precision: RiscVPrecision,
+        rounding: RiscVRounding,
+        dest: RiscVFRegister,
+        src: RiscVFRegister,
+    },
     Directive {
         name: String,
         operands: String,
@@ -151,6 +801,34 @@ pub enum RiscVInstruction {
     Label {
         name: String,
     },
+    /// add upper immediate to pc, either a constant, `%pcrel_hi(label)` or
+    /// `%got_pcrel_hi(label)`. [`pair_pcrel`](crate::pcrel::pair_pcrel)
+    /// replaces the relocation with a plain label for the whole address when
+    /// the label is near.
+    ///
+    /// `x[rd] = pc + sext(immediate[31:12] << 12)`
+    #[strum(serialize = "auipc")]
+    Auipc {
+        dest: RiscVRegister,
+        src: RiscVVal,
+    },
+    /// load local address, psuedo instruction for `auipc` and `addi` with
+    /// `%pcrel_hi` and `%pcrel_lo`
+    #[strum(serialize = "lla")]
+    Lla {
+        dest: RiscVRegister,
+        label: RiscVVal,
+    },
+    /// load address, psuedo instruction for `auipc` and `ld` of the GOT
+    /// entry with `%got_pcrel_hi` and `%pcrel_lo`
+    #[strum(serialize = "la")]
+    La {
+        dest: RiscVRegister,
+        label: RiscVVal,
+    },
+    /// load upper immediate, either a constant or `%hi(label)`
+    ///
+    /// `x[rd] = sext(immediate[31:12] << 12)`
     #[strum(serialize = "lui")]
     Lui {
         dest: RiscVRegister,
@@ -179,21 +857,36 @@ pub enum RiscVInstruction {
         src: RiscVRegister,
     },
     /// Jump label
+    ///
+    /// Psuedo instruction for `jal x0, label`, also used for `tail label`
     #[strum(serialize = "j")]
     J {
         target: RiscVVal,
     },
     /// Jump Register
-    /// Jump to address and place return address in rd.
-    /// jal rd,offset
-    ///
-    /// Psuedo instruction:
-    ///     jr offset => jal x1, offset
     ///
+    /// Psuedo instruction for `jalr x0, 0(rs)`
     #[strum(serialize = "jr")]
     Jr {
         target: RiscVRegister,
     },
+    /// Jump and link, `jal offset` links to `ra`
+    ///
+    /// `x[rd] = pc + 4; pc += sext(offset)`
+    #[strum(serialize = "jal")]
+    Jal {
+        dest: RiscVRegister,
+        target: RiscVVal,
+    },
+    /// Jump and link register, `jalr rs` links to `ra`
+    ///
+    /// `t = pc + 4; pc = (x[rs1] + sext(offset)) & ~1; x[rd] = t`
+    #[strum(serialize = "jalr")]
+    Jalr {
+        dest: RiscVRegister,
+        target: RiscVRegister,
+        offset: i32,
+    },
     /// Load Immediate
     /// This is a pseudo instruction, so it's not a real instruction
     ///
@@ -203,20 +896,191 @@ pub enum RiscVInstruction {
     /// arguments or in some case reversed arguments, that result in distinct
     /// semantics.
     /// https://michaeljclark.github.io/asm.html
+    ///
+    /// The immediate can be any 64 bit constant.
     #[strum(serialize = "li")]
     Li {
         dest: RiscVRegister,
-        imm: i32,
+        imm: i64,
+    },
+    /// Order the memory accesses in `pred` before those in `succ`. A bare
+    /// `fence` is `fence iorw, iorw`.
+    #[strum(serialize = "fence")]
+    Fence {
+        pred: RiscVFenceSet,
+        succ: RiscVFenceSet,
     },
+    /// `fence rw, rw` without ordering writes before reads, the ordering of
+    /// x86's TSO
+    #[strum(serialize = "fence.tso")]
+    FenceTso,
+    /// Make earlier stores visible to instruction fetches on this hart
+    #[strum(serialize = "fence.i")]
+    FenceI,
+    /// Hint that this is a spin-wait loop
+    #[strum(serialize = "pause")]
+    Pause,
     /// System Call
     #[strum(serialize = "ecall")]
     ECall,
+    /// No operation, encoded as `addi x0, x0, 0`
+    #[strum(serialize = "nop")]
+    Nop,
     #[strum(serialize = "verbatim")]
     Verbatim {
         text: String,
     },
 }
 
+/// The predecessor or successor set of a `fence`, written as a subset of
+/// `iorw` such as `rw`.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct RiscVFenceSet {
+    /// `i`, device input
+    pub input: bool,
+    /// `o`, device output
+    pub output: bool,
+    /// `r`, memory reads
+    pub read: bool,
+    /// `w`, memory writes
+    pub write: bool,
+}
+
+impl RiscVFenceSet {
+    /// `iorw`, every access
+    pub const ALL: Self = Self {
+        input: true,
+        output: true,
+        read: true,
+        write: true,
+    };
+
+    /// Whether the set has reads of memory or devices.
+    pub fn reads(self) -> bool {
+        self.input || self.read
+    }
+
+    /// Whether the set has writes to memory or devices.
+    pub fn writes(self) -> bool {
+        self.output || self.write
+    }
+}
+
+impl FromStr for RiscVFenceSet {
+    type Err = ();
+
+    /// Parse the letters of `iorw` in that order, each at most once.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut set = Self::default();
+        let mut rest = s;
+        for (letter, bit) in [
+            ('i', &mut set.input),
+            ('o', &mut set.output),
+            ('r', &mut set.read),
+            ('w', &mut set.write),
+        ] {
+            if let Some(after) = rest.strip_prefix(letter) {
+                *bit = true;
+                rest = after;
+            }
+        }
+        if s.is_empty() || !rest.is_empty() {
+            return Err(());
+        }
+        Ok(set)
+    }
+}
+
+/// The `.aq` and `.rl` bits of atomic instructions. Without either, the
+/// access is only atomic, and not ordered with other accesses.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum RiscVOrdering {
+    #[default]
+    Relaxed,
+    /// `.aq`, later accesses can't happen before this one
+    Acquire,
+    /// `.rl`, earlier accesses can't happen after this one
+    Release,
+    /// `.aqrl`, both, which makes the access sequentially consistent
+    AcquireRelease,
+}
+
+impl RiscVOrdering {
+    pub fn acquire(self) -> bool {
+        matches!(self, Self::Acquire | Self::AcquireRelease)
+    }
+
+    pub fn release(self) -> bool {
+        matches!(self, Self::Release | Self::AcquireRelease)
+    }
+}
+
+/// The operation of an atomic memory operation
+/// https://msyksphinz-self.github.io/riscv-isadoc/html/rva.html
+#[derive(Debug, EnumString, Default, Clone, Copy, PartialEq, Eq)]
+pub enum RiscVAmoOp {
+    /// `amoswap`, store `x[rs2]`
+    #[default]
+    #[strum(serialize = "amoswap")]
+    Swap,
+    #[strum(serialize = "amoadd")]
+    Add,
+    #[strum(serialize = "amoand")]
+    And,
+    #[strum(serialize = "amoor")]
+    Or,
+    #[strum(serialize = "amoxor")]
+    Xor,
+    /// `amomin`, the signed minimum
+    #[strum(serialize = "amomin")]
+    Min,
+    /// `amomax`, the signed maximum
+    #[strum(serialize = "amomax")]
+    Max,
+    /// `amominu`, the unsigned minimum
+    #[strum(serialize = "amominu")]
+    Minu,
+    /// `amomaxu`, the unsigned maximum
+    #[strum(serialize = "amomaxu")]
+    Maxu,
+}
+
+/// The format of a floating point instruction, `.s` or `.d`
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum RiscVPrecision {
+    /// 32 bit IEEE 754 single precision
+    Single,
+    /// 64 bit IEEE 754 double precision
+    #[default]
+    Double,
+}
+
+/// The static rounding mode operand of floating point instructions
+/// https://github.com/riscv-non-isa/riscv-asm-manual/blob/main/src/asm-manual.adoc#floating-point-rounding-modes
+#[derive(Debug, EnumString, Default, Clone, Copy, PartialEq, Eq)]
+pub enum RiscVRounding {
+    /// Round to nearest, ties to even
+    #[strum(serialize = "rne")]
+    Rne,
+    /// Round towards zero
+    #[strum(serialize = "rtz")]
+    Rtz,
+    /// Round down, towards -inf
+    #[strum(serialize = "rdn")]
+    Rdn,
+    /// Round up, towards +inf
+    #[strum(serialize = "rup")]
+    Rup,
+    /// Round to nearest, ties away from zero
+    #[strum(serialize = "rmm")]
+    Rmm,
+    /// The mode in the `frm` CSR, which is always `rne` since programs
+    /// can't write CSRs
+    #[default]
+    #[strum(serialize = "dyn")]
+    Dyn,
+}
+
 impl Default for RiscVInstruction {
     fn default() -> Self {
         Self::Li {
@@ -232,7 +1096,7 @@ pub enum ArmSyscalls {
 }
 
 impl ArmSyscalls {
-    const fn value(&self) -> i32 {
+    pub const fn value(&self) -> i32 {
         match self {
             Self::WRITE => 64,
             Self::EXIT => 0,
@@ -240,18 +1104,49 @@ impl ArmSyscalls {
     }
 }
 
-#[derive(Debug)]
+/// An ARM operand. The offset variants are the addressing modes of loads
+/// and stores.
+#[derive(Debug, Clone)]
 pub enum ArmVal {
     Reg(ArmRegister),
-    Imm(i32),
+    Imm(i64),
+    /// `[base, offset]`, where the offset is unsigned and scaled by the
+    /// access size. Translation uses it for any offset, and
+    /// [`legalize`](crate::legalize::legalize) switches to another mode when
+    /// the offset doesn't fit.
     RegOffset(ArmRegister, i32),
+    /// `[base, offset]` with a signed 9 bit offset, for `ldur`/`stur`
+    UnscaledOffset(ArmRegister, i32),
+    /// `[base, index]`
+    RegIndex(ArmRegister, ArmRegister),
+    /// `[base, offset]!`, adding a signed 9 bit offset to the base before
+    /// the access
+    PreIndex(ArmRegister, i32),
+    /// `[base], offset`, adding a signed 9 bit offset to the base after the
+    /// access
+    PostIndex(ArmRegister, i32),
     LabelOffset(String, i32),
+    /// A relocation of `label + offset`
+    Reloc(ArmReloc, String, i32),
+    /// `[base, reloc]`, where the offset is an [`ArmVal::Reloc`]
+    RelocOffset(ArmRegister, Box<ArmVal>),
+    /// `vN.s[index]` or `vN.d[index]`, an element of a vector register as
+    /// a [`ArmWidth::Word`] or [`ArmWidth::Double`]
+    Element(ArmRegisterName, ArmWidth, u32),
 }
 
-impl Default for ArmVal {
-    fn default() -> Self {
-        todo!()
-    }
+/// AArch64 relocation operators, as spelled by GNU `as`
+/// https://sourceware.org/binutils/docs/as/AArch64_002dRelocations.html
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ArmReloc {
+    /// The 4KB page of the address for `adrp`, a bare label
+    Page,
+    /// `:lo12:`, the offset of the address in its page
+    Lo12,
+    /// `:got:`, the page of the GOT entry for `adrp`
+    GotPage,
+    /// `:got_lo12:`, the offset of the GOT entry in its page
+    GotLo12,
 }
 
 #[derive(Debug, Copy, Clone)]
@@ -261,150 +1156,538 @@ pub enum ArmWidth {
     Half,
     SignedHalf,
     Word,
+    SignedWord,
     Double,
 }
 
-impl Default for ArmWidth {
-    fn default() -> Self {
-        todo!()
-    }
+/// ARM condition codes, tested against the flags set by `cmp`
+/// https://developer.arm.com/documentation/dui0801/l/Condition-Codes/Condition-code-suffixes-and-related-flags
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+pub enum ArmCondition {
+    /// Equal
+    #[default]
+    Eq,
+    /// Not equal
+    Ne,
+    /// Unsigned higher or same
+    Hs,
+    /// Unsigned lower
+    Lo,
+    /// Negative
+    Mi,
+    /// Positive or zero
+    Pl,
+    /// Signed overflow
+    Vs,
+    /// No signed overflow
+    Vc,
+    /// Unsigned higher
+    Hi,
+    /// Unsigned lower or same
+    Ls,
+    /// Signed greater than or equal
+    Ge,
+    /// Signed less than
+    Lt,
+    /// Signed greater than
+    Gt,
+    /// Signed less than or equal
+    Le,
+}
+
+/// The accesses ordered by a `dmb` or `dsb`, within the inner shareable
+/// domain, which has every core that runs the program
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+pub enum ArmBarrier {
+    /// `ish`, every access before every access
+    #[default]
+    Ish,
+    /// `ishld`, loads before loads and stores
+    Ishld,
+    /// `ishst`, stores before stores
+    Ishst,
+}
+
+/// The system registers which `mrs` reads
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+pub enum ArmSystemRegister {
+    /// `ctr_el0`, the cache type register, with the cache line sizes
+    #[default]
+    CtrEl0,
+}
+
+/// The operations of the ARMv8.1 LSE `ld<op>` instructions, which store
+/// `[Xn] <op> Rs` and load the old value
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+pub enum ArmAtomicOp {
+    #[default]
+    Add,
+    /// clear the bits set in `Rs`, an and with `~Rs`
+    Clr,
+    Eor,
+    /// set the bits set in `Rs`, an or
+    Set,
+    Smax,
+    Smin,
+    Umax,
+    Umin,
+}
+
+/// The rounding of a float to integer conversion, the letter after `fcvt`
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+pub enum ArmRounding {
+    /// `n`, to nearest with ties to even
+    Nearest,
+    /// `z`, towards zero
+    #[default]
+    Zero,
+    /// `m`, towards -inf
+    MinusInf,
+    /// `p`, towards +inf
+    PlusInf,
+    /// `a`, to nearest with ties away from zero
+    Away,
 }
 
 /// ARM Instructions
 /// `https://iitd-plos.github.io/col718/ref/arm-instructionset.pdf#page=3`
-#[derive(Debug, EnumString)]
+#[derive(Debug, Clone)]
 pub enum ArmInstruction {
     /// ADC Add with carry
     ///
     /// `Rd := Rn + Op2 + Carry`
-    #[strum(serialize = "adc")]
-    Adc,
+    Adc {
+        dest: ArmRegister,
+        arg1: ArmRegister,
+        arg2: ArmRegister,
+    },
     /// ADD Add Rd := Rn + Op2
-    #[strum(serialize = "add")]
     Add {
         dest: ArmRegister,
         arg1: ArmRegister,
         arg2: ArmVal,
     },
     /// AND AND Rd := Rn AND Op2
-    #[strum(serialize = "and")]
-    And,
+    And {
+        dest: ArmRegister,
+        arg1: ArmRegister,
+        arg2: ArmVal,
+    },
     /// ADRP Rd := page_addr(label)
-    #[strum(serialize = "adrp")]
     Adrp {
         dest: ArmRegister,
         label: ArmVal,
     },
     /// B Branch R15 := address
-    #[strum(serialize = "b")]
     B {
         target: ArmVal,
     },
-    /// BLR Xn
-    #[strum(serialize = "blr")]
-    Blr {
-        target: ArmRegisterName,
+    /// BLR Xn, branch to Xn and set `lr` to the return address
+    Blr {
+        target: ArmRegisterName,
+    },
+    /// BR Xn, branch to Xn
+    Br {
+        target: ArmRegisterName,
+    },
+    /// ADR Rd := address of label, within 1MB. The label may be `.` for
+    /// this instruction.
+    Adr {
+        dest: ArmRegister,
+        label: ArmVal,
+    },
+    /// B.cond label, taken if `cond` holds for the flags
+    BCond {
+        cond: ArmCondition,
+        target: ArmVal,
+    },
+    /// BL label
+    Bl {
+        target: ArmVal,
+    },
+    /// CBNZ Rt, label, branch if Rt is not zero
+    Cbnz {
+        src: ArmRegister,
+        target: ArmVal,
+    },
+    /// label:
+    Label {
+        name: String,
+    },
+    /// .directive operands
+    Directive {
+        name: String,
+        operands: String,
+    },
+    Ldr {
+        width: ArmWidth,
+        dest: ArmRegister,
+        src: ArmVal,
+    },
+    /// LDXR Rt := [Xn], and mark `Xn` for exclusive access. LDAXR with
+    /// `acquire`.
+    Ldxr {
+        acquire: bool,
+        dest: ArmRegister,
+        addr: ArmRegisterName,
+    },
+    /// STXR Ws, Rt, [Xn], store if `Xn` is still marked for exclusive
+    /// access, setting Ws to 0 if it was and 1 if not. STLXR with
+    /// `release`. Ws can't be Rt or Xn.
+    Stxr {
+        release: bool,
+        status: ArmRegister,
+        src: ArmRegister,
+        addr: ArmRegisterName,
+    },
+    /// SWP{A}{L} Rs, Rt, [Xn], atomically Rt := [Xn]; [Xn] := Rs
+    Swp {
+        acquire: bool,
+        release: bool,
+        src: ArmRegister,
+        dest: ArmRegister,
+        addr: ArmRegisterName,
+    },
+    /// LD<op>{A}{L} Rs, Rt, [Xn], atomically Rt := [Xn]; [Xn] := [Xn] op Rs
+    LdOp {
+        op: ArmAtomicOp,
+        acquire: bool,
+        release: bool,
+        src: ArmRegister,
+        dest: ArmRegister,
+        addr: ArmRegisterName,
+    },
+    /// LDP Rt1, Rt2, [address], load a pair of registers
+    Ldp {
+        dest1: ArmRegister,
+        dest2: ArmRegister,
+        src: ArmVal,
+    },
+    /// STP Rt1, Rt2, [address], store a pair of registers
+    Stp {
+        src1: ArmRegister,
+        src2: ArmRegister,
+        dest: ArmVal,
+    },
+    Mov {
+        width: ArmWidth,
+        dest: ArmRegister,
+        src: ArmVal,
+    },
+    /// MOVK Rd<shift + 15:shift> := imm, keeping the other bits
+    Movk {
+        dest: ArmRegister,
+        imm: u16,
+        shift: u32,
+    },
+    /// RET, branch to `lr`
+    Ret,
+    /// Rd := Rs << Imm
+    Lsl {
+        dest: ArmRegister,
+        src: ArmRegister,
+        imm: i32,
+    },
+    /// Rd := Rs >>u Imm
+    Lsr {
+        dest: ArmRegister,
+        src: ArmRegister,
+        imm: i32,
+    },
+    /// Rd := Rs >>s Imm
+    Asr {
+        dest: ArmRegister,
+        src: ArmRegister,
+        imm: i32,
+    },
+    /// LSLV Rd := Rn << (Rm mod width)
+    Lslv {
+        dest: ArmRegister,
+        arg1: ArmRegister,
+        arg2: ArmRegister,
+    },
+    /// LSRV Rd := Rn >>u (Rm mod width)
+    Lsrv {
+        dest: ArmRegister,
+        arg1: ArmRegister,
+        arg2: ArmRegister,
+    },
+    /// ASRV Rd := Rn >>s (Rm mod width)
+    Asrv {
+        dest: ArmRegister,
+        arg1: ArmRegister,
+        arg2: ArmRegister,
+    },
+    /// Str [r2 + offset] = r1
+    Str {
+        width: ArmWidth,
+        src: ArmRegister,
+        dest: ArmVal,
+    },
+    /// Sub Sub Rd := Rn - Op2
+    Sub {
+        dest: ArmRegister,
+        arg1: ArmRegister,
+        arg2: ArmVal,
+    },
+    /// sign extend to word
+    Sxtw {
+        dest: ArmRegister,
+        src: ArmRegister,
+    },
+    /// service call
+    Svc {
+        id: i32,
+    },
+    /// ORR Rd := Rn OR Op2
+    Orr {
+        dest: ArmRegister,
+        arg1: ArmRegister,
+        arg2: ArmVal,
+    },
+    /// EOR Rd := Rn XOR Op2
+    Eor {
+        dest: ArmRegister,
+        arg1: ArmRegister,
+        arg2: ArmVal,
+    },
+    /// MVN Rd := NOT Rm
+    Mvn {
+        dest: ArmRegister,
+        src: ArmRegister,
+    },
+    /// NEG Rd := 0 - Rm
+    Neg {
+        dest: ArmRegister,
+        src: ArmRegister,
+    },
+    /// compare
+    Cmp {
+        op1: ArmRegister,
+        op2: ArmVal,
+    },
+    /// compare negative, sets the flags of `op1 + op2`
+    Cmn {
+        op1: ArmRegister,
+        op2: ArmVal,
+    },
+    /// MUL Rd := Rn * Rm
+    Mul {
+        dest: ArmRegister,
+        arg1: ArmRegister,
+        arg2: ArmRegister,
+    },
+    /// SMULH Rd := (Rn s* Rm) >> 64
+    Smulh {
+        dest: ArmRegister,
+        arg1: ArmRegister,
+        arg2: ArmRegister,
+    },
+    /// UMULH Rd := (Rn u* Rm) >> 64
+    Umulh {
+        dest: ArmRegister,
+        arg1: ArmRegister,
+        arg2: ArmRegister,
+    },
+    /// MSUB Rd := Ra - Rn * Rm
+    Msub {
+        dest: ArmRegister,
+        arg1: ArmRegister,
+        arg2: ArmRegister,
+        arg3: ArmRegister,
+    },
+    /// SDIV Rd := Rn /s Rm, which is 0 when Rm is 0
+    Sdiv {
+        dest: ArmRegister,
+        arg1: ArmRegister,
+        arg2: ArmRegister,
+    },
+    /// UDIV Rd := Rn /u Rm, which is 0 when Rm is 0
+    Udiv {
+        dest: ArmRegister,
+        arg1: ArmRegister,
+        arg2: ArmRegister,
+    },
+    /// CSEL Rd := cond ? Rn : Rm
+    Csel {
+        dest: ArmRegister,
+        arg1: ArmRegister,
+        arg2: ArmRegister,
+        cond: ArmCondition,
+    },
+    /// CSINV Rd := cond ? Rn : !Rm
+    Csinv {
+        dest: ArmRegister,
+        arg1: ArmRegister,
+        arg2: ArmRegister,
+        cond: ArmCondition,
+    },
+    /// CSINC Rd := cond ? Rn : Rm + 1
+    Csinc {
+        dest: ArmRegister,
+        arg1: ArmRegister,
+        arg2: ArmRegister,
+        cond: ArmCondition,
+    },
+    /// CSET Rd := cond ? 1 : 0
+    Cset {
+        dest: ArmRegister,
+        cond: ArmCondition,
+    },
+    /// TST Rn, Op2, set the flags of `Rn AND Op2`
+    Tst {
+        op1: ArmRegister,
+        op2: ArmVal,
+    },
+    /// INS, the `mov` of a general register or another element into an
+    /// [`ArmVal::Element`], keeping the other elements
+    Ins {
+        dest: ArmVal,
+        src: ArmVal,
+    },
+    /// FMOV Rd := Rn, between floating point registers, or the bits between
+    /// a floating point and a general register
+    Fmov {
+        dest: ArmRegister,
+        src: ArmRegister,
+    },
+    /// FADD Rd := Rn + Rm
+    Fadd {
+        dest: ArmRegister,
+        arg1: ArmRegister,
+        arg2: ArmRegister,
+    },
+    /// FSUB Rd := Rn - Rm
+    Fsub {
+        dest: ArmRegister,
+        arg1: ArmRegister,
+        arg2: ArmRegister,
+    },
+    /// FMUL Rd := Rn * Rm
+    Fmul {
+        dest: ArmRegister,
+        arg1: ArmRegister,
+        arg2: ArmRegister,
     },
-    /// BLE label
-    #[strum(serialize = "ble")]
-    Ble {
+    /// FDIV Rd := Rn / Rm
+    Fdiv {
+        dest: ArmRegister,
         arg1: ArmRegister,
         arg2: ArmRegister,
-        target: ArmVal,
     },
-    /// BGE label
-    #[strum(serialize = "bge")]
-    Bge {
+    /// FMINNM Rd := min(Rn, Rm), where a quiet NaN operand is ignored
+    Fminnm {
+        dest: ArmRegister,
         arg1: ArmRegister,
         arg2: ArmRegister,
-        target: ArmVal,
     },
-    /// BLT label
-    #[strum(serialize = "blt")]
-    Blt {
+    /// FMAXNM Rd := max(Rn, Rm), where a quiet NaN operand is ignored
+    Fmaxnm {
+        dest: ArmRegister,
         arg1: ArmRegister,
         arg2: ArmRegister,
-        target: ArmVal,
     },
-    /// BGT label
-    #[strum(serialize = "bgt")]
-    Bgt {
+    /// FMADD Rd := Ra + Rn * Rm
+    Fmadd {
+        dest: ArmRegister,
         arg1: ArmRegister,
         arg2: ArmRegister,
-        target: ArmVal,
+        arg3: ArmRegister,
     },
-    /// BNE label
-    #[strum(serialize = "bne")]
-    Bne {
+    /// FMSUB Rd := Ra - Rn * Rm
+    Fmsub {
+        dest: ArmRegister,
         arg1: ArmRegister,
         arg2: ArmRegister,
-        target: ArmVal,
+        arg3: ArmRegister,
     },
-    /// BL label
-    #[strum(serialize = "bl")]
-    Bl {
-        target: ArmVal,
+    /// FNMADD Rd := -Ra - Rn * Rm
+    Fnmadd {
+        dest: ArmRegister,
+        arg1: ArmRegister,
+        arg2: ArmRegister,
+        arg3: ArmRegister,
     },
-    /// label:
-    Label {
-        name: String,
+    /// FNMSUB Rd := -Ra + Rn * Rm
+    Fnmsub {
+        dest: ArmRegister,
+        arg1: ArmRegister,
+        arg2: ArmRegister,
+        arg3: ArmRegister,
     },
-    /// .directive operands
-    Directive {
-        name: String,
-        operands: String,
+    /// FSQRT Rd := sqrt(Rn)
+    Fsqrt {
+        dest: ArmRegister,
+        src: ArmRegister,
     },
-    #[strum(serialize = "ldr")]
-    Ldr {
-        width: ArmWidth,
+    /// FABS Rd := |Rn|, clearing the sign bit
+    Fabs {
         dest: ArmRegister,
-        src: ArmVal,
+        src: ArmRegister,
     },
-    #[strum(serialize = "mov")]
-    Mov {
-        width: ArmWidth,
+    /// FNEG Rd := -Rn, flipping the sign bit
+    Fneg {
         dest: ArmRegister,
-        src: ArmVal,
+        src: ArmRegister,
     },
-    #[strum(serialize = "ret")]
-    Ret,
-    /// Rd := Rs << Imm
-    #[strum(serialize = "Lsl")]
-    Lsl {
+    /// FCMP, compare floats, setting C and V when unordered
+    Fcmp {
+        op1: ArmRegister,
+        op2: ArmRegister,
+    },
+    /// FCVT Rd := Rn, converting between precisions
+    Fcvt {
         dest: ArmRegister,
         src: ArmRegister,
-        imm: i32
     },
-    /// Str [r2 + offset] = r1
-    #[strum(serialize = "str")]
-    Str {
-        width: ArmWidth,
+    /// FCVT<r>S and FCVT<r>U, Rd := int(Rn) rounding with `rounding`. Out of
+    /// range values saturate, and NaN is 0.
+    FcvtToInt {
+        rounding: ArmRounding,
+        signed: bool,
+        dest: ArmRegister,
         src: ArmRegister,
-        dest: ArmVal,
     },
-    /// Sub Sub Rd := Rn - Op2
-    #[strum(serialize = "sub")]
-    Sub {
+    /// SCVTF Rd := float(Rn), for signed Rn
+    Scvtf {
         dest: ArmRegister,
-        arg1: ArmRegister,
-        arg2: ArmVal,
+        src: ArmRegister,
     },
-    /// sign extend to word
-    #[strum(serialize = "sxtw")]
-    Sxtw {
+    /// UCVTF Rd := float(Rn), for unsigned Rn
+    Ucvtf {
         dest: ArmRegister,
         src: ArmRegister,
     },
-    /// service call
-    #[strum(serialize = "svc")]
-    Svc {
-        id: i32,
+    /// DMB, data memory barrier, ordering the accesses of `option`
+    Dmb {
+        option: ArmBarrier,
     },
-    /// compare
-    Cmp {
-        op1: ArmRegister,
-        op2: ArmVal,
+    /// DSB, data synchronization barrier, which also waits for cache
+    /// maintenance to finish
+    Dsb {
+        option: ArmBarrier,
+    },
+    /// ISB, instruction synchronization barrier, refetching the following
+    /// instructions
+    Isb,
+    /// DC CVAU, Xt, clean the data cache line of the address in Xt to the
+    /// point of unification, where instruction fetches see it
+    DcCvau {
+        addr: ArmRegister,
+    },
+    /// IC IVAU, Xt, invalidate the instruction cache line of the address
+    /// in Xt to the point of unification
+    IcIvau {
+        addr: ArmRegister,
+    },
+    /// MRS Xt, sysreg, read a system register
+    Mrs {
+        dest: ArmRegister,
+        src: ArmSystemRegister,
     },
+    /// YIELD, a spin-wait hint
+    Yield,
+    Nop,
     Verbatim {
         text: String,
     },
@@ -414,19 +1697,13 @@ impl Default for ArmInstruction {
     fn default() -> Self {
         ArmInstruction::Mov {
             width: ArmWidth::Double,
-            dest: ArmRegister {
-                width: ArmWidth::Double,
-                name: ArmRegisterName::X0,
-            },
-            src: ArmVal::Reg(ArmRegister {
-                width: ArmWidth::Double,
-                name: ArmRegisterName::X0,
-            }),
+            dest: ArmRegister::new(ArmRegisterName::X0, ArmWidth::Double),
+            src: ArmVal::Reg(ArmRegister::new(ArmRegisterName::X0, ArmWidth::Double)),
         }
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub enum RiscVVal {
     RiscVRegister(RiscVRegister),
     Immediate(i32),
@@ -439,6 +1716,55 @@ pub enum RiscVVal {
         label: String,
         offset: i32,
     },
+    /// `%reloc(label + offset)`
+    Reloc {
+        reloc: RiscVReloc,
+        label: String,
+        offset: i32,
+    },
+    /// `%reloc(label + offset)(register)`, a memory operand
+    RelocOffset {
+        register: RiscVRegister,
+        reloc: RiscVReloc,
+        label: String,
+        offset: i32,
+    },
+}
+
+/// RISC-V relocation operators, which take part of the address of a label
+/// https://github.com/riscv-non-isa/riscv-asm-manual/blob/main/src/asm-manual.adoc#assembler-relocation-functions
+#[derive(Debug, EnumString, Clone, Copy, PartialEq, Eq)]
+pub enum RiscVReloc {
+    /// `%hi`, the upper 20 bits of the address for `lui`
+    #[strum(serialize = "hi")]
+    Hi20,
+    /// `%lo`, the lower 12 bits of the address
+    #[strum(serialize = "lo")]
+    Lo12,
+    /// `%pcrel_hi`, the upper 20 bits of the offset from an `auipc`
+    #[strum(serialize = "pcrel_hi")]
+    PcrelHi20,
+    /// `%pcrel_lo`, the lower 12 bits of the offset found by the `auipc` at
+    /// the label, rather than of the label itself
+    #[strum(serialize = "pcrel_lo")]
+    PcrelLo12,
+    /// A `%pcrel_lo` of an `auipc` with `%got_pcrel_hi`, the lower 12 bits
+    /// of the offset to the GOT entry of the label. This has no syntax, it
+    /// is made by [`pair_pcrel`](crate::pcrel::pair_pcrel).
+    #[strum(disabled)]
+    GotPcrelLo12,
+    /// `%got_pcrel_hi`, the upper 20 bits of the offset from an `auipc` to
+    /// the GOT entry of the label
+    #[strum(serialize = "got_pcrel_hi")]
+    GotPcrelHi20,
+    /// `%tprel_hi`, the upper 20 bits of the offset of a thread local from
+    /// the thread pointer
+    #[strum(serialize = "tprel_hi")]
+    TprelHi20,
+    /// `%tprel_lo`, the lower 12 bits of the offset of a thread local from
+    /// the thread pointer
+    #[strum(serialize = "tprel_lo")]
+    TprelLo12,
 }
 
 impl Default for RiscVVal {
@@ -449,116 +1775,250 @@ impl Default for RiscVVal {
 
 /// RISC-V Registers
 /// https://msyksphinz-self.github.io/riscv-isadoc/html/regs.html
-#[derive(Debug, EnumString, Default)]
+#[derive(Debug, EnumString, Default, Clone, Copy, PartialEq, Eq)]
 pub enum RiscVRegister {
     #[default]
-    #[strum(serialize = "x0")]
+    #[strum(serialize = "zero", serialize = "x0")]
     /// Hard-wired zero
     X0,
-    #[strum(serialize = "ra")]
+    #[strum(serialize = "ra", serialize = "x1")]
     /// Return address
     RA,
-    #[strum(serialize = "sp")]
+    #[strum(serialize = "sp", serialize = "x2")]
     /// Stack pointer
     SP,
-    #[strum(serialize = "gp")]
+    #[strum(serialize = "gp", serialize = "x3")]
     /// Global pointer
     GP,
-    #[strum(serialize = "tp")]
+    #[strum(serialize = "tp", serialize = "x4")]
     /// Thread pointer
     TP,
-    #[strum(serialize = "t0")]
+    #[strum(serialize = "t0", serialize = "x5")]
     /// Temporary/alternate link register
     T0,
-    #[strum(serialize = "t1")]
+    #[strum(serialize = "t1", serialize = "x6")]
     /// Temporaries
     T1,
-    #[strum(serialize = "t2")]
+    #[strum(serialize = "t2", serialize = "x7")]
     /// Temporaries
     T2,
-    #[strum(serialize = "s0", serialize = "fp")]
+    #[strum(serialize = "s0", serialize = "fp", serialize = "x8")]
     /// Saved register/frame pointer R29
     S0FP,
-    #[strum(serialize = "s1")]
+    #[strum(serialize = "s1", serialize = "x9")]
     /// Saved registers
     S1,
-    #[strum(serialize = "a0")]
+    #[strum(serialize = "a0", serialize = "x10")]
     /// Function arguments/return values
     A0,
-    #[strum(serialize = "a1")]
+    #[strum(serialize = "a1", serialize = "x11")]
     /// Function arguments/return values
     A1,
-    #[strum(serialize = "a2")]
+    #[strum(serialize = "a2", serialize = "x12")]
     /// Function arguments
     A2,
-    #[strum(serialize = "a3")]
+    #[strum(serialize = "a3", serialize = "x13")]
     /// Function arguments
     A3,
-    #[strum(serialize = "a4")]
+    #[strum(serialize = "a4", serialize = "x14")]
     /// Function arguments
     A4,
-    #[strum(serialize = "a5")]
+    #[strum(serialize = "a5", serialize = "x15")]
     /// Function arguments
     A5,
-    #[strum(serialize = "a6")]
+    #[strum(serialize = "a6", serialize = "x16")]
     /// Function arguments
     A6,
-    #[strum(serialize = "a7")]
+    #[strum(serialize = "a7", serialize = "x17")]
     /// Function arguments
     A7,
-    #[strum(serialize = "s2")]
+    #[strum(serialize = "s2", serialize = "x18")]
     /// Saved registers
     S2,
-    #[strum(serialize = "s3")]
+    #[strum(serialize = "s3", serialize = "x19")]
     /// Saved registers
     S3,
-    #[strum(serialize = "s4")]
+    #[strum(serialize = "s4", serialize = "x20")]
     /// Saved registers
     S4,
-    #[strum(serialize = "s5")]
+    #[strum(serialize = "s5", serialize = "x21")]
     /// Saved registers
     S5,
-    #[strum(serialize = "s6")]
+    #[strum(serialize = "s6", serialize = "x22")]
     /// Saved registers
     S6,
-    #[strum(serialize = "s7")]
+    #[strum(serialize = "s7", serialize = "x23")]
     /// Saved registers
     S7,
-    #[strum(serialize = "s8")]
+    #[strum(serialize = "s8", serialize = "x24")]
     /// Saved registers
     S8,
-    #[strum(serialize = "s9")]
+    #[strum(serialize = "s9", serialize = "x25")]
     /// Saved registers
     S9,
-    #[strum(serialize = "s10")]
+    #[strum(serialize = "s10", serialize = "x26")]
     /// Saved registers
     S10,
-    #[strum(serialize = "s11")]
+    #[strum(serialize = "s11", serialize = "x27")]
     /// Saved registers
     S11,
-    #[strum(serialize = "t3")]
+    #[strum(serialize = "t3", serialize = "x28")]
     /// Temporaries
     T3,
-    #[strum(serialize = "t4")]
+    #[strum(serialize = "t4", serialize = "x29")]
     /// Temporaries
     T4,
-    #[strum(serialize = "t5")]
+    #[strum(serialize = "t5", serialize = "x30")]
     /// Temporaries
     T5,
-    #[strum(serialize = "t6")]
+    #[strum(serialize = "t6", serialize = "x31")]
     /// Temporaries
     T6,
 }
 
+/// RISC-V floating point registers of the F and D extensions
+/// https://msyksphinz-self.github.io/riscv-isadoc/html/regs.html
+#[derive(Debug, EnumString, Default, Clone, Copy, PartialEq, Eq)]
+pub enum RiscVFRegister {
+    #[default]
+    #[strum(serialize = "ft0", serialize = "f0")]
+    /// Temporaries
+    FT0,
+    #[strum(serialize = "ft1", serialize = "f1")]
+    /// Temporaries
+    FT1,
+    #[strum(serialize = "ft2", serialize = "f2")]
+    /// Temporaries
+    FT2,
+    #[strum(serialize = "ft3", serialize = "f3")]
+    /// Temporaries
+    FT3,
+    #[strum(serialize = "ft4", serialize = "f4")]
+    /// Temporaries
+    FT4,
+    #[strum(serialize = "ft5", serialize = "f5")]
+    /// Temporaries
+    FT5,
+    #[strum(serialize = "ft6", serialize = "f6")]
+    /// Temporaries
+    FT6,
+    #[strum(serialize = "ft7", serialize = "f7")]
+    /// Temporaries
+    FT7,
+    #[strum(serialize = "fs0", serialize = "f8")]
+    /// Saved registers
+    FS0,
+    #[strum(serialize = "fs1", serialize = "f9")]
+    /// Saved registers
+    FS1,
+    #[strum(serialize = "fa0", serialize = "f10")]
+    /// Function arguments/return values
+    FA0,
+    #[strum(serialize = "fa1", serialize = "f11")]
+    /// Function arguments/return values
+    FA1,
+    #[strum(serialize = "fa2", serialize = "f12")]
+    /// Function arguments
+    FA2,
+    #[strum(serialize = "fa3", serialize = "f13")]
+    /// Function arguments
+    FA3,
+    #[strum(serialize = "fa4", serialize = "f14")]
+    /// Function arguments
+    FA4,
+    #[strum(serialize = "fa5", serialize = "f15")]
+    /// Function arguments
+    FA5,
+    #[strum(serialize = "fa6", serialize = "f16")]
+    /// Function arguments
+    FA6,
+    #[strum(serialize = "fa7", serialize = "f17")]
+    /// Function arguments
+    FA7,
+    #[strum(serialize = "fs2", serialize = "f18")]
+    /// Saved registers
+    FS2,
+    #[strum(serialize = "fs3", serialize = "f19")]
+    /// Saved registers
+    FS3,
+    #[strum(serialize = "fs4", serialize = "f20")]
+    /// Saved registers
+    FS4,
+    #[strum(serialize = "fs5", serialize = "f21")]
+    /// Saved registers
+    FS5,
+    #[strum(serialize = "fs6", serialize = "f22")]
+    /// Saved registers
+    FS6,
+    #[strum(serialize = "fs7", serialize = "f23")]
+    /// Saved registers
+    FS7,
+    #[strum(serialize = "fs8", serialize = "f24")]
+    /// Saved registers
+    FS8,
+    #[strum(serialize = "fs9", serialize = "f25")]
+    /// Saved registers
+    FS9,
+    #[strum(serialize = "fs10", serialize = "f26")]
+    /// Saved registers
+    FS10,
+    #[strum(serialize = "fs11", serialize = "f27")]
+    /// Saved registers
+    FS11,
+    #[strum(serialize = "ft8", serialize = "f28")]
+    /// Temporaries
+    FT8,
+    #[strum(serialize = "ft9", serialize = "f29")]
+    /// Temporaries
+    FT9,
+    #[strum(serialize = "ft10", serialize = "f30")]
+    /// Temporaries
+    FT10,
+    #[strum(serialize = "ft11", serialize = "f31")]
+    /// Temporaries
+    FT11,
+}
+
+/// A register operand: the 32 bit `w` or 64 bit `x` view of a register, or
+/// the 32 bit `s` or 64 bit `d` view of a SIMD and floating point register.
+///
+/// Only the views which exist can be built, so printing never fails.
 #[derive(Debug, Copy, Clone)]
 pub struct ArmRegister {
-    pub width: ArmWidth,
-    pub name: ArmRegisterName,
+    width: ArmWidth,
+    name: ArmRegisterName,
 }
 
-impl Default for ArmRegister {
-    fn default() -> Self {
-        todo!()
+impl ArmRegister {
+    /// The `width` view of `name`, for registers and widths which aren't
+    /// chosen by the input, which go through [`ArmRegister::try_new`].
+    ///
+    /// # Panics
+    ///
+    /// If the register has no such view, see [`ArmRegister::try_new`].
+    pub fn new(name: ArmRegisterName, width: ArmWidth) -> ArmRegister {
+        ArmRegister::try_new(name, width)
+            .unwrap_or_else(|| panic!("{:?} has no {:?} register view", name, width))
+    }
+
+    /// The `width` view of `name`, or `None` if there isn't one. Registers
+    /// are viewed as [`ArmWidth::Word`] or [`ArmWidth::Double`], since
+    /// narrower and sign extended widths only apply to memory accesses, and
+    /// `pc` can't be an operand at all.
+    pub fn try_new(name: ArmRegisterName, width: ArmWidth) -> Option<ArmRegister> {
+        match (name, width) {
+            (ArmRegisterName::Pc, _) => None,
+            (_, ArmWidth::Word | ArmWidth::Double) => Some(ArmRegister { width, name }),
+            _ => None,
+        }
+    }
+
+    pub fn name(&self) -> ArmRegisterName {
+        self.name
+    }
+
+    pub fn width(&self) -> ArmWidth {
+        self.width
     }
 }
 
@@ -566,7 +2026,7 @@ impl Default for ArmRegister {
 /// https://developer.arm.com/documentation/dui0056/d/using-the-procedure-call-standard/register-roles-and-names/register-names
 /// Image of instructions https://duetorun.com/blog/arm/images/AArch64-registers.png
 ///   - https://duetorun.com/blog/20230601/a64-regs/#user_program_registers
-#[derive(Debug, EnumString, Copy, Clone)]
+#[derive(Debug, EnumString, Copy, Clone, PartialEq, Eq)]
 pub enum ArmRegisterName {
     #[strum(serialize = "wzr", serialize = "xzr")]
     /// Zero register. Hardware special.
@@ -618,84 +2078,217 @@ pub enum ArmRegisterName {
     X28,
     /// FP
     X29,
+    // SIMD and floating point registers, viewed as `s` and `d` registers.
+    // The lower 64 bits of V8-V15 are callee-saved.
+    V0,
+    V1,
+    V2,
+    V3,
+    V4,
+    V5,
+    V6,
+    V7,
+    V8,
+    V9,
+    V10,
+    V11,
+    V12,
+    V13,
+    V14,
+    V15,
+    V16,
+    V17,
+    V18,
+    V19,
+    V20,
+    V21,
+    V22,
+    V23,
+    V24,
+    V25,
+    V26,
+    V27,
+    V28,
+    V29,
+    V30,
+    V31,
 }
 
-impl Default for ArmRegisterName {
-    fn default() -> Self {
-        todo!()
+impl ArmRegisterName {
+    /// The register number in instruction encodings. `sp` and the zero
+    /// register are both 31, `pc` has none, and `vN` is N.
+    pub fn number(self) -> Option<u32> {
+        match self {
+            ArmRegisterName::Pc => None,
+            ArmRegisterName::Zero | ArmRegisterName::Sp => Some(31),
+            ArmRegisterName::Lr => Some(30),
+            v if v.is_vector() => Some(v as u32 - ArmRegisterName::V0 as u32),
+            x => Some(x as u32 - ArmRegisterName::X0 as u32),
+        }
     }
-}
 
-/// Parse a text file into our enum.
-pub fn parse_asm(asm: &str) -> Vec<RiscVInstruction> {
-    asm.lines()
-        .filter_map(|line| {
-            // TODO (Samir): Not sure that this will handle assembly labels
-            // We probably need to construct a map for those to find the
-            // original instruction they map to.
-            let parts: Vec<&str> = line.split_whitespace().collect();
-            if parts.is_empty() {
-                None
-            } else {
-                // RiscVInstruction::from_str(parts[0]).ok()
-                todo!()
-            }
-        })
-        .collect()
+    /// Whether this is one of the SIMD and floating point registers `v0-v31`.
+    pub fn is_vector(self) -> bool {
+        self as u32 >= ArmRegisterName::V0 as u32
+    }
 }
 
-impl Into<String> for ArmInstruction {
-    fn into(self) -> String {
-        match self {
-            ArmInstruction::Adc => todo!(),
+impl From<ArmInstruction> for String {
+    fn from(instr: ArmInstruction) -> String {
+        match instr {
+            ArmInstruction::Adc { dest, arg1, arg2 } => {
+                format!("adc {}, {}, {}", dest, arg1, arg2)
+            }
             ArmInstruction::Add { dest, arg1, arg2 } => {
                 format!("add {}, {}, {}", dest, arg1, arg2)
             }
-            ArmInstruction::And => todo!(),
+            ArmInstruction::And { dest, arg1, arg2 } => {
+                format!("and {}, {}, {}", dest, arg1, arg2)
+            }
             ArmInstruction::Adrp { dest, label } => {
                 format!("adrp {}, {}", dest, label)
             }
             ArmInstruction::B { target } => {
                 format!("b {}", target)
             }
-            // TODO: Fix these branching instructions need to map to
-            // multiple instructions. Would be better separation of concerns
-            // if this was produced by the translation function instead of a
-            // hack in the string formatting.
-            ArmInstruction::Ble { arg1, arg2, target } => {
-                format!("cmp {}, {}\nble {}", arg1, arg2, target)
+            ArmInstruction::BCond { cond, target } => {
+                format!("b.{} {}", cond, target)
             }
-            ArmInstruction::Bge { arg1, arg2, target } => {
-                format!("cmp {}, {}\nbge {}", arg1, arg2, target)
+            ArmInstruction::Blr { target } => {
+                format!("blr {}", double_name(target))
             }
-            ArmInstruction::Blt { arg1, arg2, target } => {
-                format!("cmp {}, {}\nblt {}", arg1, arg2, target)
+            ArmInstruction::Br { target } => {
+                format!("br {}", double_name(target))
             }
-            ArmInstruction::Bgt { arg1, arg2, target } => {
-                format!("cmp {}, {}\nbgt {}", arg1, arg2, target)
+            ArmInstruction::Adr { dest, label } => {
+                format!("adr {}, {}", dest, label)
             }
-            ArmInstruction::Bne { arg1, arg2, target } => {
-                format!("cmp {}, {}\nbne {}", arg1, arg2, target)
+            ArmInstruction::Ldr { width, dest, src } => {
+                let op = match width {
+                    ArmWidth::Byte => "ldrb",
+                    ArmWidth::SignedByte => "ldrsb",
+                    ArmWidth::Half => "ldrh",
+                    ArmWidth::SignedHalf => "ldrsh",
+                    ArmWidth::SignedWord => "ldrsw",
+                    ArmWidth::Word | ArmWidth::Double => "ldr",
+                };
+                match src {
+                    ArmVal::UnscaledOffset(..) => {
+                        format!("{} {}, {}", op.replacen("ldr", "ldur", 1), dest, src)
+                    }
+                    _ => format!("{} {}, {}", op, dest, src),
+                }
             }
-            ArmInstruction::Blr { target } => {
-                format!("blr {}", Into::<ArmRegister>::into(target))
+            ArmInstruction::Ldxr {
+                acquire,
+                dest,
+                addr,
+            } => {
+                let a = if acquire { "a" } else { "" };
+                format!("ld{}xr {}, [{}]", a, dest, double_name(addr))
             }
-            ArmInstruction::Ldr { width, dest, src } => match width {
-                ArmWidth::Word | ArmWidth::Double => format!("ldr {}, {}", dest, src),
-                _ => todo!(),
-            },
-            ArmInstruction::Mov { width, dest, src } => {
+            ArmInstruction::Stxr {
+                release,
+                status,
+                src,
+                addr,
+            } => {
+                let l = if release { "l" } else { "" };
+                format!("st{}xr {}, {}, [{}]", l, status, src, double_name(addr))
+            }
+            ArmInstruction::Swp {
+                acquire,
+                release,
+                src,
+                dest,
+                addr,
+            } => {
+                format!(
+                    "swp{} {}, {}, [{}]",
+                    ordering_suffix(acquire, release),
+                    src,
+                    dest,
+                    double_name(addr)
+                )
+            }
+            ArmInstruction::LdOp {
+                op,
+                acquire,
+                release,
+                src,
+                dest,
+                addr,
+            } => {
+                format!(
+                    "ld{}{} {}, {}, [{}]",
+                    op,
+                    ordering_suffix(acquire, release),
+                    src,
+                    dest,
+                    double_name(addr)
+                )
+            }
+            ArmInstruction::Ldp { dest1, dest2, src } => {
+                format!("ldp {}, {}, {}", dest1, dest2, src)
+            }
+            ArmInstruction::Stp { src1, src2, dest } => {
+                format!("stp {}, {}, {}", src1, src2, dest)
+            }
+            ArmInstruction::Mov {
+                width: _,
+                dest,
+                src,
+            } => {
                 format!("mov {}, {}", dest, src)
             }
-            ArmInstruction::Ret => todo!(),
-            ArmInstruction::Str { width, src, dest } => match width {
-                ArmWidth::Word => format!("str {}, {}", src, dest),
-                ArmWidth::Double => format!("str {}, {}", src, dest),
-                _ => todo!("{:?}", width),
+            ArmInstruction::Movk { dest, imm, shift } => match shift {
+                0 => format!("movk {}, {}", dest, imm),
+                _ => format!("movk {}, {}, lsl {}", dest, imm, shift),
             },
+            ArmInstruction::Ret => "ret".to_string(),
+            ArmInstruction::Str { width, src, dest } => {
+                let op = match width {
+                    ArmWidth::Byte | ArmWidth::SignedByte => "strb",
+                    ArmWidth::Half | ArmWidth::SignedHalf => "strh",
+                    ArmWidth::Word | ArmWidth::SignedWord | ArmWidth::Double => "str",
+                };
+                match dest {
+                    ArmVal::UnscaledOffset(..) => {
+                        format!("{} {}, {}", op.replacen("str", "stur", 1), src, dest)
+                    }
+                    _ => format!("{} {}, {}", op, src, dest),
+                }
+            }
             ArmInstruction::Lsl { dest, src, imm } => {
                 format!("lsl {}, {}, {}", dest, src, imm)
             }
+            ArmInstruction::Lsr { dest, src, imm } => {
+                format!("lsr {}, {}, {}", dest, src, imm)
+            }
+            ArmInstruction::Asr { dest, src, imm } => {
+                format!("asr {}, {}, {}", dest, src, imm)
+            }
+            ArmInstruction::Lslv { dest, arg1, arg2 } => {
+                format!("lslv {}, {}, {}", dest, arg1, arg2)
+            }
+            ArmInstruction::Lsrv { dest, arg1, arg2 } => {
+                format!("lsrv {}, {}, {}", dest, arg1, arg2)
+            }
+            ArmInstruction::Asrv { dest, arg1, arg2 } => {
+                format!("asrv {}, {}, {}", dest, arg1, arg2)
+            }
+            ArmInstruction::Orr { dest, arg1, arg2 } => {
+                format!("orr {}, {}, {}", dest, arg1, arg2)
+            }
+            ArmInstruction::Eor { dest, arg1, arg2 } => {
+                format!("eor {}, {}, {}", dest, arg1, arg2)
+            }
+            ArmInstruction::Mvn { dest, src } => {
+                format!("mvn {}, {}", dest, src)
+            }
+            ArmInstruction::Neg { dest, src } => {
+                format!("neg {}, {}", dest, src)
+            }
             ArmInstruction::Sub { dest, arg1, arg2 } => {
                 format!("sub {}, {}, {}", dest, arg1, arg2)
             }
@@ -705,6 +2298,9 @@ impl Into<String> for ArmInstruction {
             ArmInstruction::Bl { target } => {
                 format!("bl {}", target)
             }
+            ArmInstruction::Cbnz { src, target } => {
+                format!("cbnz {}, {}", src, target)
+            }
             ArmInstruction::Label { name } => {
                 format!("{}:", name)
             }
@@ -717,255 +2313,344 @@ impl Into<String> for ArmInstruction {
             ArmInstruction::Cmp { op1, op2 } => {
                 format!("cmp {}, {}", op1, op2)
             }
+            ArmInstruction::Cmn { op1, op2 } => {
+                format!("cmn {}, {}", op1, op2)
+            }
+            ArmInstruction::Mul { dest, arg1, arg2 } => {
+                format!("mul {}, {}, {}", dest, arg1, arg2)
+            }
+            ArmInstruction::Smulh { dest, arg1, arg2 } => {
+                format!("smulh {}, {}, {}", dest, arg1, arg2)
+            }
+            ArmInstruction::Umulh { dest, arg1, arg2 } => {
+                format!("umulh {}, {}, {}", dest, arg1, arg2)
+            }
+            ArmInstruction::Msub {
+                dest,
+                arg1,
+                arg2,
+                arg3,
+            } => {
+                format!("msub {}, {}, {}, {}", dest, arg1, arg2, arg3)
+            }
+            ArmInstruction::Sdiv { dest, arg1, arg2 } => {
+                format!("sdiv {}, {}, {}", dest, arg1, arg2)
+            }
+            ArmInstruction::Udiv { dest, arg1, arg2 } => {
+                format!("udiv {}, {}, {}", dest, arg1, arg2)
+            }
+            ArmInstruction::Csel {
+                dest,
+                arg1,
+                arg2,
+                cond,
+            } => {
+                format!("csel {}, {}, {}, {}", dest, arg1, arg2, cond)
+            }
+            ArmInstruction::Csinv {
+                dest,
+                arg1,
+                arg2,
+                cond,
+            } => {
+                format!("csinv {}, {}, {}, {}", dest, arg1, arg2, cond)
+            }
+            ArmInstruction::Csinc {
+                dest,
+                arg1,
+                arg2,
+                cond,
+            } => {
+                format!("csinc {}, {}, {}, {}", dest, arg1, arg2, cond)
+            }
+            ArmInstruction::Cset { dest, cond } => {
+                format!("cset {}, {}", dest, cond)
+            }
+            ArmInstruction::Tst { op1, op2 } => {
+                format!("tst {}, {}", op1, op2)
+            }
+            ArmInstruction::Ins { dest, src } => {
+                format!("mov {}, {}", dest, src)
+            }
+            ArmInstruction::Fmov { dest, src } => {
+                format!("fmov {}, {}", dest, src)
+            }
+            ArmInstruction::Fadd { dest, arg1, arg2 } => {
+                format!("fadd {}, {}, {}", dest, arg1, arg2)
+            }
+            ArmInstruction::Fsub { dest, arg1, arg2 } => {
+                format!("fsub {}, {}, {}", dest, arg1, arg2)
+            }
+            ArmInstruction::Fmul { dest, arg1, arg2 } => {
+                format!("fmul {}, {}, {}", dest, arg1, arg2)
+            }
+            ArmInstruction::Fdiv { dest, arg1, arg2 } => {
+                format!("fdiv {}, {}, {}", dest, arg1, arg2)
+            }
+            ArmInstruction::Fminnm { dest, arg1, arg2 } => {
+                format!("fminnm {}, {}, {}", dest, arg1, arg2)
+            }
+            ArmInstruction::Fmaxnm { dest, arg1, arg2 } => {
+                format!("fmaxnm {}, {}, {}", dest, arg1, arg2)
+            }
+            ArmInstruction::Fmadd {
+                dest,
+                arg1,
+                arg2,
+                arg3,
+            } => {
+                format!("fmadd {}, {}, {}, {}", dest, arg1, arg2, arg3)
+            }
+            ArmInstruction::Fmsub {
+                dest,
+                arg1,
+                arg2,
+                arg3,
+            } => {
+                format!("fmsub {}, {}, {}, {}", dest, arg1, arg2, arg3)
+            }
+            ArmInstruction::Fnmadd {
+                dest,
+                arg1,
+                arg2,
+                arg3,
+            } => {
+                format!("fnmadd {}, {}, {}, {}", dest, arg1, arg2, arg3)
+            }
+            ArmInstruction::Fnmsub {
+                dest,
+                arg1,
+                arg2,
+                arg3,
+            } => {
+                format!("fnmsub {}, {}, {}, {}", dest, arg1, arg2, arg3)
+            }
+            ArmInstruction::Fsqrt { dest, src } => {
+                format!("fsqrt {}, {}", dest, src)
+            }
+            ArmInstruction::Fabs { dest, src } => {
+                format!("fabs {}, {}", dest, src)
+            }
+            ArmInstruction::Fneg { dest, src } => {
+                format!("fneg {}, {}", dest, src)
+            }
+            ArmInstruction::Fcmp { op1, op2 } => {
+                format!("fcmp {}, {}", op1, op2)
+            }
+            ArmInstruction::Fcvt { dest, src } => {
+                format!("fcvt {}, {}", dest, src)
+            }
+            ArmInstruction::FcvtToInt {
+                rounding,
+                signed,
+                dest,
+                src,
+            } => {
+                let sign = if signed { 's' } else { 'u' };
+                format!("fcvt{}{} {}, {}", rounding, sign, dest, src)
+            }
+            ArmInstruction::Scvtf { dest, src } => {
+                format!("scvtf {}, {}", dest, src)
+            }
+            ArmInstruction::Ucvtf { dest, src } => {
+                format!("ucvtf {}, {}", dest, src)
+            }
+            ArmInstruction::Dmb { option } => {
+                format!("dmb {}", option)
+            }
+            ArmInstruction::Dsb { option } => {
+                format!("dsb {}", option)
+            }
+            ArmInstruction::Isb => "isb".to_string(),
+            ArmInstruction::DcCvau { addr } => {
+                format!("dc cvau, {}", addr)
+            }
+            ArmInstruction::IcIvau { addr } => {
+                format!("ic ivau, {}", addr)
+            }
+            ArmInstruction::Mrs { dest, src } => {
+                format!("mrs {}, {}", dest, src)
+            }
+            ArmInstruction::Yield => "yield".to_string(),
+            ArmInstruction::Nop => "nop".to_string(),
             ArmInstruction::Verbatim { text } => text,
         }
     }
 }
 
-impl Into<String> for ArmRegister {
-    fn into(self) -> String {
-        let s: &str = match (self.name, self.width) {
-            (ArmRegisterName::Zero, ArmWidth::Word) => "wzr",
-            (ArmRegisterName::Zero, ArmWidth::Double) => "xzr",
-            (ArmRegisterName::Zero, _) => panic!("invalid width for zero register"),
-            (ArmRegisterName::Pc, ArmWidth::Byte) => todo!(),
-            (ArmRegisterName::Pc, ArmWidth::SignedByte) => todo!(),
-            (ArmRegisterName::Pc, ArmWidth::Half) => todo!(),
-            (ArmRegisterName::Pc, ArmWidth::SignedHalf) => todo!(),
-            (ArmRegisterName::Pc, ArmWidth::Word) => todo!(),
-            (ArmRegisterName::Pc, ArmWidth::Double) => todo!(),
-            (ArmRegisterName::Sp, ArmWidth::Word) => "wsp",
-            (ArmRegisterName::Sp, ArmWidth::Double) => "sp",
-            (ArmRegisterName::Sp, _) => todo!(),
-            (ArmRegisterName::Lr, ArmWidth::Byte) => todo!(),
-            (ArmRegisterName::Lr, ArmWidth::SignedByte) => todo!(),
-            (ArmRegisterName::Lr, ArmWidth::Half) => todo!(),
-            (ArmRegisterName::Lr, ArmWidth::SignedHalf) => todo!(),
-            (ArmRegisterName::Lr, ArmWidth::Word) => todo!(),
-            (ArmRegisterName::Lr, ArmWidth::Double) => "lr",
-            (ArmRegisterName::X0, ArmWidth::Byte) => todo!(),
-            (ArmRegisterName::X0, ArmWidth::SignedByte) => todo!(),
-            (ArmRegisterName::X0, ArmWidth::Half) => todo!(),
-            (ArmRegisterName::X0, ArmWidth::SignedHalf) => todo!(),
-            (ArmRegisterName::X0, ArmWidth::Word) => "w0",
-            (ArmRegisterName::X0, ArmWidth::Double) => "x0",
-            (ArmRegisterName::X1, ArmWidth::Byte) => todo!(),
-            (ArmRegisterName::X1, ArmWidth::SignedByte) => todo!(),
-            (ArmRegisterName::X1, ArmWidth::Half) => todo!(),
-            (ArmRegisterName::X1, ArmWidth::SignedHalf) => todo!(),
-            (ArmRegisterName::X1, ArmWidth::Word) => "w1",
-            (ArmRegisterName::X1, ArmWidth::Double) => "x1",
-            (ArmRegisterName::X2, ArmWidth::Byte) => todo!(),
-            (ArmRegisterName::X2, ArmWidth::SignedByte) => todo!(),
-            (ArmRegisterName::X2, ArmWidth::Half) => todo!(),
-            (ArmRegisterName::X2, ArmWidth::SignedHalf) => todo!(),
-            (ArmRegisterName::X2, ArmWidth::Word) => "w2",
-            (ArmRegisterName::X2, ArmWidth::Double) => "x2",
-            (ArmRegisterName::X3, ArmWidth::Byte) => todo!(),
-            (ArmRegisterName::X3, ArmWidth::SignedByte) => todo!(),
-            (ArmRegisterName::X3, ArmWidth::Half) => todo!(),
-            (ArmRegisterName::X3, ArmWidth::SignedHalf) => todo!(),
-            (ArmRegisterName::X3, ArmWidth::Word) => "w3",
-            (ArmRegisterName::X3, ArmWidth::Double) => "x3",
-            (ArmRegisterName::X4, ArmWidth::Byte) => todo!(),
-            (ArmRegisterName::X4, ArmWidth::SignedByte) => todo!(),
-            (ArmRegisterName::X4, ArmWidth::Half) => todo!(),
-            (ArmRegisterName::X4, ArmWidth::SignedHalf) => todo!(),
-            (ArmRegisterName::X4, ArmWidth::Word) => "w4",
-            (ArmRegisterName::X4, ArmWidth::Double) => "x4",
-            (ArmRegisterName::X5, ArmWidth::Byte) => todo!(),
-            (ArmRegisterName::X5, ArmWidth::SignedByte) => todo!(),
-            (ArmRegisterName::X5, ArmWidth::Half) => todo!(),
-            (ArmRegisterName::X5, ArmWidth::SignedHalf) => todo!(),
-            (ArmRegisterName::X5, ArmWidth::Word) => "w5",
-            (ArmRegisterName::X5, ArmWidth::Double) => "x5",
-            (ArmRegisterName::X6, ArmWidth::Byte) => todo!(),
-            (ArmRegisterName::X6, ArmWidth::SignedByte) => todo!(),
-            (ArmRegisterName::X6, ArmWidth::Half) => todo!(),
-            (ArmRegisterName::X6, ArmWidth::SignedHalf) => todo!(),
-            (ArmRegisterName::X6, ArmWidth::Word) => todo!(),
-            (ArmRegisterName::X6, ArmWidth::Double) => todo!(),
-            (ArmRegisterName::X7, ArmWidth::Byte) => todo!(),
-            (ArmRegisterName::X7, ArmWidth::SignedByte) => todo!(),
-            (ArmRegisterName::X7, ArmWidth::Half) => todo!(),
-            (ArmRegisterName::X7, ArmWidth::SignedHalf) => todo!(),
-            (ArmRegisterName::X7, ArmWidth::Word) => "w7",
-            (ArmRegisterName::X7, ArmWidth::Double) => "x7",
-            (ArmRegisterName::X8, ArmWidth::Byte) => todo!(),
-            (ArmRegisterName::X8, ArmWidth::SignedByte) => todo!(),
-            (ArmRegisterName::X8, ArmWidth::Half) => todo!(),
-            (ArmRegisterName::X8, ArmWidth::SignedHalf) => todo!(),
-            (ArmRegisterName::X8, ArmWidth::Word) => todo!(),
-            (ArmRegisterName::X8, ArmWidth::Double) => "x8",
-            (ArmRegisterName::X9, ArmWidth::Byte) => todo!(),
-            (ArmRegisterName::X9, ArmWidth::SignedByte) => todo!(),
-            (ArmRegisterName::X9, ArmWidth::Half) => todo!(),
-            (ArmRegisterName::X9, ArmWidth::SignedHalf) => todo!(),
-            (ArmRegisterName::X9, ArmWidth::Word) => todo!(),
-            (ArmRegisterName::X9, ArmWidth::Double) => todo!(),
-            (ArmRegisterName::X10, ArmWidth::Byte) => todo!(),
-            (ArmRegisterName::X10, ArmWidth::SignedByte) => todo!(),
-            (ArmRegisterName::X10, ArmWidth::Half) => todo!(),
-            (ArmRegisterName::X10, ArmWidth::SignedHalf) => todo!(),
-            (ArmRegisterName::X10, ArmWidth::Word) => todo!(),
-            (ArmRegisterName::X10, ArmWidth::Double) => todo!(),
-            (ArmRegisterName::X11, ArmWidth::Byte) => todo!(),
-            (ArmRegisterName::X11, ArmWidth::SignedByte) => todo!(),
-            (ArmRegisterName::X11, ArmWidth::Half) => todo!(),
-            (ArmRegisterName::X11, ArmWidth::SignedHalf) => todo!(),
-            (ArmRegisterName::X11, ArmWidth::Word) => "w11",
-            (ArmRegisterName::X11, ArmWidth::Double) => "x11",
-            (ArmRegisterName::X12, ArmWidth::Byte) => todo!(),
-            (ArmRegisterName::X12, ArmWidth::SignedByte) => todo!(),
-            (ArmRegisterName::X12, ArmWidth::Half) => todo!(),
-            (ArmRegisterName::X12, ArmWidth::SignedHalf) => todo!(),
-            (ArmRegisterName::X12, ArmWidth::Word) => "w12",
-            (ArmRegisterName::X12, ArmWidth::Double) => "x12",
-            (ArmRegisterName::X13, ArmWidth::Byte) => todo!(),
-            (ArmRegisterName::X13, ArmWidth::SignedByte) => todo!(),
-            (ArmRegisterName::X13, ArmWidth::Half) => todo!(),
-            (ArmRegisterName::X13, ArmWidth::SignedHalf) => todo!(),
-            (ArmRegisterName::X13, ArmWidth::Word) => todo!(),
-            (ArmRegisterName::X13, ArmWidth::Double) => todo!(),
-            (ArmRegisterName::X14, ArmWidth::Byte) => todo!(),
-            (ArmRegisterName::X14, ArmWidth::SignedByte) => todo!(),
-            (ArmRegisterName::X14, ArmWidth::Half) => todo!(),
-            (ArmRegisterName::X14, ArmWidth::SignedHalf) => todo!(),
-            (ArmRegisterName::X14, ArmWidth::Word) => todo!(),
-            (ArmRegisterName::X14, ArmWidth::Double) => todo!(),
-            (ArmRegisterName::X15, ArmWidth::Byte) => todo!(),
-            (ArmRegisterName::X15, ArmWidth::SignedByte) => todo!(),
-            (ArmRegisterName::X15, ArmWidth::Half) => todo!(),
-            (ArmRegisterName::X15, ArmWidth::SignedHalf) => todo!(),
-            (ArmRegisterName::X15, ArmWidth::Word) => todo!(),
-            (ArmRegisterName::X15, ArmWidth::Double) => todo!(),
-            (ArmRegisterName::X16, ArmWidth::Byte) => todo!(),
-            (ArmRegisterName::X16, ArmWidth::SignedByte) => todo!(),
-            (ArmRegisterName::X16, ArmWidth::Half) => todo!(),
-            (ArmRegisterName::X16, ArmWidth::SignedHalf) => todo!(),
-            (ArmRegisterName::X16, ArmWidth::Word) => todo!(),
-            (ArmRegisterName::X16, ArmWidth::Double) => todo!(),
-            (ArmRegisterName::X17, ArmWidth::Byte) => todo!(),
-            (ArmRegisterName::X17, ArmWidth::SignedByte) => todo!(),
-            (ArmRegisterName::X17, ArmWidth::Half) => todo!(),
-            (ArmRegisterName::X17, ArmWidth::SignedHalf) => todo!(),
-            (ArmRegisterName::X17, ArmWidth::Word) => todo!(),
-            (ArmRegisterName::X17, ArmWidth::Double) => todo!(),
-            (ArmRegisterName::X18, ArmWidth::Byte) => todo!(),
-            (ArmRegisterName::X18, ArmWidth::SignedByte) => todo!(),
-            (ArmRegisterName::X18, ArmWidth::Half) => todo!(),
-            (ArmRegisterName::X18, ArmWidth::SignedHalf) => todo!(),
-            (ArmRegisterName::X18, ArmWidth::Word) => todo!(),
-            (ArmRegisterName::X18, ArmWidth::Double) => todo!(),
-            (ArmRegisterName::X19, ArmWidth::Byte) => todo!(),
-            (ArmRegisterName::X19, ArmWidth::SignedByte) => todo!(),
-            (ArmRegisterName::X19, ArmWidth::Half) => todo!(),
-            (ArmRegisterName::X19, ArmWidth::SignedHalf) => todo!(),
-            (ArmRegisterName::X19, ArmWidth::Word) => todo!(),
-            (ArmRegisterName::X19, ArmWidth::Double) => todo!(),
-            (ArmRegisterName::X20, ArmWidth::Byte) => todo!(),
-            (ArmRegisterName::X20, ArmWidth::SignedByte) => todo!(),
-            (ArmRegisterName::X20, ArmWidth::Half) => todo!(),
-            (ArmRegisterName::X20, ArmWidth::SignedHalf) => todo!(),
-            (ArmRegisterName::X20, ArmWidth::Word) => todo!(),
-            (ArmRegisterName::X20, ArmWidth::Double) => todo!(),
-            (ArmRegisterName::X21, ArmWidth::Byte) => todo!(),
-            (ArmRegisterName::X21, ArmWidth::SignedByte) => todo!(),
-            (ArmRegisterName::X21, ArmWidth::Half) => todo!(),
-            (ArmRegisterName::X21, ArmWidth::SignedHalf) => todo!(),
-            (ArmRegisterName::X21, ArmWidth::Word) => todo!(),
-            (ArmRegisterName::X21, ArmWidth::Double) => todo!(),
-            (ArmRegisterName::X22, ArmWidth::Byte) => todo!(),
-            (ArmRegisterName::X22, ArmWidth::SignedByte) => todo!(),
-            (ArmRegisterName::X22, ArmWidth::Half) => todo!(),
-            (ArmRegisterName::X22, ArmWidth::SignedHalf) => todo!(),
-            (ArmRegisterName::X22, ArmWidth::Word) => todo!(),
-            (ArmRegisterName::X22, ArmWidth::Double) => todo!(),
-            (ArmRegisterName::X23, ArmWidth::Byte) => todo!(),
-            (ArmRegisterName::X23, ArmWidth::SignedByte) => todo!(),
-            (ArmRegisterName::X23, ArmWidth::Half) => todo!(),
-            (ArmRegisterName::X23, ArmWidth::SignedHalf) => todo!(),
-            (ArmRegisterName::X23, ArmWidth::Word) => todo!(),
-            (ArmRegisterName::X23, ArmWidth::Double) => todo!(),
-            (ArmRegisterName::X24, ArmWidth::Byte) => todo!(),
-            (ArmRegisterName::X24, ArmWidth::SignedByte) => todo!(),
-            (ArmRegisterName::X24, ArmWidth::Half) => todo!(),
-            (ArmRegisterName::X24, ArmWidth::SignedHalf) => todo!(),
-            (ArmRegisterName::X24, ArmWidth::Word) => todo!(),
-            (ArmRegisterName::X24, ArmWidth::Double) => todo!(),
-            (ArmRegisterName::X25, ArmWidth::Byte) => todo!(),
-            (ArmRegisterName::X25, ArmWidth::SignedByte) => todo!(),
-            (ArmRegisterName::X25, ArmWidth::Half) => todo!(),
-            (ArmRegisterName::X25, ArmWidth::SignedHalf) => todo!(),
-            (ArmRegisterName::X25, ArmWidth::Word) => todo!(),
-            (ArmRegisterName::X25, ArmWidth::Double) => todo!(),
-            (ArmRegisterName::X26, ArmWidth::Byte) => todo!(),
-            (ArmRegisterName::X26, ArmWidth::SignedByte) => todo!(),
-            (ArmRegisterName::X26, ArmWidth::Half) => todo!(),
-            (ArmRegisterName::X26, ArmWidth::SignedHalf) => todo!(),
-            (ArmRegisterName::X26, ArmWidth::Word) => todo!(),
-            (ArmRegisterName::X26, ArmWidth::Double) => todo!(),
-            (ArmRegisterName::X27, ArmWidth::Byte) => todo!(),
-            (ArmRegisterName::X27, ArmWidth::SignedByte) => todo!(),
-            (ArmRegisterName::X27, ArmWidth::Half) => todo!(),
-            (ArmRegisterName::X27, ArmWidth::SignedHalf) => todo!(),
-            (ArmRegisterName::X27, ArmWidth::Word) => todo!(),
-            (ArmRegisterName::X27, ArmWidth::Double) => todo!(),
-            (ArmRegisterName::X28, ArmWidth::Byte) => todo!(),
-            (ArmRegisterName::X28, ArmWidth::SignedByte) => todo!(),
-            (ArmRegisterName::X28, ArmWidth::Half) => todo!(),
-            (ArmRegisterName::X28, ArmWidth::SignedHalf) => todo!(),
-            (ArmRegisterName::X28, ArmWidth::Word) => todo!(),
-            (ArmRegisterName::X28, ArmWidth::Double) => todo!(),
-            (ArmRegisterName::X29, ArmWidth::Byte) => todo!(),
-            (ArmRegisterName::X29, ArmWidth::SignedByte) => todo!(),
-            (ArmRegisterName::X29, ArmWidth::Half) => todo!(),
-            (ArmRegisterName::X29, ArmWidth::SignedHalf) => todo!(),
-            (ArmRegisterName::X29, ArmWidth::Word) => "w29",
-            (ArmRegisterName::X29, ArmWidth::Double) => "x29",
-        };
-        s.to_string()
+impl From<ArmRegister> for String {
+    fn from(reg: ArmRegister) -> String {
+        let double = matches!(reg.width, ArmWidth::Double);
+        match reg.name {
+            ArmRegisterName::Zero if double => "xzr".to_string(),
+            ArmRegisterName::Zero => "wzr".to_string(),
+            ArmRegisterName::Sp if double => "sp".to_string(),
+            ArmRegisterName::Sp => "wsp".to_string(),
+            ArmRegisterName::Lr if double => "lr".to_string(),
+            name if name.is_vector() => {
+                let number = name.number().unwrap();
+                format!("{}{}", if double { 'd' } else { 's' }, number)
+            }
+            name => {
+                let number = name.number().expect("pc is not a register operand");
+                format!("{}{}", if double { 'x' } else { 'w' }, number)
+            }
+        }
     }
 }
 
 impl Display for ArmRegister {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        let x: String = self.clone().into();
+        let x: String = (*self).into();
         write!(f, "{}", x)
-        // let s: String = self.into();
-        // write!(f, "{}", s)
     }
 }
 
+impl Display for ArmCondition {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let s = match self {
+            ArmCondition::Eq => "eq",
+            ArmCondition::Ne => "ne",
+            ArmCondition::Hs => "hs",
+            ArmCondition::Lo => "lo",
+            ArmCondition::Mi => "mi",
+            ArmCondition::Pl => "pl",
+            ArmCondition::Vs => "vs",
+            ArmCondition::Vc => "vc",
+            ArmCondition::Hi => "hi",
+            ArmCondition::Ls => "ls",
+            ArmCondition::Ge => "ge",
+            ArmCondition::Lt => "lt",
+            ArmCondition::Gt => "gt",
+            ArmCondition::Le => "le",
+        };
+        write!(f, "{}", s)
+    }
+}
+
+/// The `a`, `l` or `al` suffix of LSE instructions.
+fn ordering_suffix(acquire: bool, release: bool) -> &'static str {
+    match (acquire, release) {
+        (false, false) => "",
+        (true, false) => "a",
+        (false, true) => "l",
+        (true, true) => "al",
+    }
+}
+
+impl Display for ArmRounding {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let s = match self {
+            ArmRounding::Nearest => "n",
+            ArmRounding::Zero => "z",
+            ArmRounding::MinusInf => "m",
+            ArmRounding::PlusInf => "p",
+            ArmRounding::Away => "a",
+        };
+        write!(f, "{}", s)
+    }
+}
+
+impl Display for ArmBarrier {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let s = match self {
+            ArmBarrier::Ish => "ish",
+            ArmBarrier::Ishld => "ishld",
+            ArmBarrier::Ishst => "ishst",
+        };
+        write!(f, "{}", s)
+    }
+}
+
+impl Display for ArmSystemRegister {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let s = match self {
+            ArmSystemRegister::CtrEl0 => "ctr_el0",
+        };
+        write!(f, "{}", s)
+    }
+}
+
+impl Display for ArmAtomicOp {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let s = match self {
+            ArmAtomicOp::Add => "add",
+            ArmAtomicOp::Clr => "clr",
+            ArmAtomicOp::Eor => "eor",
+            ArmAtomicOp::Set => "set",
+            ArmAtomicOp::Smax => "smax",
+            ArmAtomicOp::Smin => "smin",
+            ArmAtomicOp::Umax => "umax",
+            ArmAtomicOp::Umin => "umin",
+        };
+        write!(f, "{}", s)
+    }
+}
+
+/// Addresses are always 64 bit registers.
+fn double(arm_register: &ArmRegister) -> ArmRegister {
+    ArmRegister::new(arm_register.name, ArmWidth::Double)
+}
+
+/// The 64 bit view of a register operand which is only a name, such as a
+/// branch target. `pc` has no view, so it is printed as itself for the
+/// assembler to reject.
+fn double_name(name: ArmRegisterName) -> String {
+    ArmRegister::try_new(name, ArmWidth::Double).map_or_else(|| "pc".to_string(), String::from)
+}
+
 impl Display for ArmVal {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
             ArmVal::Reg(arm_register) => arm_register.fmt(f),
             ArmVal::Imm(x) => write!(f, "{}", x),
-            ArmVal::RegOffset(arm_register, offset) => {
-                let double_reg = ArmRegister {
-                    name: arm_register.name,
-                    width: ArmWidth::Double,
+            ArmVal::RegOffset(arm_register, offset)
+            | ArmVal::UnscaledOffset(arm_register, offset) => {
+                write!(f, "[{}, {}]", double(arm_register), offset)
+            }
+            ArmVal::RegIndex(base, index) => {
+                write!(f, "[{}, {}]", double(base), double(index))
+            }
+            ArmVal::PreIndex(arm_register, offset) => {
+                write!(f, "[{}, {}]!", double(arm_register), offset)
+            }
+            ArmVal::PostIndex(arm_register, offset) => {
+                write!(f, "[{}], {}", double(arm_register), offset)
+            }
+            ArmVal::RelocOffset(arm_register, reloc) => {
+                write!(f, "[{}, {}]", double(arm_register), reloc)
+            }
+            ArmVal::Element(name, width, index) => {
+                let size = match width {
+                    ArmWidth::Double => 'd',
+                    _ => 's',
                 };
-                write!(f, "[{}, {}]", double_reg, offset)
-            }
-            ArmVal::LabelOffset(name, offset) => {
-                match offset {
-                    0 => write!(f, "{}", name),
-                    9998 => write!(f, "{}", name), // %hi in riscv is adrp with no offset in arm
-                    9999 => write!(f, ":lo12:{}", name), // reserved for 12 low bits of label addr
-                    _ => write!(f, "[{}, {}]", name, offset),
-                }
+                let number = name.number().expect("pc is not a register operand");
+                write!(f, "v{}.{}[{}]", number, size, index)
+            }
+            ArmVal::LabelOffset(name, offset) => match offset {
+                0 => write!(f, "{}", name),
+                _ => write!(f, "{}{:+}", name, offset),
+            },
+            ArmVal::Reloc(reloc, name, offset) => {
+                let prefix = match reloc {
+                    ArmReloc::Page => "",
+                    ArmReloc::Lo12 => ":lo12:",
+                    ArmReloc::GotPage => ":got:",
+                    ArmReloc::GotLo12 => ":got_lo12:",
+                };
+                write!(f, "{}", prefix)?;
+                ArmVal::LabelOffset(name.clone(), *offset).fmt(f)
             }
         }
     }
 }
 
-impl Into<ArmRegister> for ArmRegisterName {
-    fn into(self) -> ArmRegister {
-        ArmRegister {
-            width: ArmWidth::Double,
-            name: self,
-        }
+/// The 64 bit view of a register, which `pc` doesn't have.
+impl TryFrom<ArmRegisterName> for ArmRegister {
+    type Error = &'static str;
+
+    fn try_from(name: ArmRegisterName) -> Result<ArmRegister, Self::Error> {
+        ArmRegister::try_new(name, ArmWidth::Double).ok_or("pc can't be an operand")
     }
 }
@@ -0,0 +1,329 @@
+//! Rewrite immediates which ARM cannot encode.
+//!
+//! [`translate`](crate::translate::translate) maps RISC-V immediates
+//! straight to [`ArmVal::Imm`], but ARM instructions only encode some
+//! values: `add`/`sub`/`cmp`/`cmn` take a 12 bit unsigned immediate,
+//! optionally shifted left by 12, logical instructions take repeating bit
+//! patterns (see [`is_logical_imm`]), and `mov` takes whatever a single
+//! `movz`, `movn` or `orr` can build. [`legalize`] splits the other values
+//! across several instructions or moves them into the scratch register.
+//!
+//! Load and store offsets are legalized the same way, by picking an
+//! addressing mode which can encode the offset, and so are `adr` offsets
+//! beyond its 1MB reach.
+use crate::instruction::{ArmInstruction, ArmRegister, ArmRegisterName, ArmVal, ArmWidth};
+use crate::translate::SCRATCH;
+
+/// Make every immediate in `arm_instrs` encodable.
+///
+/// The scratch register is used for values which have to be moved into a
+/// register, so it must not be an operand of an instruction that has an
+/// immediate.
+pub fn legalize(arm_instrs: Vec<ArmInstruction>) -> Vec<ArmInstruction> {
+    arm_instrs.into_iter().flat_map(legalize_instr).collect()
+}
+
+fn legalize_instr(arm_instr: ArmInstruction) -> Vec<ArmInstruction> {
+    match arm_instr {
+        ArmInstruction::Mov {
+            dest,
+            src: ArmVal::Imm(imm),
+            ..
+        } => legalize_mov(dest, imm),
+        ArmInstruction::Add {
+            dest,
+            arg1,
+            arg2: ArmVal::Imm(imm),
+        } => legalize_add(dest, arg1, imm),
+        ArmInstruction::Sub {
+            dest,
+            arg1,
+            arg2: ArmVal::Imm(imm),
+        } => legalize_add(dest, arg1, imm.wrapping_neg()),
+        ArmInstruction::Cmp {
+            op1,
+            op2: ArmVal::Imm(imm),
+        } => legalize_cmp(op1, imm),
+        ArmInstruction::Cmn {
+            op1,
+            op2: ArmVal::Imm(imm),
+        } => legalize_cmp(op1, imm.wrapping_neg()),
+        ArmInstruction::And {
+            dest,
+            arg1,
+            arg2: ArmVal::Imm(imm),
+        } => legalize_logical(dest, arg1, imm, |dest, arg1, arg2| ArmInstruction::And {
+            dest,
+            arg1,
+            arg2,
+        }),
+        ArmInstruction::Orr {
+            dest,
+            arg1,
+            arg2: ArmVal::Imm(imm),
+        } => legalize_logical(dest, arg1, imm, |dest, arg1, arg2| ArmInstruction::Orr {
+            dest,
+            arg1,
+            arg2,
+        }),
+        ArmInstruction::Eor {
+            dest,
+            arg1,
+            arg2: ArmVal::Imm(imm),
+        } => legalize_logical(dest, arg1, imm, |dest, arg1, arg2| ArmInstruction::Eor {
+            dest,
+            arg1,
+            arg2,
+        }),
+        ArmInstruction::Ldr {
+            width,
+            dest,
+            src: ArmVal::RegOffset(base, offset),
+        } => {
+            let (mut instrs, src) = legalize_address(base, offset, width);
+            instrs.push(ArmInstruction::Ldr { width, dest, src });
+            instrs
+        }
+        ArmInstruction::Str {
+            width,
+            src,
+            dest: ArmVal::RegOffset(base, offset),
+        } => {
+            let (mut instrs, dest) = legalize_address(base, offset, width);
+            instrs.push(ArmInstruction::Str { width, src, dest });
+            instrs
+        }
+        ArmInstruction::Adr {
+            dest,
+            label: ArmVal::LabelOffset(label, offset),
+        } if label == "." && !(-(1 << 20)..1 << 20).contains(&offset) => {
+            let mut instrs = vec![ArmInstruction::Adr {
+                dest,
+                label: ArmVal::LabelOffset(label, 0),
+            }];
+            instrs.extend(legalize_add(dest, dest, offset.into()));
+            instrs
+        }
+        other => vec![other],
+    }
+}
+
+/// Pick an addressing mode for `[base, offset]` with an access of `width`.
+///
+/// The plain form only takes unsigned offsets scaled by the access size, so
+/// small negative or unaligned offsets use the unscaled form of `ldur` and
+/// `stur`, and anything else is moved into the scratch register as an
+/// index. The returned instructions have to come before the access.
+///
+/// Register 31 is `sp` as a base, so a RISC-V address relative to `zero`
+/// is moved into the scratch register whole.
+fn legalize_address(
+    base: ArmRegister,
+    offset: i32,
+    width: ArmWidth,
+) -> (Vec<ArmInstruction>, ArmVal) {
+    if let ArmRegisterName::Zero = base.name() {
+        let scratch = scratch(ArmWidth::Double);
+        return (
+            load_immediate(scratch, offset.into()),
+            ArmVal::RegOffset(scratch, 0),
+        );
+    }
+    let size = match width {
+        ArmWidth::Byte | ArmWidth::SignedByte => 1,
+        ArmWidth::Half | ArmWidth::SignedHalf => 2,
+        ArmWidth::Word | ArmWidth::SignedWord => 4,
+        ArmWidth::Double => 8,
+    };
+    if offset >= 0 && offset % size == 0 && offset / size < 4096 {
+        (vec![], ArmVal::RegOffset(base, offset))
+    } else if (-256..256).contains(&offset) {
+        (vec![], ArmVal::UnscaledOffset(base, offset))
+    } else {
+        let scratch = scratch(ArmWidth::Double);
+        (
+            legalize_mov(scratch, offset.into()),
+            ArmVal::RegIndex(base, scratch),
+        )
+    }
+}
+
+/// Writing a `w` register clears the upper half, so a 32 bit `mov` is the
+/// same as a 64 bit one of the zero extended value.
+fn legalize_mov(dest: ArmRegister, imm: i64) -> Vec<ArmInstruction> {
+    // a write to the zero register does nothing, and register 31 is `sp`
+    // in the `orr` of a logical immediate
+    if let ArmRegisterName::Zero = dest.name() {
+        return vec![];
+    }
+    match dest.width() {
+        ArmWidth::Double => load_immediate(dest, imm),
+        _ => load_immediate(
+            ArmRegister::new(dest.name(), ArmWidth::Double),
+            imm as u32 as i64,
+        ),
+    }
+}
+
+/// `dest = arg1 + imm` for `add` and `sub`, with `sub` as a negative `imm`.
+fn legalize_add(dest: ArmRegister, arg1: ArmRegister, imm: i64) -> Vec<ArmInstruction> {
+    // register 31 is `sp` in the immediate forms, so writes to the zero
+    // register are dropped and reads of it are a plain `mov`
+    if let ArmRegisterName::Zero = dest.name() {
+        return vec![];
+    }
+    if let ArmRegisterName::Zero = arg1.name() {
+        return legalize_mov(dest, imm);
+    }
+
+    let op = |dest, arg1, arg2| {
+        if imm < 0 {
+            ArmInstruction::Sub { dest, arg1, arg2 }
+        } else {
+            ArmInstruction::Add { dest, arg1, arg2 }
+        }
+    };
+    let magnitude = imm.unsigned_abs();
+    if is_arith_imm(magnitude) {
+        vec![op(dest, arg1, ArmVal::Imm(magnitude as i64))]
+    } else if magnitude < 1 << 24 {
+        // the upper 12 bits as a shifted immediate, then the lower 12
+        vec![
+            op(dest, arg1, ArmVal::Imm((magnitude & !0xfff) as i64)),
+            op(dest, dest, ArmVal::Imm((magnitude & 0xfff) as i64)),
+        ]
+    } else {
+        let scratch = scratch(dest.width());
+        let mut instrs = legalize_mov(scratch, magnitude as i64);
+        instrs.push(op(dest, arg1, ArmVal::Reg(scratch)));
+        instrs
+    }
+}
+
+/// Set the flags for `op1 - imm`, for `cmp` and `cmn` with `cmn` as a
+/// negative `imm`.
+fn legalize_cmp(op1: ArmRegister, imm: i64) -> Vec<ArmInstruction> {
+    let magnitude = imm.unsigned_abs();
+    // register 31 is `sp` in the immediate forms
+    if matches!(op1.name(), ArmRegisterName::Zero) || !is_arith_imm(magnitude) {
+        let scratch = scratch(op1.width());
+        let mut instrs = legalize_mov(scratch, imm);
+        instrs.push(ArmInstruction::Cmp {
+            op1,
+            op2: ArmVal::Reg(scratch),
+        });
+        instrs
+    } else if imm < 0 {
+        vec![ArmInstruction::Cmn {
+            op1,
+            op2: ArmVal::Imm(magnitude as i64),
+        }]
+    } else {
+        vec![ArmInstruction::Cmp {
+            op1,
+            op2: ArmVal::Imm(imm),
+        }]
+    }
+}
+
+/// `and`, `orr` and `eor`, with `op` building the instruction.
+fn legalize_logical(
+    dest: ArmRegister,
+    arg1: ArmRegister,
+    imm: i64,
+    op: fn(ArmRegister, ArmRegister, ArmVal) -> ArmInstruction,
+) -> Vec<ArmInstruction> {
+    // register 31 is `sp` as the destination of the immediate forms
+    if let ArmRegisterName::Zero = dest.name() {
+        return vec![];
+    }
+    if is_logical_imm(imm as u64, dest.width()) {
+        return vec![op(dest, arg1, ArmVal::Imm(imm))];
+    }
+    let scratch = scratch(dest.width());
+    let mut instrs = legalize_mov(scratch, imm);
+    instrs.push(op(dest, arg1, ArmVal::Reg(scratch)));
+    instrs
+}
+
+fn scratch(width: ArmWidth) -> ArmRegister {
+    ArmRegister::new(SCRATCH, width)
+}
+
+/// Whether `value` can be the immediate of `add`, `sub`, `cmp` or `cmn`:
+/// 12 bits, optionally shifted left by 12.
+pub(crate) fn is_arith_imm(value: u64) -> bool {
+    value < 1 << 12 || (value & 0xfff == 0 && value < 1 << 24)
+}
+
+/// Whether `value` can be the immediate of an ARM logical instruction on
+/// registers of `width`: a 2, 4, 8, 16, 32 or 64 bit element repeated
+/// across the register, whose set bits are a single run once rotated. All
+/// zeros and all ones are not encodable.
+pub(crate) fn is_logical_imm(value: u64, width: ArmWidth) -> bool {
+    let value = match width {
+        ArmWidth::Double => value,
+        // only the low half is used, so repeat it
+        _ => (value & 0xffff_ffff) | value << 32,
+    };
+    let mut size = 64;
+    while size > 2 {
+        let half = size / 2;
+        let mask = (1u64 << half) - 1;
+        if value & mask != (value >> half) & mask {
+            break;
+        }
+        size = half;
+    }
+    let mask = u64::MAX >> (64 - size);
+    let element = value & mask;
+    let rotated = ((element >> 1) | (element << (size - 1))) & mask;
+    // a single run of ones has exactly two edges going around the element
+    (element ^ rotated).count_ones() == 2
+}
+
+/// Move any 64 bit constant into `dest`, in as few instructions as we can.
+///
+/// The first instruction is a `mov`, which assembles to a `movz`, `movn`,
+/// or an `orr` with a logical immediate. It is followed by a `movk` for
+/// each 16 bit chunk that it got wrong.
+fn load_immediate(dest: ArmRegister, value: i64) -> Vec<ArmInstruction> {
+    let value = value as u64;
+    let chunk = |bits: u64, i: u32| (bits >> (16 * i)) & 0xffff;
+
+    // `movz` and `movn` get one chunk right on top of all zeros or all ones
+    let mut candidates = vec![];
+    for i in 0..4 {
+        candidates.push(chunk(value, i) << (16 * i));
+        candidates.push(!(0xffff << (16 * i)) | chunk(value, i) << (16 * i));
+    }
+    // logical immediates are repeating patterns, so try repeating the
+    // parts of `value` which would be the pattern
+    let low = value & 0xffff_ffff;
+    let high = value >> 32;
+    let mut patterns = vec![value, low | low << 32, high | high << 32];
+    patterns.extend((0..4).map(|i| chunk(value, i) * 0x0001_0001_0001_0001));
+    candidates.extend(
+        patterns
+            .into_iter()
+            .filter(|&bits| is_logical_imm(bits, ArmWidth::Double)),
+    );
+
+    let wrong_chunks = |bits: u64| (0..4).filter(move |&i| chunk(bits, i) != chunk(value, i));
+    let first = candidates
+        .into_iter()
+        .min_by_key(|&bits| wrong_chunks(bits).count())
+        .unwrap();
+
+    let mut instrs = vec![ArmInstruction::Mov {
+        width: ArmWidth::Double,
+        dest,
+        src: ArmVal::Imm(first as i64),
+    }];
+    instrs.extend(wrong_chunks(first).map(|i| ArmInstruction::Movk {
+        dest,
+        imm: chunk(value, i) as u16,
+        shift: 16 * i,
+    }));
+    instrs
+}
@@ -1,3 +1,13 @@
+pub mod compressed;
+pub mod decode;
+pub mod elf;
+pub mod encode;
+pub mod error;
+pub mod executable;
+pub mod float;
 pub mod instruction;
+pub mod legalize;
+pub mod parse;
+pub mod pcrel;
 pub mod translate;
 pub mod utils;
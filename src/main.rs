@@ -1,19 +1,244 @@
 use std::fs;
-use std::str::FromStr;
-pub mod instruction;
-pub mod translate;
-pub mod utils;
-use instruction::RiscVInstruction;
-use translate::binary_translate;
-
-// Samir: I am using main for testing, but it not needed since you can run
-// `cargo test` instead.
+use std::io::{self, Read, Write};
+use std::process::exit;
+
+use binary_room::elf::read_elf;
+use binary_room::error::AsmError;
+use binary_room::instruction::RiscVInstruction;
+use binary_room::parse::parse_asm_lines;
+use binary_room::translate::{
+    binary_translate, binary_translate_executable, translate_program, translate_program_executable,
+    Dialect, Prologue, TranslateOptions,
+};
+
+const USAGE: &str = "\
+Binary translator from RISC-V to ARM
+
+Usage:
+    binary-room translate [OPTIONS] [INPUT] [-o OUTPUT]   translate RISC-V assembly to AArch64 assembly
+    binary-room parse [INPUT] [-o OUTPUT]                 dump the parsed RISC-V instructions
+    binary-room check [INPUT]                             list unsupported instructions
+
+INPUT is RISC-V assembly, or an RV64 ELF object file or static executable.
+INPUT and OUTPUT default to stdin and stdout, and `-` means the same.
+
+Translate options:
+    --prologue none|start|loop   entry point to emit before the program (default: none)
+    --dialect gnu|apple          assembler syntax of the output (default: gnu)
+    --annotate                   comment each translation with its RISC-V source line
+    --elide-sext                 drop `sext.w` of a register that was just sign extended
+    --lse                        use ARMv8.1 LSE atomics rather than ldxr/stxr loops
+    --executable                 write a static AArch64 Linux executable rather than
+                                 assembly, which needs a prologue or a `_start`
+
+Exit status is 0 on success, 1 if the input has unsupported or invalid
+instructions, and 2 for usage or I/O errors.";
+
+/// Input was read but could not be parsed or translated.
+const EXIT_INVALID_INPUT: i32 = 1;
+/// Bad arguments, or a file could not be read or written.
+const EXIT_USAGE: i32 = 2;
+
+#[derive(Debug, PartialEq)]
+enum Command {
+    Translate,
+    Parse,
+    Check,
+}
+
+struct Args {
+    command: Command,
+    input: Option<String>,
+    output: Option<String>,
+    options: TranslateOptions,
+    /// Assemble the translation into an executable
+    executable: bool,
+}
+
 fn main() {
-    // Hard code the arguments for now.
-    let path = "../test/binaries/hello_world.s";
-    let output_path = "../test/binaries/hello_world_translated.s";
-    let riscv_asm = fs::read_to_string(path).expect("Unable to read file");
+    let args = parse_args(std::env::args().skip(1)).unwrap_or_else(|reason| {
+        eprintln!("error: {}\n\n{}", reason, USAGE);
+        exit(EXIT_USAGE);
+    });
+
+    let input = read_input(args.input.as_deref()).unwrap_or_else(|error| {
+        eprintln!("error: unable to read input: {}", error);
+        exit(EXIT_USAGE);
+    });
+    let input_name = args.input.as_deref().unwrap_or("<stdin>");
+
+    let result = if input.starts_with(b"\x7fELF") {
+        run_elf(&args, &input)
+    } else {
+        match String::from_utf8(input) {
+            Ok(riscv_asm) => run_asm(&args, &riscv_asm),
+            Err(_) => {
+                eprintln!("error: unable to read input: not UTF-8 text or an ELF file");
+                exit(EXIT_USAGE);
+            }
+        }
+    };
+
+    match result {
+        Ok(output) => {
+            if let Err(error) = write_output(args.output.as_deref(), &output, args.executable) {
+                eprintln!("error: unable to write output: {}", error);
+                exit(EXIT_USAGE);
+            }
+        }
+        Err(errors) => {
+            for error in errors {
+                let message = format!("{}:{}", input_name, error);
+                if args.command == Command::Check {
+                    println!("{}", message);
+                } else {
+                    eprintln!("{}", message);
+                }
+            }
+            exit(EXIT_INVALID_INPUT);
+        }
+    }
+}
+
+fn parse_args(mut args: impl Iterator<Item = String>) -> Result<Args, String> {
+    let command = match args.next().as_deref() {
+        Some("translate") => Command::Translate,
+        Some("parse") => Command::Parse,
+        Some("check") => Command::Check,
+        Some("-h" | "--help") => {
+            println!("{}", USAGE);
+            exit(0);
+        }
+        Some(other) => return Err(format!("unknown command `{}`", other)),
+        None => return Err("missing command".to_string()),
+    };
+
+    let mut input = None;
+    let mut output = None;
+    let mut options = TranslateOptions::default();
+    let mut executable = false;
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--prologue" if command == Command::Translate => {
+                options.prologue = match args.next().as_deref() {
+                    Some("none") => Prologue::None,
+                    Some("start") => Prologue::Start,
+                    Some("loop") => Prologue::LoopStart,
+                    _ => return Err("`--prologue` must be none, start or loop".to_string()),
+                };
+            }
+            "--dialect" if command == Command::Translate => {
+                options.dialect = match args.next().as_deref() {
+                    Some("gnu") => Dialect::Gnu,
+                    Some("apple") => Dialect::Apple,
+                    _ => return Err("`--dialect` must be gnu or apple".to_string()),
+                };
+            }
+            "--annotate" if command == Command::Translate => options.annotate = true,
+            "--elide-sext" if command == Command::Translate => options.elide_sign_extensions = true,
+            "--lse" if command == Command::Translate => options.lse = true,
+            "--executable" if command == Command::Translate => executable = true,
+            "-o" | "--output" if command != Command::Check => {
+                let path = args.next().ok_or("missing path after `-o`")?;
+                output = Some(path);
+            }
+            "-h" | "--help" => {
+                println!("{}", USAGE);
+                exit(0);
+            }
+            _ if arg.starts_with('-') && arg != "-" => {
+                return Err(format!("unknown option `{}`", arg))
+            }
+            _ if input.is_none() => input = Some(arg),
+            _ => return Err(format!("unexpected argument `{}`", arg)),
+        }
+    }
+
+    if executable && options.dialect != Dialect::Gnu {
+        return Err("`--executable` is for Linux, which needs `--dialect gnu`".to_string());
+    }
+
+    Ok(Args {
+        command,
+        input: input.filter(|path| path != "-"),
+        output: output.filter(|path| path != "-"),
+        options,
+        executable,
+    })
+}
+
+fn run_asm(args: &Args, riscv_asm: &str) -> Result<Vec<u8>, Vec<AsmError>> {
+    match args.command {
+        Command::Translate if args.executable => {
+            binary_translate_executable(riscv_asm, &args.options)
+        }
+        Command::Translate => binary_translate(riscv_asm, &args.options).map(String::into_bytes),
+        Command::Parse => dump_asm(riscv_asm).map(String::into_bytes),
+        Command::Check => binary_translate(riscv_asm, &args.options).map(|_| vec![]),
+    }
+}
+
+fn run_elf(args: &Args, bytes: &[u8]) -> Result<Vec<u8>, Vec<AsmError>> {
+    let riscv_instrs = read_elf(bytes)
+        .map_err(|error| vec![error])?
+        .instructions()?;
+    match args.command {
+        Command::Translate if args.executable => {
+            translate_program_executable(riscv_instrs, &args.options)
+        }
+        Command::Translate => {
+            translate_program(riscv_instrs, &args.options).map(String::into_bytes)
+        }
+        Command::Parse => Ok(dump((1..).zip(riscv_instrs)).into_bytes()),
+        Command::Check => translate_program(riscv_instrs, &args.options).map(|_| vec![]),
+    }
+}
+
+fn read_input(path: Option<&str>) -> io::Result<Vec<u8>> {
+    match path {
+        Some(path) => fs::read(path),
+        None => {
+            let mut input = vec![];
+            io::stdin().read_to_end(&mut input)?;
+            Ok(input)
+        }
+    }
+}
+
+fn write_output(path: Option<&str>, contents: &[u8], executable: bool) -> io::Result<()> {
+    match path {
+        Some(path) => {
+            fs::write(path, contents)?;
+            if executable {
+                set_executable(path)?;
+            }
+            Ok(())
+        }
+        None => io::stdout().write_all(contents),
+    }
+}
+
+#[cfg(unix)]
+fn set_executable(path: &str) -> io::Result<()> {
+    use std::os::unix::fs::PermissionsExt;
+    fs::set_permissions(path, fs::Permissions::from_mode(0o755))
+}
+
+#[cfg(not(unix))]
+fn set_executable(_path: &str) -> io::Result<()> {
+    Ok(())
+}
+
+/// Dump the parsed instructions, one per line.
+fn dump_asm(riscv_asm: &str) -> Result<String, Vec<AsmError>> {
+    Ok(dump(parse_asm_lines(riscv_asm)?))
+}
 
-    let translated_asm = binary_translate(&riscv_asm);
-    fs::write(output_path, translated_asm).expect("Unable to write file");
+/// Each instruction after its line, or its position in an ELF file.
+fn dump(riscv_instrs: impl IntoIterator<Item = (usize, RiscVInstruction)>) -> String {
+    let mut contents = String::new();
+    for (line, instr) in riscv_instrs {
+        contents.push_str(&format!("{}: {:?}\n", line, instr));
+    }
+    contents
 }
@@ -0,0 +1,1203 @@
+//! This file parses GNU-syntax RISC-V assembly (the output of `gcc -S`)
+//! into our [`RiscVInstruction`] enum.
+//!
+//! Each line is handled on its own. A line can contain any number of
+//! `label:` definitions, followed by either an assembler directive
+//! (`.name operands`) or a single instruction. Everything after a `#`
+//! which is not inside a string literal is a comment.
+//!
+//! GNU assembler syntax reference
+//! https://sourceware.org/binutils/docs/as/RISC_002dV_002dDirectives.html
+//! https://github.com/riscv-non-isa/riscv-asm-manual/blob/main/src/asm-manual.adoc
+
+use std::str::FromStr;
+
+use crate::error::AsmError;
+use crate::instruction::{
+    RiscVAmoOp, RiscVFRegister, RiscVFenceSet, RiscVInstruction, RiscVOrdering, RiscVPrecision,
+    RiscVRegister, RiscVReloc, RiscVRounding, RiscVVal, RiscVWidth,
+};
+
+/// Why part of a line was rejected. `text` is always a slice of the line
+/// being parsed, so we can recover its column.
+struct LineError<'a> {
+    text: &'a str,
+    reason: String,
+}
+
+type LineResult<'a, T> = Result<T, LineError<'a>>;
+
+fn error<'a, T>(text: &'a str, reason: impl Into<String>) -> LineResult<'a, T> {
+    Err(LineError {
+        text,
+        reason: reason.into(),
+    })
+}
+
+/// Parse a text file into our enum.
+///
+/// Every line is parsed even after an error, so all unsupported constructs
+/// are reported at once.
+pub fn parse_asm(asm: &str) -> Result<Vec<RiscVInstruction>, Vec<AsmError>> {
+    let instrs = parse_asm_lines(asm)?;
+    Ok(instrs.into_iter().map(|(_, instr)| instr).collect())
+}
+
+/// Like [`parse_asm`], but pairs every instruction with its 1-based source
+/// line number.
+pub fn parse_asm_lines(asm: &str) -> Result<Vec<(usize, RiscVInstruction)>, Vec<AsmError>> {
+    let (instrs, errors) = parse_asm_partial(asm);
+    if errors.is_empty() {
+        Ok(instrs)
+    } else {
+        Err(errors)
+    }
+}
+
+/// Parse every line we can, returning the instructions of the lines which
+/// parsed alongside the errors for the lines which did not.
+pub fn parse_asm_partial(asm: &str) -> (Vec<(usize, RiscVInstruction)>, Vec<AsmError>) {
+    let mut instrs = vec![];
+    let mut errors = vec![];
+    for (i, line) in asm.lines().enumerate() {
+        let mut parsed = vec![];
+        match parse_line(line, &mut parsed) {
+            Ok(()) => instrs.extend(parsed.into_iter().map(|instr| (i + 1, instr))),
+            Err(LineError { text, reason }) => {
+                let column = text.as_ptr() as usize - line.as_ptr() as usize + 1;
+                errors.push(AsmError::new(i + 1, column, text, reason));
+            }
+        }
+    }
+    (instrs, errors)
+}
+
+/// Parse one line of assembly, appending whatever it defines to `instrs`.
+fn parse_line<'a>(line: &'a str, instrs: &mut Vec<RiscVInstruction>) -> LineResult<'a, ()> {
+    let mut rest = strip_comment(line).trim();
+
+    while let Some((name, after)) = split_label(rest) {
+        instrs.push(RiscVInstruction::Label {
+            name: name.to_string(),
+        });
+        rest = after.trim_start();
+    }
+
+    if rest.is_empty() {
+        return Ok(());
+    }
+
+    if let Some(directive) = rest.strip_prefix('.') {
+        let (name, operands) = split_mnemonic(directive);
+        instrs.push(RiscVInstruction::Directive {
+            name: name.to_string(),
+            operands: operands.to_string(),
+        });
+        return Ok(());
+    }
+
+    let (mnemonic, operands) = split_mnemonic(rest);
+    let operands: Vec<&str> = if operands.is_empty() {
+        vec![]
+    } else {
+        operands.split(',').map(str::trim).collect()
+    };
+    instrs.push(parse_instruction(rest, mnemonic, &operands)?);
+    Ok(())
+}
+
+/// Map a mnemonic and its operands to an instruction. `text` is the whole
+/// instruction, used for errors that are not about a single operand.
+fn parse_instruction<'a>(
+    text: &'a str,
+    mnemonic: &'a str,
+    operands: &[&'a str],
+) -> LineResult<'a, RiscVInstruction> {
+    let counts = OPERAND_COUNTS
+        .iter()
+        .find(|(mnemonics, _)| mnemonics.contains(&mnemonic));
+    if let Some((_, counts)) = counts {
+        if !counts.contains(&operands.len()) {
+            return error(text, format!("wrong number of operands for `{}`", mnemonic));
+        }
+    }
+    if let Some(instr) = match_instruction(mnemonic, operands)? {
+        return Ok(instr);
+    }
+    if let Some((name, width, ordering)) = split_atomic(mnemonic) {
+        return parse_atomic(text, mnemonic, name, width, ordering, operands);
+    }
+    if let Some((name, precision)) = split_float(mnemonic) {
+        return parse_float(text, mnemonic, name, precision, operands);
+    }
+    error(mnemonic, "unsupported instruction")
+}
+
+/// The numbers of operands which the mnemonics of [`match_instruction`]
+/// take, to tell a wrong number of operands from an unsupported
+/// instruction.
+const OPERAND_COUNTS: &[(&[&str], &[usize])] = &[
+    (
+        &["ret", "fence.tso", "fence.i", "pause", "ecall", "nop"],
+        &[0],
+    ),
+    (&["fence"], &[0, 2]),
+    (&["call", "tail", "j", "jr"], &[1]),
+    (&["jal"], &[1, 2]),
+    (&["jalr"], &[1, 2, 3]),
+    (
+        &[
+            "not", "neg", "negw", "seqz", "snez", "sltz", "sgtz", "beqz", "bnez", "bltz", "bgez",
+            "bgtz", "blez", "sb", "sh", "sw", "sd", "lb", "lbu", "lh", "lhu", "lw", "lwu", "ld",
+            "lui", "auipc", "lla", "la", "mv", "sext.w", "li", "flw", "fld", "fsw", "fsd",
+        ],
+        &[2],
+    ),
+    (
+        &[
+            "addi", "addiw", "add", "addw", "sub", "subw", "mul", "mulw", "div", "divw", "divu",
+            "divuw", "rem", "remw", "remu", "remuw", "mulh", "mulhu", "mulhsu", "and", "or", "xor",
+            "andi", "ori", "xori", "sll", "sllw", "srl", "srlw", "sra", "sraw", "slli", "slliw",
+            "srli", "srliw", "srai", "sraiw", "slt", "sltu", "sgt", "sgtu", "slti", "sltiu", "beq",
+            "bne", "blt", "bge", "bgt", "ble", "bltu", "bgeu", "bgtu", "bleu",
+        ],
+        &[3],
+    ),
+];
+
+/// [`parse_instruction`] for the mnemonics which aren't atomics or
+/// floating point arithmetic, or `None` if no instruction has `mnemonic`
+/// and this many operands.
+fn match_instruction<'a>(
+    mnemonic: &'a str,
+    operands: &[&'a str],
+) -> LineResult<'a, Option<RiscVInstruction>> {
+    let instr = match (mnemonic, operands) {
+        ("addi", [dest, src, imm]) if is_reloc(imm) => {
+            let (reloc, label, offset) = parse_lo(imm)?;
+            RiscVInstruction::Addl {
+                dest: parse_register(dest)?,
+                src: parse_register(src)?,
+                label: RiscVVal::Reloc {
+                    reloc,
+                    label,
+                    offset,
+                },
+            }
+        }
+        ("addi", [dest, src, imm]) => RiscVInstruction::Addi {
+            dest: parse_register(dest)?,
+            src: parse_register(src)?,
+            imm: parse_imm12(imm)?,
+        },
+        ("addiw", [dest, src, imm]) => RiscVInstruction::Addiw {
+            dest: parse_register(dest)?,
+            src: parse_register(src)?,
+            imm: parse_imm12(imm)?,
+        },
+        ("add" | "addw", [dest, arg1, arg2]) => RiscVInstruction::Add {
+            width: word_if(mnemonic == "addw"),
+            dest: parse_register(dest)?,
+            arg1: parse_register(arg1)?,
+            arg2: parse_register(arg2)?,
+        },
+        ("sub" | "subw", [dest, arg1, arg2]) => RiscVInstruction::Sub {
+            width: word_if(mnemonic == "subw"),
+            dest: parse_register(dest)?,
+            arg1: parse_register(arg1)?,
+            arg2: parse_register(arg2)?,
+        },
+        (
+            "mul" | "mulw" | "div" | "divw" | "divu" | "divuw" | "rem" | "remw" | "remu" | "remuw",
+            [dest, arg1, arg2],
+        ) => {
+            let width = word_if(mnemonic.ends_with('w'));
+            let dest = parse_register(dest)?;
+            let arg1 = parse_register(arg1)?;
+            let arg2 = parse_register(arg2)?;
+            match mnemonic.trim_end_matches('w') {
+                "mul" => RiscVInstruction::Mul {
+                    width,
+                    dest,
+                    arg1,
+                    arg2,
+                },
+                "div" => RiscVInstruction::Div {
+                    width,
+                    dest,
+                    arg1,
+                    arg2,
+                },
+                "divu" => RiscVInstruction::Divu {
+                    width,
+                    dest,
+                    arg1,
+                    arg2,
+                },
+                "rem" => RiscVInstruction::Rem {
+                    width,
+                    dest,
+                    arg1,
+                    arg2,
+                },
+                _ => RiscVInstruction::Remu {
+                    width,
+                    dest,
+                    arg1,
+                    arg2,
+                },
+            }
+        }
+        ("mulh" | "mulhu" | "mulhsu", [dest, arg1, arg2]) => {
+            let dest = parse_register(dest)?;
+            let arg1 = parse_register(arg1)?;
+            let arg2 = parse_register(arg2)?;
+            match mnemonic {
+                "mulh" => RiscVInstruction::Mulh { dest, arg1, arg2 },
+                "mulhu" => RiscVInstruction::Mulhu { dest, arg1, arg2 },
+                _ => RiscVInstruction::Mulhsu { dest, arg1, arg2 },
+            }
+        }
+        ("and" | "or" | "xor", [dest, arg1, arg2]) => {
+            let dest = parse_register(dest)?;
+            let arg1 = parse_register(arg1)?;
+            let arg2 = parse_register(arg2)?;
+            match mnemonic {
+                "and" => RiscVInstruction::And { dest, arg1, arg2 },
+                "or" => RiscVInstruction::Or { dest, arg1, arg2 },
+                _ => RiscVInstruction::Xor { dest, arg1, arg2 },
+            }
+        }
+        ("andi" | "ori" | "xori", [dest, src, imm]) => {
+            let dest = parse_register(dest)?;
+            let src = parse_register(src)?;
+            let imm = parse_imm12(imm)?;
+            match mnemonic {
+                "andi" => RiscVInstruction::Andi { dest, src, imm },
+                "ori" => RiscVInstruction::Ori { dest, src, imm },
+                _ => RiscVInstruction::Xori { dest, src, imm },
+            }
+        }
+        ("not", [dest, src]) => RiscVInstruction::Not {
+            dest: parse_register(dest)?,
+            src: parse_register(src)?,
+        },
+        ("neg" | "negw", [dest, src]) => RiscVInstruction::Neg {
+            width: word_if(mnemonic == "negw"),
+            dest: parse_register(dest)?,
+            src: parse_register(src)?,
+        },
+        ("sll" | "sllw" | "srl" | "srlw" | "sra" | "sraw", [dest, arg1, arg2]) => {
+            let width = word_if(mnemonic.ends_with('w'));
+            let dest = parse_register(dest)?;
+            let arg1 = parse_register(arg1)?;
+            let arg2 = parse_register(arg2)?;
+            match mnemonic.trim_end_matches('w') {
+                "sll" => RiscVInstruction::Sll {
+                    width,
+                    dest,
+                    arg1,
+                    arg2,
+                },
+                "srl" => RiscVInstruction::Srl {
+                    width,
+                    dest,
+                    arg1,
+                    arg2,
+                },
+                _ => RiscVInstruction::Sra {
+                    width,
+                    dest,
+                    arg1,
+                    arg2,
+                },
+            }
+        }
+        ("slli" | "slliw" | "srli" | "srliw" | "srai" | "sraiw", [dest, src, imm]) => {
+            let width = word_if(mnemonic.ends_with('w'));
+            let dest = parse_register(dest)?;
+            let src = parse_register(src)?;
+            let imm = parse_imm(imm)?;
+            match mnemonic.trim_end_matches('w') {
+                "slli" => RiscVInstruction::Slli {
+                    width,
+                    dest,
+                    src,
+                    imm,
+                },
+                "srli" => RiscVInstruction::Srli {
+                    width,
+                    dest,
+                    src,
+                    imm,
+                },
+                _ => RiscVInstruction::Srai {
+                    width,
+                    dest,
+                    src,
+                    imm,
+                },
+            }
+        }
+        ("slt" | "sltu" | "sgt" | "sgtu", [dest, arg1, arg2]) => {
+            let dest = parse_register(dest)?;
+            let mut arg1 = parse_register(arg1)?;
+            let mut arg2 = parse_register(arg2)?;
+            // `sgt rd, rs, rt` is `slt rd, rt, rs`
+            if mnemonic.starts_with("sgt") {
+                std::mem::swap(&mut arg1, &mut arg2);
+            }
+            match mnemonic {
+                "slt" | "sgt" => RiscVInstruction::Slt { dest, arg1, arg2 },
+                _ => RiscVInstruction::Sltu { dest, arg1, arg2 },
+            }
+        }
+        ("slti" | "sltiu", [dest, src, imm]) => {
+            let dest = parse_register(dest)?;
+            let src = parse_register(src)?;
+            let imm = parse_imm12(imm)?;
+            match mnemonic {
+                "slti" => RiscVInstruction::Slti { dest, src, imm },
+                _ => RiscVInstruction::Sltiu { dest, src, imm },
+            }
+        }
+        ("seqz" | "snez" | "sltz" | "sgtz", [dest, src]) => {
+            let dest = parse_register(dest)?;
+            let src = parse_register(src)?;
+            match mnemonic {
+                "seqz" => RiscVInstruction::Seqz { dest, src },
+                "snez" => RiscVInstruction::Snez { dest, src },
+                "sltz" => RiscVInstruction::Sltz { dest, src },
+                _ => RiscVInstruction::Sgtz { dest, src },
+            }
+        }
+        (
+            "beq" | "bne" | "blt" | "bge" | "bgt" | "ble" | "bltu" | "bgeu" | "bgtu" | "bleu",
+            [arg1, arg2, target],
+        ) => {
+            let arg1 = parse_register(arg1)?;
+            let arg2 = parse_register(arg2)?;
+            let target = parse_label(target)?;
+            branch(mnemonic, arg1, arg2, target)
+        }
+        // compare against zero, e.g. `beqz rs, offset` is `beq rs, x0, offset`
+        ("beqz" | "bnez" | "bltz" | "bgez" | "bgtz" | "blez", [arg, target]) => {
+            let arg = parse_register(arg)?;
+            let target = parse_label(target)?;
+            branch(
+                mnemonic.trim_end_matches('z'),
+                arg,
+                RiscVRegister::X0,
+                target,
+            )
+        }
+        ("call", [label]) => RiscVInstruction::Call {
+            label: parse_jump_target(label)?,
+        },
+        ("tail", [label]) => RiscVInstruction::J {
+            target: parse_jump_target(label)?,
+        },
+        ("sb" | "sh" | "sw" | "sd", [src, dest]) => RiscVInstruction::S {
+            width: access_width(mnemonic),
+            src: parse_register(src)?,
+            dest: parse_offset(dest)?,
+        },
+        ("lb" | "lbu" | "lh" | "lhu" | "lw" | "lwu" | "ld", [dest, src]) => RiscVInstruction::L {
+            width: access_width(mnemonic),
+            dest: parse_register(dest)?,
+            src: parse_offset(src)?,
+        },
+        ("lui", [dest, src]) if is_reloc(src) => RiscVInstruction::Lui {
+            dest: parse_register(dest)?,
+            src: parse_hi(src)?,
+        },
+        ("lui", [dest, src]) => RiscVInstruction::Lui {
+            dest: parse_register(dest)?,
+            src: RiscVVal::Immediate(parse_upper_imm(src)?),
+        },
+        ("auipc", [dest, src]) if is_reloc(src) => RiscVInstruction::Auipc {
+            dest: parse_register(dest)?,
+            src: parse_pcrel_hi(src)?,
+        },
+        ("auipc", [dest, src]) => RiscVInstruction::Auipc {
+            dest: parse_register(dest)?,
+            src: RiscVVal::Immediate(parse_upper_imm(src)?),
+        },
+        ("lla", [dest, label]) => RiscVInstruction::Lla {
+            dest: parse_register(dest)?,
+            label: parse_label(label)?,
+        },
+        ("la", [dest, label]) => RiscVInstruction::La {
+            dest: parse_register(dest)?,
+            label: parse_label(label)?,
+        },
+        ("mv", [dest, src]) => RiscVInstruction::Mv {
+            dest: parse_register(dest)?,
+            src: parse_register(src)?,
+        },
+        ("sext.w", [dest, src]) => RiscVInstruction::SextW {
+            dest: parse_register(dest)?,
+            src: parse_register(src)?,
+        },
+        ("j", [target]) => RiscVInstruction::J {
+            target: parse_label(target)?,
+        },
+        ("jr", [target]) => RiscVInstruction::Jr {
+            target: parse_register(target)?,
+        },
+        ("jal", [target]) => RiscVInstruction::Jal {
+            dest: RiscVRegister::RA,
+            target: parse_jump_target(target)?,
+        },
+        ("jal", [dest, target]) => RiscVInstruction::Jal {
+            dest: parse_register(dest)?,
+            target: parse_jump_target(target)?,
+        },
+        // binutils also takes `jalr offset(rs)`, linking to `ra`
+        ("jalr", [target]) if target.ends_with(')') => {
+            let (target, offset) = parse_base_offset(target)?;
+            RiscVInstruction::Jalr {
+                dest: RiscVRegister::RA,
+                target,
+                offset,
+            }
+        }
+        ("jalr", [target]) => RiscVInstruction::Jalr {
+            dest: RiscVRegister::RA,
+            target: parse_register(target)?,
+            offset: 0,
+        },
+        ("jalr", [dest, target]) if target.ends_with(')') => {
+            let (target, offset) = parse_base_offset(target)?;
+            RiscVInstruction::Jalr {
+                dest: parse_register(dest)?,
+                target,
+                offset,
+            }
+        }
+        ("jalr", [dest, target]) => RiscVInstruction::Jalr {
+            dest: parse_register(dest)?,
+            target: parse_register(target)?,
+            offset: 0,
+        },
+        ("jalr", [dest, target, offset]) => RiscVInstruction::Jalr {
+            dest: parse_register(dest)?,
+            target: parse_register(target)?,
+            offset: parse_imm12(offset)?,
+        },
+        // `ret` is `jalr x0, 0(ra)`, the same as `jr ra`
+        ("ret", []) => RiscVInstruction::Jr {
+            target: RiscVRegister::RA,
+        },
+        ("li", [dest, imm]) => RiscVInstruction::Li {
+            dest: parse_register(dest)?,
+            imm: parse_imm64(imm)?,
+        },
+        ("fence", []) => RiscVInstruction::Fence {
+            pred: RiscVFenceSet::ALL,
+            succ: RiscVFenceSet::ALL,
+        },
+        ("fence", [pred, succ]) => RiscVInstruction::Fence {
+            pred: parse_fence_set(pred)?,
+            succ: parse_fence_set(succ)?,
+        },
+        ("flw" | "fld", [dest, src]) => RiscVInstruction::FL {
+            precision: float_access_precision(mnemonic),
+            dest: parse_fregister(dest)?,
+            src: parse_offset(src)?,
+        },
+        ("fsw" | "fsd", [src, dest]) => RiscVInstruction::FS {
+            precision: float_access_precision(mnemonic),
+            src: parse_fregister(src)?,
+            dest: parse_offset(dest)?,
+        },
+        ("fence.tso", []) => RiscVInstruction::FenceTso,
+        ("fence.i", []) => RiscVInstruction::FenceI,
+        ("pause", []) => RiscVInstruction::Pause,
+        ("ecall", []) => RiscVInstruction::ECall,
+        ("nop", []) => RiscVInstruction::Nop,
+        _ => return Ok(None),
+    };
+    Ok(Some(instr))
+}
+
+/// Parse `lr`, `sc` or an AMO, once [`split_atomic`] has taken `name` and
+/// the suffixes off `mnemonic`.
+fn parse_atomic<'a>(
+    text: &'a str,
+    mnemonic: &str,
+    name: &str,
+    width: RiscVWidth,
+    ordering: RiscVOrdering,
+    operands: &[&'a str],
+) -> LineResult<'a, RiscVInstruction> {
+    let instr = match (name, RiscVAmoOp::from_str(name), operands) {
+        ("lr", _, [dest, addr]) => RiscVInstruction::Lr {
+            width,
+            ordering,
+            dest: parse_register(dest)?,
+            addr: parse_atomic_address(addr)?,
+        },
+        ("sc", _, [dest, src, addr]) => RiscVInstruction::Sc {
+            width,
+            ordering,
+            dest: parse_register(dest)?,
+            src: parse_register(src)?,
+            addr: parse_atomic_address(addr)?,
+        },
+        (_, Ok(op), [dest, src, addr]) => RiscVInstruction::Amo {
+            op,
+            width,
+            ordering,
+            dest: parse_register(dest)?,
+            src: parse_register(src)?,
+            addr: parse_atomic_address(addr)?,
+        },
+        _ => return error(text, format!("wrong number of operands for `{}`", mnemonic)),
+    };
+    Ok(instr)
+}
+
+/// Split an atomic mnemonic such as `amoadd.w.aqrl` into its name, width
+/// and ordering, or `None` if it isn't one.
+fn split_atomic(mnemonic: &str) -> Option<(&str, RiscVWidth, RiscVOrdering)> {
+    let mut parts = mnemonic.split('.');
+    let name = parts.next()?;
+    if name != "lr" && name != "sc" && RiscVAmoOp::from_str(name).is_err() {
+        return None;
+    }
+    let width = match parts.next()? {
+        "w" => RiscVWidth::Word,
+        "d" => RiscVWidth::Double,
+        _ => return None,
+    };
+    let ordering = match parts.next() {
+        None => RiscVOrdering::Relaxed,
+        Some("aq") => RiscVOrdering::Acquire,
+        Some("rl") => RiscVOrdering::Release,
+        Some("aqrl") => RiscVOrdering::AcquireRelease,
+        Some(_) => return None,
+    };
+    match parts.next() {
+        None => Some((name, width, ordering)),
+        Some(_) => None,
+    }
+}
+
+/// Parse an F or D extension instruction, once [`split_float`] has taken
+/// `name` and `precision` off `mnemonic`.
+fn parse_float<'a>(
+    text: &'a str,
+    mnemonic: &str,
+    name: &str,
+    precision: RiscVPrecision,
+    operands: &[&'a str],
+) -> LineResult<'a, RiscVInstruction> {
+    let instr = match (name, operands) {
+        ("fadd" | "fsub" | "fmul" | "fdiv", [dest, arg1, arg2, rounding @ ..])
+            if rounding.len() <= 1 =>
+        {
+            let rounding = parse_rounding(rounding)?;
+            let dest = parse_fregister(dest)?;
+            let arg1 = parse_fregister(arg1)?;
+            let arg2 = parse_fregister(arg2)?;
+            match name {
+                "fadd" => RiscVInstruction::FAdd {
+                    precision,
+                    rounding,
+                    dest,
+                    arg1,
+                    arg2,
+                },
+                "fsub" => RiscVInstruction::FSub {
+                    precision,
+                    rounding,
+                    dest,
+                    arg1,
+                    arg2,
+                },
+                "fmul" => RiscVInstruction::FMul {
+                    precision,
+                    rounding,
+                    dest,
+                    arg1,
+                    arg2,
+                },
+                _ => RiscVInstruction::FDiv {
+                    precision,
+                    rounding,
+                    dest,
+                    arg1,
+                    arg2,
+                },
+            }
+        }
+        ("fsqrt", [dest, src, rounding @ ..]) if rounding.len() <= 1 => RiscVInstruction::FSqrt {
+            precision,
+            rounding: parse_rounding(rounding)?,
+            dest: parse_fregister(dest)?,
+            src: parse_fregister(src)?,
+        },
+        ("fmadd" | "fmsub" | "fnmadd" | "fnmsub", [dest, arg1, arg2, arg3, rounding @ ..])
+            if rounding.len() <= 1 =>
+        {
+            let rounding = parse_rounding(rounding)?;
+            let dest = parse_fregister(dest)?;
+            let arg1 = parse_fregister(arg1)?;
+            let arg2 = parse_fregister(arg2)?;
+            let arg3 = parse_fregister(arg3)?;
+            match name {
+                "fmadd" => RiscVInstruction::FMadd {
+                    precision,
+                    rounding,
+                    dest,
+                    arg1,
+                    arg2,
+                    arg3,
+                },
+                "fmsub" => RiscVInstruction::FMsub {
+                    precision,
+                    rounding,
+                    dest,
+                    arg1,
+                    arg2,
+                    arg3,
+                },
+                "fnmadd" => RiscVInstruction::FNmadd {
+                    precision,
+                    rounding,
+                    dest,
+                    arg1,
+                    arg2,
+                    arg3,
+                },
+                _ => RiscVInstruction::FNmsub {
+                    precision,
+                    rounding,
+                    dest,
+                    arg1,
+                    arg2,
+                    arg3,
+                },
+            }
+        }
+        ("fmin" | "fmax" | "fsgnj" | "fsgnjn" | "fsgnjx", [dest, arg1, arg2]) => float_binary(
+            name,
+            precision,
+            parse_fregister(dest)?,
+            parse_fregister(arg1)?,
+            parse_fregister(arg2)?,
+        ),
+        // `fmv`, `fneg` and `fabs` are sign injections with the same
+        // operand twice
+        ("fmv" | "fneg" | "fabs", [dest, src]) => {
+            let name = match name {
+                "fmv" => "fsgnj",
+                "fneg" => "fsgnjn",
+                _ => "fsgnjx",
+            };
+            let src = parse_fregister(src)?;
+            float_binary(name, precision, parse_fregister(dest)?, src, src)
+        }
+        ("feq" | "flt" | "fle" | "fgt" | "fge", [dest, arg1, arg2]) => {
+            let dest = parse_register(dest)?;
+            let arg1 = parse_fregister(arg1)?;
+            let arg2 = parse_fregister(arg2)?;
+            // `fgt` and `fge` are `flt` and `fle` with the operands swapped
+            match name {
+                "feq" => RiscVInstruction::FEq {
+                    precision,
+                    dest,
+                    arg1,
+                    arg2,
+                },
+                "flt" => RiscVInstruction::FLt {
+                    precision,
+                    dest,
+                    arg1,
+                    arg2,
+                },
+                "fle" => RiscVInstruction::FLe {
+                    precision,
+                    dest,
+                    arg1,
+                    arg2,
+                },
+                "fgt" => RiscVInstruction::FLt {
+                    precision,
+                    dest,
+                    arg1: arg2,
+                    arg2: arg1,
+                },
+                _ => RiscVInstruction::FLe {
+                    precision,
+                    dest,
+                    arg1: arg2,
+                    arg2: arg1,
+                },
+            }
+        }
+        ("fclass", [dest, src]) => RiscVInstruction::FClass {
+            precision,
+            dest: parse_register(dest)?,
+            src: parse_fregister(src)?,
+        },
+        ("fmv.x.f", [dest, src]) => RiscVInstruction::FMvToInt {
+            precision,
+            dest: parse_register(dest)?,
+            src: parse_fregister(src)?,
+        },
+        ("fmv.f.x", [dest, src]) => RiscVInstruction::FMvFromInt {
+            precision,
+            dest: parse_fregister(dest)?,
+            src: parse_register(src)?,
+        },
+        ("fcvt.x.f", [dest, src, rounding @ ..]) if rounding.len() <= 1 => {
+            let (width, signed) = fcvt_int_format(mnemonic, 1);
+            RiscVInstruction::FCvtToInt {
+                precision,
+                width,
+                signed,
+                rounding: parse_rounding(rounding)?,
+                dest: parse_register(dest)?,
+                src: parse_fregister(src)?,
+            }
+        }
+        ("fcvt.f.x", [dest, src, rounding @ ..]) if rounding.len() <= 1 => {
+            let (width, signed) = fcvt_int_format(mnemonic, 2);
+            RiscVInstruction::FCvtFromInt {
+                precision,
+                width,
+                signed,
+                rounding: parse_rounding(rounding)?,
+                dest: parse_fregister(dest)?,
+                src: parse_register(src)?,
+            }
+        }
+        ("fcvt", [dest, src, rounding @ ..]) if rounding.len() <= 1 => RiscVInstruction::FCvt {
+            precision,
+            rounding: parse_rounding(rounding)?,
+            dest: parse_fregister(dest)?,
+            src: parse_fregister(src)?,
+        },
+        _ => return error(text, format!("wrong number of operands for `{}`", mnemonic)),
+    };
+    Ok(instr)
+}
+
+/// Split a floating point mnemonic such as `fadd.s` into its name and
+/// precision, or `None` if it isn't one.
+///
+/// The conversions and moves between register files get the generic names
+/// of the spec, with `x` for the integer side and `f` for the float side:
+/// `fcvt.w.d` is `fcvt.x.f` of a double, and `fmv.w.x` is `fmv.f.x` of a
+/// single. Converting between precisions is `fcvt`, with the precision of
+/// the result.
+fn split_float(mnemonic: &str) -> Option<(&str, RiscVPrecision)> {
+    let parts: Vec<&str> = mnemonic.split('.').collect();
+    match parts[..] {
+        ["fcvt", to, from] => match (float_precision(to), float_precision(from)) {
+            (Some(to), Some(from)) if to != from => Some(("fcvt", to)),
+            (Some(to), None) if int_format(from).is_some() => Some(("fcvt.f.x", to)),
+            (None, Some(from)) if int_format(to).is_some() => Some(("fcvt.x.f", from)),
+            _ => None,
+        },
+        // moves of the raw bits name singles `w`, or `s` in older code
+        ["fmv", "x", format] => Some(("fmv.x.f", bits_precision(format)?)),
+        ["fmv", format, "x"] => Some(("fmv.f.x", bits_precision(format)?)),
+        [name, format] => match name {
+            "fadd" | "fsub" | "fmul" | "fdiv" | "fsqrt" | "fmadd" | "fmsub" | "fnmadd"
+            | "fnmsub" | "fmin" | "fmax" | "fsgnj" | "fsgnjn" | "fsgnjx" | "fmv" | "fneg"
+            | "fabs" | "feq" | "flt" | "fle" | "fgt" | "fge" | "fclass" => {
+                Some((name, float_precision(format)?))
+            }
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+/// Build the floating point instruction `name` with three register operands.
+fn float_binary(
+    name: &str,
+    precision: RiscVPrecision,
+    dest: RiscVFRegister,
+    arg1: RiscVFRegister,
+    arg2: RiscVFRegister,
+) -> RiscVInstruction {
+    match name {
+        "fmin" => RiscVInstruction::FMin {
+            precision,
+            dest,
+            arg1,
+            arg2,
+        },
+        "fmax" => RiscVInstruction::FMax {
+            precision,
+            dest,
+            arg1,
+            arg2,
+        },
+        "fsgnj" => RiscVInstruction::FSgnj {
+            precision,
+            dest,
+            arg1,
+            arg2,
+        },
+        "fsgnjn" => RiscVInstruction::FSgnjn {
+            precision,
+            dest,
+            arg1,
+            arg2,
+        },
+        _ => RiscVInstruction::FSgnjx {
+            precision,
+            dest,
+            arg1,
+            arg2,
+        },
+    }
+}
+
+/// The precision of the format suffix `s` or `d`.
+fn float_precision(format: &str) -> Option<RiscVPrecision> {
+    match format {
+        "s" => Some(RiscVPrecision::Single),
+        "d" => Some(RiscVPrecision::Double),
+        _ => None,
+    }
+}
+
+/// The width and signedness of the integer format suffix of `fcvt`, `w`,
+/// `wu`, `l` or `lu`.
+fn int_format(format: &str) -> Option<(RiscVWidth, bool)> {
+    match format {
+        "w" => Some((RiscVWidth::Word, true)),
+        "wu" => Some((RiscVWidth::Word, false)),
+        "l" => Some((RiscVWidth::Double, true)),
+        "lu" => Some((RiscVWidth::Double, false)),
+        _ => None,
+    }
+}
+
+/// The integer format of `fcvt`, which is suffix `index` of `mnemonic`.
+fn fcvt_int_format(mnemonic: &str, index: usize) -> (RiscVWidth, bool) {
+    let format = mnemonic.split('.').nth(index).unwrap_or_default();
+    int_format(format).unwrap_or_default()
+}
+
+/// The precision of `fmv.x.*` and `fmv.*.x`, which name singles `w`.
+fn bits_precision(format: &str) -> Option<RiscVPrecision> {
+    match format {
+        "w" => Some(RiscVPrecision::Single),
+        _ => float_precision(format),
+    }
+}
+
+/// The precision of `flw`/`fsw` or `fld`/`fsd`.
+fn float_access_precision(mnemonic: &str) -> RiscVPrecision {
+    match &mnemonic[2..] {
+        "w" => RiscVPrecision::Single,
+        _ => RiscVPrecision::Double,
+    }
+}
+
+/// The optional rounding mode operand, `dyn` when it is left out.
+fn parse_rounding<'a>(rounding: &[&'a str]) -> LineResult<'a, RiscVRounding> {
+    match rounding {
+        [] => Ok(RiscVRounding::Dyn),
+        [text, ..] => {
+            RiscVRounding::from_str(text).or_else(|_| error(text, "invalid rounding mode"))
+        }
+    }
+}
+
+fn parse_fence_set(text: &str) -> LineResult<'_, RiscVFenceSet> {
+    RiscVFenceSet::from_str(text).or_else(|_| error(text, "invalid fence set"))
+}
+
+/// Atomics address memory with a bare register, `(rs1)`, or an offset of 0.
+fn parse_atomic_address(text: &str) -> LineResult<'_, RiscVRegister> {
+    match parse_base_offset(text)? {
+        (register, 0) => Ok(register),
+        _ => error(text, "atomic memory operands can't have an offset"),
+    }
+}
+
+/// Build the branch for a two register branch mnemonic.
+fn branch(
+    mnemonic: &str,
+    arg1: RiscVRegister,
+    arg2: RiscVRegister,
+    target: RiscVVal,
+) -> RiscVInstruction {
+    match mnemonic {
+        "beq" => RiscVInstruction::Beq { arg1, arg2, target },
+        "bne" => RiscVInstruction::Bne { arg1, arg2, target },
+        "blt" => RiscVInstruction::Blt { arg1, arg2, target },
+        "bge" => RiscVInstruction::Bge { arg1, arg2, target },
+        "bgt" => RiscVInstruction::Bgt { arg1, arg2, target },
+        "ble" => RiscVInstruction::Ble { arg1, arg2, target },
+        "bltu" => RiscVInstruction::Bltu { arg1, arg2, target },
+        "bgeu" => RiscVInstruction::Bgeu { arg1, arg2, target },
+        "bgtu" => RiscVInstruction::Bgtu { arg1, arg2, target },
+        _ => RiscVInstruction::Bleu { arg1, arg2, target },
+    }
+}
+
+/// The width of a load or store from its mnemonic, e.g. `lbu` or `sh`.
+fn access_width(mnemonic: &str) -> RiscVWidth {
+    match &mnemonic[1..] {
+        "b" => RiscVWidth::Byte,
+        "bu" => RiscVWidth::UnsignedByte,
+        "h" => RiscVWidth::Half,
+        "hu" => RiscVWidth::UnsignedHalf,
+        "w" => RiscVWidth::Word,
+        "wu" => RiscVWidth::UnsignedWord,
+        _ => RiscVWidth::Double,
+    }
+}
+
+fn word_if(is_word: bool) -> RiscVWidth {
+    if is_word {
+        RiscVWidth::Word
+    } else {
+        RiscVWidth::Double
+    }
+}
+
+/// Remove a trailing `#` comment, ignoring `#` inside string literals.
+fn strip_comment(line: &str) -> &str {
+    let mut in_string = false;
+    let mut escaped = false;
+    for (i, c) in line.char_indices() {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' if in_string => escaped = true,
+            '"' => in_string = !in_string,
+            '#' if !in_string => return &line[..i],
+            _ => {}
+        }
+    }
+    line
+}
+
+/// Split `name: rest` into the label name and the rest of the line.
+fn split_label(line: &str) -> Option<(&str, &str)> {
+    let end = line.find(|c: char| !is_symbol_char(c))?;
+    let (name, rest) = line.split_at(end);
+    if name.is_empty() {
+        return None;
+    }
+    rest.strip_prefix(':').map(|rest| (name, rest))
+}
+
+/// Split the mnemonic (or directive name) from its operands.
+fn split_mnemonic(text: &str) -> (&str, &str) {
+    match text.split_once(char::is_whitespace) {
+        Some((mnemonic, operands)) => (mnemonic, operands.trim()),
+        None => (text, ""),
+    }
+}
+
+pub(crate) fn is_symbol_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || c == '_' || c == '.' || c == '$'
+}
+
+fn is_reloc(text: &str) -> bool {
+    text.starts_with('%')
+}
+
+fn parse_register(text: &str) -> LineResult<'_, RiscVRegister> {
+    RiscVRegister::from_str(text).or_else(|_| error(text, "unknown register"))
+}
+
+fn parse_fregister(text: &str) -> LineResult<'_, RiscVFRegister> {
+    RiscVFRegister::from_str(text).or_else(|_| error(text, "unknown register"))
+}
+
+/// Parse a decimal or `0x` hex immediate, optionally negative, which fits
+/// in 32 bits.
+fn parse_imm(text: &str) -> LineResult<'_, i32> {
+    let value = parse_imm64(text)?;
+    i32::try_from(value).or_else(|_| error(text, "immediate out of range"))
+}
+
+/// The 12 bit signed immediate of an I-type or S-type instruction, such as
+/// `addi` or the offset of a load or store.
+fn parse_imm12(text: &str) -> LineResult<'_, i32> {
+    let value = parse_imm(text)?;
+    if (-2048..=2047).contains(&value) {
+        Ok(value)
+    } else {
+        error(text, "immediate out of range")
+    }
+}
+
+/// Like [`parse_imm`] but 64 bits. Values up to `u64::MAX` are allowed and
+/// wrap, so `0xffffffffffffffff` is -1.
+fn parse_imm64(text: &str) -> LineResult<'_, i64> {
+    let (negative, digits) = match text.strip_prefix('-') {
+        Some(digits) => (true, digits),
+        None => (false, text.strip_prefix('+').unwrap_or(text)),
+    };
+    let magnitude = if let Some(hex) = digits
+        .strip_prefix("0x")
+        .or_else(|| digits.strip_prefix("0X"))
+    {
+        u64::from_str_radix(hex, 16)
+    } else {
+        digits.parse::<u64>()
+    };
+    let Ok(magnitude) = magnitude else {
+        return error(text, "invalid immediate");
+    };
+    if !negative {
+        Ok(magnitude as i64)
+    } else if magnitude <= 1 << 63 {
+        Ok((magnitude as i64).wrapping_neg())
+    } else {
+        error(text, "immediate out of range")
+    }
+}
+
+/// The 20 bit immediate of `lui`, either unsigned or sign extended.
+fn parse_upper_imm(text: &str) -> LineResult<'_, i32> {
+    let value = parse_imm(text)?;
+    if (-0x80000..=0xfffff).contains(&value) {
+        Ok(value)
+    } else {
+        error(text, "immediate out of range")
+    }
+}
+
+/// Parse a memory operand `offset(register)`. The offset may be omitted,
+/// or be the lower bits of an address as in `%lo(label)(register)`.
+fn parse_offset(text: &str) -> LineResult<'_, RiscVVal> {
+    let (offset, register) = split_offset(text)?;
+    if is_reloc(offset) {
+        let (reloc, label, offset) = parse_lo(offset)?;
+        return Ok(RiscVVal::RelocOffset {
+            register: parse_register(register)?,
+            reloc,
+            label,
+            offset,
+        });
+    }
+    let (register, offset) = parse_base_offset(text)?;
+    Ok(RiscVVal::Offset { register, offset })
+}
+
+/// Parse `offset(register)` with a constant offset.
+fn parse_base_offset(text: &str) -> LineResult<'_, (RiscVRegister, i32)> {
+    let (offset, register) = split_offset(text)?;
+    let offset = match offset {
+        "" => 0,
+        offset => parse_imm12(offset)?,
+    };
+    Ok((parse_register(register)?, offset))
+}
+
+/// Split `offset(register)` into the offset and the register.
+fn split_offset(text: &str) -> LineResult<'_, (&str, &str)> {
+    let Some((offset, register)) = text
+        .strip_suffix(')')
+        .and_then(|text| text.rsplit_once('('))
+    else {
+        return error(text, "invalid memory operand");
+    };
+    Ok((offset.trim(), register.trim()))
+}
+
+/// Parse a branch or call target, optionally with an offset as in
+/// `label+8`.
+fn parse_label(text: &str) -> LineResult<'_, RiscVVal> {
+    let (label, offset) = parse_symbol(text)?;
+    Ok(RiscVVal::LabelOffset { label, offset })
+}
+
+/// The target of `call`, `tail` or `jal`. GCC 12 and earlier write PIC
+/// calls as `call puts@plt`, which calls the same `puts` in a static program.
+fn parse_jump_target(text: &str) -> LineResult<'_, RiscVVal> {
+    parse_label(text.strip_suffix("@plt").unwrap_or(text))
+}
+
+fn parse_symbol(text: &str) -> LineResult<'_, (String, i32)> {
+    let (label, offset) = match text.find(['+', '-']) {
+        Some(i) if i > 0 => {
+            let offset = text[i + 1..].trim();
+            let offset = match text.as_bytes()[i] {
+                b'-' => parse_imm(offset)?.checked_neg(),
+                _ => Some(parse_imm(offset)?),
+            };
+            let Some(offset) = offset else {
+                return error(text, "immediate out of range");
+            };
+            (text[..i].trim(), offset)
+        }
+        _ => (text, 0),
+    };
+    if label.is_empty() || !label.chars().all(is_symbol_char) {
+        return error(text, "invalid label");
+    }
+    Ok((label.to_string(), offset))
+}
+
+/// Parse a relocation operator such as `%hi(label)`, which has to be one of
+/// `relocs`. `expected` describes them for the error.
+fn parse_reloc<'a>(
+    text: &'a str,
+    relocs: &[RiscVReloc],
+    expected: &str,
+) -> LineResult<'a, (RiscVReloc, String, i32)> {
+    let Some((reloc, label)) = text
+        .strip_prefix('%')
+        .and_then(|text| text.strip_suffix(')'))
+        .and_then(|text| text.split_once('('))
+    else {
+        return error(text, "invalid relocation");
+    };
+    let Ok(reloc) = RiscVReloc::from_str(reloc) else {
+        return error(text, "unknown relocation");
+    };
+    if !relocs.contains(&reloc) {
+        return error(text, format!("expected {}", expected));
+    }
+    let (label, offset) = parse_symbol(label.trim())?;
+    Ok((reloc, label, offset))
+}
+
+/// The upper bits of an address for `lui`, `%hi(label)` or
+/// `%tprel_hi(label)`.
+fn parse_hi(text: &str) -> LineResult<'_, RiscVVal> {
+    let relocs = [RiscVReloc::Hi20, RiscVReloc::TprelHi20];
+    let (reloc, label, offset) = parse_reloc(text, &relocs, "%hi(label)")?;
+    Ok(RiscVVal::Reloc {
+        reloc,
+        label,
+        offset,
+    })
+}
+
+/// The upper bits of an offset for `auipc`, `%pcrel_hi(label)` or
+/// `%got_pcrel_hi(label)`.
+fn parse_pcrel_hi(text: &str) -> LineResult<'_, RiscVVal> {
+    let relocs = [RiscVReloc::PcrelHi20, RiscVReloc::GotPcrelHi20];
+    let (reloc, label, offset) = parse_reloc(text, &relocs, "%pcrel_hi(label)")?;
+    Ok(RiscVVal::Reloc {
+        reloc,
+        label,
+        offset,
+    })
+}
+
+/// The lower bits of an address for `addi` and memory operands,
+/// `%lo(label)`, `%pcrel_lo(label)` or `%tprel_lo(label)`.
+fn parse_lo(text: &str) -> LineResult<'_, (RiscVReloc, String, i32)> {
+    let relocs = [
+        RiscVReloc::Lo12,
+        RiscVReloc::PcrelLo12,
+        RiscVReloc::TprelLo12,
+    ];
+    parse_reloc(text, &relocs, "%lo(label)")
+}
@@ -0,0 +1,181 @@
+//! Pair `%pcrel_lo` relocations with their `auipc`.
+//!
+//! Position independent RISC-V code builds addresses with an `auipc` of
+//! `%pcrel_hi(symbol)`, and then an `addi`, load or store whose
+//! `%pcrel_lo(.Lanchor)` names the label of that `auipc` rather than the
+//! symbol:
+//!
+//! ```text
+//! .Lpcrel_hi0:
+//!     auipc a0, %pcrel_hi(buf)
+//!     addi  a0, a0, %pcrel_lo(.Lpcrel_hi0)
+//! ```
+//!
+//! ARM splits addresses with `adrp` and `:lo12:` of the symbol itself, so
+//! [`pair_pcrel`] looks up the symbol of each `%pcrel_lo` before
+//! translation.
+use std::collections::HashMap;
+
+use crate::instruction::{RiscVInstruction, RiscVReloc, RiscVVal};
+
+/// The `auipc` at an anchor label.
+struct Anchor {
+    reloc: RiscVReloc,
+    label: String,
+    offset: i32,
+    near: bool,
+}
+
+/// Replace each `%pcrel_lo` with a relocation of the symbol from its
+/// `auipc`.
+///
+/// When the symbol is a label in the same section as the `auipc`, it is
+/// assumed to be within the 1MB reach of `adr`, so the `auipc` takes the
+/// whole address of the label and the `%pcrel_lo`s become 0. `lla` of such
+/// a label becomes an `auipc` in the same way.
+///
+/// A `%pcrel_lo` without an `auipc` is left alone, for translation to
+/// reject.
+pub fn pair_pcrel<'a>(riscv_instrs: impl IntoIterator<Item = &'a mut RiscVInstruction>) {
+    let mut riscv_instrs: Vec<&mut RiscVInstruction> = riscv_instrs.into_iter().collect();
+
+    // the section of every label, and of every instruction
+    let mut label_sections = HashMap::new();
+    let mut sections = vec![];
+    let mut section = ".text".to_string();
+    for riscv_instr in &riscv_instrs {
+        match &**riscv_instr {
+            RiscVInstruction::Directive { name, operands } => match name.as_str() {
+                "text" | "data" | "bss" | "rodata" => section = format!(".{}", name),
+                "section" => section = operands.split(',').next().unwrap_or("").trim().to_string(),
+                _ => {}
+            },
+            RiscVInstruction::Label { name } => {
+                label_sections.insert(name.clone(), section.clone());
+            }
+            _ => {}
+        }
+        sections.push(section.clone());
+    }
+    let is_near = |label: &str, section: &str| {
+        label_sections
+            .get(label)
+            .is_some_and(|label_section| label_section == section)
+    };
+
+    // labels right before an `auipc` anchor it
+    let mut anchors = HashMap::new();
+    let mut labels = vec![];
+    for (riscv_instr, section) in riscv_instrs.iter().zip(&sections) {
+        match &**riscv_instr {
+            RiscVInstruction::Label { name } => labels.push(name.clone()),
+            RiscVInstruction::Auipc {
+                src:
+                    RiscVVal::Reloc {
+                        reloc,
+                        label,
+                        offset,
+                    },
+                ..
+            } => {
+                for name in labels.drain(..) {
+                    let anchor = Anchor {
+                        reloc: *reloc,
+                        label: label.clone(),
+                        offset: *offset,
+                        near: *reloc == RiscVReloc::PcrelHi20 && is_near(label, section),
+                    };
+                    anchors.insert(name, anchor);
+                }
+            }
+            _ => labels.clear(),
+        }
+    }
+
+    for (riscv_instr, section) in riscv_instrs.iter_mut().zip(&sections) {
+        match &mut **riscv_instr {
+            RiscVInstruction::Auipc {
+                dest,
+                src:
+                    RiscVVal::Reloc {
+                        reloc: RiscVReloc::PcrelHi20,
+                        label,
+                        offset,
+                    },
+            }
+            | RiscVInstruction::Lla {
+                dest,
+                label: RiscVVal::LabelOffset { label, offset },
+            } if is_near(label, section) => {
+                **riscv_instr = RiscVInstruction::Auipc {
+                    dest: *dest,
+                    src: RiscVVal::LabelOffset {
+                        label: label.clone(),
+                        offset: *offset,
+                    },
+                };
+            }
+            RiscVInstruction::Addl { label: val, .. }
+            | RiscVInstruction::L { src: val, .. }
+            | RiscVInstruction::S { dest: val, .. }
+            | RiscVInstruction::FL { src: val, .. }
+            | RiscVInstruction::FS { dest: val, .. } => {
+                if let Some(paired) = pair(val, &anchors) {
+                    *val = paired;
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+/// `val` with its `%pcrel_lo` paired, or `None` if it has no `%pcrel_lo` or
+/// there is no `auipc` for it.
+fn pair(val: &RiscVVal, anchors: &HashMap<String, Anchor>) -> Option<RiscVVal> {
+    let (register, label) = match val {
+        RiscVVal::Reloc {
+            reloc: RiscVReloc::PcrelLo12,
+            label,
+            ..
+        } => (None, label),
+        RiscVVal::RelocOffset {
+            register,
+            reloc: RiscVReloc::PcrelLo12,
+            label,
+            ..
+        } => (Some(*register), label),
+        _ => return None,
+    };
+    let anchor = anchors.get(label)?;
+
+    // the `auipc` has the whole address
+    if anchor.near {
+        return Some(match register {
+            None => RiscVVal::Immediate(0),
+            Some(register) => RiscVVal::Offset {
+                register,
+                offset: 0,
+            },
+        });
+    }
+
+    let reloc = match anchor.reloc {
+        RiscVReloc::GotPcrelHi20 => RiscVReloc::GotPcrelLo12,
+        _ => RiscVReloc::Lo12,
+    };
+    let label = anchor.label.clone();
+    let offset = anchor.offset;
+    Some(match register {
+        None => RiscVVal::Reloc {
+            reloc,
+            label,
+            offset,
+        },
+        Some(register) => RiscVVal::RelocOffset {
+            register,
+            reloc,
+            label,
+            offset,
+        },
+    })
+}
@@ -1,115 +1,365 @@
-use core::panic;
-
+use crate::encode::assemble;
+use crate::error::AsmError;
+use crate::executable::write_executable;
+use crate::float::translate_float;
 use crate::instruction::{
-    parse_asm, ArmInstruction, ArmRegister, ArmRegisterName, ArmVal, ArmWidth, RiscVInstruction,
-    RiscVRegister, RiscVVal, RiscVWidth,
+    ArmAtomicOp, ArmBarrier, ArmCondition, ArmInstruction, ArmRegister, ArmRegisterName, ArmReloc,
+    ArmSystemRegister, ArmVal, ArmWidth, RiscVAmoOp, RiscVFenceSet, RiscVInstruction,
+    RiscVOrdering, RiscVPrecision, RiscVRegister, RiscVReloc, RiscVRounding, RiscVVal, RiscVWidth,
 };
+use crate::legalize::{is_logical_imm, legalize};
+use crate::parse::parse_asm_partial;
+use crate::pcrel::pair_pcrel;
+use crate::utils::{ARM_LOOP_START, ARM_START, START_MAIN};
 
-macro_rules! sorry {
-    () => {
-        todo!()
-    };
+/// Run the core logic to match from RISC-V to ARM Instructions.
+///
+/// Translate one instruction at a time.
+///
+/// Immediates are copied as is, even when ARM can't encode them, so the
+/// result needs [`legalize`] before it can be assembled.
+///
+/// Errors have `line` 0 since a lone instruction has no position;
+/// [`translate_instrs`] fills it in.
+pub fn translate(riscv_instr: RiscVInstruction) -> Result<Vec<ArmInstruction>, AsmError> {
+    translate_with_options(riscv_instr, &TranslateOptions::default())
 }
 
-/// Run the core logic to match from RISC-V to ARM Instructions.
+/// [`translate`] with the instruction choices of `options`, such as
+/// [`TranslateOptions::lse`].
+pub fn translate_with_options(
+    riscv_instr: RiscVInstruction,
+    options: &TranslateOptions,
+) -> Result<Vec<ArmInstruction>, AsmError> {
+    if let Err(reason) = check_operands(&riscv_instr) {
+        return Err(unsupported(riscv_instr, reason));
+    }
 
-/// Translate one instruction at a time.
-pub fn translate(riscv_instr: RiscVInstruction) -> Vec<ArmInstruction> {
-    match riscv_instr {
+    let arm_instrs = match riscv_instr {
         RiscVInstruction::Addi { dest, src, imm } => {
             if let RiscVRegister::X0 = src {
-                return translate(RiscVInstruction::Mvi { dest, imm });
+                return translate_with_options(RiscVInstruction::Mvi { dest, imm }, options);
+            }
+            // such as the `addi` of a decoded `auipc` pair, which `adr`
+            // already points at the label
+            if dest == src && imm == 0 {
+                return Ok(vec![]);
             }
 
             let width = RiscVWidth::Double;
             if imm >= 0 {
                 vec![ArmInstruction::Add {
-                    dest: map_register(dest, &width),
-                    arg1: map_register(src, &width),
-                    arg2: ArmVal::Imm(imm),
+                    dest: map_register(dest, &width)?,
+                    arg1: map_register(src, &width)?,
+                    arg2: ArmVal::Imm(imm.into()),
                 }]
             } else {
                 vec![ArmInstruction::Sub {
-                    dest: map_register(dest, &width),
-                    arg1: map_register(src, &width),
-                    arg2: ArmVal::Imm(imm.abs()),
+                    dest: map_register(dest, &width)?,
+                    arg1: map_register(src, &width)?,
+                    arg2: ArmVal::Imm(i64::from(imm).abs()),
                 }]
             }
         }
-        RiscVInstruction::Ble { arg1, arg2, target } => vec![{
-            let width = RiscVWidth::Double;
-            ArmInstruction::Ble {
-                arg1: map_register(arg1, &width),
-                arg2: map_register(arg2, &width),
-                target: map_val(target, &width),
-            }
-        }],
-        RiscVInstruction::Bge { arg1, arg2, target } => vec![{
-            let width = RiscVWidth::Double;
-            ArmInstruction::Bge {
-                arg1: map_register(arg1, &width),
-                arg2: map_register(arg2, &width),
-                target: map_val(target, &width),
-            }
+        RiscVInstruction::Addiw { dest, src, imm } => {
+            let width = RiscVWidth::Word;
+            let add = if imm >= 0 {
+                ArmInstruction::Add {
+                    dest: map_register(dest, &width)?,
+                    arg1: map_register(src, &width)?,
+                    arg2: ArmVal::Imm(imm.into()),
+                }
+            } else {
+                ArmInstruction::Sub {
+                    dest: map_register(dest, &width)?,
+                    arg1: map_register(src, &width)?,
+                    arg2: ArmVal::Imm(i64::from(imm).abs()),
+                }
+            };
+            sign_extend_if_word(width, dest, add)?
+        }
+        RiscVInstruction::Slt { dest, arg1, arg2 } => {
+            set_if(dest, compare(arg1, arg2)?, ArmCondition::Lt)?
+        }
+        RiscVInstruction::Sltu { dest, arg1, arg2 } => {
+            set_if(dest, compare(arg1, arg2)?, ArmCondition::Lo)?
+        }
+        RiscVInstruction::Slti { dest, src, imm } => {
+            set_if(dest, compare_imm(src, imm)?, ArmCondition::Lt)?
+        }
+        // the immediate is sign extended before comparing unsigned, so
+        // `sltiu rd, rs, -1` is true for every value but all ones
+        RiscVInstruction::Sltiu { dest, src, imm } => {
+            set_if(dest, compare_imm(src, imm)?, ArmCondition::Lo)?
+        }
+        RiscVInstruction::Seqz { dest, src } => {
+            set_if(dest, compare(src, RiscVRegister::X0)?, ArmCondition::Eq)?
+        }
+        RiscVInstruction::Snez { dest, src } => {
+            set_if(dest, compare(src, RiscVRegister::X0)?, ArmCondition::Ne)?
+        }
+        RiscVInstruction::Sltz { dest, src } => {
+            set_if(dest, compare(src, RiscVRegister::X0)?, ArmCondition::Lt)?
+        }
+        RiscVInstruction::Sgtz { dest, src } => {
+            set_if(dest, compare(src, RiscVRegister::X0)?, ArmCondition::Gt)?
+        }
+        RiscVInstruction::Beq { arg1, arg2, target } => {
+            branch_if(compare(arg1, arg2)?, ArmCondition::Eq, target)?
+        }
+        RiscVInstruction::Bne { arg1, arg2, target } => {
+            branch_if(compare(arg1, arg2)?, ArmCondition::Ne, target)?
+        }
+        RiscVInstruction::Blt { arg1, arg2, target } => {
+            branch_if(compare(arg1, arg2)?, ArmCondition::Lt, target)?
+        }
+        RiscVInstruction::Bge { arg1, arg2, target } => {
+            branch_if(compare(arg1, arg2)?, ArmCondition::Ge, target)?
+        }
+        RiscVInstruction::Bgt { arg1, arg2, target } => {
+            branch_if(compare(arg1, arg2)?, ArmCondition::Gt, target)?
+        }
+        RiscVInstruction::Ble { arg1, arg2, target } => {
+            branch_if(compare(arg1, arg2)?, ArmCondition::Le, target)?
+        }
+        RiscVInstruction::Bltu { arg1, arg2, target } => {
+            branch_if(compare(arg1, arg2)?, ArmCondition::Lo, target)?
+        }
+        RiscVInstruction::Bgeu { arg1, arg2, target } => {
+            branch_if(compare(arg1, arg2)?, ArmCondition::Hs, target)?
+        }
+        RiscVInstruction::Bgtu { arg1, arg2, target } => {
+            branch_if(compare(arg1, arg2)?, ArmCondition::Hi, target)?
+        }
+        RiscVInstruction::Bleu { arg1, arg2, target } => {
+            branch_if(compare(arg1, arg2)?, ArmCondition::Ls, target)?
+        }
+        RiscVInstruction::J { target } => {
+            return translate_with_options(
+                RiscVInstruction::Jal {
+                    dest: RiscVRegister::X0,
+                    target,
+                },
+                options,
+            )
+        }
+        RiscVInstruction::S { width, src, dest } => vec![ArmInstruction::Str {
+            width: map_width(&width),
+            src: map_register(src, &width)?,
+            dest: map_val(dest, &width)?,
         }],
-        RiscVInstruction::Blt { arg1, arg2, target } => vec![{
+        RiscVInstruction::And { dest, arg1, arg2 } => {
             let width = RiscVWidth::Double;
-            ArmInstruction::Blt {
-                arg1: map_register(arg1, &width),
-                arg2: map_register(arg2, &width),
-                target: map_val(target, &width),
-            }
-        }],
-        RiscVInstruction::Bgt { arg1, arg2, target } => vec![{
+            vec![ArmInstruction::And {
+                dest: map_register(dest, &width)?,
+                arg1: map_register(arg1, &width)?,
+                arg2: ArmVal::Reg(map_register(arg2, &width)?),
+            }]
+        }
+        RiscVInstruction::Or { dest, arg1, arg2 } => {
             let width = RiscVWidth::Double;
-            ArmInstruction::Bgt {
-                arg1: map_register(arg1, &width),
-                arg2: map_register(arg2, &width),
-                target: map_val(target, &width),
-            }
-        }],
-        RiscVInstruction::Bne { arg1, arg2, target } => vec![{
+            vec![ArmInstruction::Orr {
+                dest: map_register(dest, &width)?,
+                arg1: map_register(arg1, &width)?,
+                arg2: ArmVal::Reg(map_register(arg2, &width)?),
+            }]
+        }
+        RiscVInstruction::Xor { dest, arg1, arg2 } => {
             let width = RiscVWidth::Double;
-            ArmInstruction::Bne {
-                arg1: map_register(arg1, &width),
-                arg2: map_register(arg2, &width),
-                target: map_val(target, &width),
-            }
-        }],
-        RiscVInstruction::J { target } => vec![ArmInstruction::B {
-            target: map_val(target, &RiscVWidth::Double),
-        }],
-        RiscVInstruction::S { width, src, dest } => vec![ArmInstruction::Str {
-            width: map_width(&width),
-            src: map_register(src, &width),
-            dest: map_val(dest, &width),
-        }],
-        RiscVInstruction::Slli { dest, src, imm } => {
+            vec![ArmInstruction::Eor {
+                dest: map_register(dest, &width)?,
+                arg1: map_register(arg1, &width)?,
+                arg2: ArmVal::Reg(map_register(arg2, &width)?),
+            }]
+        }
+        RiscVInstruction::Andi { dest, src, imm } => {
+            translate_logical_imm(dest, src, imm, |dest, arg1, arg2| ArmInstruction::And {
+                dest,
+                arg1,
+                arg2,
+            })?
+        }
+        RiscVInstruction::Ori { dest, src, imm } => {
+            translate_logical_imm(dest, src, imm, |dest, arg1, arg2| ArmInstruction::Orr {
+                dest,
+                arg1,
+                arg2,
+            })?
+        }
+        RiscVInstruction::Xori { dest, src, imm } => {
+            translate_logical_imm(dest, src, imm, |dest, arg1, arg2| ArmInstruction::Eor {
+                dest,
+                arg1,
+                arg2,
+            })?
+        }
+        RiscVInstruction::Not { dest, src } => {
             let width = RiscVWidth::Double;
-            vec![ArmInstruction::Lsl {
-                dest: map_register(dest, &width),
-                src: map_register(src, &width),
-                imm: imm
+            vec![ArmInstruction::Mvn {
+                dest: map_register(dest, &width)?,
+                src: map_register(src, &width)?,
             }]
-        },
-        RiscVInstruction::L { width, dest, src } => vec![ArmInstruction::Ldr {
-            width: map_width(&width),
-            dest: map_register(dest, &width),
-            src: map_val(src, &width),
-        }],
-        RiscVInstruction::Directive { name, operands } => {
-            let arm_operands = operands.replace("@", "%");
-            vec![ArmInstruction::Directive {
-                name,
-                operands: arm_operands,
+        }
+        RiscVInstruction::Neg { width, dest, src } => sign_extend_if_word(
+            width,
+            dest,
+            ArmInstruction::Neg {
+                dest: map_register(dest, &width)?,
+                src: map_register(src, &width)?,
+            },
+        )?,
+        // ARM register shifts use the shift amount modulo the register
+        // width, which is also what RISC-V does
+        RiscVInstruction::Sll {
+            width,
+            dest,
+            arg1,
+            arg2,
+        } => sign_extend_if_word(
+            width,
+            dest,
+            ArmInstruction::Lslv {
+                dest: map_register(dest, &width)?,
+                arg1: map_register(arg1, &width)?,
+                arg2: map_register(arg2, &width)?,
+            },
+        )?,
+        RiscVInstruction::Srl {
+            width,
+            dest,
+            arg1,
+            arg2,
+        } => sign_extend_if_word(
+            width,
+            dest,
+            ArmInstruction::Lsrv {
+                dest: map_register(dest, &width)?,
+                arg1: map_register(arg1, &width)?,
+                arg2: map_register(arg2, &width)?,
+            },
+        )?,
+        RiscVInstruction::Sra {
+            width,
+            dest,
+            arg1,
+            arg2,
+        } => sign_extend_if_word(
+            width,
+            dest,
+            ArmInstruction::Asrv {
+                dest: map_register(dest, &width)?,
+                arg1: map_register(arg1, &width)?,
+                arg2: map_register(arg2, &width)?,
+            },
+        )?,
+        RiscVInstruction::Slli {
+            width,
+            dest,
+            src,
+            imm,
+        } => sign_extend_if_word(
+            width,
+            dest,
+            ArmInstruction::Lsl {
+                dest: map_register(dest, &width)?,
+                src: map_register(src, &width)?,
+                imm,
+            },
+        )?,
+        RiscVInstruction::Srli {
+            width,
+            dest,
+            src,
+            imm,
+        } => sign_extend_if_word(
+            width,
+            dest,
+            ArmInstruction::Lsr {
+                dest: map_register(dest, &width)?,
+                src: map_register(src, &width)?,
+                imm,
+            },
+        )?,
+        RiscVInstruction::Srai {
+            width,
+            dest,
+            src,
+            imm,
+        } => sign_extend_if_word(
+            width,
+            dest,
+            ArmInstruction::Asr {
+                dest: map_register(dest, &width)?,
+                src: map_register(src, &width)?,
+                imm,
+            },
+        )?,
+        RiscVInstruction::L { width, dest, src } => {
+            // sign extending loads write the whole register, the others
+            // write a `w` register which zero extends
+            let register_width = match width {
+                RiscVWidth::UnsignedByte | RiscVWidth::UnsignedHalf | RiscVWidth::UnsignedWord => {
+                    RiscVWidth::Word
+                }
+                _ => RiscVWidth::Double,
+            };
+            vec![ArmInstruction::Ldr {
+                width: map_width(&width),
+                dest: map_register(dest, &register_width)?,
+                src: map_val(src, &width)?,
             }]
         }
+        RiscVInstruction::Lr {
+            width,
+            ordering,
+            dest,
+            addr,
+        } => sign_extend_if_word(
+            width,
+            dest,
+            ArmInstruction::Ldxr {
+                acquire: ordering.acquire(),
+                dest: map_register(dest, &width)?,
+                addr: map_register_name(addr),
+            },
+        )?,
+        RiscVInstruction::Sc {
+            width,
+            ordering,
+            dest,
+            src,
+            addr,
+        } => translate_sc(width, ordering, dest, src, addr)?,
+        RiscVInstruction::Amo {
+            op,
+            width,
+            ordering,
+            dest,
+            src,
+            addr,
+        } => {
+            if options.lse {
+                translate_amo_lse(op, width, ordering, dest, src, addr)?
+            } else {
+                translate_amo(op, width, ordering, dest, src, addr)?
+            }
+        }
+        RiscVInstruction::Directive { name, operands } => {
+            // RISC-V specific directives have no ARM equivalent. Call frame
+            // information names RISC-V DWARF registers and describes RISC-V
+            // frames, so it would be wrong for the translation.
+            if name == "option" || name == "attribute" || name.starts_with("cfi_") {
+                return Ok(vec![]);
+            }
+            let operands = arm_directive_operands(&name, &operands);
+            vec![ArmInstruction::Directive { name, operands }]
+        }
         RiscVInstruction::Label { name } => vec![ArmInstruction::Label { name }],
         RiscVInstruction::Mv { dest, src } => {
             let width = RiscVWidth::Double;
             vec![ArmInstruction::Add {
-                dest: map_register(dest, &width),
-                arg1: map_register(src, &width),
+                dest: map_register(dest, &width)?,
+                arg1: map_register(src, &width)?,
                 arg2: ArmVal::Imm(0),
             }]
         }
@@ -117,8 +367,8 @@ pub fn translate(riscv_instr: RiscVInstruction) -> Vec<ArmInstruction> {
             let width = RiscVWidth::Double;
             vec![ArmInstruction::Mov {
                 width: map_width(&width),
-                dest: map_register(dest, &width),
-                src: ArmVal::Imm(imm),
+                dest: map_register(dest, &width)?,
+                src: ArmVal::Imm(imm.into()),
             }]
         }
         RiscVInstruction::Add {
@@ -126,151 +376,1300 @@ pub fn translate(riscv_instr: RiscVInstruction) -> Vec<ArmInstruction> {
             dest,
             arg1,
             arg2,
-        } => match width {
-            RiscVWidth::Word => vec![ArmInstruction::Add {
-                dest: ArmRegister {
-                    width: ArmWidth::Word,
-                    name: map_register_name(dest),
-                },
-                arg1: ArmRegister {
-                    width: ArmWidth::Word,
-                    name: map_register_name(arg1),
-                },
-                arg2: ArmVal::Reg(ArmRegister {
-                    width: ArmWidth::Word,
-                    name: map_register_name(arg2),
-                }),
-            }],
-            RiscVWidth::Double => vec![ArmInstruction::Add {
-                dest: ArmRegister {
-                    width: ArmWidth::Double,
-                    name: map_register_name(dest),
-                },
-                arg1: ArmRegister {
-                    width: ArmWidth::Double,
-                    name: map_register_name(arg1),
-                },
-                arg2: ArmVal::Reg(ArmRegister {
-                    width: ArmWidth::Double,
-                    name: map_register_name(arg2),
-                }),
-            }],
-        },
+        } => translate_add_sub(width, dest, arg1, arg2, true, |dest, arg1, arg2| {
+            ArmInstruction::Add { dest, arg1, arg2 }
+        })?,
         RiscVInstruction::Sub {
             width,
             dest,
             arg1,
             arg2,
-        } => match width {
-            RiscVWidth::Word => vec![ArmInstruction::Sub {
-                dest: ArmRegister {
-                    width: ArmWidth::Word,
-                    name: map_register_name(dest),
+        } => translate_add_sub(width, dest, arg1, arg2, false, |dest, arg1, arg2| {
+            ArmInstruction::Sub { dest, arg1, arg2 }
+        })?,
+        RiscVInstruction::Mul {
+            width,
+            dest,
+            arg1,
+            arg2,
+        } => {
+            let mut instrs = vec![ArmInstruction::Mul {
+                dest: map_register(dest, &width)?,
+                arg1: map_register(arg1, &width)?,
+                arg2: map_register(arg2, &width)?,
+            }];
+            if width == RiscVWidth::Word {
+                instrs.extend(sign_extend_word(dest)?);
+            }
+            instrs
+        }
+        RiscVInstruction::Mulh { dest, arg1, arg2 } => {
+            let width = RiscVWidth::Double;
+            vec![ArmInstruction::Smulh {
+                dest: map_register(dest, &width)?,
+                arg1: map_register(arg1, &width)?,
+                arg2: map_register(arg2, &width)?,
+            }]
+        }
+        RiscVInstruction::Mulhu { dest, arg1, arg2 } => {
+            let width = RiscVWidth::Double;
+            vec![ArmInstruction::Umulh {
+                dest: map_register(dest, &width)?,
+                arg1: map_register(arg1, &width)?,
+                arg2: map_register(arg2, &width)?,
+            }]
+        }
+        RiscVInstruction::Mulhsu { dest, arg1, arg2 } => {
+            // There is no signed x unsigned multiply on ARM, but
+            // (a s*u b) >> 64 == ((a u*u b) >> 64) - (a < 0 ? b : 0)
+            let width = RiscVWidth::Double;
+            let scratch = ArmRegister::new(SCRATCH, ArmWidth::Double);
+            vec![
+                ArmInstruction::Cmp {
+                    op1: map_register(arg1, &width)?,
+                    op2: ArmVal::Reg(zero_register(&width)?),
                 },
-                arg1: ArmRegister {
-                    width: ArmWidth::Word,
-                    name: map_register_name(arg1),
+                ArmInstruction::Csel {
+                    dest: scratch,
+                    arg1: map_register(arg2, &width)?,
+                    arg2: zero_register(&width)?,
+                    cond: ArmCondition::Lt,
                 },
-                arg2: ArmVal::Reg(ArmRegister {
-                    width: ArmWidth::Word,
-                    name: map_register_name(arg2),
-                }),
-            }],
-            RiscVWidth::Double => vec![ArmInstruction::Sub {
-                dest: ArmRegister {
-                    width: ArmWidth::Double,
-                    name: map_register_name(dest),
+                ArmInstruction::Umulh {
+                    dest: map_register(dest, &width)?,
+                    arg1: map_register(arg1, &width)?,
+                    arg2: map_register(arg2, &width)?,
                 },
-                arg1: ArmRegister {
-                    width: ArmWidth::Double,
-                    name: map_register_name(arg1),
+                ArmInstruction::Sub {
+                    dest: map_register(dest, &width)?,
+                    arg1: map_register(dest, &width)?,
+                    arg2: ArmVal::Reg(scratch),
                 },
-                arg2: ArmVal::Reg(ArmRegister {
-                    width: ArmWidth::Double,
-                    name: map_register_name(arg2),
-                }),
-            }],
-        },
+            ]
+        }
+        RiscVInstruction::Div {
+            width,
+            dest,
+            arg1,
+            arg2,
+        } => translate_div(width, dest, arg1, arg2, true)?,
+        RiscVInstruction::Divu {
+            width,
+            dest,
+            arg1,
+            arg2,
+        } => translate_div(width, dest, arg1, arg2, false)?,
+        RiscVInstruction::Rem {
+            width,
+            dest,
+            arg1,
+            arg2,
+        } => translate_rem(width, dest, arg1, arg2, true)?,
+        RiscVInstruction::Remu {
+            width,
+            dest,
+            arg1,
+            arg2,
+        } => translate_rem(width, dest, arg1, arg2, false)?,
         RiscVInstruction::SextW { dest, src } => vec![ArmInstruction::Sxtw {
-            dest: ArmRegister {
-                width: ArmWidth::Double,
-                name: map_register_name(dest),
-            },
-            src: ArmRegister {
-                width: ArmWidth::Word,
-                name: map_register_name(src),
-            },
-        }],
-        RiscVInstruction::Jr { target } => vec![ArmInstruction::Blr {
-            target: map_register_name(target),
+            dest: arm_register(map_register_name(dest), ArmWidth::Double)?,
+            src: arm_register(map_register_name(src), ArmWidth::Word)?,
         }],
+        RiscVInstruction::Jr { target } => {
+            return translate_with_options(
+                RiscVInstruction::Jalr {
+                    dest: RiscVRegister::X0,
+                    target,
+                    offset: 0,
+                },
+                options,
+            )
+        }
+        RiscVInstruction::Jal { dest, target } => translate_jal(dest, target)?,
+        RiscVInstruction::Jalr {
+            dest,
+            target,
+            offset,
+        } => translate_jalr(dest, target, offset)?,
         RiscVInstruction::Li { dest, imm } => {
-            if imm > 4095 || imm < 0 {
-                panic!("Li with imm out of range");
-            }
-
             let width = RiscVWidth::Double;
             vec![ArmInstruction::Mov {
                 width: map_width(&width),
-                dest: map_register(dest, &width),
+                dest: map_register(dest, &width)?,
                 src: ArmVal::Imm(imm),
             }]
-            // ArmInstruction::Add {
-            //     dest: map_register(dest, &RiscVWidth::Double),
-            //     arg1: ArmRegister {
-            //         width: ArmWidth::Double,
-            //         name: ArmRegisterName::Zero,
-            //     },
-            //     arg2: ArmVal::Imm(imm),
-            // }
         }
         RiscVInstruction::Addl { dest, src, label } => {
             let width = RiscVWidth::Double;
             vec![ArmInstruction::Add {
-                dest: map_register(dest, &width),
-                arg1: map_register(src, &width),
-                arg2: map_val(label, &width),
+                dest: map_register(dest, &width)?,
+                arg1: map_register(src, &width)?,
+                arg2: map_val(label, &width)?,
+            }]
+        }
+        RiscVInstruction::Lui {
+            dest,
+            src: RiscVVal::Immediate(imm),
+        } => {
+            let width = RiscVWidth::Double;
+            let value = ((imm as u32) << 12) as i32;
+            vec![ArmInstruction::Mov {
+                width: map_width(&width),
+                dest: map_register(dest, &width)?,
+                src: ArmVal::Imm(value.into()),
             }]
         }
         RiscVInstruction::Lui { dest, src } => {
             // only used to load upper bits or adrp in arm
             let width = RiscVWidth::Double;
             vec![ArmInstruction::Adrp {
-                dest: map_register(dest, &width),
-                label: map_val(src, &width),
+                dest: map_register(dest, &width)?,
+                label: map_val(src, &width)?,
             }]
         }
-        RiscVInstruction::Call { label } => {
+        RiscVInstruction::Auipc {
+            dest,
+            src: src @ RiscVVal::LabelOffset { .. },
+        } => {
             let width = RiscVWidth::Double;
-            vec![ArmInstruction::Bl {
-                target: map_val(label, &width),
+            vec![ArmInstruction::Adr {
+                dest: map_register(dest, &width)?,
+                label: map_val(src, &width)?,
             }]
         }
-        RiscVInstruction::ECall => {
-            let syscall_num_reg = ArmRegister {
-                width: ArmWidth::Double,
-                name: ArmRegisterName::X8,
-            };
+        RiscVInstruction::Auipc { dest, src } => {
+            let width = RiscVWidth::Double;
+            vec![ArmInstruction::Adrp {
+                dest: map_register(dest, &width)?,
+                label: map_val(src, &width)?,
+            }]
+        }
+        RiscVInstruction::Lla { dest, label } => {
+            let dest = map_register(dest, &RiscVWidth::Double)?;
             vec![
-                // ArmInstruction::Cmp(syscall_num_reg, ArmVal::Imm(RISCV_WRITE)),                                                      // if (x8 == RISCV_WRITE) {
-                // ArmInstruction::Bne("else"),
-                // ArmInstruction::Mov { width: ArmWidth::Double, dest: x8, src: ArmVal::Imm(SYS_WRITE) }, // x8 = ARM_WRITE;
-                // ArmInstruction::B("done"),
-                // ArmInstruction::Label("else"),                                                   // } else {
-                // ArmInstruction::Mov { width: ArmWidth::Double, dest: x8, src: ArmVal::Imm(__) }, //        x8 = ARM_EXIT
-                //                                                                                  // }
-                // ArmInstruction::Label("done"),
-                ArmInstruction::Svc { id: 0 },
+                ArmInstruction::Adrp {
+                    dest,
+                    label: relocate(ArmReloc::Page, label.clone())?,
+                },
+                ArmInstruction::Add {
+                    dest,
+                    arg1: dest,
+                    arg2: relocate(ArmReloc::Lo12, label)?,
+                },
             ]
         }
+        RiscVInstruction::La { dest, label } => {
+            let dest = map_register(dest, &RiscVWidth::Double)?;
+            vec![
+                ArmInstruction::Adrp {
+                    dest,
+                    label: relocate(ArmReloc::GotPage, label.clone())?,
+                },
+                ArmInstruction::Ldr {
+                    width: ArmWidth::Double,
+                    dest,
+                    src: ArmVal::RelocOffset(dest, Box::new(relocate(ArmReloc::GotLo12, label)?)),
+                },
+            ]
+        }
+        RiscVInstruction::Call { label } => {
+            return translate_with_options(
+                RiscVInstruction::Jal {
+                    dest: RiscVRegister::RA,
+                    target: label,
+                },
+                options,
+            )
+        }
+        // `a7` maps to `x8`, and both use the generic Linux system call
+        // numbers
+        RiscVInstruction::ECall => vec![ArmInstruction::Svc { id: 0 }],
+        RiscVInstruction::Fence { pred, succ } => fence_barrier(pred, succ)
+            .map(|option| ArmInstruction::Dmb { option })
+            .into_iter()
+            .collect(),
+        // ARM has no barrier which orders everything but writes before
+        // reads
+        RiscVInstruction::FenceTso => vec![ArmInstruction::Dmb {
+            option: ArmBarrier::Ish,
+        }],
+        RiscVInstruction::FenceI => translate_fence_i(),
+        RiscVInstruction::Pause => vec![ArmInstruction::Yield],
+        riscv_instr @ (RiscVInstruction::FL { .. }
+        | RiscVInstruction::FS { .. }
+        | RiscVInstruction::FAdd { .. }
+        | RiscVInstruction::FSub { .. }
+        | RiscVInstruction::FMul { .. }
+        | RiscVInstruction::FDiv { .. }
+        | RiscVInstruction::FSqrt { .. }
+        | RiscVInstruction::FMin { .. }
+        | RiscVInstruction::FMax { .. }
+        | RiscVInstruction::FMadd { .. }
+        | RiscVInstruction::FMsub { .. }
+        | RiscVInstruction::FNmadd { .. }
+        | RiscVInstruction::FNmsub { .. }
+        | RiscVInstruction::FSgnj { .. }
+        | RiscVInstruction::FSgnjn { .. }
+        | RiscVInstruction::FSgnjx { .. }
+        | RiscVInstruction::FEq { .. }
+        | RiscVInstruction::FLt { .. }
+        | RiscVInstruction::FLe { .. }
+        | RiscVInstruction::FClass { .. }
+        | RiscVInstruction::FMvToInt { .. }
+        | RiscVInstruction::FMvFromInt { .. }
+        | RiscVInstruction::FCvtToInt { .. }
+        | RiscVInstruction::FCvtFromInt { .. }
+        | RiscVInstruction::FCvt { .. }) => translate_float(riscv_instr)?,
+        RiscVInstruction::Nop => vec![ArmInstruction::Nop],
         RiscVInstruction::Verbatim { text } => vec![ArmInstruction::Verbatim { text }],
+    };
+    Ok(arm_instrs)
+}
+
+/// The weakest `dmb` which orders the accesses in `pred` before those in
+/// `succ`, or `None` if either set is empty.
+///
+/// | `pred`    | `succ`    | ARM         |
+/// |-----------|-----------|-------------|
+/// | `r`       | anything  | `dmb ishld` |
+/// | `w`       | `w`       | `dmb ishst` |
+/// | `w`       | `r`, `rw` | `dmb ish`   |
+/// | `rw`      | anything  | `dmb ish`   |
+///
+/// `dmb ishld` orders loads before loads and stores, and `dmb ishst`
+/// stores before stores, but only `dmb ish` orders stores before loads,
+/// or covers both of the other two. Device input and output are ordered
+/// like reads and writes, since translated programs are user processes
+/// which can only reach devices through memory mapped by the kernel.
+pub fn fence_barrier(pred: RiscVFenceSet, succ: RiscVFenceSet) -> Option<ArmBarrier> {
+    let is_empty = |set: RiscVFenceSet| !set.reads() && !set.writes();
+    if is_empty(pred) || is_empty(succ) {
+        None
+    } else if pred.writes() && (pred.reads() || succ.reads()) {
+        Some(ArmBarrier::Ish)
+    } else if pred.reads() {
+        Some(ArmBarrier::Ishld)
+    } else {
+        Some(ArmBarrier::Ishst)
+    }
+}
+
+/// `fence.i` as the ARM sequence which makes stores visible to instruction
+/// fetches, over the whole code of the program:
+///
+/// ```text
+/// stp   x9, x10, [sp, -16]!
+/// mrs   x7, ctr_el0
+/// lsr   x9, x7, 16
+/// and   x9, x9, 15
+/// and   x7, x7, 15
+/// cmp   x7, x9
+/// csel  x7, x7, x9, lo
+/// mov   x9, 4
+/// lsl   x7, x9, x7
+/// adrp  x9, __executable_start
+/// add   x9, x9, :lo12:__executable_start
+/// adrp  x10, etext
+/// add   x10, x10, :lo12:etext
+/// dc    cvau, x9
+/// dsb   ish
+/// ic    ivau, x9
+/// add   x9, x9, x7
+/// cmp   x9, x10
+/// b.lo  .-20
+/// dsb   ish
+/// isb
+/// ldp   x9, x10, [sp], 16
+/// ```
+///
+/// ARM cleans the data cache and invalidates the instruction cache one
+/// line at a time, but `fence.i` doesn't say which lines were written, so
+/// every line from `__executable_start` to `etext` is, which GNU ld
+/// provides around the code as [`assemble`](crate::encode::assemble)
+/// does. The lines are as long as the smaller of the data and instruction
+/// cache lines in `ctr_el0`, so none are skipped.
+fn translate_fence_i() -> Vec<ArmInstruction> {
+    let x = |name| ArmRegister::new(name, ArmWidth::Double);
+    let (line, cursor, end) = (x(SCRATCH), x(ArmRegisterName::X9), x(ArmRegisterName::X10));
+    let address = |dest, symbol: &str| {
+        [
+            ArmInstruction::Adrp {
+                dest,
+                label: ArmVal::Reloc(ArmReloc::Page, symbol.to_string(), 0),
+            },
+            ArmInstruction::Add {
+                dest,
+                arg1: dest,
+                arg2: ArmVal::Reloc(ArmReloc::Lo12, symbol.to_string(), 0),
+            },
+        ]
+    };
+    let sp = ArmRegister::new(ArmRegisterName::Sp, ArmWidth::Double);
+
+    let mut instrs = vec![
+        ArmInstruction::Stp {
+            src1: cursor,
+            src2: end,
+            dest: ArmVal::PreIndex(sp, -16),
+        },
+        // the line sizes are log2 of their words, `DminLine` in bits 16 to
+        // 19 and `IminLine` in bits 0 to 3
+        ArmInstruction::Mrs {
+            dest: line,
+            src: ArmSystemRegister::CtrEl0,
+        },
+        ArmInstruction::Lsr {
+            dest: cursor,
+            src: line,
+            imm: 16,
+        },
+        ArmInstruction::And {
+            dest: cursor,
+            arg1: cursor,
+            arg2: ArmVal::Imm(15),
+        },
+        ArmInstruction::And {
+            dest: line,
+            arg1: line,
+            arg2: ArmVal::Imm(15),
+        },
+        ArmInstruction::Cmp {
+            op1: line,
+            op2: ArmVal::Reg(cursor),
+        },
+        ArmInstruction::Csel {
+            dest: line,
+            arg1: line,
+            arg2: cursor,
+            cond: ArmCondition::Lo,
+        },
+        ArmInstruction::Mov {
+            width: ArmWidth::Double,
+            dest: cursor,
+            src: ArmVal::Imm(4),
+        },
+        ArmInstruction::Lslv {
+            dest: line,
+            arg1: cursor,
+            arg2: line,
+        },
+    ];
+    // `__executable_start` is page aligned, so `cursor` stays at the start
+    // of a line
+    instrs.extend(address(cursor, "__executable_start"));
+    instrs.extend(address(end, "etext"));
+    let body = [
+        ArmInstruction::DcCvau { addr: cursor },
+        ArmInstruction::Dsb {
+            option: ArmBarrier::Ish,
+        },
+        ArmInstruction::IcIvau { addr: cursor },
+        ArmInstruction::Add {
+            dest: cursor,
+            arg1: cursor,
+            arg2: ArmVal::Reg(line),
+        },
+        ArmInstruction::Cmp {
+            op1: cursor,
+            op2: ArmVal::Reg(end),
+        },
+    ];
+    let back = loop_back(&body);
+    instrs.extend(body);
+    instrs.extend([
+        ArmInstruction::BCond {
+            cond: ArmCondition::Lo,
+            target: back,
+        },
+        ArmInstruction::Dsb {
+            option: ArmBarrier::Ish,
+        },
+        ArmInstruction::Isb,
+        ArmInstruction::Ldp {
+            dest1: cursor,
+            dest2: end,
+            src: ArmVal::PostIndex(sp, 16),
+        },
+    ]);
+    instrs
+}
+
+/// A branch target back to the start of `body`, the loop right before the
+/// branch. The offset is counted before [`legalize`] runs on the
+/// translation, so it must leave the loop as it is.
+fn loop_back(body: &[ArmInstruction]) -> ArmVal {
+    debug_assert_eq!(
+        legalize(body.to_vec()).len(),
+        body.len(),
+        "legalize changes the loop {:?}",
+        body
+    );
+    ArmVal::LabelOffset(".".to_string(), -4 * body.len() as i32)
+}
+
+/// Scratch register for translations which need more than one instruction.
+/// X7 is the only general purpose register that [`map_register_name`] does
+/// not map a RISC-V register to, so it is free between translated
+/// instructions.
+pub(crate) const SCRATCH: ArmRegisterName = ArmRegisterName::X7;
+
+fn zero_register(riscv_width: &RiscVWidth) -> Result<ArmRegister, AsmError> {
+    arm_register(ArmRegisterName::Zero, map_register_width(riscv_width))
+}
+
+/// RISC-V `*w` instructions sign extend their 32 bit result into the whole
+/// register, while ARM `w` registers zero extend. A result written to
+/// `zero` is discarded, so it needs no extension.
+pub(crate) fn sign_extend_word(reg: RiscVRegister) -> Result<Option<ArmInstruction>, AsmError> {
+    if reg == RiscVRegister::X0 {
+        return Ok(None);
+    }
+    Ok(Some(ArmInstruction::Sxtw {
+        dest: map_register(reg, &RiscVWidth::Double)?,
+        src: map_register(reg, &RiscVWidth::Word)?,
+    }))
+}
+
+/// The register which `riscv_instr` leaves holding a sign extended 32 bit
+/// value, so that a `sext.w` of it right after does nothing.
+fn sign_extended_dest(riscv_instr: &RiscVInstruction) -> Option<RiscVRegister> {
+    use RiscVInstruction::*;
+
+    match *riscv_instr {
+        Addiw { dest, .. } | SextW { dest, .. } => Some(dest),
+        Add { width, dest, .. }
+        | Sub { width, dest, .. }
+        | Mul { width, dest, .. }
+        | Div { width, dest, .. }
+        | Divu { width, dest, .. }
+        | Rem { width, dest, .. }
+        | Remu { width, dest, .. }
+        | Neg { width, dest, .. }
+        | Sll { width, dest, .. }
+        | Srl { width, dest, .. }
+        | Sra { width, dest, .. }
+        | Slli { width, dest, .. }
+        | Srli { width, dest, .. }
+        | Srai { width, dest, .. }
+        | L { width, dest, .. }
+        | Lr { width, dest, .. }
+        | Amo { width, dest, .. }
+        | FCvtToInt { width, dest, .. }
+            if width == RiscVWidth::Word =>
+        {
+            Some(dest)
+        }
+        FMvToInt {
+            precision: RiscVPrecision::Single,
+            dest,
+            ..
+        } => Some(dest),
+        _ => None,
+    }
+}
+
+/// Drop each `sext.w` of a register which the instruction right before it
+/// left sign extended, keeping the line of every other instruction.
+fn elide_sign_extensions(
+    riscv_instrs: Vec<(usize, RiscVInstruction)>,
+) -> Vec<(usize, RiscVInstruction)> {
+    let mut sign_extended = None;
+    riscv_instrs
+        .into_iter()
+        .filter(|(_, riscv_instr)| {
+            let redundant = matches!(
+                *riscv_instr,
+                RiscVInstruction::SextW { dest, src } if dest == src && Some(dest) == sign_extended
+            );
+            sign_extended = sign_extended_dest(riscv_instr);
+            !redundant
+        })
+        .collect()
+}
+
+/// `ra` is `lr`, so linking to it is a `bl`. Other link registers get the
+/// return address from an `adr`, which leaves `lr` alone.
+fn translate_jal(dest: RiscVRegister, target: RiscVVal) -> Result<Vec<ArmInstruction>, AsmError> {
+    let target = map_val(target, &RiscVWidth::Double)?;
+    Ok(match dest {
+        RiscVRegister::X0 => vec![ArmInstruction::B { target }],
+        RiscVRegister::RA => vec![ArmInstruction::Bl { target }],
+        _ => vec![link(dest)?, ArmInstruction::B { target }],
+    })
+}
+
+/// `jalr x0, 0(ra)` is a `ret`, linking to `ra` is a `blr`, and anything
+/// else is a `br`, after an `adr` for other link registers.
+fn translate_jalr(
+    dest: RiscVRegister,
+    target: RiscVRegister,
+    offset: i32,
+) -> Result<Vec<ArmInstruction>, AsmError> {
+    if dest == RiscVRegister::X0 && target == RiscVRegister::RA && offset == 0 {
+        return Ok(vec![ArmInstruction::Ret]);
+    }
+
+    let mut instrs = vec![];
+    let mut target_name = map_register_name(target);
+    // `adr` would overwrite the target before the jump reads it
+    let overwritten = dest == target && !matches!(dest, RiscVRegister::X0 | RiscVRegister::RA);
+    if offset != 0 || overwritten {
+        let width = RiscVWidth::Double;
+        instrs.push(ArmInstruction::Add {
+            dest: ArmRegister::new(SCRATCH, ArmWidth::Double),
+            arg1: map_register(target, &width)?,
+            arg2: ArmVal::Imm(offset.into()),
+        });
+        target_name = SCRATCH;
+    }
+    match dest {
+        RiscVRegister::X0 => instrs.push(ArmInstruction::Br {
+            target: target_name,
+        }),
+        RiscVRegister::RA => instrs.push(ArmInstruction::Blr {
+            target: target_name,
+        }),
+        _ => {
+            instrs.push(link(dest)?);
+            instrs.push(ArmInstruction::Br {
+                target: target_name,
+            });
+        }
+    }
+    Ok(instrs)
+}
+
+/// Set `dest` to the return address of the jump after this instruction.
+fn link(dest: RiscVRegister) -> Result<ArmInstruction, AsmError> {
+    Ok(ArmInstruction::Adr {
+        dest: map_register(dest, &RiscVWidth::Double)?,
+        label: ArmVal::LabelOffset(".".to_string(), 8),
+    })
+}
+
+/// `stxr` sets its status to 0 on success and 1 on failure, which is what
+/// `sc` leaves in `dest`. The status can't be one of the other operands, so
+/// it goes through the scratch register when `dest` is.
+fn translate_sc(
+    width: RiscVWidth,
+    ordering: RiscVOrdering,
+    dest: RiscVRegister,
+    src: RiscVRegister,
+    addr: RiscVRegister,
+) -> Result<Vec<ArmInstruction>, AsmError> {
+    let via_scratch = dest == src || dest == addr;
+    let status = if via_scratch {
+        SCRATCH
+    } else {
+        map_register_name(dest)
+    };
+    let mut instrs = vec![ArmInstruction::Stxr {
+        release: ordering.release(),
+        status: ArmRegister::new(status, ArmWidth::Word),
+        src: map_register(src, &width)?,
+        addr: map_register_name(addr),
+    }];
+    if via_scratch {
+        instrs.push(ArmInstruction::Mov {
+            width: ArmWidth::Double,
+            dest: map_register(dest, &RiscVWidth::Double)?,
+            src: ArmVal::Reg(ArmRegister::new(SCRATCH, ArmWidth::Double)),
+        });
+    }
+    Ok(instrs)
+}
+
+/// An AMO as a `ldxr`/`stxr` loop, which retries until nothing else wrote
+/// the address between the load and the store:
+///
+/// ```text
+/// stp   x9, x10, [sp, -16]!
+/// ldxr  x7, [addr]
+/// add   x9, x7, src
+/// stxr  w10, x9, [addr]
+/// cbnz  w10, .-12
+/// ldp   x9, x10, [sp], 16
+/// mov   dest, x7
+/// ```
+///
+/// The loop needs the old value, the new value and the status of `stxr`
+/// in registers which aren't operands, but only the scratch register is
+/// free, so the other two are saved on the stack around the loop.
+fn translate_amo(
+    op: RiscVAmoOp,
+    width: RiscVWidth,
+    ordering: RiscVOrdering,
+    dest: RiscVRegister,
+    src: RiscVRegister,
+    addr: RiscVRegister,
+) -> Result<Vec<ArmInstruction>, AsmError> {
+    let addr = map_register_name(addr);
+    let src = map_register(src, &width)?;
+    let mut spares = [
+        ArmRegisterName::X9,
+        ArmRegisterName::X10,
+        ArmRegisterName::X11,
+        ArmRegisterName::X12,
+    ]
+    .into_iter()
+    .filter(|&name| name != addr && name != src.name());
+    let (spare1, spare2) = (spares.next().unwrap(), spares.next().unwrap());
+
+    let old = arm_register(SCRATCH, map_register_width(&width))?;
+    let new = arm_register(spare1, map_register_width(&width))?;
+    let status = ArmRegister::new(spare2, ArmWidth::Word);
+    let mut body = vec![ArmInstruction::Ldxr {
+        acquire: ordering.acquire(),
+        dest: old,
+        addr,
+    }];
+    let stored = match op {
+        RiscVAmoOp::Swap => src,
+        _ => {
+            body.extend(amo_op(op, new, old, src));
+            new
+        }
+    };
+    body.push(ArmInstruction::Stxr {
+        release: ordering.release(),
+        status,
+        src: stored,
+        addr,
+    });
+    body.push(ArmInstruction::Cbnz {
+        src: status,
+        target: loop_back(&body),
+    });
+
+    let sp = ArmRegister::new(ArmRegisterName::Sp, ArmWidth::Double);
+    let mut instrs = vec![ArmInstruction::Stp {
+        src1: ArmRegister::new(spare1, ArmWidth::Double),
+        src2: ArmRegister::new(spare2, ArmWidth::Double),
+        dest: ArmVal::PreIndex(sp, -16),
+    }];
+    instrs.extend(body);
+    instrs.push(ArmInstruction::Ldp {
+        dest1: ArmRegister::new(spare1, ArmWidth::Double),
+        dest2: ArmRegister::new(spare2, ArmWidth::Double),
+        src: ArmVal::PostIndex(sp, 16),
+    });
+    if dest != RiscVRegister::X0 {
+        instrs.push(match width {
+            RiscVWidth::Word => ArmInstruction::Sxtw {
+                dest: map_register(dest, &RiscVWidth::Double)?,
+                src: old,
+            },
+            _ => ArmInstruction::Mov {
+                width: ArmWidth::Double,
+                dest: map_register(dest, &RiscVWidth::Double)?,
+                src: ArmVal::Reg(old),
+            },
+        });
+    }
+    Ok(instrs)
+}
+
+/// `new = old op src`, for every AMO but `amoswap`.
+fn amo_op(
+    op: RiscVAmoOp,
+    new: ArmRegister,
+    old: ArmRegister,
+    src: ArmRegister,
+) -> Vec<ArmInstruction> {
+    let select = |cond| {
+        vec![
+            ArmInstruction::Cmp {
+                op1: old,
+                op2: ArmVal::Reg(src),
+            },
+            ArmInstruction::Csel {
+                dest: new,
+                arg1: old,
+                arg2: src,
+                cond,
+            },
+        ]
+    };
+    match op {
+        RiscVAmoOp::Swap => vec![],
+        RiscVAmoOp::Add => vec![ArmInstruction::Add {
+            dest: new,
+            arg1: old,
+            arg2: ArmVal::Reg(src),
+        }],
+        RiscVAmoOp::And => vec![ArmInstruction::And {
+            dest: new,
+            arg1: old,
+            arg2: ArmVal::Reg(src),
+        }],
+        RiscVAmoOp::Or => vec![ArmInstruction::Orr {
+            dest: new,
+            arg1: old,
+            arg2: ArmVal::Reg(src),
+        }],
+        RiscVAmoOp::Xor => vec![ArmInstruction::Eor {
+            dest: new,
+            arg1: old,
+            arg2: ArmVal::Reg(src),
+        }],
+        RiscVAmoOp::Min => select(ArmCondition::Lt),
+        RiscVAmoOp::Max => select(ArmCondition::Gt),
+        RiscVAmoOp::Minu => select(ArmCondition::Lo),
+        RiscVAmoOp::Maxu => select(ArmCondition::Hi),
+    }
+}
+
+/// An AMO as a single ARMv8.1 LSE instruction. LSE has no atomic and, so
+/// `amoand` clears the bits of the inverted source instead.
+fn translate_amo_lse(
+    op: RiscVAmoOp,
+    width: RiscVWidth,
+    ordering: RiscVOrdering,
+    dest: RiscVRegister,
+    src: RiscVRegister,
+    addr: RiscVRegister,
+) -> Result<Vec<ArmInstruction>, AsmError> {
+    let acquire = ordering.acquire();
+    let release = ordering.release();
+    let mut src = map_register(src, &width)?;
+    let addr = map_register_name(addr);
+    let mut instrs = vec![];
+    let op = match op {
+        RiscVAmoOp::Swap => None,
+        RiscVAmoOp::Add => Some(ArmAtomicOp::Add),
+        RiscVAmoOp::And => {
+            let scratch = arm_register(SCRATCH, map_register_width(&width))?;
+            instrs.push(ArmInstruction::Mvn { dest: scratch, src });
+            src = scratch;
+            Some(ArmAtomicOp::Clr)
+        }
+        RiscVAmoOp::Or => Some(ArmAtomicOp::Set),
+        RiscVAmoOp::Xor => Some(ArmAtomicOp::Eor),
+        RiscVAmoOp::Min => Some(ArmAtomicOp::Smin),
+        RiscVAmoOp::Max => Some(ArmAtomicOp::Smax),
+        RiscVAmoOp::Minu => Some(ArmAtomicOp::Umin),
+        RiscVAmoOp::Maxu => Some(ArmAtomicOp::Umax),
+    };
+    let atomic = match op {
+        None => ArmInstruction::Swp {
+            acquire,
+            release,
+            src,
+            dest: map_register(dest, &width)?,
+            addr,
+        },
+        Some(op) => ArmInstruction::LdOp {
+            op,
+            acquire,
+            release,
+            src,
+            dest: map_register(dest, &width)?,
+            addr,
+        },
+    };
+    instrs.extend(sign_extend_if_word(width, dest, atomic)?);
+    Ok(instrs)
+}
+
+/// Compare two registers, setting the flags for `arg1 - arg2`.
+fn compare(arg1: RiscVRegister, arg2: RiscVRegister) -> Result<Vec<ArmInstruction>, AsmError> {
+    let width = RiscVWidth::Double;
+    Ok(vec![ArmInstruction::Cmp {
+        op1: map_register(arg1, &width)?,
+        op2: ArmVal::Reg(map_register(arg2, &width)?),
+    }])
+}
+
+/// Compare a register with an immediate, setting the flags for
+/// `src - imm`.
+fn compare_imm(src: RiscVRegister, imm: i32) -> Result<Vec<ArmInstruction>, AsmError> {
+    Ok(vec![ArmInstruction::Cmp {
+        op1: map_register(src, &RiscVWidth::Double)?,
+        op2: ArmVal::Imm(imm.into()),
+    }])
+}
+
+/// Set `dest` to 1 if `cond` holds after `compare`, and to 0 otherwise.
+fn set_if(
+    dest: RiscVRegister,
+    mut compare: Vec<ArmInstruction>,
+    cond: ArmCondition,
+) -> Result<Vec<ArmInstruction>, AsmError> {
+    compare.push(ArmInstruction::Cset {
+        dest: map_register(dest, &RiscVWidth::Double)?,
+        cond,
+    });
+    Ok(compare)
+}
+
+/// Branch to `target` if `cond` holds after `compare`.
+fn branch_if(
+    mut compare: Vec<ArmInstruction>,
+    cond: ArmCondition,
+    target: RiscVVal,
+) -> Result<Vec<ArmInstruction>, AsmError> {
+    compare.push(ArmInstruction::BCond {
+        cond,
+        target: map_val(target, &RiscVWidth::Double)?,
+    });
+    Ok(compare)
+}
+
+/// Append the sign extension of `dest` after `arm_instr` for `*w`
+/// instructions.
+fn sign_extend_if_word(
+    width: RiscVWidth,
+    dest: RiscVRegister,
+    arm_instr: ArmInstruction,
+) -> Result<Vec<ArmInstruction>, AsmError> {
+    let mut instrs = vec![arm_instr];
+    if width == RiscVWidth::Word {
+        instrs.extend(sign_extend_word(dest)?);
+    }
+    Ok(instrs)
+}
+
+/// `add`/`sub` and their `*w` forms, with `op` building the ARM instruction.
+///
+/// The register form reads a second operand of register 31 as the zero
+/// register, so an `sp` there is swapped to the first operand when the
+/// operation `commutes`, and copied to the scratch register otherwise.
+fn translate_add_sub(
+    width: RiscVWidth,
+    dest: RiscVRegister,
+    arg1: RiscVRegister,
+    arg2: RiscVRegister,
+    commutes: bool,
+    op: fn(ArmRegister, ArmRegister, ArmVal) -> ArmInstruction,
+) -> Result<Vec<ArmInstruction>, AsmError> {
+    let (arg1, arg2) = match arg2 {
+        RiscVRegister::SP if commutes => (arg2, arg1),
+        _ => (arg1, arg2),
+    };
+    let mut instrs = vec![];
+    let mut arg2 = map_register(arg2, &width)?;
+    if let ArmRegisterName::Sp = arg2.name() {
+        instrs.push(ArmInstruction::Mov {
+            width: ArmWidth::Double,
+            dest: ArmRegister::new(SCRATCH, ArmWidth::Double),
+            src: ArmVal::Reg(ArmRegister::new(ArmRegisterName::Sp, ArmWidth::Double)),
+        });
+        arg2 = arm_register(SCRATCH, map_register_width(&width))?;
+    }
+    instrs.extend(sign_extend_if_word(
+        width,
+        dest,
+        op(
+            map_register(dest, &width)?,
+            map_register(arg1, &width)?,
+            ArmVal::Reg(arg2),
+        ),
+    )?);
+    Ok(instrs)
+}
+
+/// `andi`/`ori`/`xori`, with `op` building the ARM instruction.
+///
+/// In the immediate form a source of register 31 is the zero register, so
+/// an `sp` source is copied to the scratch register instead.
+fn translate_logical_imm(
+    dest: RiscVRegister,
+    src: RiscVRegister,
+    imm: i32,
+    op: fn(ArmRegister, ArmRegister, ArmVal) -> ArmInstruction,
+) -> Result<Vec<ArmInstruction>, AsmError> {
+    let width = RiscVWidth::Double;
+    let dest = map_register(dest, &width)?;
+    let mut src = map_register(src, &width)?;
+    let mut instrs = vec![];
+    if let ArmRegisterName::Sp = src.name() {
+        let scratch = ArmRegister::new(SCRATCH, ArmWidth::Double);
+        instrs.push(ArmInstruction::Mov {
+            width: ArmWidth::Double,
+            dest: scratch,
+            src: ArmVal::Reg(src),
+        });
+        src = scratch;
+        // `sp` took the scratch register that legalizing the immediate
+        // needs, so the immediate goes in `dest` instead, which
+        // `check_operands` made sure isn't `sp`
+        if !is_logical_imm(imm as u64, ArmWidth::Double) {
+            if let ArmRegisterName::Zero = dest.name() {
+                return Ok(vec![]);
+            }
+            instrs.extend([
+                ArmInstruction::Mov {
+                    width: ArmWidth::Double,
+                    dest,
+                    src: ArmVal::Imm(imm.into()),
+                },
+                op(dest, src, ArmVal::Reg(dest)),
+            ]);
+            return Ok(instrs);
+        }
+    }
+    instrs.push(op(dest, src, ArmVal::Imm(imm.into())));
+    Ok(instrs)
+}
+
+/// RISC-V division never traps. Dividing by zero gives a quotient of all
+/// ones, but ARM `sdiv`/`udiv` give 0, so the quotient is inverted with
+/// `csinv` when the divisor is zero. Signed overflow (`MIN / -1`) gives
+/// `MIN` on both.
+fn translate_div(
+    width: RiscVWidth,
+    dest: RiscVRegister,
+    arg1: RiscVRegister,
+    arg2: RiscVRegister,
+    signed: bool,
+) -> Result<Vec<ArmInstruction>, AsmError> {
+    let dest_reg = map_register(dest, &width)?;
+    let arg1 = map_register(arg1, &width)?;
+    let arg2 = map_register(arg2, &width)?;
+    let div = if signed {
+        ArmInstruction::Sdiv {
+            dest: dest_reg,
+            arg1,
+            arg2,
+        }
+    } else {
+        ArmInstruction::Udiv {
+            dest: dest_reg,
+            arg1,
+            arg2,
+        }
+    };
+    let mut instrs = vec![
+        // compare before dividing, which may overwrite the divisor
+        ArmInstruction::Cmp {
+            op1: arg2,
+            op2: ArmVal::Reg(zero_register(&width)?),
+        },
+        div,
+        ArmInstruction::Csinv {
+            dest: dest_reg,
+            arg1: dest_reg,
+            arg2: zero_register(&width)?,
+            cond: ArmCondition::Ne,
+        },
+    ];
+    if width == RiscVWidth::Word {
+        instrs.extend(sign_extend_word(dest)?);
+    }
+    Ok(instrs)
+}
+
+/// The remainder is `a - (a / b) * b`. This already matches RISC-V for
+/// division by zero, where ARM's quotient of 0 leaves the dividend, and for
+/// signed overflow, where the remainder is 0.
+fn translate_rem(
+    width: RiscVWidth,
+    dest: RiscVRegister,
+    arg1: RiscVRegister,
+    arg2: RiscVRegister,
+    signed: bool,
+) -> Result<Vec<ArmInstruction>, AsmError> {
+    let scratch = arm_register(SCRATCH, map_register_width(&width))?;
+    let arg1 = map_register(arg1, &width)?;
+    let arg2 = map_register(arg2, &width)?;
+    let div = if signed {
+        ArmInstruction::Sdiv {
+            dest: scratch,
+            arg1,
+            arg2,
+        }
+    } else {
+        ArmInstruction::Udiv {
+            dest: scratch,
+            arg1,
+            arg2,
+        }
+    };
+    let mut instrs = vec![
+        div,
+        ArmInstruction::Msub {
+            dest: map_register(dest, &width)?,
+            arg1: scratch,
+            arg2,
+            arg3: arg1,
+        },
+    ];
+    if width == RiscVWidth::Word {
+        instrs.extend(sign_extend_word(dest)?);
+    }
+    Ok(instrs)
+}
+
+/// Check operands which the ARM translation of an instruction cannot
+/// express, before translating it.
+fn check_operands(riscv_instr: &RiscVInstruction) -> Result<(), &'static str> {
+    use RiscVInstruction::*;
+
+    match *riscv_instr {
+        Slli { width, imm, .. } | Srli { width, imm, .. } | Srai { width, imm, .. } => {
+            let bits = match width {
+                RiscVWidth::Word => 32,
+                _ => 64,
+            };
+            if !(0..bits).contains(&imm) {
+                return Err("shift amount out of range");
+            }
+        }
+        // `pair_pcrel` replaces every `%pcrel_lo` which has an `auipc`
+        Addl {
+            label:
+                RiscVVal::Reloc {
+                    reloc: RiscVReloc::PcrelLo12,
+                    ..
+                },
+            ..
+        }
+        | L {
+            src:
+                RiscVVal::RelocOffset {
+                    reloc: RiscVReloc::PcrelLo12,
+                    ..
+                },
+            ..
+        }
+        | S {
+            dest:
+                RiscVVal::RelocOffset {
+                    reloc: RiscVReloc::PcrelLo12,
+                    ..
+                },
+            ..
+        }
+        | FL {
+            src:
+                RiscVVal::RelocOffset {
+                    reloc: RiscVReloc::PcrelLo12,
+                    ..
+                },
+            ..
+        }
+        | FS {
+            dest:
+                RiscVVal::RelocOffset {
+                    reloc: RiscVReloc::PcrelLo12,
+                    ..
+                },
+            ..
+        } => return Err("`%pcrel_lo` without a matching `%pcrel_hi`"),
+        // the result is relative to the RISC-V address of the `auipc`,
+        // which the translation doesn't keep
+        Auipc {
+            src: RiscVVal::Immediate(_),
+            ..
+        } => return Err("auipc needs a %pcrel_hi/%pcrel_lo pair"),
+        // ARM arithmetic rounds with the mode in `FPCR`, which is always
+        // `rne`. Conversions which are always exact can ignore the mode.
+        FAdd { rounding, .. }
+        | FSub { rounding, .. }
+        | FMul { rounding, .. }
+        | FDiv { rounding, .. }
+        | FSqrt { rounding, .. }
+        | FMadd { rounding, .. }
+        | FMsub { rounding, .. }
+        | FNmadd { rounding, .. }
+        | FNmsub { rounding, .. }
+        | FCvt {
+            precision: RiscVPrecision::Single,
+            rounding,
+            ..
+        }
+        | FCvtFromInt { rounding, .. }
+            if !matches!(rounding, RiscVRounding::Rne | RiscVRounding::Dyn)
+                && !matches!(
+                    *riscv_instr,
+                    FCvtFromInt {
+                        precision: RiscVPrecision::Double,
+                        width: RiscVWidth::Word,
+                        ..
+                    }
+                ) =>
+        {
+            return Err("static rounding modes other than `rne` are not supported")
+        }
+        // register 31 is `sp` as the address of exclusives and atomics
+        Lr {
+            addr: RiscVRegister::X0,
+            ..
+        }
+        | Sc {
+            addr: RiscVRegister::X0,
+            ..
+        }
+        | Amo {
+            addr: RiscVRegister::X0,
+            ..
+        } => return Err("`zero` is not supported as the address of an atomic"),
+        _ => {}
     }
+
+    // ARM reads register 31 as the zero register rather than `sp` in the
+    // register forms of logical and shift instructions
+    let registers = match *riscv_instr {
+        And { dest, arg1, arg2 }
+        | Or { dest, arg1, arg2 }
+        | Xor { dest, arg1, arg2 }
+        | Sll {
+            dest, arg1, arg2, ..
+        }
+        | Srl {
+            dest, arg1, arg2, ..
+        }
+        | Sra {
+            dest, arg1, arg2, ..
+        } => vec![dest, arg1, arg2],
+        // and in multiply and divide instructions
+        Mul {
+            dest, arg1, arg2, ..
+        }
+        | Mulh { dest, arg1, arg2 }
+        | Mulhu { dest, arg1, arg2 }
+        | Mulhsu { dest, arg1, arg2 }
+        | Div {
+            dest, arg1, arg2, ..
+        }
+        | Divu {
+            dest, arg1, arg2, ..
+        }
+        | Rem {
+            dest, arg1, arg2, ..
+        }
+        | Remu {
+            dest, arg1, arg2, ..
+        } => vec![dest, arg1, arg2],
+        Not { dest, src }
+        | Neg { dest, src, .. }
+        | Slli { dest, src, .. }
+        | Srli { dest, src, .. }
+        | Srai { dest, src, .. } => vec![dest, src],
+        // the second operand of `cmp` can't be `sp`, and neither can the
+        // destination of `cset`
+        Slt { dest, arg2, .. } | Sltu { dest, arg2, .. } => vec![dest, arg2],
+        Slti { dest, .. }
+        | Sltiu { dest, .. }
+        | Seqz { dest, .. }
+        | Snez { dest, .. }
+        | Sltz { dest, .. }
+        | Sgtz { dest, .. } => vec![dest],
+        Beq { arg2, .. }
+        | Bne { arg2, .. }
+        | Blt { arg2, .. }
+        | Bge { arg2, .. }
+        | Bgt { arg2, .. }
+        | Ble { arg2, .. }
+        | Bltu { arg2, .. }
+        | Bgeu { arg2, .. }
+        | Bgtu { arg2, .. }
+        | Bleu { arg2, .. } => vec![arg2],
+        // register 31 is the zero register for the values of atomics, and
+        // the stack pointer moves during an AMO loop
+        Lr { dest, .. } => vec![dest],
+        Sc { dest, src, .. } => vec![dest, src],
+        Amo {
+            dest, src, addr, ..
+        } => vec![dest, src, addr],
+        // and for the integer operands of floating point instructions
+        FEq { dest, .. }
+        | FLt { dest, .. }
+        | FLe { dest, .. }
+        | FClass { dest, .. }
+        | FMvToInt { dest, .. }
+        | FCvtToInt { dest, .. } => vec![dest],
+        FMvFromInt { src, .. } | FCvtFromInt { src, .. } => vec![src],
+        // an immediate which isn't a logical immediate needs the register
+        // form, which can't write `sp`
+        Andi { dest, imm, .. } | Ori { dest, imm, .. } | Xori { dest, imm, .. }
+            if !is_logical_imm(imm as u64, ArmWidth::Double) =>
+        {
+            vec![dest]
+        }
+        // nor for the data of loads and stores
+        L { dest, .. } => vec![dest],
+        S { src, .. } => vec![src],
+        // `mov`, `movk`, `adr` and `adrp` can't write `sp`
+        Li { dest, .. }
+        | Mvi { dest, .. }
+        | Lui { dest, .. }
+        | Auipc { dest, .. }
+        | Lla { dest, .. }
+        | La { dest, .. }
+        | Jal { dest, .. } => vec![dest],
+        // and neither can `br` or `blr` jump to it
+        Jalr { dest, target, .. } => vec![dest, target],
+        SextW { dest, src } => vec![dest, src],
+        // `sxtw` can't write `sp`
+        _ => sign_extended_dest(riscv_instr).into_iter().collect(),
+    };
+    if registers.contains(&RiscVRegister::SP) {
+        return Err("`sp` is not supported as an operand of this instruction");
+    }
+    Ok(())
 }
 
-fn map_register(riscv_reg: RiscVRegister, riscv_width: &RiscVWidth) -> ArmRegister {
-    ArmRegister {
-        width: map_width(riscv_width),
-        name: map_register_name(riscv_reg),
+/// The operands of a directive in ARM syntax, which writes the `@type` of
+/// a symbol or section as `%type`. An `@` in a string is left alone.
+fn arm_directive_operands(name: &str, operands: &str) -> String {
+    if !matches!(name, "type" | "section" | "pushsection") {
+        return operands.to_string();
+    }
+    let mut in_string = false;
+    let mut escaped = false;
+    operands
+        .chars()
+        .map(|c| {
+            match c {
+                _ if escaped => escaped = false,
+                '\\' if in_string => escaped = true,
+                '"' => in_string = !in_string,
+                '@' if !in_string => return '%',
+                _ => {}
+            }
+            c
+        })
+        .collect()
+}
+
+/// Reject an instruction we have no translation for.
+fn unsupported(riscv_instr: RiscVInstruction, reason: &str) -> AsmError {
+    AsmError::new(0, 0, &format!("{:?}", riscv_instr), reason)
+}
+
+pub(crate) fn map_register(
+    riscv_reg: RiscVRegister,
+    riscv_width: &RiscVWidth,
+) -> Result<ArmRegister, AsmError> {
+    arm_register(
+        map_register_name(riscv_reg),
+        map_register_width(riscv_width),
+    )
+}
+
+/// The `width` view of `name`, which is an error rather than a panic since
+/// the width comes from the program being translated.
+pub(crate) fn arm_register(
+    name: ArmRegisterName,
+    width: ArmWidth,
+) -> Result<ArmRegister, AsmError> {
+    ArmRegister::try_new(name, width).ok_or_else(|| {
+        AsmError::new(
+            0,
+            0,
+            &format!("{:?}", name),
+            format!("{:?} has no {:?} register view", name, width),
+        )
+    })
+}
+
+/// Registers are used as 64 bit `x` registers, or as 32 bit `w` registers
+/// for anything narrower.
+fn map_register_width(riscv_width: &RiscVWidth) -> ArmWidth {
+    match riscv_width {
+        RiscVWidth::Double => ArmWidth::Double,
+        _ => ArmWidth::Word,
     }
 }
 
@@ -286,16 +1685,6 @@ fn map_register_name(riscv_reg: RiscVRegister) -> ArmRegisterName {
         RiscVRegister::T0 => ArmRegisterName::X9,
         RiscVRegister::T1 => ArmRegisterName::X10,
         RiscVRegister::T2 => ArmRegisterName::X11,
-        // skipped X5
-        //         RiscVRegister::S1 => ArmRegisterName::X6,
-        //         RiscVRegister::A0 => ArmRegisterName::X0,
-        //         RiscVRegister::A1 => ArmRegisterName::X1,
-        //         RiscVRegister::A2 => ArmRegisterName::X2,
-        //         RiscVRegister::A3 => ArmRegisterName::X3,
-        //         RiscVRegister::A4 => ArmRegisterName::X4,
-        //         RiscVRegister::A5 => ArmRegisterName::X5,
-        //         RiscVRegister::A6 => ArmRegisterName::X6,
-        //         RiscVRegister::A7 => ArmRegisterName::X7,
         RiscVRegister::S1 => ArmRegisterName::X13,
         RiscVRegister::A0 => ArmRegisterName::X0, // return value/syscall arg 0
         RiscVRegister::A1 => ArmRegisterName::X1, // syscall arg 1
@@ -306,8 +1695,10 @@ fn map_register_name(riscv_reg: RiscVRegister) -> ArmRegisterName {
         RiscVRegister::A6 => ArmRegisterName::X6, // syscall arg 6
         RiscVRegister::A7 => ArmRegisterName::X8, // syscall number
         RiscVRegister::S2 => ArmRegisterName::X15,
-        RiscVRegister::S3 => ArmRegisterName::X16,
-        RiscVRegister::S4 => ArmRegisterName::X17,
+        // a linker veneer may clobber x16 and x17 on the way to a call, so
+        // they hold temporaries, which don't live across calls
+        RiscVRegister::T3 => ArmRegisterName::X16,
+        RiscVRegister::T4 => ArmRegisterName::X17,
         RiscVRegister::S5 => ArmRegisterName::X18,
         RiscVRegister::S6 => ArmRegisterName::X19,
         RiscVRegister::S7 => ArmRegisterName::X20,
@@ -315,52 +1706,517 @@ fn map_register_name(riscv_reg: RiscVRegister) -> ArmRegisterName {
         RiscVRegister::S9 => ArmRegisterName::X22,
         RiscVRegister::S10 => ArmRegisterName::X23,
         RiscVRegister::S11 => ArmRegisterName::X24,
-        RiscVRegister::T3 => ArmRegisterName::X25,
-        RiscVRegister::T4 => ArmRegisterName::X26,
+        RiscVRegister::S3 => ArmRegisterName::X25,
+        RiscVRegister::S4 => ArmRegisterName::X26,
         RiscVRegister::T5 => ArmRegisterName::X27,
         RiscVRegister::T6 => ArmRegisterName::X28,
         RiscVRegister::S0FP => ArmRegisterName::X29,
     }
 }
 
-fn map_val(riscv_val: RiscVVal, riscv_width: &RiscVWidth) -> ArmVal {
-    match riscv_val {
-        RiscVVal::RiscVRegister(riscv_reg) => ArmVal::Reg(map_register(riscv_reg, riscv_width)),
-        RiscVVal::Immediate(imm) => ArmVal::Imm(imm),
+pub(crate) fn map_val(riscv_val: RiscVVal, riscv_width: &RiscVWidth) -> Result<ArmVal, AsmError> {
+    Ok(match riscv_val {
+        RiscVVal::RiscVRegister(riscv_reg) => ArmVal::Reg(map_register(riscv_reg, riscv_width)?),
+        RiscVVal::Immediate(imm) => ArmVal::Imm(imm.into()),
         RiscVVal::Offset { register, offset } => {
-            ArmVal::RegOffset(map_register(register, riscv_width), offset)
+            ArmVal::RegOffset(map_register(register, riscv_width)?, offset)
         }
         RiscVVal::LabelOffset { label, offset } => ArmVal::LabelOffset(label, offset),
-    }
+        RiscVVal::Reloc {
+            reloc,
+            label,
+            offset,
+        } => ArmVal::Reloc(map_reloc(reloc)?, label, offset),
+        RiscVVal::RelocOffset {
+            register,
+            reloc,
+            label,
+            offset,
+        } => ArmVal::RelocOffset(
+            map_register(register, riscv_width)?,
+            Box::new(ArmVal::Reloc(map_reloc(reloc)?, label, offset)),
+        ),
+    })
+}
+
+/// The relocation `reloc` of a plain label, for pseudo instructions which
+/// hide their relocations.
+fn relocate(reloc: ArmReloc, label: RiscVVal) -> Result<ArmVal, AsmError> {
+    Ok(match map_val(label, &RiscVWidth::Double)? {
+        ArmVal::LabelOffset(label, offset) => ArmVal::Reloc(reloc, label, offset),
+        other => other,
+    })
+}
+
+/// The parts of an address are split at the page on ARM rather than at the
+/// lower 12 bits, so `adrp` and `:lo12:` stand in for `%hi` and `%lo`.
+fn map_reloc(riscv_reloc: RiscVReloc) -> Result<ArmReloc, AsmError> {
+    Ok(match riscv_reloc {
+        RiscVReloc::Hi20 | RiscVReloc::PcrelHi20 => ArmReloc::Page,
+        RiscVReloc::Lo12 | RiscVReloc::PcrelLo12 => ArmReloc::Lo12,
+        RiscVReloc::GotPcrelHi20 => ArmReloc::GotPage,
+        RiscVReloc::GotPcrelLo12 => ArmReloc::GotLo12,
+        // the thread pointer is `tpidr_el0` on ARM rather than a register
+        // which `tp` maps to
+        RiscVReloc::TprelHi20 | RiscVReloc::TprelLo12 => {
+            return Err(AsmError::new(
+                0,
+                0,
+                &format!("{:?}", riscv_reloc),
+                "thread-local relocations are not supported",
+            ))
+        }
+    })
 }
 
+/// RISC-V loads sign extend by default and ARM loads zero extend, so the
+/// signedness of the narrow widths flips.
 fn map_width(riscv_width: &RiscVWidth) -> ArmWidth {
-    // todo!()
-    // FIXME: do real implementation
     match riscv_width {
+        RiscVWidth::Byte => ArmWidth::SignedByte,
+        RiscVWidth::UnsignedByte => ArmWidth::Byte,
+        RiscVWidth::Half => ArmWidth::SignedHalf,
+        RiscVWidth::UnsignedHalf => ArmWidth::Half,
+        RiscVWidth::Word => ArmWidth::SignedWord,
+        RiscVWidth::UnsignedWord => ArmWidth::Word,
         RiscVWidth::Double => ArmWidth::Double,
-        RiscVWidth::Word => ArmWidth::Word,
     }
 }
 
-// Translate every instruction 1:1
-pub fn translate_instrs(riscv_instrs: Vec<RiscVInstruction>) -> Vec<ArmInstruction> {
-    riscv_instrs
+/// Translate every instruction 1:1, after [`pair_pcrel`]
+///
+/// All instructions are translated even after an error, so every
+/// unsupported instruction is reported at once. The `line` of each error is
+/// the 1-based position of the instruction in `riscv_instrs`.
+pub fn translate_instrs(
+    riscv_instrs: Vec<RiscVInstruction>,
+) -> Result<Vec<ArmInstruction>, Vec<AsmError>> {
+    translate_instrs_with_options(riscv_instrs, &TranslateOptions::default())
+}
+
+/// [`translate_instrs`] with the instruction choices of `options`, such as
+/// [`TranslateOptions::elide_sign_extensions`]. The options for text output
+/// don't apply.
+pub fn translate_instrs_with_options(
+    mut riscv_instrs: Vec<RiscVInstruction>,
+    options: &TranslateOptions,
+) -> Result<Vec<ArmInstruction>, Vec<AsmError>> {
+    pair_pcrel(&mut riscv_instrs);
+    let mut riscv_instrs: Vec<(usize, RiscVInstruction)> = (1..).zip(riscv_instrs).collect();
+    if options.elide_sign_extensions {
+        riscv_instrs = elide_sign_extensions(riscv_instrs);
+    }
+    let mut arm_instrs = vec![];
+    let mut errors = vec![];
+    for (line, riscv_instr) in riscv_instrs {
+        match translate_with_options(riscv_instr, options) {
+            Ok(instrs) => arm_instrs.extend(instrs),
+            Err(error) => errors.push(AsmError { line, ..error }),
+        }
+    }
+    if errors.is_empty() {
+        Ok(legalize(arm_instrs))
+    } else {
+        Err(errors)
+    }
+}
+
+/// Entry point code emitted before the translated program.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Prologue {
+    /// No prologue, the program provides its own `_start`.
+    #[default]
+    None,
+    /// [`ARM_START`]: call `main` once and exit with its return value.
+    Start,
+    /// [`ARM_LOOP_START`]: call `main` 10,000 times for benchmarking.
+    LoopStart,
+}
+
+impl Prologue {
+    /// The prologue text. Our prologues end by defining `main` and its
+    /// alias `_main`, which are left out if the program defines `main`
+    /// itself, along with the `.global _main` of the alias.
+    fn text(self, defines_main: bool) -> String {
+        let text = match self {
+            Prologue::None => return String::new(),
+            Prologue::Start => ARM_START,
+            Prologue::LoopStart => ARM_LOOP_START,
+        };
+        if defines_main {
+            text.strip_suffix(START_MAIN)
+                .unwrap_or(text)
+                .replacen(".global _main\n", "", 1)
+        } else {
+            text.to_string()
+        }
+    }
+
+    /// The instructions of [`Prologue::text`], for [`assemble`]. Comments
+    /// aside, they render as the same text.
+    pub fn instructions(self, defines_main: bool) -> Vec<ArmInstruction> {
+        if self == Prologue::None {
+            return vec![];
+        }
+        let directive = |name: &str, operands: &str| ArmInstruction::Directive {
+            name: name.to_string(),
+            operands: operands.to_string(),
+        };
+        let label = |name: &str| ArmInstruction::Label {
+            name: name.to_string(),
+        };
+        let x3 = ArmRegister::new(ArmRegisterName::X3, ArmWidth::Double);
+        let call_main = ArmInstruction::Bl {
+            target: ArmVal::LabelOffset("main".to_string(), 0),
+        };
+        let mut instrs = vec![directive("text", ""), directive("global", "_start")];
+        if !defines_main {
+            instrs.push(directive("global", "_main"));
+        }
+        instrs.extend([directive("balign", "4"), label("_start")]);
+        if self == Prologue::LoopStart {
+            instrs.extend([
+                ArmInstruction::Mov {
+                    width: ArmWidth::Double,
+                    dest: x3,
+                    src: ArmVal::Imm(10000),
+                },
+                label(".loop"),
+                ArmInstruction::Sub {
+                    dest: x3,
+                    arg1: x3,
+                    arg2: ArmVal::Imm(1),
+                },
+                ArmInstruction::Cmp {
+                    op1: x3,
+                    op2: ArmVal::Reg(ArmRegister::new(ArmRegisterName::Zero, ArmWidth::Double)),
+                },
+                ArmInstruction::BCond {
+                    cond: ArmCondition::Le,
+                    target: ArmVal::LabelOffset(".end".to_string(), 0),
+                },
+                call_main,
+                ArmInstruction::B {
+                    target: ArmVal::LabelOffset(".loop".to_string(), 0),
+                },
+                label(".end"),
+            ]);
+        } else {
+            instrs.push(call_main);
+        }
+        instrs.extend([
+            ArmInstruction::Mov {
+                width: ArmWidth::Double,
+                dest: ArmRegister::new(ArmRegisterName::X8, ArmWidth::Double),
+                src: ArmVal::Imm(93),
+            },
+            ArmInstruction::Svc { id: 0 },
+        ]);
+        if !defines_main {
+            instrs.extend([directive("balign", "4"), label("_main"), label("main")]);
+        }
+        instrs
+    }
+}
+
+/// Assembler syntax of the translated output.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Dialect {
+    /// GNU `as` for ELF targets, e.g. `aarch64-unknown-linux-gnu-as`.
+    #[default]
+    Gnu,
+    /// Apple `as`, which is LLVM's, for Mach-O targets. It spells page
+    /// relocations as `label@PAGE`/`label@PAGEOFF`, names sections by
+    /// segment and has no `.type` or `.size`. Symbols are kept as they are,
+    /// and `ecall` still makes Linux system calls.
+    Apple,
+}
+
+impl Dialect {
+    /// Rewrite an instruction into this dialect, or drop it.
+    fn convert(self, arm_instr: ArmInstruction) -> Option<ArmInstruction> {
+        if self == Dialect::Gnu {
+            return Some(arm_instr);
+        }
+        let arm_instr = match arm_instr {
+            ArmInstruction::Directive { name, operands } => {
+                return self.convert_directive(name, operands)
+            }
+            ArmInstruction::Adrp { dest, label } => ArmInstruction::Adrp {
+                dest,
+                label: self.convert_val(label),
+            },
+            ArmInstruction::Add { dest, arg1, arg2 } => ArmInstruction::Add {
+                dest,
+                arg1,
+                arg2: self.convert_val(arg2),
+            },
+            ArmInstruction::Ldr { width, dest, src } => ArmInstruction::Ldr {
+                width,
+                dest,
+                src: self.convert_val(src),
+            },
+            ArmInstruction::Str { width, src, dest } => ArmInstruction::Str {
+                width,
+                src,
+                dest: self.convert_val(dest),
+            },
+            other => other,
+        };
+        Some(arm_instr)
+    }
+
+    fn convert_directive(self, name: String, operands: String) -> Option<ArmInstruction> {
+        let directive = |name: &str, operands: &str| {
+            Some(ArmInstruction::Directive {
+                name: name.to_string(),
+                operands: operands.to_string(),
+            })
+        };
+        let section = operands.split(',').next().unwrap_or("").trim();
+        match name.as_str() {
+            "type" | "size" => None,
+            "dword" => directive("quad", &operands),
+            "section" if section == ".note.GNU-stack" => None,
+            "section" if section.starts_with(".text") => directive("text", ""),
+            "section" if section.starts_with(".rodata") || section.starts_with(".srodata") => {
+                directive("section", "__TEXT,__const")
+            }
+            "section" if section.starts_with(".data") || section.starts_with(".sdata") => {
+                directive("data", "")
+            }
+            "section" if section.starts_with(".bss") || section.starts_with(".sbss") => {
+                directive("bss", "")
+            }
+            _ => directive(&name, &operands),
+        }
+    }
+
+    fn convert_val(self, val: ArmVal) -> ArmVal {
+        match val {
+            ArmVal::RelocOffset(base, reloc) => {
+                ArmVal::RelocOffset(base, Box::new(self.convert_val(*reloc)))
+            }
+            ArmVal::Reloc(ArmReloc::Page, name, offset) => {
+                ArmVal::LabelOffset(format!("{}@PAGE", name), offset)
+            }
+            ArmVal::Reloc(ArmReloc::Lo12, name, offset) => {
+                ArmVal::LabelOffset(format!("{}@PAGEOFF", name), offset)
+            }
+            ArmVal::Reloc(ArmReloc::GotPage, name, offset) => {
+                ArmVal::LabelOffset(format!("{}@GOTPAGE", name), offset)
+            }
+            ArmVal::Reloc(ArmReloc::GotLo12, name, offset) => {
+                ArmVal::LabelOffset(format!("{}@GOTPAGEOFF", name), offset)
+            }
+            other => other,
+        }
+    }
+}
+
+/// Options for [`binary_translate`].
+#[derive(Debug, Clone, Default)]
+pub struct TranslateOptions {
+    /// Entry point code emitted before the program.
+    pub prologue: Prologue,
+    /// Assembler syntax of the output.
+    pub dialect: Dialect,
+    /// Emit each RISC-V source line as a comment above its translation.
+    pub annotate: bool,
+    /// Drop a `sext.w` of a register which the instruction right before it
+    /// already sign extended, such as the one GCC emits after `addw`.
+    pub elide_sign_extensions: bool,
+    /// Translate AMOs to the single instruction atomics of ARMv8.1 LSE
+    /// rather than `ldxr`/`stxr` loops, which all ARMv8 cores have.
+    pub lse: bool,
+}
+
+/// Print ARM instructions as assembly text, one per line.
+pub fn arm_to_asm(arm_instrs: Vec<ArmInstruction>, dialect: Dialect) -> String {
+    let mut contents = String::new();
+    for instr in arm_instrs
         .into_iter()
-        .map(translate)
-        .fold(vec![], |mut acc, x| {
-            acc.extend(x);
-            acc
-        })
+        .filter_map(|instr| dialect.convert(instr))
+    {
+        contents.push_str(&String::from(instr));
+        contents.push('\n');
+    }
+    contents
 }
 
 /// Runs binary translation
-///   text file -> [`Instruction`] enum array -> text file
-pub fn binary_translate(riscv_asm: &str) -> String {
-    let instructions = parse_asm(riscv_asm);
-    instructions
-        .into_iter()
-        .map(|instr| format!("{:?}", instr))
-        .collect::<Vec<String>>()
-        .join("\n")
+///   text file -> [`RiscVInstruction`] enum array -> [`ArmInstruction`] enum
+///   array -> text file
+///
+/// Every line is parsed and translated even after an error, so all
+/// unsupported constructs are reported at once, sorted by source line.
+pub fn binary_translate(
+    riscv_asm: &str,
+    options: &TranslateOptions,
+) -> Result<String, Vec<AsmError>> {
+    let source: Vec<&str> = riscv_asm.lines().collect();
+    let (riscv_instrs, errors) = parse_asm_partial(riscv_asm);
+    translate_source(riscv_instrs, &source, errors, options)
+}
+
+/// [`binary_translate`] of a program which is not text, such as one read
+/// by [`read_elf`](crate::elf::read_elf).
+///
+/// Annotations and errors show the [`RiscVInstruction`] itself, and the
+/// `line` of errors is its 1-based position in `riscv_instrs`.
+pub fn translate_program(
+    riscv_instrs: Vec<RiscVInstruction>,
+    options: &TranslateOptions,
+) -> Result<String, Vec<AsmError>> {
+    let source: Vec<String> = riscv_instrs
+        .iter()
+        .map(|riscv_instr| format!("{:?}", riscv_instr))
+        .collect();
+    let source: Vec<&str> = source.iter().map(String::as_str).collect();
+    let riscv_instrs = (1..).zip(riscv_instrs).collect();
+    translate_source(riscv_instrs, &source, vec![], options)
+}
+
+/// [`binary_translate`] into a static AArch64 Linux executable, which is
+/// [`assemble`]d rather than printed. The dialect and annotations don't
+/// apply, and errors in the assembly are reported at the source line of the
+/// instruction which they came from, with the AArch64 instruction in the
+/// reason.
+pub fn binary_translate_executable(
+    riscv_asm: &str,
+    options: &TranslateOptions,
+) -> Result<Vec<u8>, Vec<AsmError>> {
+    let source: Vec<&str> = riscv_asm.lines().collect();
+    let (riscv_instrs, errors) = parse_asm_partial(riscv_asm);
+    translate_executable(riscv_instrs, &source, errors, options)
+}
+
+/// [`binary_translate_executable`] of a program which is not text, like
+/// [`translate_program`].
+pub fn translate_program_executable(
+    riscv_instrs: Vec<RiscVInstruction>,
+    options: &TranslateOptions,
+) -> Result<Vec<u8>, Vec<AsmError>> {
+    let source: Vec<String> = riscv_instrs
+        .iter()
+        .map(|riscv_instr| format!("{:?}", riscv_instr))
+        .collect();
+    let source: Vec<&str> = source.iter().map(String::as_str).collect();
+    let riscv_instrs = (1..).zip(riscv_instrs).collect();
+    translate_executable(riscv_instrs, &source, vec![], options)
+}
+
+/// The legalized translation of the instruction on a source line.
+struct Translation {
+    line: usize,
+    /// Labels and directives aren't worth annotating
+    is_instruction: bool,
+    arm_instrs: Vec<ArmInstruction>,
+}
+
+/// Translate instructions which came from the `source` line of their
+/// number, after the `errors` found while parsing. Also returns whether
+/// the program defines `main`, which the prologue would otherwise define.
+fn translate_lines(
+    mut riscv_instrs: Vec<(usize, RiscVInstruction)>,
+    source: &[&str],
+    mut errors: Vec<AsmError>,
+    options: &TranslateOptions,
+) -> Result<(bool, Vec<Translation>), Vec<AsmError>> {
+    pair_pcrel(riscv_instrs.iter_mut().map(|(_, riscv_instr)| riscv_instr));
+
+    let defines_main = riscv_instrs
+        .iter()
+        .any(|(_, instr)| matches!(instr, RiscVInstruction::Label { name } if name == "main"));
+
+    if options.elide_sign_extensions {
+        riscv_instrs = elide_sign_extensions(riscv_instrs);
+    }
+
+    let mut translations = vec![];
+    for (line, riscv_instr) in riscv_instrs {
+        let is_instruction = !matches!(
+            riscv_instr,
+            RiscVInstruction::Label { .. } | RiscVInstruction::Directive { .. }
+        );
+        match translate_with_options(riscv_instr, options) {
+            Ok(arm_instrs) => translations.push(Translation {
+                line,
+                is_instruction,
+                arm_instrs: legalize(arm_instrs),
+            }),
+            Err(error) => {
+                let text = source[line - 1].trim();
+                let column = source[line - 1].find(text).unwrap_or(0) + 1;
+                errors.push(AsmError::new(line, column, text, error.reason));
+            }
+        }
+    }
+
+    if errors.is_empty() {
+        Ok((defines_main, translations))
+    } else {
+        errors.sort_by_key(|error| error.line);
+        Err(errors)
+    }
+}
+
+/// [`translate_lines`] printed as assembly text.
+fn translate_source(
+    riscv_instrs: Vec<(usize, RiscVInstruction)>,
+    source: &[&str],
+    errors: Vec<AsmError>,
+    options: &TranslateOptions,
+) -> Result<String, Vec<AsmError>> {
+    let (defines_main, translations) = translate_lines(riscv_instrs, source, errors, options)?;
+    let mut contents = options.prologue.text(defines_main);
+    let mut annotated_line = 0;
+    for translation in translations {
+        if options.annotate && translation.is_instruction && translation.line != annotated_line {
+            contents.push_str(&format!("// {}\n", source[translation.line - 1].trim()));
+            annotated_line = translation.line;
+        }
+        contents.push_str(&arm_to_asm(translation.arm_instrs, options.dialect));
+    }
+    Ok(contents)
+}
+
+/// [`translate_lines`] assembled into an executable.
+fn translate_executable(
+    riscv_instrs: Vec<(usize, RiscVInstruction)>,
+    source: &[&str],
+    errors: Vec<AsmError>,
+    options: &TranslateOptions,
+) -> Result<Vec<u8>, Vec<AsmError>> {
+    let (defines_main, translations) = translate_lines(riscv_instrs, source, errors, options)?;
+    let mut arm_instrs = options.prologue.instructions(defines_main);
+    // the source line of each instruction, where the prologue has none
+    let mut lines = vec![0; arm_instrs.len()];
+    for translation in translations {
+        lines.extend(std::iter::repeat_n(
+            translation.line,
+            translation.arm_instrs.len(),
+        ));
+        arm_instrs.extend(translation.arm_instrs);
+    }
+    let image = assemble(arm_instrs).map_err(|errors| {
+        let mut errors: Vec<AsmError> = errors
+            .into_iter()
+            .map(
+                |error| match error.line.checked_sub(1).map(|index| lines[index]) {
+                    // an instruction of the prologue
+                    None | Some(0) => AsmError::new(0, 0, &error.text, error.reason),
+                    Some(line) => {
+                        let text = source[line - 1].trim();
+                        let column = source[line - 1].find(text).unwrap_or(0) + 1;
+                        let reason = format!("{} in `{}`", error.reason, error.text);
+                        AsmError::new(line, column, text, reason)
+                    }
+                },
+            )
+            .collect();
+        errors.sort_by_key(|error| error.line);
+        errors
+    })?;
+    Ok(write_executable(&image))
 }
@@ -1,6 +1,9 @@
 use std::fs;
 
-use crate::{instruction::RiscVInstruction, translate::translate_instrs};
+use crate::{
+    instruction::RiscVInstruction,
+    translate::{arm_to_asm, translate_instrs, Dialect},
+};
 
 /// Loop main() 10,000 times. Uses a3.
 pub const RISCV_LOOP_START: &str = r#"
@@ -48,7 +51,7 @@ mov x3, #10000
 sub x3, x3, 1
 
 cmp x3, xzr
-ble .end
+b.le .end
 
 # main()
 bl main
@@ -80,11 +83,12 @@ _main:
 main:
 "#;
 
+/// [`ARM_START`] for a program which defines `main` itself, so without
+/// `_main`.
 pub const START_NO_MAIN: &str = r#"
 .text
 
 .global _start
-.global _main
 
 .balign 4
 _start:
@@ -102,13 +106,11 @@ main:
 "#;
 
 pub fn translate_to_file(instrs: Vec<RiscVInstruction>, path: String) {
-    let arm_instrs = translate_instrs(instrs);
-    let mut contents = String::new();
-    for instr in arm_instrs {
-        let x: String = instr.into();
-        contents.push_str(&x);
-        contents.push_str("\n");
-    }
+    let arm_instrs = translate_instrs(instrs).unwrap_or_else(|errors| {
+        let errors: Vec<String> = errors.iter().map(ToString::to_string).collect();
+        panic!("Unable to translate:\n{}", errors.join("\n"))
+    });
+    let contents = arm_to_asm(arm_instrs, Dialect::Gnu);
     fs::write(&path, contents).expect("Unable to write file");
     println!("Saved ARM assembly to {}", path);
 }
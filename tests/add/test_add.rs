@@ -1,8 +1,6 @@
 #[cfg(test)]
 mod tests {
     use binary_room::instruction::*;
-    use binary_room::translate::*;
-    use binary_room::utils;
     use binary_room::utils::translate_to_file;
     use binary_room::utils::ARM_LOOP_START;
 
@@ -122,6 +120,7 @@ mod tests {
             },
         ];
 
-        translate_to_file(riscv_asm, "./tests/add/add.arm.s".to_string());
+        let path = std::env::temp_dir().join("binary_room_add.arm.s");
+        translate_to_file(riscv_asm, path.display().to_string());
     }
 }
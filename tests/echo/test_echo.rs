@@ -1,12 +1,11 @@
 #[cfg(test)]
 mod tests {
     use binary_room::instruction::*;
-    use binary_room::translate::*;
-    use binary_room::utils;
     use binary_room::utils::translate_to_file;
     use binary_room::utils::ARM_START;
 
-    const buf: &str = r#"
+    #[allow(dead_code)]
+    const BUF: &str = r#"
 .buf:
     .string "hello world"
 "#;
@@ -14,7 +13,7 @@ mod tests {
     #[test]
     fn test_print_translate() {
         let riscv_asm: Vec<RiscVInstruction> = vec![
-            // RiscVInstruction::Verbatim { text: buf.to_string() },
+            // RiscVInstruction::Verbatim { text: BUF.to_string() },
             RiscVInstruction::Verbatim {
                 text: ARM_START.to_string(),
             },
@@ -68,6 +67,7 @@ mod tests {
             RiscVInstruction::ECall,
         ];
 
-        translate_to_file(riscv_asm, "./tests/echo/echo.arm.s".to_string());
+        let path = std::env::temp_dir().join("binary_room_echo.arm.s");
+        translate_to_file(riscv_asm, path.display().to_string());
     }
 }
@@ -0,0 +1,24 @@
+# Assembled and linked into exit.riscv with
+#     llvm-mc -triple=riscv64 -mattr=+m,+a,+f,+d,+c -filetype=obj
+#     ld.lld
+#     llvm-objcopy --strip-all
+    .text
+    .globl  _start
+_start:
+    lla     a1,message
+    ld      a2,length
+    li      a0,1
+    li      a7,64
+    ecall
+    li      a0,0
+    li      a7,93
+    ecall
+
+    .section .rodata
+message:
+    .string "hello\n"
+
+    .data
+    .p2align 3
+length:
+    .dword  6
@@ -0,0 +1,43 @@
+# Assembled into hello.riscv.o with
+#     llvm-mc -triple=riscv64 -mattr=+m,+a,+f,+d,+c,+relax -filetype=obj
+    .text
+    .globl  main
+main:
+    addi    sp,sp,-16
+    sd      ra,8(sp)
+.Lpcrel_hi0:
+    auipc   a1,%pcrel_hi(message)
+    addi    a1,a1,%pcrel_lo(.Lpcrel_hi0)
+    lui     a2,%hi(length)
+    lw      a2,%lo(length)(a2)
+    call    write
+    li      a0,0
+.Lloop:
+    addi    a0,a0,1
+    blt     a0,a2,.Lloop
+    ld      ra,8(sp)
+    addi    sp,sp,16
+    ret
+
+write:
+    li      a0,1
+    li      a7,64
+    ecall
+    ret
+
+    .section .rodata
+message:
+    .string "hello\n"
+
+    .data
+    .p2align 3
+length:
+    .word   6
+    .word   0
+handler:
+    .quad   write+2
+
+    .bss
+    .p2align 3
+buffer:
+    .zero   64
@@ -0,0 +1,330 @@
+#[cfg(test)]
+mod tests {
+    use binary_room::elf::*;
+    use binary_room::error::AsmError;
+    use binary_room::instruction::{parse_asm, RiscVInstruction, RiscVVal};
+    use binary_room::translate::{translate_program, TranslateOptions};
+
+    const HELLO: &[u8] = include_bytes!("hello.riscv.o");
+    /// `exit.riscv.s` linked at the default addresses and stripped, so the
+    /// entry point has no symbol
+    const EXIT: &[u8] = include_bytes!("exit.riscv");
+    /// The offsets of `sh_size` in the section headers of `HELLO`
+    const RODATA_SIZE: usize = 0x418;
+    const BSS_SIZE: usize = 0x4d8;
+
+    /// `HELLO` with the size of a section changed.
+    fn with_size(offset: usize, size: u64) -> Vec<u8> {
+        let mut elf = HELLO.to_vec();
+        elf[offset..offset + 8].copy_from_slice(&size.to_le_bytes());
+        elf
+    }
+
+    #[test]
+    fn test_read_elf() {
+        let elf = read_elf(HELLO).unwrap();
+        assert!(elf.relocatable);
+        assert_eq!(elf.entry, 0);
+        assert_eq!(
+            elf.sections
+                .iter()
+                .map(|section| (
+                    section.name.as_str(),
+                    section.kind,
+                    section.address,
+                    section.size
+                ))
+                .collect::<Vec<_>>(),
+            [
+                (".text", SectionKind::Text, 0x0, 0x36),
+                (".rodata", SectionKind::ReadOnly, 0x36, 0x7),
+                (".data", SectionKind::Data, 0x40, 0x10),
+                (".bss", SectionKind::Bss, 0x50, 0x40),
+            ]
+        );
+        assert_eq!(elf.sections[1].data, b"hello\n\0");
+        assert!(elf.sections[3].data.is_empty());
+
+        let symbol = |index: usize| (elf.symbols[index].name.as_str(), elf.symbols[index].address);
+        let relocations: Vec<_> = elf.sections[0]
+            .relocations
+            .iter()
+            .filter(|relocation| relocation.kind != RelocationKind::Relax)
+            .map(|relocation| {
+                (
+                    relocation.address,
+                    relocation.kind,
+                    symbol(relocation.symbol),
+                )
+            })
+            .collect();
+        assert_eq!(
+            relocations,
+            [
+                (0x4, RelocationKind::PcrelHi20, ("message", 0x36)),
+                (0x8, RelocationKind::PcrelLo12I, (".Lpcrel_hi0", 0x4)),
+                (0xc, RelocationKind::Hi20, ("length", 0x40)),
+                (0x10, RelocationKind::Lo12I, ("length", 0x40)),
+                (0x14, RelocationKind::Call, ("write", 0x2a)),
+                (0x20, RelocationKind::Branch, (".Lloop", 0x1e)),
+            ]
+        );
+        let handler = &elf.sections[2].relocations[0];
+        assert_eq!(
+            (
+                handler.address,
+                handler.kind,
+                symbol(handler.symbol),
+                handler.addend
+            ),
+            (0x48, RelocationKind::Abs64, ("write", 0x2a), 2)
+        );
+
+        let main = elf
+            .symbols
+            .iter()
+            .find(|symbol| symbol.name == "main")
+            .unwrap();
+        assert!(main.global);
+        assert_eq!(main.section, Some(0));
+        let buffer = elf
+            .symbols
+            .iter()
+            .find(|symbol| symbol.name == "buffer")
+            .unwrap();
+        assert!(!buffer.global);
+        assert_eq!((buffer.address, buffer.section), (0x50, Some(3)));
+    }
+
+    #[test]
+    fn test_elf_instructions() {
+        let expected = parse_asm(
+            r#"
+                .section .text,"ax",@progbits
+                .balign 4
+                .globl main
+            main:
+                addi sp, sp, -16
+                sd ra, 8(sp)
+            .Lpcrel_hi0:
+                auipc a1, %pcrel_hi(message)
+                addi a1, a1, %pcrel_lo(.Lpcrel_hi0)
+                lui a2, %hi(length)
+                lw a2, %lo(length)(a2)
+                jal ra, write
+                addi a0, zero, 0
+            .Lloop:
+                addi a0, a0, 1
+                blt a0, a2, .Lloop
+                ld ra, 8(sp)
+                addi sp, sp, 16
+                jalr zero, 0(ra)
+            write:
+                addi a0, zero, 1
+            .L2c:
+                addi a7, zero, 64
+                ecall
+                jalr zero, 0(ra)
+                .section .rodata,"a",@progbits
+            message:
+                .byte 0x68, 0x65, 0x6c, 0x6c, 0x6f, 0x0a, 0x00
+                .section .data,"aw",@progbits
+                .balign 8
+            length:
+                .byte 0x06, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00
+            handler:
+                .quad .L2c
+                .section .bss,"aw",@nobits
+                .balign 8
+            buffer:
+                .zero 64
+            "#,
+        )
+        .unwrap();
+        let elf = read_elf(HELLO).unwrap();
+        assert_eq!(elf.instructions().unwrap(), expected);
+    }
+
+    #[test]
+    fn test_translate_elf() {
+        let riscv_instrs = read_elf(HELLO).unwrap().instructions().unwrap();
+        let arm_asm = translate_program(riscv_instrs, &TranslateOptions::default()).unwrap();
+        assert!(arm_asm.contains("adrp x1, message\nadd x1, x1, :lo12:message\n"));
+        assert!(arm_asm.contains("ldrsw x2, [x2, :lo12:length]\nbl write\n"));
+        // the RISC-V offset `write+2` becomes a label at the instruction
+        assert!(arm_asm.contains("handler:\n.quad .L2c\n"));
+        assert!(arm_asm.contains(".L2c:\nmov x8, 64\n"));
+    }
+
+    #[test]
+    fn test_elf_gnu_anchors() {
+        // GNU `as` names the anchors of `la` and `call` `.L0 `, all of
+        // them the same. There is no GNU toolchain to build a fixture with,
+        // so the local labels of `HELLO` are renamed to match.
+        let mut elf = read_elf(HELLO).unwrap();
+        for symbol in &mut elf.symbols {
+            if symbol.name.starts_with(".L") {
+                symbol.name = ".L0 ".to_string();
+            }
+        }
+        let riscv_instrs = elf.instructions().unwrap();
+        let arm_asm = translate_program(riscv_instrs, &TranslateOptions::default()).unwrap();
+        assert!(!arm_asm.contains(".L0 "));
+        assert!(arm_asm.contains(".L4:\nadrp x1, message\nadd x1, x1, :lo12:message\n"));
+        assert!(arm_asm.contains(".L1e:\nadd x0, x0, 1\n"));
+        assert!(arm_asm.contains("b.lt .L1e\n"));
+    }
+
+    #[test]
+    fn test_elf_label_inside_instruction() {
+        // `write+4` is the middle of the `addi a7, zero, 64` after `c.li`
+        let mut elf = read_elf(HELLO).unwrap();
+        elf.sections[2].relocations[0].addend = 4;
+        assert_eq!(
+            elf.instructions(),
+            Err(vec![AsmError::new(
+                0,
+                0,
+                ".L2e",
+                "label is inside of an instruction"
+            )])
+        );
+    }
+
+    #[test]
+    fn test_read_executable() {
+        let elf = read_elf(EXIT).unwrap();
+        assert!(!elf.relocatable);
+        assert_eq!(elf.entry, 0x11160);
+        assert!(elf.symbols.is_empty());
+        assert_eq!(
+            elf.sections
+                .iter()
+                .map(|section| (
+                    section.name.as_str(),
+                    section.kind,
+                    section.address,
+                    section.size
+                ))
+                .collect::<Vec<_>>(),
+            [
+                (".rodata", SectionKind::ReadOnly, 0x10158, 0x7),
+                (".text", SectionKind::Text, 0x11160, 0x24),
+                (".data", SectionKind::Data, 0x12188, 0x8),
+            ]
+        );
+        assert!(elf
+            .sections
+            .iter()
+            .all(|section| section.relocations.is_empty()));
+    }
+
+    #[test]
+    fn test_executable_instructions() {
+        let mut expected = parse_asm(
+            r#"
+                .section .rodata,"a",@progbits
+            .L10158:
+                .byte 0x68, 0x65, 0x6c, 0x6c, 0x6f, 0x0a, 0x00
+                .section .text,"ax",@progbits
+                .balign 4
+                .globl _start
+            _start:
+                auipc a1, 0
+                addi a1, a1, 0
+                auipc a2, 0
+                ld a2, 0(a2)
+                addi a0, zero, 1
+                addi a7, zero, 64
+                ecall
+                addi a0, zero, 0
+                addi a7, zero, 93
+                ecall
+                .section .data,"aw",@progbits
+                .balign 8
+            .L12188:
+                .byte 0x06, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00
+            "#,
+        )
+        .unwrap();
+        // the assembler has no syntax for the label of an `auipc`
+        for (index, label) in [(7, ".L10158"), (9, ".L12188")] {
+            let RiscVInstruction::Auipc { src, .. } = &mut expected[index] else {
+                panic!("{:?} isn't an auipc", expected[index]);
+            };
+            *src = RiscVVal::LabelOffset {
+                label: label.to_string(),
+                offset: 0,
+            };
+        }
+        let elf = read_elf(EXIT).unwrap();
+        assert_eq!(elf.instructions().unwrap(), expected);
+
+        // `adr` already has the address which the `addi` added nothing to
+        let arm_asm = translate_program(expected, &TranslateOptions::default()).unwrap();
+        assert!(arm_asm.contains("_start:\nadr x1, .L10158\nadr x2, .L12188\nldr x2, [x2, 0]\n"));
+    }
+
+    #[test]
+    fn test_executable_absolute_addresses() {
+        // `lui a1, 0x10`, the upper bits of the address of `message`
+        let mut elf = read_elf(EXIT).unwrap();
+        elf.sections[1].data[..4].copy_from_slice(&0x000105b7u32.to_le_bytes());
+        // a pointer to `message`
+        elf.sections[2].data = 0x10158u64.to_le_bytes().to_vec();
+        let reason = "absolute address in an executable";
+        assert_eq!(
+            elf.instructions(),
+            Err(vec![
+                AsmError::new(1, 0, "11160: 000105b7", reason),
+                AsmError::new(0, 0, "12188: 0x10158", reason),
+            ])
+        );
+    }
+
+    #[test]
+    fn test_read_elf_errors() {
+        assert_eq!(
+            read_elf(b"main:\n"),
+            Err(AsmError::new(0, 0, "header", "not an ELF file"))
+        );
+        // EM_X86_64
+        let mut x86 = HELLO.to_vec();
+        x86[0x12] = 62;
+        assert_eq!(
+            read_elf(&x86),
+            Err(AsmError::new(0, 0, "e_machine 62", "not a RISC-V ELF file"))
+        );
+        assert_eq!(
+            read_elf(&HELLO[..0x30]),
+            Err(AsmError::new(0, 0, "offset 0x3a", "truncated ELF file"))
+        );
+        assert_eq!(
+            read_elf(&with_size(RODATA_SIZE, 0xffff_ffff_ffff_fff0)),
+            Err(AsmError::new(
+                0,
+                0,
+                ".rodata",
+                "section doesn't fit in the address space"
+            ))
+        );
+        assert_eq!(
+            read_elf(&with_size(BSS_SIZE, 0xf400_0000_0040)),
+            Err(AsmError::new(0, 0, ".bss", "section is too large"))
+        );
+    }
+
+    #[test]
+    fn test_elf_large_bss() {
+        // .bss isn't read a byte at a time
+        let elf = read_elf(&with_size(BSS_SIZE, 1 << 32)).unwrap();
+        let riscv_instrs = elf.instructions().unwrap();
+        assert_eq!(
+            riscv_instrs.last(),
+            Some(&RiscVInstruction::Directive {
+                name: "zero".to_string(),
+                operands: "4294967296".to_string(),
+            })
+        );
+    }
+}
@@ -1,12 +1,10 @@
 #[cfg(test)]
 mod tests {
     use binary_room::instruction::*;
-    use binary_room::translate::*;
-    use binary_room::utils;
     use binary_room::utils::translate_to_file;
     use binary_room::utils::ARM_START;
 
-    const buf: &str = r#"
+    const BUF: &str = r#"
 buf:
     .string "hello world\n"
 "#;
@@ -15,7 +13,7 @@ buf:
     fn test_print_translate() {
         let riscv_asm: Vec<RiscVInstruction> = vec![
             RiscVInstruction::Verbatim {
-                text: buf.to_string(),
+                text: BUF.to_string(),
             },
             RiscVInstruction::Verbatim {
                 text: ARM_START.to_string(),
@@ -52,17 +50,19 @@ buf:
             },
             RiscVInstruction::Lui {
                 dest: RiscVRegister::A0,
-                src: RiscVVal::LabelOffset {
+                src: RiscVVal::Reloc {
+                    reloc: RiscVReloc::Hi20,
                     label: "buf".to_string(),
-                    offset: 9998,
+                    offset: 0,
                 },
             },
             RiscVInstruction::Addl {
                 dest: RiscVRegister::A1,
                 src: RiscVRegister::A0,
-                label: RiscVVal::LabelOffset {
+                label: RiscVVal::Reloc {
+                    reloc: RiscVReloc::Lo12,
                     label: "buf".to_string(),
-                    offset: 9999,
+                    offset: 0,
                 },
             },
             RiscVInstruction::Li {
@@ -91,6 +91,7 @@ buf:
             RiscVInstruction::ECall,
         ];
 
-        translate_to_file(riscv_asm, "./tests/print/print.arm.s".to_string());
+        let path = std::env::temp_dir().join("binary_room_print.arm.s");
+        translate_to_file(riscv_asm, path.display().to_string());
     }
 }
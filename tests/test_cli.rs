@@ -0,0 +1,148 @@
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+    use std::process::{Command, Output, Stdio};
+
+    fn run(args: &[&str], stdin: &str) -> Output {
+        let mut child = Command::new(env!("CARGO_BIN_EXE_binary-room"))
+            .args(args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .expect("Unable to run binary-room");
+        child
+            .stdin
+            .take()
+            .unwrap()
+            .write_all(stdin.as_bytes())
+            .unwrap();
+        child.wait_with_output().unwrap()
+    }
+
+    #[test]
+    fn test_translate_stdin() {
+        let output = run(&["translate"], "main:\n    li a0, 3\n    jr ra\n");
+        assert_eq!(output.status.code(), Some(0));
+        assert_eq!(
+            String::from_utf8(output.stdout).unwrap(),
+            "main:\nmov x0, 3\nret\n"
+        );
+    }
+
+    #[test]
+    fn test_translate_file() {
+        let output_path = std::env::temp_dir().join("binary_room_test_cli_prime.arm.s");
+        let output = run(
+            &[
+                "translate",
+                "tests/prime/prime.riscv.s",
+                "-o",
+                output_path.to_str().unwrap(),
+            ],
+            "",
+        );
+        assert_eq!(output.status.code(), Some(0));
+        let arm_asm = std::fs::read_to_string(&output_path).unwrap();
+        assert!(arm_asm.starts_with("is_prime:\nsub sp, sp, 48\n"));
+    }
+
+    #[test]
+    fn test_translate_options() {
+        let output = run(
+            &["translate", "--prologue", "start", "--annotate"],
+            "li a0, 3\n",
+        );
+        assert_eq!(output.status.code(), Some(0));
+        let arm_asm = String::from_utf8(output.stdout).unwrap();
+        assert!(arm_asm.starts_with(binary_room::utils::ARM_START));
+        assert!(arm_asm.ends_with("main:\n// li a0, 3\nmov x0, 3\n"));
+
+        let output = run(&["translate", "--dialect", "intel"], "");
+        assert_eq!(output.status.code(), Some(2));
+        let output = run(&["translate", "--dialect", "apple"], "lui a0, %hi(buf)\n");
+        assert_eq!(output.status.code(), Some(0));
+        assert_eq!(
+            String::from_utf8(output.stdout).unwrap(),
+            "adrp x0, buf@PAGE\n"
+        );
+        // the executable makes Linux system calls
+        let output = run(&["translate", "--executable", "--dialect", "apple"], "");
+        assert_eq!(output.status.code(), Some(2));
+    }
+
+    #[test]
+    fn test_translate_elf() {
+        let output = run(&["translate", "tests/elf/hello.riscv.o"], "");
+        assert_eq!(output.status.code(), Some(0));
+        let arm_asm = String::from_utf8(output.stdout).unwrap();
+        assert!(arm_asm.starts_with(".section .text,\"ax\",%progbits\n"));
+        assert!(arm_asm.contains("main:\nsub sp, sp, 16\n"));
+    }
+
+    #[test]
+    fn test_translate_executable() {
+        let output_path = std::env::temp_dir().join("binary_room_test_cli_fib");
+        let output = run(
+            &[
+                "translate",
+                "--executable",
+                "--prologue",
+                "start",
+                "tests/fib/fib.riscv.s",
+                "-o",
+                output_path.to_str().unwrap(),
+            ],
+            "",
+        );
+        assert_eq!(output.status.code(), Some(0));
+        let executable = std::fs::read(&output_path).unwrap();
+        assert!(executable.starts_with(b"\x7fELF"));
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = std::fs::metadata(&output_path)
+                .unwrap()
+                .permissions()
+                .mode();
+            assert_eq!(mode & 0o111, 0o111);
+        }
+
+        // without a prologue there is no `_start`
+        let output = run(&["translate", "--executable"], "main:\n    ret\n");
+        assert_eq!(output.status.code(), Some(1));
+        assert_eq!(
+            String::from_utf8(output.stderr).unwrap(),
+            "<stdin>:0:0: undefined entry point: `_start`\n"
+        );
+    }
+
+    #[test]
+    fn test_parse() {
+        let output = run(&["parse", "-"], "nop\n");
+        assert_eq!(output.status.code(), Some(0));
+        assert_eq!(String::from_utf8(output.stdout).unwrap(), "1: Nop\n");
+    }
+
+    #[test]
+    fn test_check() {
+        let output = run(&["check"], "li a0, 3\n");
+        assert_eq!(output.status.code(), Some(0));
+        assert!(output.stdout.is_empty());
+
+        let output = run(&["check"], "li a0, 3\nslli a1, a1, 64\n");
+        assert_eq!(output.status.code(), Some(1));
+        assert_eq!(
+            String::from_utf8(output.stdout).unwrap(),
+            "<stdin>:2:1: shift amount out of range: `slli a1, a1, 64`\n"
+        );
+    }
+
+    #[test]
+    fn test_usage_error() {
+        let output = run(&["frobnicate"], "");
+        assert_eq!(output.status.code(), Some(2));
+        let output = run(&["translate", "/nonexistent/input.s"], "");
+        assert_eq!(output.status.code(), Some(2));
+    }
+}
@@ -0,0 +1,239 @@
+#[cfg(test)]
+mod tests {
+    use binary_room::decode::*;
+    use binary_room::error::AsmError;
+    use binary_room::instruction::*;
+
+    fn bytes(words: &[u32]) -> Vec<u8> {
+        words.iter().flat_map(|word| word.to_le_bytes()).collect()
+    }
+
+    #[test]
+    fn test_decode() {
+        // assembled with `llvm-mc -triple=riscv64 -mattr=+m,+a,+f,+d`
+        let code = bytes(&[
+            0xfff50513, // addi a0, a0, -1
+            0x00351593, // slli a1, a0, 3
+            0x41f5d61b, // sraiw a2, a1, 31
+            0x40b606bb, // subw a3, a2, a1
+            0x02c6b733, // mulhu a4, a3, a2
+            0x02d777bb, // remuw a5, a4, a3
+            0xffc14283, // lbu t0, -4(sp)
+            0x00113423, // sd ra, 8(sp)
+            0xfffff537, // lui a0, 0xfffff
+            0xfc051ee3, // bne a0, zero, -36
+            0x05c000ef, // jal ra, 92
+            0x06b6252f, // amoadd.w.aqrl a0, a1, (a2)
+            0x140532af, // lr.d.aq t0, (a0)
+            0x1a75332f, // sc.d.rl t1, t2, (a0)
+            0x0230000f, // fence r, rw
+            0x8330000f, // fence.tso
+            0x0100000f, // pause
+            0x0000100f, // fence.i
+            0x01013507, // fld fa0, 16(sp)
+            0xfe152c27, // fsw ft1, -8(a0)
+            0x6ac59543, // fmadd.d fa0, fa1, fa2, fa3, rtz
+            0x1820f04b, // fnmsub.s ft0, ft1, ft2, ft3
+            0xc2151553, // fcvt.wu.d a0, fa0, rtz
+            0xd025f553, // fcvt.s.l fa0, a1
+            0x4015f553, // fcvt.s.d fa0, fa1
+            0xa2b50553, // fle.d a0, fa0, fa1
+            0xe00515d3, // fclass.s a1, fa0
+            0xe0058653, // fmv.x.w a2, fa1
+            0xf2068653, // fmv.d.x fa2, a3
+            0x20b5a553, // fsgnjx.s fa0, fa1, fa1
+            0x2ac58553, // fmin.d fa0, fa1, fa2
+            0x00000013, // nop
+            0x00000073, // ecall
+            0x00008067, // jalr zero, 0(ra)
+        ]);
+        let expected = parse_asm(
+            "
+            .L1000:
+                addi a0, a0, -1
+                slli a1, a0, 3
+                sraiw a2, a1, 31
+                subw a3, a2, a1
+                mulhu a4, a3, a2
+                remuw a5, a4, a3
+                lbu t0, -4(sp)
+                sd ra, 8(sp)
+                lui a0, 0xfffff
+                bne a0, zero, .L1000
+                jal ra, .L1084
+                amoadd.w.aqrl a0, a1, (a2)
+                lr.d.aq t0, (a0)
+                sc.d.rl t1, t2, (a0)
+                fence r, rw
+                fence.tso
+                pause
+                fence.i
+                fld fa0, 16(sp)
+                fsw ft1, -8(a0)
+                fmadd.d fa0, fa1, fa2, fa3, rtz
+                fnmsub.s ft0, ft1, ft2, ft3
+                fcvt.wu.d a0, fa0, rtz
+                fcvt.s.l fa0, a1
+                fcvt.s.d fa0, fa1
+                fle.d a0, fa0, fa1
+                fclass.s a1, fa0
+                fmv.x.w a2, fa1
+                fmv.d.x fa2, a3
+                fsgnjx.s fa0, fa1, fa1
+                fmin.d fa0, fa1, fa2
+                nop
+                ecall
+            .L1084:
+                jalr zero, 0(ra)
+            ",
+        )
+        .unwrap();
+        assert_eq!(decode(&code, 0x1000).unwrap(), expected);
+    }
+
+    #[test]
+    fn test_decode_auipc() {
+        let code = bytes(&[
+            0x00000797, // auipc a5, 0
+            0x00c7b503, // ld a0, 12(a5)
+            0x0107b583, // ld a1, 16(a5)
+            0x00000097, // auipc ra, 0
+            0x008080e7, // jalr ra, 8(ra)
+            0x00000013, // nop
+        ]);
+        let decoded = decode_instructions(&code, 0x1000).unwrap();
+        assert_eq!(
+            decoded.iter().map(|d| d.target).collect::<Vec<_>>(),
+            [Some(0x100c), None, None, Some(0x1014), None, None]
+        );
+        let auipc = |dest, label: &str| RiscVInstruction::Auipc {
+            dest,
+            src: RiscVVal::LabelOffset {
+                label: label.to_string(),
+                offset: 0,
+            },
+        };
+        let mut expected = parse_asm(
+            "
+                ld a0, 0(a5)
+                ld a1, 4(a5)
+            .L100c:
+                jalr ra, 0(ra)
+            .L1014:
+                nop
+            ",
+        )
+        .unwrap();
+        expected.insert(0, auipc(RiscVRegister::A5, ".L100c"));
+        expected.insert(4, auipc(RiscVRegister::RA, ".L1014"));
+        assert_eq!(decode(&code, 0x1000).unwrap(), expected);
+    }
+
+    #[test]
+    fn test_decode_compressed() {
+        // each compressed instruction and the instruction it expands to,
+        // assembled with `llvm-mc -triple=riscv64 -mattr=+c,+d`
+        let expansions: [(u16, u32); 33] = [
+            (0x1fe8, 0x3fc10513), // c.addi4spn a0, sp, 1020
+            (0x3fe8, 0x0f87b507), // c.fld fa0, 248(a5)
+            (0x5e6c, 0x07c62583), // c.lw a1, 124(a2)
+            (0x6480, 0x0084b403), // c.ld s0, 8(s1)
+            (0xa904, 0x00953827), // c.fsd fs1, 16(a0)
+            (0xc354, 0x00d72223), // c.sw a3, 4(a4)
+            (0xfc7c, 0x0ef43c23), // c.sd a5, 248(s0)
+            (0x0001, 0x00000013), // c.nop
+            (0x1501, 0xfe050513), // c.addi a0, -32
+            (0x22fd, 0x01f2829b), // c.addiw t0, 31
+            (0x58fd, 0xfff00893), // c.li a7, -1
+            (0x7101, 0xe0010113), // c.addi16sp sp, -512
+            (0x7905, 0xfffe1937), // c.lui s2, 0xfffe1
+            (0x917d, 0x03f55513), // c.srli a0, 63
+            (0x8585, 0x4015d593), // c.srai a1, 1
+            (0x9a65, 0xff967613), // c.andi a2, -7
+            (0x8c05, 0x40940433), // c.sub s0, s1
+            (0x8eb9, 0x00e6c6b3), // c.xor a3, a4
+            (0x8fc1, 0x0087e7b3), // c.or a5, s0
+            (0x8ce9, 0x00a4f4b3), // c.and s1, a0
+            (0x9d91, 0x40c585bb), // c.subw a1, a2
+            (0x9eb9, 0x00e686bb), // c.addw a3, a4
+            (0x1f86, 0x021f9f93), // c.slli t6, 33
+            (0x3ffe, 0x1f813f87), // c.fldsp ft11, 504(sp)
+            (0x50fe, 0x0fc12083), // c.lwsp ra, 252(sp)
+            (0x6da2, 0x00813d83), // c.ldsp s11, 8(sp)
+            (0x8282, 0x00028067), // c.jr t0
+            (0x840a, 0x00200433), // c.mv s0, sp
+            (0x9782, 0x000780e7), // c.jalr a5
+            (0x912a, 0x00a10133), // c.add sp, a0
+            (0xbfa2, 0x1e813c27), // c.fsdsp fs0, 504(sp)
+            (0xdf82, 0x0e012e23), // c.swsp zero, 252(sp)
+            (0xe006, 0x00113023), // c.sdsp ra, 0(sp)
+        ];
+        for (parcel, word) in expansions {
+            let compressed = decode_instructions(&parcel.to_le_bytes(), 0).unwrap();
+            let expanded = decode_instructions(&word.to_le_bytes(), 0).unwrap();
+            assert_eq!(compressed[0].size, 2);
+            assert_eq!(compressed[0].instr, expanded[0].instr, "{:04x}", parcel);
+        }
+    }
+
+    #[test]
+    fn test_decode_mixed_sizes() {
+        let code = [
+            0x19, 0xc1, // c.beqz a0, 6
+            0x93, 0x85, 0x15, 0x00, // addi a1, a1, 1
+            0xed, 0xbf, // c.j -6
+            0x82, 0x80, // c.jr ra
+        ];
+        let decoded = decode_instructions(&code, 0x1000).unwrap();
+        assert_eq!(
+            decoded
+                .iter()
+                .map(|d| (d.address, d.size))
+                .collect::<Vec<_>>(),
+            [(0x1000, 2), (0x1002, 4), (0x1006, 2), (0x1008, 2)]
+        );
+        let expected = parse_asm(
+            "
+            .L1000:
+                beq a0, zero, .L1006
+                addi a1, a1, 1
+            .L1006:
+                jal zero, .L1000
+                jalr zero, 0(ra)
+            ",
+        )
+        .unwrap();
+        assert_eq!(decode(&code, 0x1000).unwrap(), expected);
+    }
+
+    #[test]
+    fn test_decode_errors() {
+        let mut code = bytes(&[
+            0x00102573, // csrr a0, fflags
+            0xffffffff,
+        ]);
+        // c.ebreak, and half of an addi
+        code.extend([0x02, 0x90, 0x13, 0x05]);
+        assert_eq!(
+            decode(&code, 0x1000),
+            Err(vec![
+                AsmError::new(1, 0, "1000: 00102573", "unsupported instruction"),
+                AsmError::new(2, 0, "1004: ffffffff", "unknown instruction"),
+                AsmError::new(3, 0, "1008: 9002", "unsupported instruction"),
+                AsmError::new(4, 0, "100a: 00000513", "truncated instruction"),
+            ])
+        );
+
+        // jal zero, 2
+        let code = bytes(&[0x0020006f, 0x00000013]);
+        assert_eq!(
+            decode(&code, 0),
+            Err(vec![AsmError::new(
+                1,
+                0,
+                "0: 0020006f",
+                "target is inside of an instruction"
+            )])
+        );
+    }
+}
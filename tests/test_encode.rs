@@ -0,0 +1,746 @@
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use binary_room::encode::*;
+    use binary_room::error::AsmError;
+    use binary_room::executable::{Layout, BASE_ADDRESS};
+    use binary_room::instruction::*;
+    use binary_room::translate::*;
+
+    fn x(name: ArmRegisterName) -> ArmRegister {
+        ArmRegister::new(name, ArmWidth::Double)
+    }
+
+    fn w(name: ArmRegisterName) -> ArmRegister {
+        ArmRegister::new(name, ArmWidth::Word)
+    }
+
+    fn label(name: &str, offset: i32) -> ArmVal {
+        ArmVal::LabelOffset(name.to_string(), offset)
+    }
+
+    fn words(bytes: &[u8]) -> Vec<u32> {
+        bytes
+            .chunks(4)
+            .map(|word| u32::from_le_bytes(word.try_into().unwrap()))
+            .collect()
+    }
+
+    #[test]
+    fn test_encode() {
+        use ArmInstruction as I;
+        use ArmRegisterName::*;
+        // each word is the `llvm-mc -triple=aarch64 -mattr=+lse` encoding
+        // of the text of the instruction
+        let expected = [
+            (
+                I::Add {
+                    dest: w(X3),
+                    arg1: w(X4),
+                    arg2: ArmVal::Imm(0x5000),
+                },
+                0x11401483, // add w3, w4, 20480
+            ),
+            (
+                I::Add {
+                    dest: x(X0),
+                    arg1: x(Sp),
+                    arg2: ArmVal::Reg(x(X2)),
+                },
+                0x8b2263e0, // add x0, sp, x2
+            ),
+            (
+                I::Sub {
+                    dest: w(X5),
+                    arg1: w(Zero),
+                    arg2: ArmVal::Reg(w(X6)),
+                },
+                0x4b0603e5, // sub w5, wzr, w6
+            ),
+            (
+                I::Cmp {
+                    op1: x(X3),
+                    op2: ArmVal::Reg(x(Zero)),
+                },
+                0xeb1f007f, // cmp x3, xzr
+            ),
+            (
+                I::And {
+                    dest: w(X0),
+                    arg1: w(X1),
+                    arg2: ArmVal::Imm(0xfffffff0),
+                },
+                0x121c6c20, // and w0, w1, 4294967280
+            ),
+            (
+                I::And {
+                    dest: x(Sp),
+                    arg1: x(X1),
+                    arg2: ArmVal::Imm(-16),
+                },
+                0x927cec3f, // and sp, x1, -16
+            ),
+            (
+                I::Eor {
+                    dest: x(X0),
+                    arg1: x(X1),
+                    arg2: ArmVal::Imm(0x0f0f0f0f0f0f0f0f),
+                },
+                0xd200cc20, // eor x0, x1, 1085102592571150095
+            ),
+            (
+                I::Tst {
+                    op1: x(X0),
+                    op2: ArmVal::Imm(1),
+                },
+                0xf240001f, // tst x0, 1
+            ),
+            (
+                I::Mov {
+                    width: ArmWidth::Double,
+                    dest: x(X0),
+                    src: ArmVal::Imm(0x12340000),
+                },
+                0xd2a24680, // mov x0, 305397760
+            ),
+            (
+                I::Mov {
+                    width: ArmWidth::Double,
+                    dest: x(X0),
+                    src: ArmVal::Imm(-0x10001),
+                },
+                0x92a00020, // mov x0, -65537
+            ),
+            (
+                I::Mov {
+                    width: ArmWidth::Double,
+                    dest: x(X0),
+                    src: ArmVal::Imm(0xffff0000ffff),
+                },
+                0xb2003fe0, // mov x0, 281470681808895
+            ),
+            (
+                I::Mov {
+                    width: ArmWidth::Word,
+                    dest: w(X0),
+                    src: ArmVal::Imm(-2),
+                },
+                0x12800020, // mov w0, -2
+            ),
+            (
+                I::Mov {
+                    width: ArmWidth::Double,
+                    dest: x(X29),
+                    src: ArmVal::Reg(x(Sp)),
+                },
+                0x910003fd, // mov x29, sp
+            ),
+            (
+                I::Movk {
+                    dest: x(X0),
+                    imm: 0xbeef,
+                    shift: 48,
+                },
+                0xf2f7dde0, // movk x0, 48879, lsl 48
+            ),
+            (
+                I::Lsl {
+                    dest: x(X0),
+                    src: x(X1),
+                    imm: 3,
+                },
+                0xd37df020, // lsl x0, x1, 3
+            ),
+            (
+                I::Asr {
+                    dest: w(X0),
+                    src: w(X1),
+                    imm: 5,
+                },
+                0x13057c20, // asr w0, w1, 5
+            ),
+            (
+                I::Msub {
+                    dest: x(X0),
+                    arg1: x(X1),
+                    arg2: x(X2),
+                    arg3: x(X3),
+                },
+                0x9b028c20, // msub x0, x1, x2, x3
+            ),
+            (
+                I::Cset {
+                    dest: w(X0),
+                    cond: ArmCondition::Gt,
+                },
+                0x1a9fd7e0, // cset w0, gt
+            ),
+            (
+                I::Ldr {
+                    width: ArmWidth::Double,
+                    dest: x(X0),
+                    src: ArmVal::RegOffset(x(X1), -8),
+                },
+                0xf85f8020, // ldr x0, [x1, -8]
+            ),
+            (
+                I::Ldr {
+                    width: ArmWidth::SignedByte,
+                    dest: w(X0),
+                    src: ArmVal::RegOffset(x(X1), 1),
+                },
+                0x39c00420, // ldrsb w0, [x1, 1]
+            ),
+            (
+                I::Ldr {
+                    width: ArmWidth::Half,
+                    dest: w(X0),
+                    src: ArmVal::RegIndex(x(X1), x(X7)),
+                },
+                0x78676820, // ldrh w0, [x1, x7]
+            ),
+            (
+                I::Ldr {
+                    width: ArmWidth::Double,
+                    dest: x(Lr),
+                    src: ArmVal::PostIndex(x(Sp), 16),
+                },
+                0xf84107fe, // ldr lr, [sp], 16
+            ),
+            (
+                I::Str {
+                    width: ArmWidth::Half,
+                    src: w(X3),
+                    dest: ArmVal::UnscaledOffset(x(X1), -3),
+                },
+                0x781fd023, // sturh w3, [x1, -3]
+            ),
+            (
+                I::Str {
+                    width: ArmWidth::Double,
+                    src: x(V31),
+                    dest: ArmVal::PreIndex(x(Sp), -16),
+                },
+                0xfc1f0fff, // str d31, [sp, -16]!
+            ),
+            (
+                I::Stp {
+                    src1: x(X29),
+                    src2: x(Lr),
+                    dest: ArmVal::PreIndex(x(Sp), -16),
+                },
+                0xa9bf7bfd, // stp x29, lr, [sp, -16]!
+            ),
+            (
+                I::Ldp {
+                    dest1: x(V8),
+                    dest2: x(V9),
+                    src: ArmVal::RegOffset(x(Sp), -512),
+                },
+                0x6d6027e8, // ldp d8, d9, [sp, -512]
+            ),
+            (
+                I::Stxr {
+                    release: true,
+                    status: w(X7),
+                    src: w(X0),
+                    addr: X1,
+                },
+                0x8807fc20, // stlxr w7, w0, [x1]
+            ),
+            (
+                I::Swp {
+                    acquire: true,
+                    release: true,
+                    src: x(X0),
+                    dest: x(X1),
+                    addr: X2,
+                },
+                0xf8e08041, // swpal x0, x1, [x2]
+            ),
+            (
+                I::LdOp {
+                    op: ArmAtomicOp::Clr,
+                    acquire: true,
+                    release: false,
+                    src: w(X0),
+                    dest: w(X1),
+                    addr: X2,
+                },
+                0xb8a01041, // ldclra w0, w1, [x2]
+            ),
+            (
+                I::Ins {
+                    dest: ArmVal::Element(V0, ArmWidth::Word, 3),
+                    src: ArmVal::Element(V5, ArmWidth::Word, 2),
+                },
+                0x6e1c44a0, // mov v0.s[3], v5.s[2]
+            ),
+            (
+                I::Mov {
+                    width: ArmWidth::Word,
+                    dest: w(X7),
+                    src: ArmVal::Element(V1, ArmWidth::Word, 1),
+                },
+                0x0e0c3c27, // mov w7, v1.s[1]
+            ),
+            (
+                I::Mov {
+                    width: ArmWidth::Double,
+                    dest: x(X7),
+                    src: ArmVal::Element(V2, ArmWidth::Double, 1),
+                },
+                0x4e183c47, // mov x7, v2.d[1]
+            ),
+            (
+                I::Fmov {
+                    dest: x(V0),
+                    src: x(X1),
+                },
+                0x9e670020, // fmov d0, x1
+            ),
+            (
+                I::Fnmsub {
+                    dest: w(V0),
+                    arg1: w(V1),
+                    arg2: w(V2),
+                    arg3: w(V3),
+                },
+                0x1f228c20, // fnmsub s0, s1, s2, s3
+            ),
+            (
+                I::Fcvt {
+                    dest: x(V0),
+                    src: w(V1),
+                },
+                0x1e22c020, // fcvt d0, s1
+            ),
+            (
+                I::FcvtToInt {
+                    rounding: ArmRounding::MinusInf,
+                    signed: false,
+                    dest: x(X0),
+                    src: w(V1),
+                },
+                0x9e310020, // fcvtmu x0, s1
+            ),
+            (
+                I::Ucvtf {
+                    dest: x(V0),
+                    src: w(X1),
+                },
+                0x1e630020, // ucvtf d0, w1
+            ),
+            (
+                I::Dmb {
+                    option: ArmBarrier::Ishld,
+                },
+                0xd50339bf, // dmb ishld
+            ),
+            (I::DcCvau { addr: x(X9) }, 0xd50b7b29), // dc cvau, x9
+            (I::IcIvau { addr: x(X9) }, 0xd50b7529), // ic ivau, x9
+            (
+                I::Mrs {
+                    dest: x(X7),
+                    src: ArmSystemRegister::CtrEl0,
+                },
+                0xd53b0027, // mrs x7, ctr_el0
+            ),
+            (
+                I::Cbnz {
+                    src: w(X7),
+                    target: label(".", -16),
+                },
+                0x35ffff87, // cbnz w7, .-16
+            ),
+            (
+                I::Adr {
+                    dest: x(X0),
+                    label: label(".", -0x1235),
+                },
+                0x70ff6e40, // adr x0, .-4661
+            ),
+        ];
+        let labels = HashMap::new();
+        for (instr, word) in expected {
+            assert_eq!(encode(&instr, 0x1000, &labels), Ok(word), "{:?}", instr);
+        }
+    }
+
+    #[test]
+    fn test_encode_labels() {
+        let labels = HashMap::from([
+            ("loop".to_string(), 0x400100),
+            ("message".to_string(), 0x411008),
+        ]);
+        let x0 = x(ArmRegisterName::X0);
+        let message = |reloc| ArmVal::Reloc(reloc, "message".to_string(), 0);
+        let expected = [
+            (
+                ArmInstruction::B {
+                    target: label("loop", 0),
+                },
+                0x17ffffc0, // b .-256
+            ),
+            (
+                ArmInstruction::Bl {
+                    target: label("loop", 8),
+                },
+                0x97ffffc2, // bl .-248
+            ),
+            (
+                ArmInstruction::BCond {
+                    cond: ArmCondition::Ne,
+                    target: label("loop", 0),
+                },
+                0x54fff801, // b.ne .-256
+            ),
+            (
+                ArmInstruction::Adrp {
+                    dest: x0,
+                    label: message(ArmReloc::Page),
+                },
+                0xb0000080, // adrp x0, .+0x11000
+            ),
+            (
+                ArmInstruction::Add {
+                    dest: x0,
+                    arg1: x0,
+                    arg2: message(ArmReloc::Lo12),
+                },
+                0x91002000, // add x0, x0, 8
+            ),
+            (
+                ArmInstruction::Ldr {
+                    width: ArmWidth::Double,
+                    dest: x0,
+                    src: ArmVal::RelocOffset(x0, Box::new(message(ArmReloc::Lo12))),
+                },
+                0xf9400400, // ldr x0, [x0, 8]
+            ),
+            // the GOT entry of a static executable is relaxed to the address
+            (
+                ArmInstruction::Adrp {
+                    dest: x0,
+                    label: message(ArmReloc::GotPage),
+                },
+                0xb0000080, // adrp x0, .+0x11000
+            ),
+            (
+                ArmInstruction::Ldr {
+                    width: ArmWidth::Double,
+                    dest: x0,
+                    src: ArmVal::RelocOffset(x0, Box::new(message(ArmReloc::GotLo12))),
+                },
+                0x91002000, // add x0, x0, 8
+            ),
+        ];
+        for (instr, word) in expected {
+            assert_eq!(encode(&instr, 0x400200, &labels), Ok(word), "{:?}", instr);
+        }
+    }
+
+    #[test]
+    fn test_encode_errors() {
+        use ArmRegisterName::*;
+        let labels = HashMap::new();
+        let errors = [
+            (
+                ArmInstruction::Add {
+                    dest: x(X0),
+                    arg1: x(X1),
+                    arg2: ArmVal::Imm(0x1001),
+                },
+                "immediate out of range",
+            ),
+            (
+                ArmInstruction::Orr {
+                    dest: x(X0),
+                    arg1: x(X1),
+                    arg2: ArmVal::Imm(5),
+                },
+                "immediate can't be encoded as a bit pattern",
+            ),
+            (
+                ArmInstruction::Mov {
+                    width: ArmWidth::Double,
+                    dest: x(X0),
+                    src: ArmVal::Imm(0x12345678),
+                },
+                "immediate can't be moved",
+            ),
+            (
+                ArmInstruction::Mul {
+                    dest: x(X0),
+                    arg1: w(X1),
+                    arg2: x(X2),
+                },
+                "registers must have the same width",
+            ),
+            (
+                ArmInstruction::Mvn {
+                    dest: x(X0),
+                    src: x(Sp),
+                },
+                "sp can't be this operand",
+            ),
+            (
+                ArmInstruction::Ldr {
+                    width: ArmWidth::Double,
+                    dest: x(X0),
+                    src: ArmVal::RegOffset(x(X1), 0x8000),
+                },
+                "offset out of range",
+            ),
+            (
+                ArmInstruction::Ldr {
+                    width: ArmWidth::Byte,
+                    dest: x(X0),
+                    src: ArmVal::RegOffset(x(X1), 0),
+                },
+                "the register doesn't match the access width",
+            ),
+            (
+                ArmInstruction::Stxr {
+                    release: false,
+                    status: w(X0),
+                    src: x(X0),
+                    addr: X1,
+                },
+                "the status of stxr can't be another operand",
+            ),
+            (
+                ArmInstruction::Mov {
+                    width: ArmWidth::Double,
+                    dest: x(X7),
+                    src: ArmVal::Element(V1, ArmWidth::Word, 1),
+                },
+                "the register doesn't match the element width",
+            ),
+            (
+                ArmInstruction::B {
+                    target: label("nowhere", 0),
+                },
+                "undefined label",
+            ),
+            (
+                ArmInstruction::BCond {
+                    cond: ArmCondition::Eq,
+                    target: label(".", 1 << 20),
+                },
+                "branch target out of range",
+            ),
+            (
+                ArmInstruction::IcIvau { addr: w(X9) },
+                "expected a 64 bit register",
+            ),
+            (
+                ArmInstruction::Verbatim {
+                    text: "csrr a0, fflags".to_string(),
+                },
+                "verbatim text can't be encoded",
+            ),
+        ];
+        for (instr, reason) in errors {
+            assert_eq!(encode(&instr, 0, &labels), Err(reason), "{:?}", instr);
+        }
+    }
+
+    #[test]
+    fn test_assemble() {
+        let directive = |name: &str, operands: &str| ArmInstruction::Directive {
+            name: name.to_string(),
+            operands: operands.to_string(),
+        };
+        let label_at = |name: &str| ArmInstruction::Label {
+            name: name.to_string(),
+        };
+        let x0 = x(ArmRegisterName::X0);
+        let arm_instrs = vec![
+            directive("text", ""),
+            directive("globl", "_start"),
+            label_at("_start"),
+            ArmInstruction::Adrp {
+                dest: x0,
+                label: ArmVal::Reloc(ArmReloc::Page, "value".to_string(), 0),
+            },
+            ArmInstruction::Ldr {
+                width: ArmWidth::Double,
+                dest: x0,
+                src: ArmVal::RelocOffset(
+                    x0,
+                    Box::new(ArmVal::Reloc(ArmReloc::Lo12, "value".to_string(), 0)),
+                ),
+            },
+            directive("balign", "16"),
+            ArmInstruction::B {
+                target: label("_start", 0),
+            },
+            directive("section", ".rodata,\"a\",%progbits"),
+            directive("string", "\"hi\\n\""),
+            directive("p2align", "3"),
+            label_at("value"),
+            directive("quad", "_start+4, -1"),
+            directive("bss", ""),
+            directive("zero", "32"),
+            directive("section", ".note.GNU-stack,\"\",%progbits"),
+        ];
+        let image = assemble(arm_instrs).unwrap();
+        let layout = Layout::new(20, 24);
+        assert_eq!(
+            words(&image.text),
+            [
+                0xb0000080, // adrp x0, .+0x11000
+                0xf9400400, // ldr x0, [x0, 8]
+                0xd503201f, // nop, up to 16 bytes past 0x4000f0
+                0xd503201f, 0x17fffffc, // b .-16
+            ]
+        );
+        let mut data = b"hi\n\0\0\0\0\0".to_vec();
+        data.extend((layout.text + 4).to_le_bytes());
+        data.extend(u64::MAX.to_le_bytes());
+        assert_eq!(image.data, data);
+        assert_eq!(image.bss_size, 32);
+        assert_eq!(image.entry, 0);
+    }
+
+    #[test]
+    fn test_assemble_code_bounds() {
+        let arm_instrs = vec![
+            ArmInstruction::Label {
+                name: "_start".to_string(),
+            },
+            ArmInstruction::Ret,
+            ArmInstruction::Directive {
+                name: "data".to_string(),
+                operands: "".to_string(),
+            },
+            ArmInstruction::Directive {
+                name: "quad".to_string(),
+                operands: "__executable_start, etext".to_string(),
+            },
+        ];
+        let image = assemble(arm_instrs).unwrap();
+        let mut data = BASE_ADDRESS.to_le_bytes().to_vec();
+        data.extend((Layout::new(4, 16).text + 4).to_le_bytes());
+        assert_eq!(image.data, data);
+    }
+
+    #[test]
+    fn test_assemble_errors() {
+        let arm_instrs = vec![
+            ArmInstruction::Label {
+                name: "main".to_string(),
+            },
+            ArmInstruction::Directive {
+                name: "byte".to_string(),
+                operands: "0x100".to_string(),
+            },
+            ArmInstruction::Ret,
+            ArmInstruction::Directive {
+                name: "set".to_string(),
+                operands: "x, 1".to_string(),
+            },
+        ];
+        assert_eq!(
+            assemble(arm_instrs),
+            Err(vec![
+                AsmError::new(4, 0, ".set x, 1", "unsupported directive"),
+                AsmError::new(2, 0, ".byte 0x100", "value out of range"),
+                AsmError::new(3, 0, "ret", "instruction is not 4 byte aligned"),
+                AsmError::new(0, 0, "_start", "undefined entry point"),
+            ])
+        );
+    }
+
+    #[test]
+    fn test_assemble_float() {
+        let options = TranslateOptions {
+            prologue: Prologue::Start,
+            ..TranslateOptions::default()
+        };
+        let riscv_asm = "main:
+    fadd.s fa0, fa1, fa2
+    fmin.s fa3, fa0, fa1
+    feq.s a0, fa0, fa1
+    fclass.s a1, fa3
+    fcvt.w.s a2, fa0, rtz
+    fmadd.s fa4, fa0, fa1, fa2
+    fadd.d fa5, fa1, fa2
+    fmax.d fa6, fa5, fa1
+    fcvt.s.d fa7, fa6
+    ret
+";
+        let elf = binary_translate_executable(riscv_asm, &options).unwrap();
+        // mov w7, v1.s[1], which checks that fa1 is NaN-boxed
+        assert!(words(&elf[0xf0..]).contains(&0x0e0c3c27));
+
+        let riscv_asm = "main:\n    ret\n    .byte 0x100\n";
+        assert_eq!(
+            binary_translate_executable(riscv_asm, &options),
+            Err(vec![AsmError::new(
+                3,
+                5,
+                ".byte 0x100",
+                "value out of range in `.byte 0x100`"
+            )])
+        );
+    }
+
+    #[test]
+    fn test_assemble_sp_operands() {
+        let options = TranslateOptions {
+            prologue: Prologue::Start,
+            ..TranslateOptions::default()
+        };
+        // what translates with `sp` also assembles
+        let riscv_asm = "main:
+    addi sp, sp, -2000
+    add a0, a1, sp
+    sub a0, a1, sp
+    add sp, a1, a2
+    mv a0, sp
+    andi a0, sp, 5
+    slt a0, sp, a1
+    ld a0, 8(sp)
+    sd a0, -8(sp)
+    lr.d a0, (sp)
+    jalr a0, 8(a1)
+    ret
+";
+        binary_translate(riscv_asm, &options).unwrap();
+        binary_translate_executable(riscv_asm, &options).unwrap();
+
+        // and what doesn't is rejected before it's assembled
+        for riscv_asm in [
+            "ld sp, 8(a0)",
+            "lbu sp, 8(a0)",
+            "lw sp, -8(a0)",
+            "sw sp, 8(a0)",
+            "sd sp, 8(sp)",
+            "seqz sp, a0",
+            "sgtu sp, a0, a1",
+            "slti sp, a0, 5",
+            "li sp, 5",
+            "lui sp, 5",
+            "lla sp, main",
+            "jal sp, main",
+            "jalr a0, 0(sp)",
+            "jr sp",
+            "sext.w a0, sp",
+        ] {
+            let riscv_asm = format!("main:\n    {}\n", riscv_asm);
+            for errors in [
+                binary_translate(&riscv_asm, &options).unwrap_err(),
+                binary_translate_executable(&riscv_asm, &options).unwrap_err(),
+            ] {
+                assert_eq!(
+                    errors[0].reason, "`sp` is not supported as an operand of this instruction",
+                    "{}",
+                    riscv_asm
+                );
+            }
+        }
+    }
+}
@@ -0,0 +1,115 @@
+#[cfg(test)]
+mod tests {
+    use binary_room::executable::*;
+
+    fn u16_at(bytes: &[u8], offset: usize) -> u16 {
+        u16::from_le_bytes(bytes[offset..offset + 2].try_into().unwrap())
+    }
+
+    fn u32_at(bytes: &[u8], offset: usize) -> u32 {
+        u32::from_le_bytes(bytes[offset..offset + 4].try_into().unwrap())
+    }
+
+    fn u64_at(bytes: &[u8], offset: usize) -> u64 {
+        u64::from_le_bytes(bytes[offset..offset + 8].try_into().unwrap())
+    }
+
+    /// The type, flags, offset, address, file size and memory size of each
+    /// program header.
+    fn program_headers(elf: &[u8]) -> Vec<(u32, u32, u64, u64, u64, u64)> {
+        let offset = u64_at(elf, 0x20) as usize;
+        (0..u16_at(elf, 0x38) as usize)
+            .map(|index| {
+                let header = offset + index * 56;
+                (
+                    u32_at(elf, header),
+                    u32_at(elf, header + 4),
+                    u64_at(elf, header + 8),
+                    u64_at(elf, header + 16),
+                    u64_at(elf, header + 32),
+                    u64_at(elf, header + 40),
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_write_executable() {
+        let text: Vec<u8> = [
+            0xd503201f, // nop
+            0xd2800540, // mov x0, 42
+            0xd2800ba8, // mov x8, 93
+            0xd4000001, // svc 0
+        ]
+        .iter()
+        .flat_map(|word: &u32| word.to_le_bytes())
+        .collect();
+        let image = Image {
+            text: text.clone(),
+            data: b"hello\n".to_vec(),
+            bss_size: 64,
+            entry: 4,
+        };
+        let elf = write_executable(&image);
+        let layout = Layout::new(16, 6);
+        assert_eq!(layout.text, 0x4000f0);
+        assert_eq!(layout.data, 0x411000);
+        assert_eq!(layout.bss, 0x412000);
+
+        assert!(elf.starts_with(b"\x7fELF\x02\x01\x01"));
+        // ET_EXEC, EM_AARCH64
+        assert_eq!((u16_at(&elf, 0x10), u16_at(&elf, 0x12)), (2, 183));
+        assert_eq!(u64_at(&elf, 0x18), layout.text + 4);
+        assert_eq!(
+            program_headers(&elf),
+            [
+                (1, 0x5, 0, 0x400000, 0x100, 0x100),
+                (1, 0x6, 0x1000, 0x411000, 6, 0x1040),
+                (0x6474e551, 0x6, 0, 0, 0, 0),
+            ]
+        );
+        assert_eq!(&elf[0xf0..0x100], text);
+        assert_eq!(&elf[0x1000..0x1006], b"hello\n");
+
+        // the section names, and each section's address and size
+        let sections = u64_at(&elf, 0x28) as usize;
+        let names = u64_at(&elf, sections + 4 * 64 + 24) as usize;
+        let sections: Vec<_> = (1..u16_at(&elf, 0x3c) as usize)
+            .map(|index| {
+                let header = sections + index * 64;
+                let name = &elf[names + u32_at(&elf, header) as usize..];
+                let name = &name[..name.iter().position(|&byte| byte == 0).unwrap()];
+                (
+                    std::str::from_utf8(name).unwrap(),
+                    u64_at(&elf, header + 16),
+                    u64_at(&elf, header + 32),
+                )
+            })
+            .collect();
+        assert_eq!(
+            sections,
+            [
+                (".text", 0x4000f0, 16),
+                (".data", 0x411000, 6),
+                (".bss", 0x412000, 64),
+                (".shstrtab", 0, 28),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_write_executable_without_data() {
+        let image = Image {
+            text: 0xd65f03c0u32.to_le_bytes().to_vec(),
+            ..Image::default()
+        };
+        let elf = write_executable(&image);
+        assert_eq!(
+            program_headers(&elf),
+            [
+                (1, 0x5, 0, 0x400000, 0xf4, 0xf4),
+                (0x6474e551, 0x6, 0, 0, 0, 0),
+            ]
+        );
+    }
+}
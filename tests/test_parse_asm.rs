@@ -1,48 +1,620 @@
-// #[cfg(test)]
-// mod tests {
-//     use binary_room::instruction::parse_asm;
-
-//     #[test]
-//     fn test_parse_asm() {
-//         let asm = "
-//             addi sp,sp,-32
-//             sd ra,24(sp)
-//             ld s0,16(sp)
-//             addi s0,sp,32
-//             li a5,3
-//             sw a5,-20(s0)
-//             li a5,4
-//             sw a5,-24(s0)
-//             lw a5,-20(s0)
-//             mv a4,a5
-//             lw a5,-24(s0)
-//             addw a5,a4,a5
-//             sext.w a5,a5
-//             mv a0,a5
-//             ld ra,24(sp)
-//             ld s0,16(sp)
-//             addi sp,sp,32
-//             jr ra
-//         ";
-//         let instructions = parse_asm(asm);
-//         assert_eq!(instructions.len(), 17);
-//         assert_eq!(instructions[0], RiscVInstruction::Addi);
-//         assert_eq!(instructions[1], RiscVInstruction::Sd);
-//         assert_eq!(instructions[2], RiscVInstruction::Ld);
-//         assert_eq!(instructions[3], RiscVInstruction::Addi);
-//         assert_eq!(instructions[4], RiscVInstruction::Li);
-//         assert_eq!(instructions[5], RiscVInstruction::Sw);
-//         assert_eq!(instructions[6], RiscVInstruction::Li);
-//         assert_eq!(instructions[7], RiscVInstruction::Sw);
-//         assert_eq!(instructions[8], RiscVInstruction::Lw);
-//         assert_eq!(instructions[9], RiscVInstruction::Mv);
-//         assert_eq!(instructions[10], RiscVInstruction::Lw);
-//         assert_eq!(instructions[11], RiscVInstruction::Addw);
-//         assert_eq!(instructions[12], RiscVInstruction::SextW);
-//         assert_eq!(instructions[13], RiscVInstruction::Mv);
-//         assert_eq!(instructions[14], RiscVInstruction::Ld);
-//         assert_eq!(instructions[15], RiscVInstruction::Ld);
-//         assert_eq!(instructions[16], RiscVInstruction::Addi);
-//         assert_eq!(instructions[17], RiscVInstruction::Jr);
-//     }
-// }
+#[cfg(test)]
+mod tests {
+    use binary_room::error::AsmError;
+    use binary_room::instruction::*;
+
+    #[test]
+    fn test_parse_asm() {
+        let asm = "
+            addi sp,sp,-32
+            sd ra,24(sp)
+            ld s0,16(sp)
+            addi s0,sp,32
+            li a5,3
+            sw a5,-20(s0)
+            li a5,4
+            sw a5,-24(s0)
+            lw a5,-20(s0)
+            mv a4,a5
+            lw a5,-24(s0)
+            addw a5,a4,a5
+            sext.w a5,a5
+            mv a0,a5
+            ld ra,24(sp)
+            ld s0,16(sp)
+            addi sp,sp,32
+            jr ra
+        ";
+        let instructions = parse_asm(asm).unwrap();
+        assert_eq!(instructions.len(), 18);
+        assert_eq!(
+            instructions[0],
+            RiscVInstruction::Addi {
+                dest: RiscVRegister::SP,
+                src: RiscVRegister::SP,
+                imm: -32,
+            }
+        );
+        assert_eq!(
+            instructions[1],
+            RiscVInstruction::S {
+                width: RiscVWidth::Double,
+                src: RiscVRegister::RA,
+                dest: RiscVVal::Offset {
+                    register: RiscVRegister::SP,
+                    offset: 24,
+                },
+            }
+        );
+        assert_eq!(
+            instructions[5],
+            RiscVInstruction::S {
+                width: RiscVWidth::Word,
+                src: RiscVRegister::A5,
+                dest: RiscVVal::Offset {
+                    register: RiscVRegister::S0FP,
+                    offset: -20,
+                },
+            }
+        );
+        assert_eq!(
+            instructions[11],
+            RiscVInstruction::Add {
+                width: RiscVWidth::Word,
+                dest: RiscVRegister::A5,
+                arg1: RiscVRegister::A4,
+                arg2: RiscVRegister::A5,
+            }
+        );
+        assert_eq!(
+            instructions[12],
+            RiscVInstruction::SextW {
+                dest: RiscVRegister::A5,
+                src: RiscVRegister::A5,
+            }
+        );
+        assert_eq!(
+            instructions[17],
+            RiscVInstruction::Jr {
+                target: RiscVRegister::RA,
+            }
+        );
+    }
+
+    #[test]
+    fn test_parse_labels_directives_comments() {
+        let asm = r#"
+# leading comment
+	.section	.rodata
+.LC0:	.string	"a # not a comment"
+main: # int main(void)
+.L2: nop
+	.type	main, @function
+        "#;
+        let instructions = parse_asm(asm).unwrap();
+        assert_eq!(
+            instructions,
+            vec![
+                RiscVInstruction::Directive {
+                    name: "section".to_string(),
+                    operands: ".rodata".to_string(),
+                },
+                RiscVInstruction::Label {
+                    name: ".LC0".to_string(),
+                },
+                RiscVInstruction::Directive {
+                    name: "string".to_string(),
+                    operands: "\"a # not a comment\"".to_string(),
+                },
+                RiscVInstruction::Label {
+                    name: "main".to_string(),
+                },
+                RiscVInstruction::Label {
+                    name: ".L2".to_string(),
+                },
+                RiscVInstruction::Nop,
+                RiscVInstruction::Directive {
+                    name: "type".to_string(),
+                    operands: "main, @function".to_string(),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_operands() {
+        let asm = "
+            li x10, 0x7ff
+            li t6, -0x10
+            addi a1, zero, %lo(buf)
+            lui a0, %hi(buf)
+            ld a2, (x2)
+            bne a5,zero,.L7
+            call is_prime
+            ret
+        ";
+        let instructions = parse_asm(asm).unwrap();
+        assert_eq!(
+            instructions,
+            vec![
+                RiscVInstruction::Li {
+                    dest: RiscVRegister::A0,
+                    imm: 0x7ff,
+                },
+                RiscVInstruction::Li {
+                    dest: RiscVRegister::T6,
+                    imm: -16,
+                },
+                RiscVInstruction::Addl {
+                    dest: RiscVRegister::A1,
+                    src: RiscVRegister::X0,
+                    label: RiscVVal::Reloc {
+                        reloc: RiscVReloc::Lo12,
+                        label: "buf".to_string(),
+                        offset: 0,
+                    },
+                },
+                RiscVInstruction::Lui {
+                    dest: RiscVRegister::A0,
+                    src: RiscVVal::Reloc {
+                        reloc: RiscVReloc::Hi20,
+                        label: "buf".to_string(),
+                        offset: 0,
+                    },
+                },
+                RiscVInstruction::L {
+                    width: RiscVWidth::Double,
+                    dest: RiscVRegister::A2,
+                    src: RiscVVal::Offset {
+                        register: RiscVRegister::SP,
+                        offset: 0,
+                    },
+                },
+                RiscVInstruction::Bne {
+                    arg1: RiscVRegister::A5,
+                    arg2: RiscVRegister::X0,
+                    target: RiscVVal::LabelOffset {
+                        label: ".L7".to_string(),
+                        offset: 0,
+                    },
+                },
+                RiscVInstruction::Call {
+                    label: RiscVVal::LabelOffset {
+                        label: "is_prime".to_string(),
+                        offset: 0,
+                    },
+                },
+                RiscVInstruction::Jr {
+                    target: RiscVRegister::RA,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_jumps() {
+        let asm = "
+            jal t0, f
+            jal f
+            jalr a0
+            jalr zero, 8(t1)
+            jalr s1, a1
+            jalr t0, t0, -16
+            jalr -4(a2)
+            tail f
+            call f@plt
+            tail f@plt
+        ";
+        let f = || RiscVVal::LabelOffset {
+            label: "f".to_string(),
+            offset: 0,
+        };
+        let instructions = parse_asm(asm).unwrap();
+        assert_eq!(
+            instructions,
+            vec![
+                RiscVInstruction::Jal {
+                    dest: RiscVRegister::T0,
+                    target: f(),
+                },
+                RiscVInstruction::Jal {
+                    dest: RiscVRegister::RA,
+                    target: f(),
+                },
+                RiscVInstruction::Jalr {
+                    dest: RiscVRegister::RA,
+                    target: RiscVRegister::A0,
+                    offset: 0,
+                },
+                RiscVInstruction::Jalr {
+                    dest: RiscVRegister::X0,
+                    target: RiscVRegister::T1,
+                    offset: 8,
+                },
+                RiscVInstruction::Jalr {
+                    dest: RiscVRegister::S1,
+                    target: RiscVRegister::A1,
+                    offset: 0,
+                },
+                RiscVInstruction::Jalr {
+                    dest: RiscVRegister::T0,
+                    target: RiscVRegister::T0,
+                    offset: -16,
+                },
+                RiscVInstruction::Jalr {
+                    dest: RiscVRegister::RA,
+                    target: RiscVRegister::A2,
+                    offset: -4,
+                },
+                RiscVInstruction::J { target: f() },
+                RiscVInstruction::Call { label: f() },
+                RiscVInstruction::J { target: f() },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_relocs() {
+        let asm = "
+            lui a0, %hi(buf+8)
+            addi a0, a0, %lo(buf+8)
+            addi a1, a1, %pcrel_lo(.Lpcrel_hi0)
+            lui a2, %tprel_hi(counter)
+            j .L3-4
+        ";
+        let reloc = |reloc, label: &str, offset| RiscVVal::Reloc {
+            reloc,
+            label: label.to_string(),
+            offset,
+        };
+        let instructions = parse_asm(asm).unwrap();
+        assert_eq!(
+            instructions,
+            vec![
+                RiscVInstruction::Lui {
+                    dest: RiscVRegister::A0,
+                    src: reloc(RiscVReloc::Hi20, "buf", 8),
+                },
+                RiscVInstruction::Addl {
+                    dest: RiscVRegister::A0,
+                    src: RiscVRegister::A0,
+                    label: reloc(RiscVReloc::Lo12, "buf", 8),
+                },
+                RiscVInstruction::Addl {
+                    dest: RiscVRegister::A1,
+                    src: RiscVRegister::A1,
+                    label: reloc(RiscVReloc::PcrelLo12, ".Lpcrel_hi0", 0),
+                },
+                RiscVInstruction::Lui {
+                    dest: RiscVRegister::A2,
+                    src: reloc(RiscVReloc::TprelHi20, "counter", 0),
+                },
+                RiscVInstruction::J {
+                    target: RiscVVal::LabelOffset {
+                        label: ".L3".to_string(),
+                        offset: -4,
+                    },
+                },
+            ]
+        );
+
+        let instructions = parse_asm(
+            "
+            auipc a0, %got_pcrel_hi(stdout)
+            ld a0, %pcrel_lo(.Lpcrel_hi0)(a0)
+            auipc a1, 0x10
+            lla a2, buf
+        ",
+        )
+        .unwrap();
+        assert_eq!(
+            instructions,
+            vec![
+                RiscVInstruction::Auipc {
+                    dest: RiscVRegister::A0,
+                    src: reloc(RiscVReloc::GotPcrelHi20, "stdout", 0),
+                },
+                RiscVInstruction::L {
+                    width: RiscVWidth::Double,
+                    dest: RiscVRegister::A0,
+                    src: RiscVVal::RelocOffset {
+                        register: RiscVRegister::A0,
+                        reloc: RiscVReloc::PcrelLo12,
+                        label: ".Lpcrel_hi0".to_string(),
+                        offset: 0,
+                    },
+                },
+                RiscVInstruction::Auipc {
+                    dest: RiscVRegister::A1,
+                    src: RiscVVal::Immediate(0x10),
+                },
+                RiscVInstruction::Lla {
+                    dest: RiscVRegister::A2,
+                    label: RiscVVal::LabelOffset {
+                        label: "buf".to_string(),
+                        offset: 0,
+                    },
+                },
+            ]
+        );
+
+        let errors = parse_asm("lui a0, %lo(buf)\naddi a0, a0, %foo(buf)\n").unwrap_err();
+        assert_eq!(
+            errors,
+            vec![
+                AsmError::new(1, 9, "%lo(buf)", "expected %hi(label)"),
+                AsmError::new(2, 14, "%foo(buf)", "unknown relocation"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_fences() {
+        let asm = "
+            fence
+            fence rw, w
+            fence.tso
+            fence.i
+            pause
+        ";
+        let instructions = parse_asm(asm).unwrap();
+        assert_eq!(
+            instructions,
+            vec![
+                RiscVInstruction::Fence {
+                    pred: RiscVFenceSet::ALL,
+                    succ: RiscVFenceSet::ALL,
+                },
+                RiscVInstruction::Fence {
+                    pred: RiscVFenceSet {
+                        read: true,
+                        write: true,
+                        ..RiscVFenceSet::default()
+                    },
+                    succ: RiscVFenceSet {
+                        write: true,
+                        ..RiscVFenceSet::default()
+                    },
+                },
+                RiscVInstruction::FenceTso,
+                RiscVInstruction::FenceI,
+                RiscVInstruction::Pause,
+            ]
+        );
+
+        let errors = parse_asm("fence wr, rw\n").unwrap_err();
+        assert_eq!(errors, vec![AsmError::new(1, 7, "wr", "invalid fence set")]);
+    }
+
+    #[test]
+    fn test_parse_atomics() {
+        let asm = "
+            lr.w a0, (a1)
+            sc.d.rl a2, a3, 0(a1)
+            amoadd.w.aqrl zero, t0, (t1)
+            amomaxu.d.aq a0, a1, (a2)
+        ";
+        let instructions = parse_asm(asm).unwrap();
+        assert_eq!(
+            instructions,
+            vec![
+                RiscVInstruction::Lr {
+                    width: RiscVWidth::Word,
+                    ordering: RiscVOrdering::Relaxed,
+                    dest: RiscVRegister::A0,
+                    addr: RiscVRegister::A1,
+                },
+                RiscVInstruction::Sc {
+                    width: RiscVWidth::Double,
+                    ordering: RiscVOrdering::Release,
+                    dest: RiscVRegister::A2,
+                    src: RiscVRegister::A3,
+                    addr: RiscVRegister::A1,
+                },
+                RiscVInstruction::Amo {
+                    op: RiscVAmoOp::Add,
+                    width: RiscVWidth::Word,
+                    ordering: RiscVOrdering::AcquireRelease,
+                    dest: RiscVRegister::X0,
+                    src: RiscVRegister::T0,
+                    addr: RiscVRegister::T1,
+                },
+                RiscVInstruction::Amo {
+                    op: RiscVAmoOp::Maxu,
+                    width: RiscVWidth::Double,
+                    ordering: RiscVOrdering::Acquire,
+                    dest: RiscVRegister::A0,
+                    src: RiscVRegister::A1,
+                    addr: RiscVRegister::A2,
+                },
+            ]
+        );
+
+        let asm = "amoadd.b a0, a1, (a2)\nlr.d a0, 8(a1)\nsc.w a0, (a1)\n";
+        let errors = parse_asm(asm).unwrap_err();
+        assert_eq!(
+            errors,
+            vec![
+                AsmError::new(1, 1, "amoadd.b", "unsupported instruction"),
+                AsmError::new(
+                    2,
+                    10,
+                    "8(a1)",
+                    "atomic memory operands can't have an offset"
+                ),
+                AsmError::new(3, 1, "sc.w a0, (a1)", "wrong number of operands for `sc.w`"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_floats() {
+        let asm = "
+            flw fa0, 8(a0)
+            fsd fs1, -8(sp)
+            fmadd.d ft0, fa1, fa2, fa3, rtz
+            fneg.s f1, f2
+            fge.d a0, fa0, fa1
+            fmv.x.w a0, ft0
+            fcvt.lu.s a0, fa0, rup
+            fcvt.d.w fa0, a1
+            fcvt.s.d fa0, fa1
+        ";
+        let instructions = parse_asm(asm).unwrap();
+        assert_eq!(
+            instructions,
+            vec![
+                RiscVInstruction::FL {
+                    precision: RiscVPrecision::Single,
+                    dest: RiscVFRegister::FA0,
+                    src: RiscVVal::Offset {
+                        register: RiscVRegister::A0,
+                        offset: 8,
+                    },
+                },
+                RiscVInstruction::FS {
+                    precision: RiscVPrecision::Double,
+                    src: RiscVFRegister::FS1,
+                    dest: RiscVVal::Offset {
+                        register: RiscVRegister::SP,
+                        offset: -8,
+                    },
+                },
+                RiscVInstruction::FMadd {
+                    precision: RiscVPrecision::Double,
+                    rounding: RiscVRounding::Rtz,
+                    dest: RiscVFRegister::FT0,
+                    arg1: RiscVFRegister::FA1,
+                    arg2: RiscVFRegister::FA2,
+                    arg3: RiscVFRegister::FA3,
+                },
+                RiscVInstruction::FSgnjn {
+                    precision: RiscVPrecision::Single,
+                    dest: RiscVFRegister::FT1,
+                    arg1: RiscVFRegister::FT2,
+                    arg2: RiscVFRegister::FT2,
+                },
+                RiscVInstruction::FLe {
+                    precision: RiscVPrecision::Double,
+                    dest: RiscVRegister::A0,
+                    arg1: RiscVFRegister::FA1,
+                    arg2: RiscVFRegister::FA0,
+                },
+                RiscVInstruction::FMvToInt {
+                    precision: RiscVPrecision::Single,
+                    dest: RiscVRegister::A0,
+                    src: RiscVFRegister::FT0,
+                },
+                RiscVInstruction::FCvtToInt {
+                    precision: RiscVPrecision::Single,
+                    width: RiscVWidth::Double,
+                    signed: false,
+                    rounding: RiscVRounding::Rup,
+                    dest: RiscVRegister::A0,
+                    src: RiscVFRegister::FA0,
+                },
+                RiscVInstruction::FCvtFromInt {
+                    precision: RiscVPrecision::Double,
+                    width: RiscVWidth::Word,
+                    signed: true,
+                    rounding: RiscVRounding::Dyn,
+                    dest: RiscVFRegister::FA0,
+                    src: RiscVRegister::A1,
+                },
+                RiscVInstruction::FCvt {
+                    precision: RiscVPrecision::Single,
+                    rounding: RiscVRounding::Dyn,
+                    dest: RiscVFRegister::FA0,
+                    src: RiscVFRegister::FA1,
+                },
+            ]
+        );
+
+        let asm =
+            "fadd.q fa0, fa1, fa2\nfadd.s fa0, fa1\nfsub.d fa0, fa1, a2\nfcvt.w.s a0, fa0, up\n";
+        let errors = parse_asm(asm).unwrap_err();
+        assert_eq!(
+            errors,
+            vec![
+                AsmError::new(1, 1, "fadd.q", "unsupported instruction"),
+                AsmError::new(
+                    2,
+                    1,
+                    "fadd.s fa0, fa1",
+                    "wrong number of operands for `fadd.s`"
+                ),
+                AsmError::new(3, 18, "a2", "unknown register"),
+                AsmError::new(4, 19, "up", "invalid rounding mode"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_gcc_output() {
+        let prime = parse_asm(include_str!("prime/prime.riscv.s")).unwrap();
+        assert_eq!(prime.len(), 72);
+        let fib = parse_asm(include_str!("fib/fib.riscv.s")).unwrap();
+        assert_eq!(fib.len(), 49);
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        let asm = "
+    li a0, 1
+    frobnicate a0, a1
+    addi a0, q7, 1
+    sd a0, 8[sp]
+    mv a0
+    ret a0
+    fence rw
+    jalr a0, a1, 0, a2
+";
+        let errors = parse_asm(asm).unwrap_err();
+        assert_eq!(
+            errors,
+            vec![
+                AsmError::new(3, 5, "frobnicate", "unsupported instruction"),
+                AsmError::new(4, 14, "q7", "unknown register"),
+                AsmError::new(5, 12, "8[sp]", "invalid memory operand"),
+                AsmError::new(6, 5, "mv a0", "wrong number of operands for `mv`"),
+                AsmError::new(7, 5, "ret a0", "wrong number of operands for `ret`"),
+                AsmError::new(8, 5, "fence rw", "wrong number of operands for `fence`"),
+                AsmError::new(
+                    9,
+                    5,
+                    "jalr a0, a1, 0, a2",
+                    "wrong number of operands for `jalr`"
+                ),
+            ]
+        );
+        assert_eq!(
+            errors[0].to_string(),
+            "3:5: unsupported instruction: `frobnicate`"
+        );
+    }
+
+    #[test]
+    fn test_parse_imm12_range() {
+        let asm = "
+    addi a0, a0, 2047
+    addi a0, a0, -2048
+    ld a0, -2048(sp)
+    addi a0, a0, 5000
+    ld a0, 40000(sp)
+    sw a0, -2049(a1)
+    xori a0, a0, 4096
+    jalr ra, 2048(a0)
+";
+        let errors = parse_asm(asm).unwrap_err();
+        assert_eq!(
+            errors,
+            vec![
+                AsmError::new(5, 18, "5000", "immediate out of range"),
+                AsmError::new(6, 12, "40000", "immediate out of range"),
+                AsmError::new(7, 12, "-2049", "immediate out of range"),
+                AsmError::new(8, 18, "4096", "immediate out of range"),
+                AsmError::new(9, 14, "2048", "immediate out of range"),
+            ]
+        );
+    }
+}
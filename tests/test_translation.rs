@@ -1,10 +1,12 @@
 #[cfg(test)]
 mod tests {
     use binary_room::instruction::*;
+    use binary_room::error::AsmError;
     use binary_room::translate::*;
-    use binary_room::utils;
     use binary_room::utils::translate_to_file;
+    use binary_room::utils::ARM_LOOP_START;
     use binary_room::utils::ARM_START;
+    use binary_room::utils::START_NO_MAIN;
 
     #[test]
     fn test_binary_translate() {
@@ -122,7 +124,8 @@ mod tests {
             },
         ];
 
-        translate_to_file(riscv_asm, "test_binary_translate_add.S".to_string());
+        let path = std::env::temp_dir().join("binary_room_add.S");
+        translate_to_file(riscv_asm, path.display().to_string());
     }
 
     #[test]
@@ -182,17 +185,19 @@ mod tests {
             },
             RiscVInstruction::Lui {
                 dest: RiscVRegister::A5,
-                src: RiscVVal::LabelOffset {
+                src: RiscVVal::Reloc {
+                    reloc: RiscVReloc::Hi20,
                     label: ".LC0".to_string(),
-                    offset: 9998, // %hi riscv
+                    offset: 0,
                 },
             },
             RiscVInstruction::Addl {
                 dest: RiscVRegister::A2,
                 src: RiscVRegister::A5,
-                label: RiscVVal::LabelOffset {
+                label: RiscVVal::Reloc {
+                    reloc: RiscVReloc::Lo12,
                     label: ".LC0".to_string(),
-                    offset: 9999, // %lo riscv, :lo12: arm
+                    offset: 0,
                 },
             },
             RiscVInstruction::Li {
@@ -235,7 +240,8 @@ mod tests {
             },
         ];
 
-        translate_to_file(riscv_asm, "test_binary_translate_write.S".to_string());
+        let path = std::env::temp_dir().join("binary_room_write.S");
+        translate_to_file(riscv_asm, path.display().to_string());
     }
 
     #[test]
@@ -377,6 +383,912 @@ mod tests {
                 target: RiscVRegister::RA,
             },
         ];
-        translate_to_file(riscv_asm, "test_binary_translate_loop.S".to_string());
+        let path = std::env::temp_dir().join("binary_room_loop.S");
+        translate_to_file(riscv_asm, path.display().to_string());
+    }
+
+    #[test]
+    fn test_translate_errors() {
+        let riscv_asm: Vec<RiscVInstruction> = vec![
+            RiscVInstruction::Slli {
+                width: RiscVWidth::Double,
+                dest: RiscVRegister::A0,
+                src: RiscVRegister::A0,
+                imm: 64,
+            },
+            RiscVInstruction::Nop,
+            RiscVInstruction::Not {
+                dest: RiscVRegister::SP,
+                src: RiscVRegister::A1,
+            },
+        ];
+
+        let errors = translate_instrs(riscv_asm).unwrap_err();
+        assert_eq!(errors.len(), 2);
+        assert_eq!(errors[0].line, 1);
+        assert_eq!(errors[0].reason, "shift amount out of range");
+        assert_eq!(errors[1].line, 3);
+        assert_eq!(errors[1].text, "Not { dest: SP, src: A1 }");
+    }
+
+    #[test]
+    fn test_binary_translate_text() {
+        let riscv_asm = "
+    .option nopic
+main:
+    lui a0, %hi(buf)
+    addi a1, a0, %lo(buf) # address of buf
+    jr ra
+";
+        let arm_asm = binary_translate(riscv_asm, &TranslateOptions::default()).unwrap();
+        assert_eq!(arm_asm, "main:\nadrp x0, buf\nadd x1, x0, :lo12:buf\nret\n");
+
+        let options = TranslateOptions {
+            prologue: Prologue::Start,
+            dialect: Dialect::Gnu,
+            annotate: true,
+            elide_sign_extensions: false,
+            lse: false,
+        };
+        let arm_asm = binary_translate(riscv_asm, &options).unwrap();
+        let expected = format!(
+            "{}main:\n// lui a0, %hi(buf)\nadrp x0, buf\n\
+             // addi a1, a0, %lo(buf) # address of buf\nadd x1, x0, :lo12:buf\n\
+             // jr ra\nret\n",
+            START_NO_MAIN
+        );
+        assert_eq!(arm_asm, expected);
+    }
+
+    #[test]
+    fn test_prologue_instructions() {
+        // the prologue text without its comments, blank lines and `#`
+        // before immediates
+        let normalize = |text: &str| -> Vec<String> {
+            text.lines()
+                .map(str::trim)
+                .filter(|line| !line.is_empty() && !line.starts_with('#'))
+                .map(|line| line.replace('#', ""))
+                .collect()
+        };
+        let prologues = [
+            (Prologue::Start, false, ARM_START),
+            (Prologue::Start, true, START_NO_MAIN),
+            (Prologue::LoopStart, false, ARM_LOOP_START),
+        ];
+        for (prologue, defines_main, text) in prologues {
+            let instrs = prologue.instructions(defines_main);
+            assert_eq!(
+                normalize(&arm_to_asm(instrs, Dialect::Gnu)),
+                normalize(text),
+                "{:?}",
+                prologue
+            );
+        }
+        assert!(Prologue::None.instructions(false).is_empty());
+    }
+
+    #[test]
+    fn test_binary_translate_errors() {
+        let riscv_asm = "
+    slli a0, a0, 64
+    frobnicate
+    li a1, 1
+";
+        let errors = binary_translate(riscv_asm, &TranslateOptions::default()).unwrap_err();
+        assert_eq!(
+            errors,
+            vec![
+                AsmError::new(2, 5, "slli a0, a0, 64", "shift amount out of range"),
+                AsmError::new(3, 5, "frobnicate", "unsupported instruction"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_directives() {
+        let riscv_asm = r#"
+    .section .rodata,"a",@progbits
+    .type msg, @object
+msg:
+    .string "mail me@example.com"
+    .ascii "a@b\"@"
+    .cfi_startproc
+    .cfi_offset 1, -8
+    .cfi_restore 1
+    .cfi_endproc
+"#;
+        let arm_asm = binary_translate(riscv_asm, &TranslateOptions::default()).unwrap();
+        assert_eq!(
+            arm_asm,
+            r#".section .rodata,"a",%progbits
+.type msg, %object
+msg:
+.string "mail me@example.com"
+.ascii "a@b\"@"
+"#
+        );
+    }
+
+    #[test]
+    fn test_mul_div() {
+        let riscv_asm = "
+    mulw a0, a1, a2
+    mulhsu a0, a0, a2
+    div a0, a1, a0
+    divuw a3, a4, a5
+    rem a0, a1, a2
+    remw a0, a0, a2
+";
+        let arm_asm = binary_translate(riscv_asm, &TranslateOptions::default()).unwrap();
+        assert_eq!(
+            arm_asm,
+            "mul w0, w1, w2\nsxtw x0, w0\n\
+             cmp x0, xzr\ncsel x7, x2, xzr, lt\numulh x0, x0, x2\nsub x0, x0, x7\n\
+             cmp x0, xzr\nsdiv x0, x1, x0\ncsinv x0, x0, xzr, ne\n\
+             cmp w5, wzr\nudiv w3, w4, w5\ncsinv w3, w3, wzr, ne\nsxtw x3, w3\n\
+             sdiv x7, x1, x2\nmsub x0, x7, x2, x1\n\
+             sdiv w7, w0, w2\nmsub w0, w7, w2, w0\nsxtw x0, w0\n"
+        );
+    }
+
+    #[test]
+    fn test_add_sub_sp() {
+        let riscv_asm = "
+    add a0, a1, sp
+    add a0, sp, sp
+    sub a0, a1, sp
+    addw a0, a1, sp
+";
+        let arm_asm = binary_translate(riscv_asm, &TranslateOptions::default()).unwrap();
+        assert_eq!(
+            arm_asm,
+            "add x0, sp, x1\n\
+             mov x7, sp\nadd x0, sp, x7\n\
+             mov x7, sp\nsub x0, x1, x7\n\
+             add w0, wsp, w1\nsxtw x0, w0\n"
+        );
+
+        let errors = binary_translate(
+            "mul a0, sp, a1\nremu sp, a0, a1\naddw sp, a0, a1\n",
+            &TranslateOptions::default(),
+        )
+        .unwrap_err();
+        assert_eq!(errors.len(), 3);
+        for error in errors {
+            assert_eq!(
+                error.reason,
+                "`sp` is not supported as an operand of this instruction"
+            );
+        }
+    }
+
+    #[test]
+    fn test_logic_shifts() {
+        let riscv_asm = "
+    xor a0, a1, a2
+    andi a0, a1, 255
+    ori a0, a1, 5
+    andi sp, sp, -16
+    andi a0, sp, 5
+    xori a0, sp, -1000
+    not a0, a1
+    negw a0, a1
+    sraw a0, a1, a2
+    srli a0, a1, 63
+    sraiw a0, a1, 3
+";
+        let arm_asm = binary_translate(riscv_asm, &TranslateOptions::default()).unwrap();
+        assert_eq!(
+            arm_asm,
+            "eor x0, x1, x2\n\
+             and x0, x1, 255\n\
+             mov x7, 5\norr x0, x1, x7\n\
+             mov x7, sp\nand sp, x7, -16\n\
+             mov x7, sp\nmov x0, 5\nand x0, x7, x0\n\
+             mov x7, sp\nmov x0, -1000\neor x0, x7, x0\n\
+             mvn x0, x1\n\
+             neg w0, w1\nsxtw x0, w0\n\
+             asrv w0, w1, w2\nsxtw x0, w0\n\
+             lsr x0, x1, 63\n\
+             asr w0, w1, 3\nsxtw x0, w0\n"
+        );
+
+        let errors = binary_translate(
+            "slliw a0, a1, 32\nor sp, sp, a0\nandi sp, sp, 5\n",
+            &TranslateOptions::default(),
+        )
+        .unwrap_err();
+        assert_eq!(errors[0].reason, "shift amount out of range");
+        assert_eq!(
+            errors[1].reason,
+            "`sp` is not supported as an operand of this instruction"
+        );
+        assert_eq!(errors[2].reason, errors[1].reason);
+    }
+
+    #[test]
+    fn test_set_less_than() {
+        let riscv_asm = "
+    slt a0, a1, a2
+    sltu a0, a1, a2
+    slti a0, a1, -5
+    sltiu a0, a1, 100
+    seqz a0, a1
+    sgtz a0, a1
+    sgt a0, a4, a0
+    sgtu a0, a1, a2
+";
+        let arm_asm = binary_translate(riscv_asm, &TranslateOptions::default()).unwrap();
+        assert_eq!(
+            arm_asm,
+            "cmp x1, x2\ncset x0, lt\n\
+             cmp x1, x2\ncset x0, lo\n\
+             cmn x1, 5\ncset x0, lt\n\
+             cmp x1, 100\ncset x0, lo\n\
+             cmp x1, xzr\ncset x0, eq\n\
+             cmp x1, xzr\ncset x0, gt\n\
+             cmp x0, x4\ncset x0, lt\n\
+             cmp x2, x1\ncset x0, lo\n"
+        );
+    }
+
+    #[test]
+    fn test_branches() {
+        let riscv_asm = "
+    beq a0, a1, .L1
+    bltu a0, a1, .L1
+    bgeu a0, a1, .L1
+    bgtu a0, a1, .L1
+    beqz a0, .L1
+    blez a0, .L1
+    bgtz a0, .L1
+.L1:
+";
+        let arm_asm = binary_translate(riscv_asm, &TranslateOptions::default()).unwrap();
+        assert_eq!(
+            arm_asm,
+            "cmp x0, x1\nb.eq .L1\n\
+             cmp x0, x1\nb.lo .L1\n\
+             cmp x0, x1\nb.hs .L1\n\
+             cmp x0, x1\nb.hi .L1\n\
+             cmp x0, xzr\nb.eq .L1\n\
+             cmp x0, xzr\nb.le .L1\n\
+             cmp x0, xzr\nb.gt .L1\n\
+             .L1:\n"
+        );
+    }
+
+    #[test]
+    fn test_sub_word_loads_stores() {
+        let riscv_asm = "
+    lb a0, 0(a1)
+    lbu a0, 1(a1)
+    lh a0, 2(a1)
+    lhu a0, 2(a1)
+    lw a0, 4(a1)
+    lwu a0, 4(a1)
+    sb a0, 0(a1)
+    sh a0, 2(a1)
+";
+        let arm_asm = binary_translate(riscv_asm, &TranslateOptions::default()).unwrap();
+        assert_eq!(
+            arm_asm,
+            "ldrsb x0, [x1, 0]\n\
+             ldrb w0, [x1, 1]\n\
+             ldrsh x0, [x1, 2]\n\
+             ldrh w0, [x1, 2]\n\
+             ldrsw x0, [x1, 4]\n\
+             ldr w0, [x1, 4]\n\
+             strb w0, [x1, 0]\n\
+             strh w0, [x1, 2]\n"
+        );
+    }
+
+    #[test]
+    fn test_load_immediate() {
+        let riscv_asm = "
+    li a0, 5000
+    li a0, -0x12345678
+    li a0, 0x5555555555555555
+    li a0, 0x00ff00ff00ff1234
+    li a0, 0x123456789abcdef0
+    lui a0, 0xfffff
+    li zero, 0x5555555555555555
+    lui zero, 1
+";
+        let arm_asm = binary_translate(riscv_asm, &TranslateOptions::default()).unwrap();
+        assert_eq!(
+            arm_asm,
+            "mov x0, 5000\n\
+             mov x0, -22136\nmovk x0, 60875, lsl 16\n\
+             mov x0, 6148914691236517205\n\
+             mov x0, 71777214294589695\nmovk x0, 4660\n\
+             mov x0, 57072\nmovk x0, 39612, lsl 16\nmovk x0, 22136, lsl 32\nmovk x0, 4660, lsl 48\n\
+             mov x0, -4096\n"
+        );
+    }
+
+    #[test]
+    fn test_legalize_immediates() {
+        let riscv_asm = vec![
+            RiscVInstruction::Addi {
+                dest: RiscVRegister::SP,
+                src: RiscVRegister::SP,
+                imm: -0x12345,
+            },
+            RiscVInstruction::Addi {
+                dest: RiscVRegister::A0,
+                src: RiscVRegister::A1,
+                imm: 0x1234567,
+            },
+            RiscVInstruction::Slti {
+                dest: RiscVRegister::A0,
+                src: RiscVRegister::A1,
+                imm: 5000,
+            },
+            RiscVInstruction::Xori {
+                dest: RiscVRegister::A0,
+                src: RiscVRegister::A1,
+                imm: 0xff,
+            },
+            RiscVInstruction::Ori {
+                dest: RiscVRegister::A0,
+                src: RiscVRegister::A1,
+                imm: 3000,
+            },
+            RiscVInstruction::Mv {
+                dest: RiscVRegister::A0,
+                src: RiscVRegister::X0,
+            },
+        ];
+        let arm_instrs = translate_instrs(riscv_asm).unwrap();
+        assert_eq!(
+            arm_to_asm(arm_instrs, Dialect::Gnu),
+            "sub sp, sp, 73728\nsub sp, sp, 837\n\
+             mov x7, 17767\nmovk x7, 291, lsl 16\nadd x0, x1, x7\n\
+             mov x7, 5000\ncmp x1, x7\ncset x0, lt\n\
+             eor x0, x1, 255\n\
+             mov x7, 3000\norr x0, x1, x7\n\
+             mov x0, 0\n"
+        );
+    }
+
+    #[test]
+    fn test_addressing_modes() {
+        let offset = |register, offset| RiscVVal::Offset { register, offset };
+        let riscv_asm = vec![
+            RiscVInstruction::L {
+                width: RiscVWidth::Double,
+                dest: RiscVRegister::A0,
+                src: offset(RiscVRegister::SP, 32760),
+            },
+            RiscVInstruction::L {
+                width: RiscVWidth::Word,
+                dest: RiscVRegister::A0,
+                src: offset(RiscVRegister::S0FP, -20),
+            },
+            RiscVInstruction::L {
+                width: RiscVWidth::UnsignedHalf,
+                dest: RiscVRegister::A0,
+                src: offset(RiscVRegister::A1, 3),
+            },
+            RiscVInstruction::S {
+                width: RiscVWidth::Byte,
+                src: RiscVRegister::A0,
+                dest: offset(RiscVRegister::A1, 4095),
+            },
+            RiscVInstruction::S {
+                width: RiscVWidth::Double,
+                src: RiscVRegister::RA,
+                dest: offset(RiscVRegister::SP, 40000),
+            },
+            RiscVInstruction::S {
+                width: RiscVWidth::Word,
+                src: RiscVRegister::A0,
+                dest: offset(RiscVRegister::A1, -2048),
+            },
+            RiscVInstruction::L {
+                width: RiscVWidth::Word,
+                dest: RiscVRegister::A0,
+                src: offset(RiscVRegister::X0, 8),
+            },
+            RiscVInstruction::S {
+                width: RiscVWidth::Half,
+                src: RiscVRegister::A0,
+                dest: offset(RiscVRegister::X0, -8),
+            },
+        ];
+        let arm_instrs = translate_instrs(riscv_asm).unwrap();
+        assert_eq!(
+            arm_to_asm(arm_instrs, Dialect::Gnu),
+            "ldr x0, [sp, 32760]\n\
+             ldursw x0, [x29, -20]\n\
+             ldurh w0, [x1, 3]\n\
+             strb w0, [x1, 4095]\n\
+             mov x7, 40000\nstr lr, [sp, x7]\n\
+             mov x7, -2048\nstr w0, [x1, x7]\n\
+             mov x7, 8\nldrsw x0, [x7, 0]\n\
+             mov x7, -8\nstrh w0, [x7, 0]\n"
+        );
+    }
+
+    #[test]
+    fn test_register_names() {
+        let names: Vec<String> = [
+            ArmRegisterName::Zero,
+            ArmRegisterName::Sp,
+            ArmRegisterName::Lr,
+            ArmRegisterName::X0,
+            ArmRegisterName::X8,
+            ArmRegisterName::X17,
+            ArmRegisterName::X29,
+        ]
+        .iter()
+        .flat_map(|&name| {
+            [ArmWidth::Word, ArmWidth::Double]
+                .map(|width| ArmRegister::new(name, width).to_string())
+        })
+        .collect();
+        assert_eq!(
+            names,
+            [
+                "wzr", "xzr", "wsp", "sp", "w30", "lr", "w0", "x0", "w8", "x8", "w17", "x17",
+                "w29", "x29"
+            ]
+        );
+
+        assert!(ArmRegister::try_new(ArmRegisterName::X0, ArmWidth::Byte).is_none());
+        assert!(ArmRegister::try_new(ArmRegisterName::X0, ArmWidth::SignedWord).is_none());
+        assert!(ArmRegister::try_new(ArmRegisterName::Pc, ArmWidth::Double).is_none());
+        assert_eq!(
+            ArmRegister::try_from(ArmRegisterName::X3).map(|reg| reg.to_string()),
+            Ok("x3".to_string())
+        );
+        assert!(ArmRegister::try_from(ArmRegisterName::Pc).is_err());
+        assert_eq!(
+            String::from(ArmInstruction::Br {
+                target: ArmRegisterName::Pc
+            }),
+            "br pc"
+        );
+
+        let riscv_asm = vec![
+            RiscVInstruction::Add {
+                width: RiscVWidth::Double,
+                dest: RiscVRegister::T0,
+                arg1: RiscVRegister::S1,
+                arg2: RiscVRegister::S2,
+            },
+            RiscVInstruction::Add {
+                width: RiscVWidth::Word,
+                dest: RiscVRegister::T6,
+                arg1: RiscVRegister::S11,
+                arg2: RiscVRegister::A7,
+            },
+        ];
+        let arm_instrs = translate_instrs(riscv_asm).unwrap();
+        assert_eq!(
+            arm_to_asm(arm_instrs, Dialect::Gnu),
+            "add x9, x13, x15\nadd w28, w24, w8\nsxtw x28, w28\n"
+        );
+
+        // s3 and s4 live across calls, so they stay out of the veneer registers
+        let arm_asm = binary_translate(
+            "add s3, s4, t3\nadd t4, s3, s4\n",
+            &TranslateOptions::default(),
+        )
+        .unwrap();
+        assert_eq!(arm_asm, "add x25, x26, x16\nadd x17, x25, x26\n");
+    }
+
+    #[test]
+    fn test_relocs() {
+        let riscv_asm = "
+    lui a0, %hi(buf+9998)
+    addi a0, a0, %lo(buf+9998)
+    j .L3-4
+";
+        let arm_asm = binary_translate(riscv_asm, &TranslateOptions::default()).unwrap();
+        assert_eq!(
+            arm_asm,
+            "adrp x0, buf+9998\nadd x0, x0, :lo12:buf+9998\nb .L3-4\n"
+        );
+
+        let options = TranslateOptions {
+            dialect: Dialect::Apple,
+            ..TranslateOptions::default()
+        };
+        let arm_asm = binary_translate(riscv_asm, &options).unwrap();
+        assert_eq!(
+            arm_asm,
+            "adrp x0, buf@PAGE+9998\nadd x0, x0, buf@PAGEOFF+9998\nb .L3-4\n"
+        );
+        let riscv_asm = "
+    .section .rodata.str1.8,\"aMS\",@progbits,1
+    .type buf, @object
+buf:
+    .dword 1
+    .size buf, 8
+    .section .note.GNU-stack,\"\",@progbits
+    .text
+    ld a1, %lo(buf)(a0)
+    la a2, buf
+";
+        let arm_asm = binary_translate(riscv_asm, &options).unwrap();
+        assert_eq!(
+            arm_asm,
+            ".section __TEXT,__const\nbuf:\n.quad 1\n.text \n\
+             ldr x1, [x0, buf@PAGEOFF]\n\
+             adrp x2, buf@GOTPAGE\nldr x2, [x2, buf@GOTPAGEOFF]\n"
+        );
+
+        let errors =
+            binary_translate("lui a0, %tprel_hi(x)\n", &TranslateOptions::default()).unwrap_err();
+        assert_eq!(
+            errors,
+            vec![AsmError::new(
+                1,
+                1,
+                "lui a0, %tprel_hi(x)",
+                "thread-local relocations are not supported"
+            )]
+        );
+        for line in ["addi a0, a0, %tprel_lo(x)", "ld a0, %tprel_lo(x)(a0)"] {
+            let errors =
+                binary_translate_executable(line, &TranslateOptions::default()).unwrap_err();
+            assert_eq!(
+                errors[0].reason,
+                "thread-local relocations are not supported"
+            );
+        }
+    }
+
+    #[test]
+    fn test_pcrel() {
+        let riscv_asm = "
+    .section .rodata
+.LC0:
+    .string \"hi\"
+    .text
+main:
+.Lpcrel_hi0:
+    auipc a0, %pcrel_hi(.LC0)
+    addi a0, a0, %pcrel_lo(.Lpcrel_hi0)
+.Lpcrel_hi1:
+    auipc a5, %pcrel_hi(counter+8)
+    ld a4, %pcrel_lo(.Lpcrel_hi1)(a5)
+.Lpcrel_hi2:
+    auipc a1, %got_pcrel_hi(stdout)
+    ld a1, %pcrel_lo(.Lpcrel_hi2)(a1)
+.Lpcrel_hi3:
+    auipc a2, %pcrel_hi(main)
+    sd a3, %pcrel_lo(.Lpcrel_hi3)(a2)
+    lla a3, .LC0
+    lla a3, main
+    la a4, stdout
+";
+        let arm_asm = binary_translate(riscv_asm, &TranslateOptions::default()).unwrap();
+        assert_eq!(
+            arm_asm,
+            ".section .rodata\n.LC0:\n.string \"hi\"\n.text \nmain:\n\
+             .Lpcrel_hi0:\nadrp x0, .LC0\nadd x0, x0, :lo12:.LC0\n\
+             .Lpcrel_hi1:\nadrp x5, counter+8\nldr x4, [x5, :lo12:counter+8]\n\
+             .Lpcrel_hi2:\nadrp x1, :got:stdout\nldr x1, [x1, :got_lo12:stdout]\n\
+             .Lpcrel_hi3:\nadr x2, main\nstr x3, [x2, 0]\n\
+             adrp x3, .LC0\nadd x3, x3, :lo12:.LC0\n\
+             adr x3, main\n\
+             adrp x4, :got:stdout\nldr x4, [x4, :got_lo12:stdout]\n"
+        );
+
+        let errors =
+            binary_translate("addi a0, a0, %pcrel_lo(.L1)\n", &Default::default()).unwrap_err();
+        assert_eq!(
+            errors[0].reason,
+            "`%pcrel_lo` without a matching `%pcrel_hi`"
+        );
+        let errors = binary_translate("auipc a6, 1\n", &Default::default()).unwrap_err();
+        assert_eq!(errors[0].reason, "auipc needs a %pcrel_hi/%pcrel_lo pair");
+    }
+
+    #[test]
+    fn test_jumps() {
+        let riscv_asm = "
+    jal t0, f
+    call f
+    j f
+    jalr a0
+    jalr t0, t0, -16
+    jr t1
+    ret
+";
+        let arm_asm = binary_translate(riscv_asm, &TranslateOptions::default()).unwrap();
+        assert_eq!(
+            arm_asm,
+            "adr x9, .+8\nb f\n\
+             bl f\n\
+             b f\n\
+             blr x0\n\
+             sub x7, x9, 16\nadr x9, .+8\nbr x7\n\
+             br x10\n\
+             ret\n"
+        );
+    }
+
+    #[test]
+    fn test_word_ops() {
+        let riscv_asm = "
+    addw a5, a5, a4
+    sext.w a5, a5
+    addiw a0, a0, -1
+    sext.w a1, a0
+    subw a2, a2, a3
+.L2:
+    sext.w a2, a2
+";
+        let arm_asm = binary_translate(riscv_asm, &TranslateOptions::default()).unwrap();
+        assert_eq!(
+            arm_asm,
+            "add w5, w5, w4\nsxtw x5, w5\nsxtw x5, w5\n\
+             sub w0, w0, 1\nsxtw x0, w0\nsxtw x1, w0\n\
+             sub w2, w2, w3\nsxtw x2, w2\n.L2:\nsxtw x2, w2\n"
+        );
+
+        // only the `sext.w` right after `addw` is redundant, since a branch
+        // to `.L2` may come with any value in `a2`
+        let options = TranslateOptions {
+            elide_sign_extensions: true,
+            ..TranslateOptions::default()
+        };
+        let arm_asm = binary_translate(riscv_asm, &options).unwrap();
+        assert_eq!(
+            arm_asm,
+            "add w5, w5, w4\nsxtw x5, w5\n\
+             sub w0, w0, 1\nsxtw x0, w0\nsxtw x1, w0\n\
+             sub w2, w2, w3\nsxtw x2, w2\n.L2:\nsxtw x2, w2\n"
+        );
+
+        // a result written to `zero` isn't sign extended
+        let riscv_asm = "
+    addiw zero, a0, 3
+    addw zero, a0, a1
+    lr.w zero, (a0)
+";
+        let arm_asm = binary_translate(riscv_asm, &TranslateOptions::default()).unwrap();
+        assert_eq!(arm_asm, "add wzr, w0, w1\nldxr wzr, [x0]\n");
+    }
+
+    #[test]
+    fn test_atomics() {
+        let riscv_asm = "
+    lr.w.aq a0, (a1)
+    sc.w.rl a2, a3, (a1)
+    sc.d a1, a3, (a1)
+    amoadd.w a0, a1, (a2)
+    amoswap.d.aqrl zero, t0, (t1)
+    amomin.w a0, t1, (t0)
+";
+        let arm_asm = binary_translate(riscv_asm, &TranslateOptions::default()).unwrap();
+        assert_eq!(
+            arm_asm,
+            "ldaxr w0, [x1]\nsxtw x0, w0\n\
+             stlxr w2, w3, [x1]\n\
+             stxr w7, x3, [x1]\nmov x1, x7\n\
+             stp x9, x10, [sp, -16]!\n\
+             ldxr w7, [x2]\nadd w9, w7, w1\nstxr w10, w9, [x2]\ncbnz w10, .-12\n\
+             ldp x9, x10, [sp], 16\nsxtw x0, w7\n\
+             stp x11, x12, [sp, -16]!\n\
+             ldaxr x7, [x10]\nstlxr w12, x9, [x10]\ncbnz w12, .-8\n\
+             ldp x11, x12, [sp], 16\n\
+             stp x11, x12, [sp, -16]!\n\
+             ldxr w7, [x9]\ncmp w7, w10\ncsel w11, w7, w10, lt\nstxr w12, w11, [x9]\n\
+             cbnz w12, .-16\n\
+             ldp x11, x12, [sp], 16\nsxtw x0, w7\n"
+        );
+
+        let options = TranslateOptions {
+            lse: true,
+            ..TranslateOptions::default()
+        };
+        let riscv_asm = "
+    amoadd.w a0, a1, (a2)
+    amoswap.d.aqrl zero, t0, (t1)
+    amoand.d.aq a0, a1, (a2)
+";
+        let arm_asm = binary_translate(riscv_asm, &options).unwrap();
+        assert_eq!(
+            arm_asm,
+            "ldadd w1, w0, [x2]\nsxtw x0, w0\n\
+             swpal x9, xzr, [x10]\n\
+             mvn x7, x1\nldclra x7, x0, [x2]\n"
+        );
+
+        let errors = binary_translate("amoadd.d a0, a1, (sp)\n", &options).unwrap_err();
+        assert_eq!(
+            errors[0].reason,
+            "`sp` is not supported as an operand of this instruction"
+        );
+
+        for riscv_asm in [
+            "lr.w a0, (zero)\n",
+            "sc.d a1, a2, (zero)\n",
+            "amoadd.w a0, a1, (zero)\n",
+        ] {
+            for lse in [false, true] {
+                let options = TranslateOptions {
+                    lse,
+                    ..TranslateOptions::default()
+                };
+                let errors = binary_translate(riscv_asm, &options).unwrap_err();
+                assert_eq!(
+                    errors[0].reason,
+                    "`zero` is not supported as the address of an atomic"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_fences() {
+        let riscv_asm = "
+    fence
+    fence r, rw
+    fence w, w
+    fence.tso
+    fence.i
+    pause
+";
+        let arm_asm = binary_translate(riscv_asm, &TranslateOptions::default()).unwrap();
+        assert_eq!(
+            arm_asm,
+            "dmb ish\ndmb ishld\ndmb ishst\ndmb ish\n\
+             stp x9, x10, [sp, -16]!\n\
+             mrs x7, ctr_el0\nlsr x9, x7, 16\nand x9, x9, 15\nand x7, x7, 15\n\
+             cmp x7, x9\ncsel x7, x7, x9, lo\nmov x9, 4\nlslv x7, x9, x7\n\
+             adrp x9, __executable_start\nadd x9, x9, :lo12:__executable_start\n\
+             adrp x10, etext\nadd x10, x10, :lo12:etext\n\
+             dc cvau, x9\ndsb ish\nic ivau, x9\nadd x9, x9, x7\ncmp x9, x10\nb.lo .-20\n\
+             dsb ish\nisb\nldp x9, x10, [sp], 16\n\
+             yield\n"
+        );
+
+        // the loop of `fence.i` branches back over what legalize left of it
+        let arm_instrs = translate_instrs(vec![RiscVInstruction::FenceI]).unwrap();
+        let position = |pattern: fn(&ArmInstruction) -> bool| {
+            arm_instrs.iter().position(pattern).unwrap()
+        };
+        let start = position(|instr| matches!(instr, ArmInstruction::DcCvau { .. }));
+        let branch = position(|instr| matches!(instr, ArmInstruction::BCond { .. }));
+        let ArmInstruction::BCond {
+            target: ArmVal::LabelOffset(label, offset),
+            ..
+        } = &arm_instrs[branch]
+        else {
+            panic!("{:?} isn't a branch to a label", arm_instrs[branch]);
+        };
+        assert_eq!((label.as_str(), *offset), (".", -4 * (branch - start) as i32));
+
+        let set = |s: &str| s.parse::<RiscVFenceSet>().unwrap();
+        assert_eq!(fence_barrier(set("r"), set("r")), Some(ArmBarrier::Ishld));
+        assert_eq!(fence_barrier(set("ir"), set("rw")), Some(ArmBarrier::Ishld));
+        assert_eq!(fence_barrier(set("w"), set("w")), Some(ArmBarrier::Ishst));
+        assert_eq!(fence_barrier(set("o"), set("ow")), Some(ArmBarrier::Ishst));
+        assert_eq!(fence_barrier(set("w"), set("r")), Some(ArmBarrier::Ish));
+        assert_eq!(fence_barrier(set("rw"), set("w")), Some(ArmBarrier::Ish));
+        assert_eq!(
+            fence_barrier(set("iorw"), set("iorw")),
+            Some(ArmBarrier::Ish)
+        );
+        assert_eq!(fence_barrier(RiscVFenceSet::default(), set("rw")), None);
+    }
+
+    #[test]
+    fn test_floats() {
+        let riscv_asm = "
+    fld fa0, 8(a0)
+    fadd.d fa0, fa1, fa2
+    fnmsub.d ft0, fa0, fa1, fa2
+    fsgnj.d fa0, fa1, fa2
+    fneg.d fa0, fa1
+    flt.d a0, fa0, fa1
+    fmv.x.d a1, fs0
+    fcvt.w.d a0, fa0, rtz
+    fcvt.lu.d a0, fa0
+    fcvt.d.l fa1, a1
+    fsd fa1, 16(sp)
+";
+        let arm_asm = binary_translate(riscv_asm, &TranslateOptions::default()).unwrap();
+        assert_eq!(
+            arm_asm,
+            "ldr d0, [x0, 8]\n\
+             fadd d0, d1, d2\n\
+             fcmp d0, d0\nb.vc .+12\nmov x7, 9221120237041090560\nfmov d0, x7\n\
+             fmsub d16, d0, d1, d2\n\
+             fcmp d16, d16\nb.vc .+12\nmov x7, 9221120237041090560\nfmov d16, x7\n\
+             fmov x7, d2\ntst x7, -9223372036854775808\nfabs d0, d1\nb.eq .+8\nfneg d0, d0\n\
+             fneg d0, d1\n\
+             fcmp d0, d1\ncset x0, mi\n\
+             fmov x1, d8\n\
+             fcmp d0, d0\nfcvtzs w0, d0\nmov x7, 2147483647\ncsel w0, w0, w7, vc\nsxtw x0, w0\n\
+             fcmp d0, d0\nfcvtnu x0, d0\ncsinv x0, x0, xzr, vc\n\
+             scvtf d1, x1\n\
+             str d1, [sp, 16]\n"
+        );
+
+        let errors = binary_translate("fadd.d fa0, fa1, fa2, rdn\n", &TranslateOptions::default())
+            .unwrap_err();
+        assert_eq!(
+            errors[0].reason,
+            "static rounding modes other than `rne` are not supported"
+        );
+        let errors =
+            binary_translate("feq.d sp, fa0, fa1\n", &TranslateOptions::default()).unwrap_err();
+        assert_eq!(
+            errors[0].reason,
+            "`sp` is not supported as an operand of this instruction"
+        );
+    }
+
+    #[test]
+    fn test_float_nan_boxing() {
+        // singles are boxed when written, and unboxed in place when read,
+        // except by moves of the raw bits
+        let riscv_asm = "
+    flw fa0, 0(a0)
+    fmul.s fa1, fa0, fa2
+    fmv.x.w a0, fa1
+    fsw fa1, 4(a0)
+";
+        let unbox = |v: u32| {
+            format!(
+                "mov v{v}.d[1], v{v}.d[0]\nmov w7, v{v}.s[1]\ncmn w7, 1\nb.eq .+12\n\
+                 mov x7, 2143289344\nmov v{v}.s[0], w7\n"
+            )
+        };
+        let arm_asm = binary_translate(riscv_asm, &TranslateOptions::default()).unwrap();
+        assert_eq!(
+            arm_asm,
+            format!(
+                "ldr s0, [x0, 0]\nmov x7, 4294967295\nmov v0.s[1], w7\n\
+                 {}{}\
+                 fmul s1, s0, s2\n\
+                 fcmp s1, s1\nb.vc .+12\nmov x7, 2143289344\nfmov s1, w7\n\
+                 mov x7, 4294967295\nmov v1.s[1], w7\n\
+                 mov v0.d[0], v0.d[1]\nmov v2.d[0], v2.d[1]\n\
+                 fmov w0, s1\nsxtw x0, w0\n\
+                 str s1, [x0, 4]\n",
+                unbox(0),
+                unbox(2)
+            )
+        );
+    }
+
+    #[test]
+    fn test_float_min_max_class() {
+        // NaN operands of `fmin`/`fmax` become the canonical NaN, which
+        // `fmaxnm` ignores
+        let riscv_asm = "
+    fmax.d fa0, fa0, fa1
+    fclass.d a0, fa0
+";
+        let arm_asm = binary_translate(riscv_asm, &TranslateOptions::default()).unwrap();
+        assert_eq!(
+            arm_asm,
+            "mov v0.d[1], v0.d[0]\n\
+             fcmp d0, d0\nb.vc .+12\nmov x7, 9221120237041090560\nmov v0.d[0], x7\n\
+             mov v1.d[1], v1.d[0]\n\
+             fcmp d1, d1\nb.vc .+12\nmov x7, 9221120237041090560\nmov v1.d[0], x7\n\
+             fmaxnm d0, d0, d1\n\
+             mov v1.d[0], v1.d[1]\n\
+             fcmp d0, d0\nb.vc .+24\n\
+             fmov x7, d0\ntst x7, 2251799813685248\ncset w0, ne\nadd w0, w0, 8\nb .+68\n\
+             fmov x7, d0\ntst x7, 4503599627370495\ncset w0, ne\n\
+             lsl x7, x7, 1\nlsr x7, x7, 53\n\
+             cmp w7, 0\ncsinc w0, w0, wzr, eq\ncsinc w0, w0, w0, eq\n\
+             cmp w7, 2047\ncsinc w0, w0, w0, ne\n\
+             fmov x7, d0\ntst x7, -9223372036854775808\n\
+             mov x7, 3\nsub w7, w7, w0\nadd w0, w0, 4\ncsel w0, w7, w0, ne\n\
+             mov x7, 1\nlslv x0, x7, x0\n"
+        );
     }
 }
@@ -11,4 +11,7 @@ mod print;
 mod fib;
 
 #[path = "prime/test_prime.rs"]
-mod prime;
\ No newline at end of file
+mod prime;
+
+#[path = "elf/test_elf.rs"]
+mod elf;